@@ -10,4 +10,10 @@ pub enum EscrowError {
     InvalidMintA,
     #[msg("Invalid mint b")]
     InvalidMintB,
+    #[msg("Amount outside the escrow band")]
+    OutOfBand,
+    #[msg("Escrow has expired")]
+    Expired,
+    #[msg("Only the maker may refund before the deadline")]
+    NotExpired,
 }
@@ -50,7 +50,15 @@ pub struct Make<'info> {
 }
 
 impl<'info> Make<'info> {
-    fn populate_escrow(&mut self, seed: u64, amount: u64, bump: u8) -> Result<()> {
+    fn populate_escrow(
+        &mut self,
+        seed: u64,
+        amount: u64,
+        bump: u8,
+        min_receive: u64,
+        max_receive: u64,
+        deadline: i64,
+    ) -> Result<()> {
         self.escrow.set_inner(Escrow {
             seed,
             maker: self.maker.key(),
@@ -58,6 +66,9 @@ impl<'info> Make<'info> {
             mint_b: self.mint_b.key(),
             receive: amount,
             bump,
+            min_receive,
+            max_receive,
+            deadline,
         });
         Ok(())
     }
@@ -80,12 +91,82 @@ impl<'info> Make<'info> {
     }
 }
 
-pub fn handler(ctx: Context<Make>, seed: u64, receive: u64, amount: u64) -> Result<()> {
+/// Check a `Make` band: every fill pays at least `min_receive`, at most `max_receive`, and
+/// neither bound can ask for more than the whole offer.
+pub fn check_band(receive: u64, min_receive: u64, max_receive: u64) -> Result<()> {
+    require_gt!(min_receive, 0, EscrowError::OutOfBand);
+    require_gte!(max_receive, min_receive, EscrowError::OutOfBand);
+    require_gte!(receive, max_receive, EscrowError::OutOfBand);
+    Ok(())
+}
+
+/// `min_receive` and `max_receive` bound how much token B a single Take may pay, allowing
+/// partial fills. Both default to `receive`, a single fill of the whole offer.
+///
+/// Past `deadline` the offer can no longer be taken and anyone may refund it to the maker.
+/// Without one the offer stands until the maker refunds it.
+pub fn handler(
+    ctx: Context<Make>,
+    seed: u64,
+    receive: u64,
+    amount: u64,
+    min_receive: Option<u64>,
+    max_receive: Option<u64>,
+    deadline: Option<i64>,
+) -> Result<()> {
     require_gt!(receive, 0, EscrowError::InvalidAmount);
     require_gt!(amount, 0, EscrowError::InvalidAmount);
 
-    ctx.accounts.populate_escrow(seed, receive, ctx.bumps.escrow)?;
+    let min_receive = min_receive.unwrap_or(receive);
+    let max_receive = max_receive.unwrap_or(receive);
+    check_band(receive, min_receive, max_receive)?;
+
+    // Zero is stored as no deadline, any other one must still be ahead
+    let deadline = deadline.unwrap_or(0);
+    if deadline != 0 {
+        require_gt!(
+            deadline,
+            Clock::get()?.unix_timestamp,
+            EscrowError::Expired
+        );
+    }
+
+    ctx.accounts.populate_escrow(
+        seed,
+        receive,
+        ctx.bumps.escrow,
+        min_receive,
+        max_receive,
+        deadline,
+    )?;
 
     ctx.accounts.deposit_tokens(amount)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_a_band_within_the_offer() {
+        assert_eq!(check_band(300, 300, 300), Ok(()));
+        assert_eq!(check_band(300, 1, 300), Ok(()));
+        assert_eq!(check_band(300, 50, 50), Ok(()));
+    }
+
+    #[test]
+    fn rejects_a_zero_minimum() {
+        assert_eq!(check_band(300, 0, 300), Err(EscrowError::OutOfBand.into()));
+    }
+
+    #[test]
+    fn rejects_an_inverted_band_or_one_above_the_offer() {
+        for (min_receive, max_receive) in [(200, 100), (100, 301), (301, 301)] {
+            assert_eq!(
+                check_band(300, min_receive, max_receive),
+                Err(EscrowError::OutOfBand.into())
+            );
+        }
+    }
+}
@@ -12,7 +12,9 @@ use crate::{errors::EscrowError, state::Escrow};
 #[derive(Accounts)]
 pub struct Refund<'info> {
     #[account(mut)]
-    pub maker: Signer<'info>,
+    pub caller: Signer<'info>,
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
     #[account(
         mut,
         close = maker,
@@ -33,7 +35,7 @@ pub struct Refund<'info> {
     pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         init_if_needed,
-        payer = maker,
+        payer = caller,
         associated_token::mint = mint_a,
         associated_token::authority = maker,
         associated_token::token_program = token_program,
@@ -81,7 +83,63 @@ impl<'info> Refund<'info> {
 
 }
 
+/// Check `caller` may refund the escrow at `now`. The maker may refund at any time, anyone
+/// else only once a set `deadline` has passed.
+pub fn check_refund(caller: &Pubkey, maker: &Pubkey, deadline: i64, now: i64) -> Result<()> {
+    if caller != maker {
+        require!(deadline != 0 && now > deadline, EscrowError::NotExpired);
+    }
+    Ok(())
+}
+
+/// The maker may refund at any time, anyone else only once the escrow's deadline has passed.
+/// The tokens and the rent always go back to the maker.
 pub fn handler(ctx: Context<Refund>) -> Result<()> {
+    check_refund(
+        ctx.accounts.caller.key,
+        ctx.accounts.maker.key,
+        ctx.accounts.escrow.deadline,
+        Clock::get()?.unix_timestamp,
+    )?;
+
     ctx.accounts.transfer_to_maker_and_close_vault()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAKER: Pubkey = Pubkey::new_from_array([1; 32]);
+    const KEEPER: Pubkey = Pubkey::new_from_array([2; 32]);
+
+    #[test]
+    fn lets_a_keeper_refund_after_the_deadline() {
+        assert_eq!(check_refund(&KEEPER, &MAKER, 100, 101), Ok(()));
+    }
+
+    #[test]
+    fn rejects_a_keeper_until_the_deadline_has_passed() {
+        for now in [0, 99, 100] {
+            assert_eq!(
+                check_refund(&KEEPER, &MAKER, 100, now),
+                Err(EscrowError::NotExpired.into())
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_a_keeper_without_a_deadline() {
+        assert_eq!(
+            check_refund(&KEEPER, &MAKER, 0, i64::MAX),
+            Err(EscrowError::NotExpired.into())
+        );
+    }
+
+    #[test]
+    fn lets_the_maker_refund_at_any_time() {
+        for (deadline, now) in [(0, 0), (100, 50), (100, 101)] {
+            assert_eq!(check_refund(&MAKER, &MAKER, deadline, now), Ok(()));
+        }
+    }
+}
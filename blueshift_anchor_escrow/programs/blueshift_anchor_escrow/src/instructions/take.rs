@@ -17,7 +17,6 @@ pub struct Take<'info> {
     pub maker: SystemAccount<'info>,
     #[account(
         mut,
-        close = maker,
         seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
         bump = escrow.bump,
         has_one = maker @ EscrowError::InvalidMaker,
@@ -65,7 +64,7 @@ pub struct Take<'info> {
 }
 
 impl<'info> Take<'info> {
-    fn transfer_to_maker(&mut self) -> Result<()> {
+    fn transfer_to_maker(&mut self, receive: u64) -> Result<()> {
         transfer_checked(
             CpiContext::new(
                 self.token_program.to_account_info(),
@@ -76,13 +75,13 @@ impl<'info> Take<'info> {
                     authority: self.taker.to_account_info(),
                 },
             ),
-            self.escrow.receive,
+            receive,
             self.mint_b.decimals,
         )?;
         Ok(())
     }
 
-    fn withdraw_and_close_vault(&mut self) -> Result<()> {
+    fn withdraw_from_vault(&mut self, amount: u64) -> Result<()> {
         let signer_seeds: [&[&[u8]]; 1] = [&[
             b"escrow",
             self.maker.to_account_info().key.as_ref(),
@@ -100,10 +99,14 @@ impl<'info> Take<'info> {
                 },
                 &signer_seeds
             ),
-            self.vault.amount,
+            amount,
             self.mint_a.decimals
         )?;
 
+        if amount < self.vault.amount {
+            return Ok(());
+        }
+
         close_account(CpiContext::new_with_signer(
             self.token_program.to_account_info(), 
             CloseAccount {
@@ -117,8 +120,128 @@ impl<'info> Take<'info> {
     }
 }
 
-pub fn handler(ctx: Context<Take>) -> Result<()> {
-    ctx.accounts.transfer_to_maker()?;
-    ctx.accounts.withdraw_and_close_vault()?;
+/// Check a Take paying `receive` of token B lies within the escrow's band. Only the rest of
+/// the offer may fall below `min_receive`, so no dust is left that nobody can take.
+fn check_fill(escrow: &Escrow, receive: u64) -> Result<()> {
+    let remaining = escrow.receive;
+    require_gt!(receive, 0, EscrowError::InvalidAmount);
+    require_gte!(remaining, receive, EscrowError::OutOfBand);
+    require_gte!(escrow.max_receive, receive, EscrowError::OutOfBand);
+    require!(
+        receive >= escrow.min_receive || receive == remaining,
+        EscrowError::OutOfBand
+    );
     Ok(())
 }
+
+/// Fill `receive` of the token B still owed, returning the share of a vault holding
+/// `vault_amount` it buys. Each partial fill lowers what the escrow still asks, so the next
+/// one is priced against the rest of the offer.
+fn fill(escrow: &mut Escrow, vault_amount: u64, receive: u64) -> Result<u64> {
+    let remaining = escrow.receive;
+    check_fill(escrow, receive)?;
+    escrow.receive = remaining - receive;
+
+    // Rounds down in the maker's favour, the last fill takes whatever is left
+    Ok(match receive == remaining {
+        true => vault_amount,
+        false => (vault_amount as u128 * receive as u128 / remaining as u128) as u64,
+    })
+}
+
+/// Pay `receive` of token B for the matching share of the vault, the last fill closes the
+/// escrow.
+pub fn handler(ctx: Context<Take>, receive: u64) -> Result<()> {
+    let deadline = ctx.accounts.escrow.deadline;
+    require!(
+        deadline == 0 || Clock::get()?.unix_timestamp <= deadline,
+        EscrowError::Expired
+    );
+
+    let vault_amount = ctx.accounts.vault.amount;
+    let amount = fill(&mut ctx.accounts.escrow, vault_amount, receive)?;
+
+    ctx.accounts.transfer_to_maker(receive)?;
+    ctx.accounts.withdraw_from_vault(amount)?;
+
+    if ctx.accounts.escrow.receive == 0 {
+        let maker = ctx.accounts.maker.to_account_info();
+        ctx.accounts.escrow.close(maker)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An escrow asking `receive` of token B, in fills between `min_receive` and `max_receive`.
+    fn escrow(receive: u64, min_receive: u64, max_receive: u64) -> Escrow {
+        Escrow {
+            seed: 0,
+            maker: Pubkey::default(),
+            mint_a: Pubkey::default(),
+            mint_b: Pubkey::default(),
+            receive,
+            bump: 255,
+            min_receive,
+            max_receive,
+            deadline: 0,
+        }
+    }
+
+    #[test]
+    fn two_partial_takes_drain_the_offer() {
+        let mut escrow = escrow(300, 1, 300);
+        let mut vault = 1_000;
+
+        // A third of what is asked buys a third of the vault, rounded down
+        let first = fill(&mut escrow, vault, 100).unwrap();
+        assert_eq!((first, escrow.receive), (333, 200));
+        vault -= first;
+
+        // The rest of the offer takes whatever is left, rounding dust included
+        let second = fill(&mut escrow, vault, 200).unwrap();
+        assert_eq!((second, escrow.receive), (667, 0));
+        vault -= second;
+
+        assert_eq!(vault, 0);
+    }
+
+    #[test]
+    fn rejected_fills_leave_the_offer_untouched() {
+        let mut escrow = escrow(300, 50, 200);
+        assert!(fill(&mut escrow, 1_000, 201).is_err());
+        assert_eq!(escrow.receive, 300);
+    }
+
+    #[test]
+    fn takes_at_both_edges_of_the_band() {
+        let escrow = escrow(300, 50, 200);
+        assert_eq!(check_fill(&escrow, 50), Ok(()));
+        assert_eq!(check_fill(&escrow, 200), Ok(()));
+    }
+
+    #[test]
+    fn rejects_fills_outside_the_band() {
+        let escrow = escrow(300, 50, 200);
+
+        for receive in [49, 201, 301] {
+            assert_eq!(
+                check_fill(&escrow, receive),
+                Err(EscrowError::OutOfBand.into())
+            );
+        }
+        assert_eq!(
+            check_fill(&escrow, 0),
+            Err(EscrowError::InvalidAmount.into())
+        );
+    }
+
+    #[test]
+    fn lets_the_last_fill_fall_below_the_band() {
+        let mut escrow = escrow(30, 50, 200);
+        assert_eq!(fill(&mut escrow, 1_000, 30), Ok(1_000));
+        assert_eq!(escrow.receive, 0);
+    }
+}
@@ -12,13 +12,21 @@ pub mod blueshift_anchor_escrow {
     use super::*;
 
     #[instruction(discriminator = 0)]
-    pub fn make(ctx: Context<Make>, seed: u64, receive: u64, amount: u64) -> Result<()> {
-        make::handler(ctx, seed, receive, amount)
+    pub fn make(
+        ctx: Context<Make>,
+        seed: u64,
+        receive: u64,
+        amount: u64,
+        min_receive: Option<u64>,
+        max_receive: Option<u64>,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        make::handler(ctx, seed, receive, amount, min_receive, max_receive, deadline)
     }
 
     #[instruction(discriminator = 1)]
-    pub fn take(ctx: Context<Take>) -> Result<()> {
-        take::handler(ctx)
+    pub fn take(ctx: Context<Take>, receive: u64) -> Result<()> {
+        take::handler(ctx, receive)
     }
 
     #[instruction(discriminator = 2)]
@@ -9,4 +9,7 @@ pub struct Escrow {
     pub mint_b: Pubkey,
     pub receive: u64,
     pub bump: u8,
+    pub min_receive: u64,
+    pub max_receive: u64,
+    pub deadline: i64,
 }
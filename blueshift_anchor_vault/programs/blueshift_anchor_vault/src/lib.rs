@@ -8,17 +8,58 @@ pub mod blueshift_anchor_vault {
     use super::*;
 
     pub fn deposit(ctx: Context<VaultAction>, amount: u64) -> Result<()> {
-        require_eq!(ctx.accounts.vault.lamports(), 0, VaultError::VaultAlreadyExists);
-        require_gt!(amount, Rent::get()?.minimum_balance(0), VaultError::InvalidAmount);
-        ctx.accounts.deposit(amount)
+        check_deposit(
+            ctx.accounts.vault.lamports(),
+            amount,
+            Rent::get()?.minimum_balance(0),
+        )?;
+        ctx.accounts.deposit(amount)?;
+        emit!(VaultDeposited {
+            owner: ctx.accounts.signer.key(),
+            amount,
+        });
+        Ok(())
     }
 
     pub fn withdraw(ctx: Context<VaultAction>) -> Result<()> {
         require_neq!(ctx.accounts.vault.lamports(), 0, VaultError::InvalidAmount);
-        ctx.accounts.withdraw(ctx.bumps.vault)
+        let amount = ctx.accounts.vault.lamports();
+        ctx.accounts.withdraw(ctx.bumps.vault)?;
+        emit!(VaultWithdrawn {
+            owner: ctx.accounts.signer.key(),
+            amount,
+        });
+        Ok(())
     }
 }
 
+/// Only an empty vault takes a deposit, so the amount never adds to an existing balance.
+fn check_deposit(vault_lamports: u64, amount: u64, minimum_balance: u64) -> Result<()> {
+    require_eq!(vault_lamports, 0, VaultError::VaultAlreadyExists);
+    require_gt!(amount, minimum_balance, VaultError::InvalidAmount);
+    Ok(())
+}
+
+/// The vault `signer` withdraws from must be its own PDA, `["vault", signer]` with `bump`.
+fn check_vault(signer: &Pubkey, bump: u8, vault: &Pubkey) -> Result<()> {
+    let derived = Pubkey::create_program_address(&[b"vault", signer.as_ref(), &[bump]], &crate::ID)
+        .map_err(|_| VaultError::VaultMismatch)?;
+    require_keys_eq!(derived, *vault, VaultError::VaultMismatch);
+    Ok(())
+}
+
+#[event]
+pub struct VaultDeposited {
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct VaultWithdrawn {
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
 #[derive(Accounts)]
 pub struct VaultAction<'info> {
     #[account(mut)]
@@ -52,6 +93,8 @@ impl<'info> VaultAction<'info> {
             self.signer.key.as_ref(),
             &[bump]
         ];
+        // Belt and braces: the seeds signed with must still derive the vault being drained
+        check_vault(self.signer.key, bump, &self.vault.key())?;
         transfer(
             CpiContext::new_with_signer(
                 self.system_program.to_account_info(), 
@@ -72,4 +115,86 @@ pub enum VaultError {
     VaultAlreadyExists,
     #[msg("Invalid amount")]
     InvalidAmount,
+    #[msg("Vault does not belong to the signer")]
+    VaultMismatch,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Event;
+
+    const MINIMUM_BALANCE: u64 = 890_880;
+
+    #[test]
+    fn takes_deposits_up_to_u64_max_into_an_empty_vault() {
+        assert_eq!(check_deposit(0, MINIMUM_BALANCE + 1, MINIMUM_BALANCE), Ok(()));
+        assert_eq!(check_deposit(0, u64::MAX, MINIMUM_BALANCE), Ok(()));
+    }
+
+    #[test]
+    fn never_tops_up_a_funded_vault() {
+        // No balance is summed, even where the sum would overflow
+        for vault_lamports in [1, MINIMUM_BALANCE + 1, u64::MAX] {
+            assert_eq!(
+                check_deposit(vault_lamports, u64::MAX, MINIMUM_BALANCE),
+                Err(VaultError::VaultAlreadyExists.into())
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_a_deposit_below_rent_exemption() {
+        assert_eq!(
+            check_deposit(0, MINIMUM_BALANCE, MINIMUM_BALANCE),
+            Err(VaultError::InvalidAmount.into())
+        );
+    }
+
+    #[test]
+    fn withdraws_only_from_the_signers_own_vault() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) = Pubkey::find_program_address(&[b"vault", signer.as_ref()], &crate::ID);
+        assert_eq!(check_vault(&signer, bump, &vault), Ok(()));
+
+        // Another signer's vault, or any foreign key, fails the derivation check
+        let (other, _) =
+            Pubkey::find_program_address(&[b"vault", Pubkey::new_unique().as_ref()], &crate::ID);
+        for foreign in [other, Pubkey::new_unique()] {
+            assert_eq!(
+                check_vault(&signer, bump, &foreign),
+                Err(VaultError::VaultMismatch.into())
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_the_deposit_event() {
+        let event = VaultDeposited {
+            owner: Pubkey::new_unique(),
+            amount: u64::MAX,
+        };
+        let data = event.data();
+        // sha256("event:VaultDeposited")[..8], what indexers match the log against
+        assert_eq!(VaultDeposited::DISCRIMINATOR, [59, 62, 43, 200, 220, 104, 100, 67]);
+        assert_eq!(&data[..8], VaultDeposited::DISCRIMINATOR);
+
+        let decoded = VaultDeposited::try_from_slice(&data[8..]).unwrap();
+        assert_eq!((decoded.owner, decoded.amount), (event.owner, event.amount));
+    }
+
+    #[test]
+    fn round_trips_the_withdraw_event() {
+        let event = VaultWithdrawn {
+            owner: Pubkey::new_unique(),
+            amount: MINIMUM_BALANCE + 1,
+        };
+        let data = event.data();
+        // sha256("event:VaultWithdrawn")[..8]
+        assert_eq!(VaultWithdrawn::DISCRIMINATOR, [238, 9, 219, 172, 188, 77, 72, 104]);
+        assert_eq!(&data[..8], VaultWithdrawn::DISCRIMINATOR);
+
+        let decoded = VaultWithdrawn::try_from_slice(&data[8..]).unwrap();
+        assert_eq!((decoded.owner, decoded.amount), (event.owner, event.amount));
+    }
 }
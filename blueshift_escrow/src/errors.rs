@@ -30,6 +30,51 @@ pub enum PinocchioError {
     /// PDA mismatch
     #[error("PDA mismatch")]
     InvalidAddress,
+
+    /// 5
+    /// Unknown account layout version
+    #[error("Unknown account layout version")]
+    InvalidVersion,
+
+    /// 6
+    /// Escrow is past its expiry
+    #[error("Escrow has expired")]
+    Expired,
+
+    /// 7
+    /// Transfer fees left the maker short of the offer
+    #[error("Maker received too little")]
+    ReceivedTooLittle,
+
+    /// 8
+    /// Offer is reserved for another taker
+    #[error("Taker not allowed")]
+    TakerNotAllowed,
+
+    /// 9
+    /// Signer is not the maker recorded in the escrow
+    #[error("Signer is not the maker")]
+    InvalidMaker,
+
+    /// 10
+    /// Escrow is still live
+    #[error("Escrow has not expired")]
+    NotExpired,
+
+    /// 11
+    /// Program account does not match the expected program
+    #[error("Unexpected program account")]
+    InvalidProgram,
+
+    /// 12
+    /// Signer is not the authority the account requires
+    #[error("Invalid authority")]
+    InvalidAuthority,
+
+    /// 13
+    /// Escrow is stored in an older layout the instruction cannot write
+    #[error("Escrow needs Migrate")]
+    NeedsMigrate,
 }
 
 impl From<PinocchioError> for ProgramError {
@@ -47,6 +92,15 @@ impl TryFrom<u32> for PinocchioError {
             2 => Ok(PinocchioError::InvalidOwner),
             3 => Ok(PinocchioError::InvalidAccountData),
             4 => Ok(PinocchioError::InvalidAddress),
+            5 => Ok(PinocchioError::InvalidVersion),
+            6 => Ok(PinocchioError::Expired),
+            7 => Ok(PinocchioError::ReceivedTooLittle),
+            8 => Ok(PinocchioError::TakerNotAllowed),
+            9 => Ok(PinocchioError::InvalidMaker),
+            10 => Ok(PinocchioError::NotExpired),
+            11 => Ok(PinocchioError::InvalidProgram),
+            12 => Ok(PinocchioError::InvalidAuthority),
+            13 => Ok(PinocchioError::NeedsMigrate),
             _ => Err(ProgramError::InvalidArgument),
         }
     }
@@ -60,6 +114,15 @@ impl ToStr for PinocchioError {
             PinocchioError::InvalidOwner => "Error: Account ownership mismatch",
             PinocchioError::InvalidAccountData => "Error: Account data field is invalid",
             PinocchioError::InvalidAddress => "Error: PDA mismatch",
+            PinocchioError::InvalidVersion => "Error: Unknown account layout version",
+            PinocchioError::Expired => "Error: Escrow has expired",
+            PinocchioError::ReceivedTooLittle => "Error: Maker received too little",
+            PinocchioError::TakerNotAllowed => "Error: Taker not allowed",
+            PinocchioError::InvalidMaker => "Error: Signer is not the maker",
+            PinocchioError::NotExpired => "Error: Escrow has not expired",
+            PinocchioError::InvalidProgram => "Error: Unexpected program account",
+            PinocchioError::InvalidAuthority => "Error: Invalid authority",
+            PinocchioError::NeedsMigrate => "Error: Escrow needs Migrate",
         }
     }
 }
@@ -0,0 +1,62 @@
+use pinocchio::Address;
+
+/// Log `fields` with `sol_log_data`: an 8-byte discriminator followed by the event fields,
+/// laid out like Anchor events so the same decoders apply.
+#[inline(always)]
+fn emit(fields: &[&[u8]]) {
+    #[cfg(any(target_os = "solana", target_arch = "bpf"))]
+    unsafe {
+        pinocchio::syscalls::sol_log_data(fields.as_ptr() as *const u8, fields.len() as u64)
+    };
+
+    #[cfg(not(any(target_os = "solana", target_arch = "bpf")))]
+    core::hint::black_box(fields);
+}
+
+pub struct MakeEvent<'a> {
+    pub escrow: &'a Address,
+    pub maker: &'a Address,
+    pub amount: u64,
+    pub receive: u64,
+    pub order_ref: &'a [u8; 32],
+}
+
+impl MakeEvent<'_> {
+    /// `sha256("event:MakeEvent")[..8]`
+    pub const DISCRIMINATOR: [u8; 8] = [5, 141, 193, 202, 182, 42, 245, 74];
+
+    pub fn emit(&self) {
+        emit(&[
+            &Self::DISCRIMINATOR,
+            self.escrow.as_ref(),
+            self.maker.as_ref(),
+            &self.amount.to_le_bytes(),
+            &self.receive.to_le_bytes(),
+            self.order_ref,
+        ]);
+    }
+}
+
+pub struct TakeEvent<'a> {
+    pub escrow: &'a Address,
+    pub taker: &'a Address,
+    pub amount: u64,
+    pub receive: u64,
+    pub order_ref: &'a [u8; 32],
+}
+
+impl TakeEvent<'_> {
+    /// `sha256("event:TakeEvent")[..8]`
+    pub const DISCRIMINATOR: [u8; 8] = [139, 102, 92, 170, 101, 149, 30, 117];
+
+    pub fn emit(&self) {
+        emit(&[
+            &Self::DISCRIMINATOR,
+            self.escrow.as_ref(),
+            self.taker.as_ref(),
+            &self.amount.to_le_bytes(),
+            &self.receive.to_le_bytes(),
+            self.order_ref,
+        ]);
+    }
+}
@@ -0,0 +1,302 @@
+//! Instruction layout consumed by `shank idl`. Nothing here runs on chain: the variant order
+//! mirrors each instruction's `DISCRIMINATOR` and the accounts mirror their `TryFrom` order.
+
+use pinocchio::Address;
+use shank::ShankInstruction;
+
+type Pubkey = Address;
+
+#[derive(ShankInstruction)]
+pub enum EscrowInstruction {
+    /// Open an offer and move `amount` of token A into the vault.
+    /// `expires_at`, `refund_to`, `allowed_taker` and `order_ref` are optional trailing fields.
+    /// When `maker_authority` is a token multisig its signers follow it.
+    #[account(0, writable, signer, name = "maker", desc = "Creator of the escrow")]
+    #[account(1, writable, name = "escrow", desc = "PDA [\"escrow\", maker, seed]")]
+    #[account(2, name = "mint_a", desc = "Token being deposited")]
+    #[account(3, name = "mint_b", desc = "Token being requested")]
+    #[account(4, writable, name = "maker_ata_a", desc = "Maker token A account")]
+    #[account(5, writable, name = "vault", desc = "Escrow token A account")]
+    #[account(6, name = "system_program", desc = "System program")]
+    #[account(7, name = "token_program", desc = "Token program")]
+    #[account(
+        8,
+        name = "associated_token_program",
+        desc = "Associated token program"
+    )]
+    #[account(
+        9,
+        optional,
+        name = "maker_authority",
+        desc = "Owner of maker_ata_a, the maker if omitted"
+    )]
+    Make {
+        seed: u64,
+        receive: u64,
+        amount: u64,
+        expires_at: i64,
+        refund_to: Pubkey,
+        allowed_taker: Pubkey,
+        order_ref: [u8; 32],
+    },
+
+    /// Pay `receive` of token B to the maker, less the protocol fee, and take the vault.
+    /// When token B is the native mint the taker pays in SOL, wrapped into `taker_ata_b`.
+    #[account(0, writable, signer, name = "taker", desc = "Taker of the offer")]
+    #[account(1, writable, name = "maker", desc = "Creator of the escrow")]
+    #[account(2, writable, name = "escrow", desc = "PDA [\"escrow\", maker, seed]")]
+    #[account(3, name = "mint_a", desc = "Token being deposited")]
+    #[account(4, name = "mint_b", desc = "Token being requested")]
+    #[account(5, writable, name = "vault", desc = "Escrow token A account")]
+    #[account(6, writable, name = "taker_ata_a", desc = "Taker token A account")]
+    #[account(
+        7,
+        writable,
+        name = "taker_ata_b",
+        desc = "Taker token B account, created for a SOL payment"
+    )]
+    #[account(8, writable, name = "maker_ata_b", desc = "Maker token B account")]
+    #[account(9, name = "system_program", desc = "System program")]
+    #[account(10, name = "token_program", desc = "Token program")]
+    #[account(
+        11,
+        name = "associated_token_program",
+        desc = "Associated token program"
+    )]
+    #[account(12, name = "config", desc = "PDA [\"config\"]")]
+    #[account(13, writable, name = "treasury_ata_b", desc = "Config token B account")]
+    Take,
+
+    /// Return the vault to the refund wallet and close the escrow.
+    /// When `maker_authority` is a token multisig its signers follow it.
+    #[account(0, writable, signer, name = "maker", desc = "Creator of the escrow")]
+    #[account(1, writable, name = "escrow", desc = "PDA [\"escrow\", maker, seed]")]
+    #[account(2, name = "mint_a", desc = "Token being deposited")]
+    #[account(3, writable, name = "vault", desc = "Escrow token A account")]
+    #[account(
+        4,
+        writable,
+        name = "refund_ata_a",
+        desc = "Refund wallet token A account"
+    )]
+    #[account(5, name = "system_program", desc = "System program")]
+    #[account(6, name = "token_program", desc = "Token program")]
+    #[account(
+        7,
+        name = "associated_token_program",
+        desc = "Associated token program"
+    )]
+    #[account(8, name = "refund_to", desc = "Wallet recorded at Make")]
+    #[account(
+        9,
+        optional,
+        name = "maker_authority",
+        desc = "Owner recorded at Make, the maker if omitted"
+    )]
+    Refund,
+
+    /// Rewrite an escrow in the current layout version, recording the vault's balance as what it
+    /// holds for the offer.
+    #[account(0, writable, signer, name = "maker", desc = "Creator of the escrow")]
+    #[account(1, writable, name = "escrow", desc = "PDA [\"escrow\", maker, seed]")]
+    #[account(2, name = "system_program", desc = "System program")]
+    #[account(3, name = "vault", desc = "Escrow token A account")]
+    #[account(4, name = "token_program", desc = "Token program")]
+    Migrate,
+
+    /// Set the amount of token B a Take by `taker` charges, then the protocol fee kept out of
+    /// it, as return data. Fails where that Take would.
+    #[account(0, name = "escrow", desc = "PDA [\"escrow\", maker, seed]")]
+    #[account(1, name = "taker", desc = "Taker the quote is for")]
+    #[account(2, name = "config", desc = "PDA [\"config\"]")]
+    QuoteTake,
+
+    /// Create the protocol config. Only the program's upgrade authority may.
+    #[account(
+        0,
+        writable,
+        signer,
+        name = "payer",
+        desc = "Program upgrade authority, funds the config"
+    )]
+    #[account(1, writable, name = "config", desc = "PDA [\"config\"]")]
+    #[account(2, name = "system_program", desc = "System program")]
+    #[account(
+        3,
+        name = "program_data",
+        desc = "PDA [program_id] of the upgradeable loader"
+    )]
+    InitConfig { fee_bps: u16, fee_authority: Pubkey },
+
+    /// Update the protocol fee.
+    #[account(0, signer, name = "fee_authority", desc = "Config fee authority")]
+    #[account(1, writable, name = "config", desc = "PDA [\"config\"]")]
+    SetFee { fee_bps: u16 },
+
+    /// Refund every expired escrow in the batch. Followed by up to 8
+    /// `[escrow, mint_a, vault, refund_ata_a, maker]` groups, escrows whose vault belongs to
+    /// the other token program are skipped.
+    #[account(
+        0,
+        name = "token_program",
+        desc = "Token program of the batch's vaults"
+    )]
+    BatchRefund,
+
+    /// Return `amount` of token A to the refund wallet, closing the escrow once empty.
+    /// When `maker_authority` is a token multisig its signers follow it.
+    #[account(0, writable, signer, name = "maker", desc = "Creator of the escrow")]
+    #[account(1, writable, name = "escrow", desc = "PDA [\"escrow\", maker, seed]")]
+    #[account(2, name = "mint_a", desc = "Token being deposited")]
+    #[account(3, writable, name = "vault", desc = "Escrow token A account")]
+    #[account(
+        4,
+        writable,
+        name = "refund_ata_a",
+        desc = "Refund wallet token A account"
+    )]
+    #[account(5, name = "system_program", desc = "System program")]
+    #[account(6, name = "token_program", desc = "Token program")]
+    #[account(
+        7,
+        name = "associated_token_program",
+        desc = "Associated token program"
+    )]
+    #[account(8, name = "refund_to", desc = "Wallet recorded at Make")]
+    #[account(
+        9,
+        optional,
+        name = "maker_authority",
+        desc = "Owner recorded at Make, the maker if omitted"
+    )]
+    RefundPartial { amount: u64 },
+
+    /// Open several offers at once. The data packs `seed`, `receive` and `amount` for each
+    /// offer, the accounts are followed by up to 5 `[escrow, mint_a, mint_b, maker_ata_a,
+    /// vault]` groups.
+    #[account(0, writable, signer, name = "maker", desc = "Creator of the escrows")]
+    #[account(1, name = "system_program", desc = "System program")]
+    #[account(2, name = "token_program", desc = "Token program")]
+    #[account(
+        3,
+        name = "associated_token_program",
+        desc = "Associated token program"
+    )]
+    MakeMany,
+
+    /// Refund an expired escrow on the maker's behalf, tipping the caller from its rent.
+    #[account(0, writable, signer, name = "cranker", desc = "Receives the sweep tip")]
+    #[account(1, writable, name = "maker", desc = "Creator of the escrow")]
+    #[account(2, writable, name = "escrow", desc = "PDA [\"escrow\", maker, seed]")]
+    #[account(3, name = "mint_a", desc = "Token being deposited")]
+    #[account(4, writable, name = "vault", desc = "Escrow token A account")]
+    #[account(
+        5,
+        writable,
+        name = "refund_ata_a",
+        desc = "Refund wallet token A account"
+    )]
+    #[account(6, name = "token_program", desc = "Token program owning the vault")]
+    SweepExpired,
+
+    /// Close an escrow whose vault was never funded, returning its rent to the maker.
+    #[account(0, writable, signer, name = "maker", desc = "Creator of the escrow")]
+    #[account(1, writable, name = "escrow", desc = "PDA [\"escrow\", maker, seed]")]
+    #[account(2, name = "mint_a", desc = "Token being deposited")]
+    #[account(
+        3,
+        writable,
+        name = "vault",
+        desc = "Escrow ATA for mint_a, may not exist"
+    )]
+    #[account(4, name = "token_program", desc = "Token program")]
+    CancelUnfunded { seed: u64 },
+
+    /// Send the protocol fees the config holds in one mint to any account of that mint.
+    #[account(0, signer, name = "fee_authority", desc = "Config fee authority")]
+    #[account(1, name = "config", desc = "PDA [\"config\"]")]
+    #[account(2, name = "mint", desc = "Mint of the fees")]
+    #[account(
+        3,
+        writable,
+        name = "treasury_ata",
+        desc = "Config token account for mint"
+    )]
+    #[account(4, writable, name = "destination", desc = "Receives the fees")]
+    #[account(5, name = "token_program", desc = "Token program")]
+    WithdrawFees,
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use crate::instructions::*;
+
+    /// Every handler's discriminator, by the name of its `EscrowInstruction` variant.
+    const DISCRIMINATORS: [(&str, u8); 13] = [
+        ("Make", *Make::DISCRIMINATOR),
+        ("Take", *Take::DISCRIMINATOR),
+        ("Refund", *Refund::DISCRIMINATOR),
+        ("Migrate", *Migrate::DISCRIMINATOR),
+        ("QuoteTake", *QuoteTake::DISCRIMINATOR),
+        ("InitConfig", *InitConfig::DISCRIMINATOR),
+        ("SetFee", *SetFee::DISCRIMINATOR),
+        ("BatchRefund", *BatchRefund::DISCRIMINATOR),
+        ("RefundPartial", *RefundPartial::DISCRIMINATOR),
+        ("MakeMany", *MakeMany::DISCRIMINATOR),
+        ("SweepExpired", *SweepExpired::DISCRIMINATOR),
+        ("CancelUnfunded", *CancelUnfunded::DISCRIMINATOR),
+        ("WithdrawFees", *WithdrawFees::DISCRIMINATOR),
+    ];
+
+    /// Variant names of `EscrowInstruction`, in declaration order, which shank numbers from 0.
+    fn variants() -> Vec<&'static str> {
+        include_str!("idl.rs")
+            .lines()
+            .filter_map(|line| line.strip_prefix("    "))
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_uppercase()))
+            .map(|line| line.split([' ', ',']).next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn variant_order_matches_the_discriminators() {
+        let variants = variants();
+        assert_eq!(variants.len(), DISCRIMINATORS.len());
+        for (name, discriminator) in DISCRIMINATORS {
+            let position = variants.iter().position(|variant| *variant == name);
+            assert_eq!(position, Some(discriminator as usize), "{name}");
+        }
+    }
+
+    #[test]
+    fn checked_in_idl_is_up_to_date() {
+        let idl: serde_json::Value =
+            serde_json::from_str(include_str!("../idl/blueshift_escrow.json")).unwrap();
+        let mut instructions: Vec<(&str, u64)> = idl["instructions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|ix| {
+                (
+                    ix["name"].as_str().unwrap(),
+                    ix["discriminant"]["value"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        instructions.sort_by_key(|(_, discriminator)| *discriminator);
+
+        let mut expected: Vec<(&str, u64)> = DISCRIMINATORS
+            .iter()
+            .map(|(name, discriminator)| (*name, *discriminator as u64))
+            .collect();
+        expected.sort_by_key(|(_, discriminator)| *discriminator);
+        assert_eq!(
+            instructions, expected,
+            "regenerate with `shank idl -r blueshift_escrow -o blueshift_escrow/idl`"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use blueshift_pinocchio_helpers::require_eq;
+
+use super::helpers::*;
+use super::refund::verify_refund;
+use crate::errors::PinocchioError;
+use crate::state::Escrow;
+use pinocchio::{
+    cpi::{Seed, Signer},
+    error::ProgramError,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, ProgramResult,
+};
+
+/// Accounts per refunded escrow: `[escrow, mint_a, vault, refund_ata_a, maker]`.
+const REFUND_ACCOUNTS_LEN: usize = 5;
+/// Upper bound on escrows per instruction, keeps a full batch within the compute budget.
+const MAX_BATCH_LEN: usize = 8;
+
+pub struct BatchRefundAccounts<'a> {
+    pub token_program: &'a AccountView,
+    pub refunds: &'a [AccountView],
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for BatchRefundAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [token_program, refunds @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if refunds.is_empty() || refunds.len() % REFUND_ACCOUNTS_LEN != 0 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        if refunds.len() > REFUND_ACCOUNTS_LEN * MAX_BATCH_LEN {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        TokenProgram::check(token_program)?;
+
+        Ok(Self {
+            token_program,
+            refunds,
+        })
+    }
+}
+
+pub struct BatchRefund<'a> {
+    pub accounts: BatchRefundAccounts<'a>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for BatchRefund<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let accounts = BatchRefundAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> BatchRefund<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &7;
+
+    pub fn process(&self) -> ProgramResult {
+        let now = Clock::get()?.unix_timestamp;
+
+        for refund in self.accounts.refunds.chunks_exact(REFUND_ACCOUNTS_LEN) {
+            let [escrow, mint_a, vault, refund_ata_a, maker] = refund else {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            };
+            self.refund_if_expired(escrow, mint_a, vault, refund_ata_a, maker, now)?;
+        }
+
+        Ok(())
+    }
+
+    /// Refund a single escrow. Already closed and still live escrows are skipped so one
+    /// stale entry does not fail the whole batch, as are escrows whose vault belongs to the
+    /// other token program: a batch is refunded through one program.
+    fn refund_if_expired(
+        &self,
+        escrow: &AccountView,
+        mint_a: &AccountView,
+        vault: &AccountView,
+        refund_ata_a: &AccountView,
+        maker: &AccountView,
+        now: i64,
+    ) -> ProgramResult {
+        if !escrow.owned_by(&crate::ID) || escrow.data_len() == 0 {
+            return Ok(());
+        }
+        ProgramAccount::check(escrow)?;
+        if !vault.owned_by(self.accounts.token_program.address()) {
+            return Ok(());
+        }
+
+        let (seed_binding, bump_binding) = {
+            let data = escrow.try_borrow()?;
+            let escrow_state = Escrow::load(&data)?;
+
+            if !escrow_state.is_expired(now) {
+                return Ok(());
+            }
+
+            verify_refund(
+                &escrow_state,
+                escrow,
+                maker.address(),
+                mint_a.address(),
+                vault,
+                &TokenInterface::owner(refund_ata_a)?,
+            )?;
+            require_eq!(
+                &TokenInterface::mint(refund_ata_a)?,
+                escrow_state.mint_a(),
+                PinocchioError::InvalidAccountData
+            );
+
+            (escrow_state.seed().to_le_bytes(), escrow_state.bump())
+        };
+
+        let escrow_seeds = [
+            Seed::from(Escrow::SEED),
+            Seed::from(maker.address().as_ref()),
+            Seed::from(&seed_binding),
+            Seed::from(&bump_binding),
+        ];
+        let signers = [Signer::from(&escrow_seeds)];
+
+        let amount = TokenInterface::amount(vault)?;
+
+        TokenInterface::transfer_checked(vault, mint_a, refund_ata_a, escrow, amount, &signers)?;
+        TokenInterface::close_account(vault, maker, escrow, &signers)?;
+
+        ProgramAccount::close(escrow, maker)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+    use pinocchio::Address;
+    use pinocchio_token_2022::ID as TOKEN_2022_PROGRAM_ID;
+
+    use super::*;
+    use crate::state::derive_escrow;
+
+    const SEED: u64 = 3;
+    const NOW: i64 = 2_000;
+
+    /// Refund one escrow expired at `NOW - 1` whose vault belongs to `vault_program`, in a
+    /// batch run through the classic token program.
+    fn refund(vault_program: Address, passed_mint_a: Address) -> (ProgramResult, TestAccount) {
+        let maker_key = Address::new_from_array([1; 32]);
+        let mint_a_key = Address::new_from_array([2; 32]);
+        let (escrow_key, bump) = derive_escrow(&maker_key, SEED);
+
+        let mut data = [0u8; Escrow::LEN];
+        let state = Escrow::load_mut(&mut data).unwrap();
+        state.set_inner(
+            SEED,
+            maker_key,
+            mint_a_key,
+            Address::new_from_array([3; 32]),
+            10,
+            [bump],
+        );
+        state.set_expires_at(NOW - 1);
+        state.set_refund_to(maker_key);
+
+        let mut token_program = TestAccount::empty(pinocchio_token::ID);
+        let mut escrow = TestAccount::new(escrow_key, crate::ID, &data);
+        let mut mint_a = TestAccount::mint(passed_mint_a, vault_program, 6);
+        let mut vault = TestAccount::token(
+            Address::new_from_array([4; 32]),
+            vault_program,
+            &mint_a_key,
+            &escrow_key,
+            100,
+        );
+        let mut refund_ata_a = TestAccount::token(
+            Address::new_from_array([5; 32]),
+            vault_program,
+            &mint_a_key,
+            &maker_key,
+            0,
+        );
+        let mut maker = TestAccount::empty(maker_key);
+
+        let accounts = [
+            token_program.view(),
+            escrow.view(),
+            mint_a.view(),
+            vault.view(),
+            refund_ata_a.view(),
+            maker.view(),
+        ];
+        let batch = BatchRefund::try_from(&accounts[..]).unwrap();
+        let [escrow_view, mint_a_view, vault_view, refund_ata_a_view, maker_view] = &accounts[1..]
+        else {
+            unreachable!()
+        };
+        let result = batch.refund_if_expired(
+            escrow_view,
+            mint_a_view,
+            vault_view,
+            refund_ata_a_view,
+            maker_view,
+            NOW,
+        );
+        (result, escrow)
+    }
+
+    #[test]
+    fn skips_escrow_whose_vault_belongs_to_the_other_token_program() {
+        let mint_a = Address::new_from_array([2; 32]);
+        let (result, escrow) = refund(TOKEN_2022_PROGRAM_ID, mint_a);
+        assert_eq!(result, Ok(()));
+        // Left open for a batch run through token-2022
+        assert_eq!(escrow.data().len(), Escrow::LEN);
+    }
+
+    #[test]
+    fn rejects_mint_other_than_the_escrow_mint_a() {
+        let (result, _) = refund(pinocchio_token::ID, Address::new_from_array([9; 32]));
+        assert_eq!(result, Err(PinocchioError::InvalidAccountData.into()));
+    }
+
+    #[test]
+    fn accepts_either_token_program() {
+        for program in [pinocchio_token::ID, TOKEN_2022_PROGRAM_ID] {
+            let mut token_program = TestAccount::empty(program);
+            let mut group: [TestAccount; REFUND_ACCOUNTS_LEN] = core::array::from_fn(|i| {
+                TestAccount::empty(Address::new_from_array([i as u8; 32]))
+            });
+            let mut accounts = vec![token_program.view()];
+            accounts.extend(group.iter_mut().map(TestAccount::view));
+            assert!(BatchRefundAccounts::try_from(&accounts[..]).is_ok());
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use pinocchio::{
+    cpi::{Seed, Signer},
+    error::ProgramError,
+    AccountView, ProgramResult,
+};
+
+use super::helpers::*;
+use super::make::Make;
+use crate::errors::PinocchioError;
+use crate::state::{derive_escrow, Escrow};
+
+pub struct CancelUnfundedAccounts<'a> {
+    pub maker: &'a AccountView,
+    pub escrow: &'a AccountView,
+    pub mint_a: &'a AccountView,
+    pub vault: &'a AccountView,
+    pub token_program: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for CancelUnfundedAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [maker, escrow, mint_a, vault, token_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(maker)?;
+        TokenProgram::check(token_program)?;
+        MintInterface::check(mint_a)?;
+
+        // The vault address below derives from the token program the mint belongs to
+        if !mint_a.owned_by(token_program.address()) {
+            return Err(PinocchioError::InvalidOwner.into());
+        }
+
+        // The escrow may never have been populated, so only its owner is checked
+        if !escrow.owned_by(&crate::ID) {
+            return Err(PinocchioError::InvalidOwner.into());
+        }
+
+        Ok(Self {
+            maker,
+            escrow,
+            mint_a,
+            vault,
+            token_program,
+        })
+    }
+}
+
+pub struct CancelUnfundedInstructionData {
+    pub seed: u64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for CancelUnfundedInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<u64>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self {
+            seed: u64::from_le_bytes(data.try_into().unwrap()),
+        })
+    }
+}
+
+pub struct CancelUnfunded<'a> {
+    pub accounts: CancelUnfundedAccounts<'a>,
+    pub instruction_data: CancelUnfundedInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for CancelUnfunded<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = CancelUnfundedAccounts::try_from(accounts)?;
+        let instruction_data = CancelUnfundedInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> CancelUnfunded<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &11;
+
+    pub fn process(&self) -> ProgramResult {
+        // The seed comes from the caller, the stored one may not exist
+        let seed_binding = self.instruction_data.seed.to_le_bytes();
+        let (escrow_key, bump) =
+            derive_escrow(self.accounts.maker.address(), self.instruction_data.seed);
+        if escrow_key.ne(self.accounts.escrow.address()) {
+            return Err(PinocchioError::InvalidAddress.into());
+        }
+
+        // A populated escrow records the mint its vault holds
+        {
+            let data = self.accounts.escrow.try_borrow()?;
+            if data.iter().any(|&byte| byte != 0)
+                && Escrow::load(&data)?
+                    .mint_a()
+                    .ne(self.accounts.mint_a.address())
+            {
+                return Err(PinocchioError::InvalidAccountData.into());
+            }
+        }
+
+        // Only the escrow's own vault shows whether the offer was funded, any other empty
+        // account would let a funded escrow be closed over its tokens
+        let vault_key = AssociatedTokenAccount::address(
+            self.accounts.escrow.address(),
+            self.accounts.mint_a.address(),
+            self.accounts.token_program.address(),
+        );
+        if vault_key.ne(self.accounts.vault.address()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        if Make::is_funded(self.accounts.vault)? {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Close the empty vault if it was created
+        if self.accounts.vault.data_len() != 0 {
+            let bump_binding = [bump];
+            let escrow_seeds = [
+                Seed::from(Escrow::SEED),
+                Seed::from(self.accounts.maker.address().as_ref()),
+                Seed::from(&seed_binding),
+                Seed::from(&bump_binding),
+            ];
+
+            TokenInterface::close_account(
+                self.accounts.vault,
+                self.accounts.maker,
+                self.accounts.escrow,
+                &[Signer::from(&escrow_seeds)],
+            )?;
+        }
+
+        ProgramAccount::close(self.accounts.escrow, self.accounts.maker)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+    use pinocchio::Address;
+
+    use super::*;
+
+    const SEED: u64 = 7;
+
+    fn escrow_data(maker: &Address, mint_a: &Address, bump: u8) -> [u8; Escrow::LEN] {
+        let mut data = [0u8; Escrow::LEN];
+        let escrow = Escrow::load_mut(&mut data).unwrap();
+        escrow.set_inner(
+            SEED,
+            *maker,
+            *mint_a,
+            Address::new_from_array([3; 32]),
+            10,
+            [bump],
+        );
+        data
+    }
+
+    /// Run `CancelUnfunded` for a funded escrow, handing it `vault` as the escrow's vault.
+    fn cancel_with_vault(vault: impl FnOnce(&Address, &Address) -> TestAccount) -> ProgramResult {
+        let maker_key = Address::new_from_array([1; 32]);
+        let mint_a_key = Address::new_from_array([2; 32]);
+        let (escrow_key, bump) = derive_escrow(&maker_key, SEED);
+
+        let mut maker = TestAccount::empty(maker_key).signer();
+        let mut escrow = TestAccount::new(
+            escrow_key,
+            crate::ID,
+            &escrow_data(&maker_key, &mint_a_key, bump),
+        );
+        let mut mint_a = TestAccount::mint(mint_a_key, pinocchio_token::ID, 6);
+        let mut vault = vault(&escrow_key, &mint_a_key);
+        let mut token_program = TestAccount::empty(pinocchio_token::ID);
+
+        let accounts = [
+            maker.view(),
+            escrow.view(),
+            mint_a.view(),
+            vault.view(),
+            token_program.view(),
+        ];
+        CancelUnfunded::try_from((&SEED.to_le_bytes()[..], &accounts[..]))?.process()
+    }
+
+    #[test]
+    fn rejects_empty_account_standing_in_for_the_vault() {
+        let result = cancel_with_vault(|_, _| TestAccount::empty(Address::new_from_array([9; 32])));
+        assert_eq!(result, Err(ProgramError::InvalidSeeds));
+    }
+
+    #[test]
+    fn rejects_empty_escrow_token_account_of_another_mint() {
+        let result = cancel_with_vault(|escrow, _| {
+            let other_mint = Address::new_from_array([4; 32]);
+            let address =
+                AssociatedTokenAccount::address(escrow, &other_mint, &pinocchio_token::ID);
+            TestAccount::token(address, pinocchio_token::ID, &other_mint, escrow, 0)
+        });
+        assert_eq!(result, Err(ProgramError::InvalidSeeds));
+    }
+
+    #[test]
+    fn rejects_funded_vault() {
+        let result = cancel_with_vault(|escrow, mint_a| {
+            let address = AssociatedTokenAccount::address(escrow, mint_a, &pinocchio_token::ID);
+            TestAccount::token(address, pinocchio_token::ID, mint_a, escrow, 100)
+        });
+        assert_eq!(result, Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn closes_escrow_whose_vault_was_never_created() {
+        let result = cancel_with_vault(|escrow, mint_a| {
+            TestAccount::empty(AssociatedTokenAccount::address(
+                escrow,
+                mint_a,
+                &pinocchio_token::ID,
+            ))
+        });
+        assert_eq!(result, Ok(()));
+    }
+}
@@ -1,16 +1,20 @@
 use pinocchio::{
-    cpi::{Seed, Signer},
+    cpi::{invoke_with_bounds, Seed, Signer},
     error::ProgramError,
+    instruction::{InstructionAccount, InstructionView},
     sysvars::{rent::Rent, Sysvar},
     AccountView, Address, ProgramResult,
 };
-use pinocchio_associated_token_account::instructions::Create;
-use pinocchio_system::instructions::CreateAccount;
+use pinocchio_system::instructions::{CreateAccount, Transfer};
+use pinocchio_token::{
+    instructions::{SyncNative, MAX_MULTISIG_SIGNERS},
+    state::Multisig,
+};
 use pinocchio_token_2022::ID as TOKEN_2022_PROGRAM_ID;
 
-const TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET: usize = 165;
-const TOKEN_2022_MINT_DISCRIMINATOR: u8 = 0x01;
-const TOKEN_2022_TOKEN_ACCOUNT_DISCRIMINATOR: u8 = 0x02;
+pub use blueshift_pinocchio_helpers::{
+    AssociatedTokenAccount, MintInterface, ProgramData, TokenInterface,
+};
 
 use crate::errors::PinocchioError;
 
@@ -25,129 +29,180 @@ impl SignerAccount {
     }
 }
 
-pub struct MintInterface;
+pub struct SystemProgram;
 
-impl MintInterface {
+impl SystemProgram {
     pub fn check(account: &AccountView) -> Result<(), ProgramError> {
-        if !account.owned_by(&TOKEN_2022_PROGRAM_ID) {
-            if !account.owned_by(&pinocchio_token::ID) {
-                return Err(PinocchioError::InvalidOwner.into());
-            } else {
-                if account.data_len().ne(&pinocchio_token::state::Mint::LEN) {
-                    return Err(PinocchioError::InvalidAccountData.into());
-                }
-            }
-        } else {
-            let data = account.try_borrow()?;
-
-            if data.len().ne(&pinocchio_token::state::Mint::LEN) {
-                if data.len().le(&TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET) {
-                    return Err(PinocchioError::InvalidAccountData.into());
-                }
-                if data[TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET].ne(&TOKEN_2022_MINT_DISCRIMINATOR)
-                {
-                    return Err(PinocchioError::InvalidAccountData.into());
-                }
-            }
+        if account.address().ne(&pinocchio_system::ID) {
+            return Err(PinocchioError::InvalidProgram.into());
         }
-
         Ok(())
     }
 }
 
-pub struct TokenInterface;
-
-impl TokenInterface {
-    fn check(account: &AccountView) -> Result<(), ProgramError> {
-        if !account.owned_by(&TOKEN_2022_PROGRAM_ID) {
-            if !account.owned_by(&pinocchio_token::ID) {
-                return Err(PinocchioError::InvalidOwner.into());
-            } else {
-                if account
-                    .data_len()
-                    .ne(&pinocchio_token::state::TokenAccount::LEN)
-                {
-                    return Err(PinocchioError::InvalidAccountData.into());
-                }
-            }
-        } else {
-            let data = account.try_borrow()?;
-
-            if data.len().ne(&pinocchio_token::state::TokenAccount::LEN) {
-                if data.len().le(&TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET) {
-                    return Err(PinocchioError::InvalidAccountData.into());
-                }
-                if data[TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET]
-                    .ne(&TOKEN_2022_TOKEN_ACCOUNT_DISCRIMINATOR)
-                {
-                    return Err(PinocchioError::InvalidAccountData.into());
-                }
-            }
-        }
+pub struct TokenProgram;
 
+impl TokenProgram {
+    pub fn check(account: &AccountView) -> Result<(), ProgramError> {
+        if account.address().ne(&pinocchio_token::ID)
+            && account.address().ne(&TOKEN_2022_PROGRAM_ID)
+        {
+            return Err(PinocchioError::InvalidProgram.into());
+        }
         Ok(())
     }
 }
 
-pub struct AssociatedTokenAccount;
+pub struct AssociatedTokenProgram;
 
-impl AssociatedTokenAccount {
-    pub fn check(
-        account: &AccountView,
-        authority: &AccountView,
-        mint: &AccountView,
-        token_program: &AccountView,
-    ) -> Result<(), ProgramError> {
-        TokenInterface::check(account)?;
-
-        if Address::find_program_address(
-            &[
-                authority.address().as_array(),
-                token_program.address().as_array(),
-                mint.address().as_array(),
-            ],
-            &pinocchio_associated_token_account::ID,
-        )
-        .0
-        .ne(account.address())
+impl AssociatedTokenProgram {
+    pub fn check(account: &AccountView) -> Result<(), ProgramError> {
+        if account
+            .address()
+            .ne(&pinocchio_associated_token_account::ID)
         {
-            return Err(PinocchioError::InvalidAddress.into());
+            return Err(PinocchioError::InvalidProgram.into());
+        }
+        Ok(())
+    }
+}
+
+/// Owner of a token account: a wallet that signs itself, or a token multisig whose
+/// signers follow it in the accounts.
+pub struct TokenAuthority;
+
+impl TokenAuthority {
+    pub fn check(authority: &AccountView, signers: &[AccountView]) -> Result<(), ProgramError> {
+        if signers.is_empty() {
+            return SignerAccount::check(authority);
+        }
+        if signers.len() > MAX_MULTISIG_SIGNERS {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let multisig = Multisig::from_account_view(authority)?;
+        if !multisig.is_initialized() {
+            return Err(PinocchioError::InvalidAccountData.into());
+        }
+
+        // Each multisig member counts once, however many times it is passed
+        let approvals = multisig
+            .signers()
+            .iter()
+            .filter(|member| {
+                signers
+                    .iter()
+                    .any(|signer| signer.is_signer() && signer.address().eq(*member))
+            })
+            .count();
+        if approvals < multisig.required_signers() as usize {
+            return Err(PinocchioError::NotSigner.into());
         }
 
         Ok(())
     }
 
-    pub fn init(
-        account: &AccountView,
+    /// `TransferChecked` out of a token account owned by `authority`, passing the multisig
+    /// signers through to the token program.
+    pub fn transfer(
+        from: &AccountView,
         mint: &AccountView,
-        payer: &AccountView,
-        owner: &AccountView,
-        system_program: &AccountView,
-        token_program: &AccountView,
+        to: &AccountView,
+        authority: &AccountView,
+        signers: &[AccountView],
+        amount: u64,
     ) -> ProgramResult {
-        Create {
-            funding_account: payer,
-            account,
-            wallet: owner,
-            mint,
-            system_program,
-            token_program,
+        if signers.is_empty() {
+            return TokenInterface::transfer_checked(from, mint, to, authority, amount, &[]);
         }
-        .invoke()
+        if signers.len() > MAX_MULTISIG_SIGNERS {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        TokenInterface::check(from)?;
+        let decimals = MintInterface::decimals(mint)?;
+        let token_program = match from.owned_by(&TOKEN_2022_PROGRAM_ID) {
+            true => &TOKEN_2022_PROGRAM_ID,
+            false => &pinocchio_token::ID,
+        };
+
+        const MAX_ACCOUNTS: usize = 4 + MAX_MULTISIG_SIGNERS;
+        let account_views: [&AccountView; MAX_ACCOUNTS] = core::array::from_fn(|i| match i {
+            0 => from,
+            1 => mint,
+            2 => to,
+            3 => authority,
+            _ => signers.get(i - 4).unwrap_or(authority),
+        });
+        let instruction_accounts: [InstructionAccount; MAX_ACCOUNTS] =
+            core::array::from_fn(|i| match i {
+                0 | 2 => InstructionAccount::writable(account_views[i].address()),
+                1 => InstructionAccount::readonly(mint.address()),
+                3 => InstructionAccount::readonly(authority.address()),
+                _ => InstructionAccount::readonly_signer(account_views[i].address()),
+            });
+
+        // Same data as the single signer `TransferChecked`: discriminator, amount, decimals
+        let mut data = [12u8; 10];
+        data[1..9].copy_from_slice(&amount.to_le_bytes());
+        data[9] = decimals;
+
+        let len = 4 + signers.len();
+        invoke_with_bounds::<MAX_ACCOUNTS>(
+            &InstructionView {
+                program_id: token_program,
+                data: &data,
+                accounts: &instruction_accounts[..len],
+            },
+            &account_views[..len],
+        )
     }
+}
 
-    pub fn init_if_needed(
-        account: &AccountView,
-        mint: &AccountView,
-        payer: &AccountView,
-        owner: &AccountView,
-        system_program: &AccountView,
-        token_program: &AccountView,
-    ) -> ProgramResult {
-        match Self::check(account, payer, mint, token_program) {
-            Ok(_) => Ok(()),
-            Err(_) => Self::init(account, mint, payer, owner, system_program, token_program),
+pub struct ConfigAccount;
+
+impl ConfigAccount {
+    pub fn check(account: &AccountView) -> Result<(), ProgramError> {
+        if !account.owned_by(&crate::ID) {
+            return Err(PinocchioError::InvalidOwner.into());
+        }
+
+        if account.data_len().ne(&crate::state::Config::LEN) {
+            return Err(PinocchioError::InvalidAccountData.into());
         }
+
+        Ok(())
+    }
+}
+
+/// Wrapped SOL, the token program's native mint, whose token accounts hold their balance as
+/// lamports.
+pub struct NativeMint;
+
+impl NativeMint {
+    pub const ID: Address = Address::new_from_array([
+        6, 155, 136, 87, 254, 171, 129, 132, 251, 104, 127, 99, 70, 24, 192, 53, 218, 196, 57, 220,
+        26, 235, 59, 85, 152, 160, 240, 0, 0, 0, 0, 1,
+    ]);
+
+    /// Top the wrapped SOL account `token_account` up to `amount`, out of `owner`'s lamports.
+    pub fn wrap(owner: &AccountView, token_account: &AccountView, amount: u64) -> ProgramResult {
+        let missing = amount.saturating_sub(TokenInterface::amount(token_account)?);
+        if missing == 0 {
+            return Ok(());
+        }
+
+        Transfer {
+            from: owner,
+            to: token_account,
+            lamports: missing,
+        }
+        .invoke()?;
+
+        SyncNative {
+            native_token: token_account,
+        }
+        .invoke()
     }
 }
 
@@ -159,9 +214,8 @@ impl ProgramAccount {
             return Err(PinocchioError::InvalidOwner.into());
         }
 
-        if account.data_len().ne(&crate::state::Escrow::LEN) {
-            return Err(PinocchioError::InvalidAccountData.into());
-        }
+        // Accept every known layout, older ones are mapped on load
+        crate::state::Escrow::version_of(&account.try_borrow()?)?;
 
         Ok(())
     }
@@ -207,3 +261,39 @@ impl ProgramAccount {
         account.close()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+
+    use super::*;
+
+    /// Close an escrow holding `lamports` into a destination holding `destination_lamports`.
+    fn close(lamports: u64, destination_lamports: u64) -> (ProgramResult, u64) {
+        let mut account = TestAccount::new(Address::new_from_array([1; 32]), crate::ID, &[0; 8]);
+        let mut destination = TestAccount::empty(Address::new_from_array([2; 32]));
+        let (account, destination) = (account.view(), destination.view());
+        account.set_lamports(lamports);
+        destination.set_lamports(destination_lamports);
+
+        let result = ProgramAccount::close(&account, &destination);
+        (result, destination.lamports())
+    }
+
+    #[test]
+    fn closes_an_account_into_its_destination() {
+        assert_eq!(close(1_000, u64::MAX - 1_000), (Ok(()), u64::MAX));
+    }
+
+    #[test]
+    fn rejects_a_close_overflowing_the_destination() {
+        assert_eq!(
+            close(1_001, u64::MAX - 1_000),
+            (Err(ProgramError::ArithmeticOverflow), u64::MAX - 1_000)
+        );
+        assert_eq!(
+            close(u64::MAX, u64::MAX),
+            (Err(ProgramError::ArithmeticOverflow), u64::MAX)
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use pinocchio::{cpi::Seed, error::ProgramError, AccountView, Address, ProgramResult};
+
+use super::helpers::*;
+use crate::state::Config;
+
+pub struct InitConfigAccounts<'a> {
+    pub payer: &'a AccountView,
+    pub config: &'a AccountView,
+    pub system_program: &'a AccountView,
+    pub program_data: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for InitConfigAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [payer, config, system_program, program_data] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(payer)?;
+        SystemProgram::check(system_program)?;
+        // Whoever lands first would otherwise own the fee on every `Take` for good
+        ProgramData::check_upgrade_authority(program_data, &crate::ID, payer.address())?;
+
+        Ok(Self {
+            payer,
+            config,
+            system_program,
+            program_data,
+        })
+    }
+}
+
+pub struct InitConfigInstructionData {
+    pub fee_bps: u16,
+    pub fee_authority: Address,
+}
+
+impl<'a> TryFrom<&'a [u8]> for InitConfigInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<u16>() + size_of::<Address>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let fee_bps = u16::from_le_bytes(data[0..2].try_into().unwrap());
+        let fee_authority: [u8; 32] = data[2..34].try_into().unwrap();
+
+        Ok(Self {
+            fee_bps,
+            fee_authority: fee_authority.into(),
+        })
+    }
+}
+
+pub struct InitConfig<'a> {
+    pub accounts: InitConfigAccounts<'a>,
+    pub instruction_data: InitConfigInstructionData,
+    pub bump: u8,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for InitConfig<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = InitConfigAccounts::try_from(accounts)?;
+        let instruction_data = InitConfigInstructionData::try_from(data)?;
+
+        let (_, bump) = Address::find_program_address(&[Config::SEED], &crate::ID);
+
+        // Creating the PDA fails if it already exists, so this can only run once
+        let bump_binding = [bump];
+        let config_seeds = [Seed::from(Config::SEED), Seed::from(&bump_binding)];
+        ProgramAccount::init::<Config>(
+            accounts.payer,
+            accounts.config,
+            &config_seeds,
+            Config::LEN,
+        )?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+            bump,
+        })
+    }
+}
+
+impl<'a> InitConfig<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &5;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let mut data = self.accounts.config.try_borrow_mut()?;
+        let config = Config::load_mut(data.as_mut())?;
+
+        config.set_inner(
+            self.instruction_data.fee_bps,
+            self.instruction_data.fee_authority,
+            [self.bump],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+
+    use super::*;
+
+    fn program_data(upgrade_authority: Option<&Address>) -> TestAccount {
+        TestAccount::program_data(&crate::ID, upgrade_authority)
+    }
+
+    fn init_accounts(payer: &Address, mut program_data: TestAccount) -> Result<(), ProgramError> {
+        let mut payer = TestAccount::empty(*payer).signer();
+        let mut config = TestAccount::empty(Address::new_from_array([2; 32]));
+        let mut system_program = TestAccount::empty(pinocchio_system::ID);
+
+        let accounts = [
+            payer.view(),
+            config.view(),
+            system_program.view(),
+            program_data.view(),
+        ];
+        InitConfigAccounts::try_from(&accounts[..]).map(|_| ())
+    }
+
+    #[test]
+    fn accepts_the_upgrade_authority() {
+        let authority = Address::new_from_array([1; 32]);
+        assert_eq!(
+            init_accounts(&authority, program_data(Some(&authority))),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_anyone_else() {
+        let authority = Address::new_from_array([1; 32]);
+        let squatter = Address::new_from_array([9; 32]);
+        assert_eq!(
+            init_accounts(&squatter, program_data(Some(&authority))),
+            Err(ProgramError::IncorrectAuthority)
+        );
+    }
+
+    #[test]
+    fn rejects_immutable_program() {
+        let authority = Address::new_from_array([1; 32]);
+        assert_eq!(
+            init_accounts(&authority, program_data(None)),
+            Err(ProgramError::IncorrectAuthority)
+        );
+    }
+
+    #[test]
+    fn rejects_program_data_of_another_program() {
+        let authority = Address::new_from_array([1; 32]);
+        let mut data = [0u8; 45];
+        data[0] = 3;
+        data[12] = 1;
+        data[13..45].copy_from_slice(authority.as_ref());
+        let forged = TestAccount::new(
+            Address::new_from_array([7; 32]),
+            ProgramData::LOADER_ID,
+            &data,
+        );
+        assert_eq!(
+            init_accounts(&authority, forged),
+            Err(ProgramError::InvalidSeeds)
+        );
+    }
+}
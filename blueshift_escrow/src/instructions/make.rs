@@ -1,8 +1,14 @@
-use pinocchio::{cpi::Seed, error::ProgramError, AccountView, Address, ProgramResult};
-use pinocchio_token::instructions::Transfer;
+use pinocchio::{
+    cpi::Seed,
+    error::ProgramError,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, Address, ProgramResult,
+};
 
 use super::helpers::*;
-use crate::state::Escrow;
+use crate::errors::PinocchioError;
+use crate::events::MakeEvent;
+use crate::state::{derive_escrow, Escrow};
 
 pub struct MakeAccounts<'a> {
     pub maker: &'a AccountView,
@@ -13,26 +19,29 @@ pub struct MakeAccounts<'a> {
     pub vault: &'a AccountView,
     pub system_program: &'a AccountView,
     pub token_program: &'a AccountView,
+    pub maker_authority: &'a AccountView,
+    pub signers: &'a [AccountView],
 }
 
 impl<'a> TryFrom<&'a [AccountView]> for MakeAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
-        let [maker, escrow, mint_a, mint_b, maker_ata_a, vault, system_program, token_program, _] =
+        let [maker, escrow, mint_a, mint_b, maker_ata_a, vault, system_program, token_program, associated_token_program, authority @ ..] =
             accounts
         else {
             return Err(ProgramError::NotEnoughAccountKeys);
         };
 
-        // Basic Accounts Checks
-        SignerAccount::check(maker)?;
-        MintInterface::check(mint_a)?;
-        MintInterface::check(mint_b)?;
-        AssociatedTokenAccount::check(maker_ata_a, maker, mint_a, token_program)?;
+        // An optional `[maker_authority, signers @ ..]` tail lets another wallet or a token
+        // multisig own the deposited tokens
+        let (maker_authority, signers) = authority.split_first().unwrap_or((maker, &[]));
 
-        // Return the accounts
-        Ok(Self {
+        SystemProgram::check(system_program)?;
+        TokenProgram::check(token_program)?;
+        AssociatedTokenProgram::check(associated_token_program)?;
+
+        let accounts = Self {
             maker,
             escrow,
             mint_a,
@@ -41,7 +50,31 @@ impl<'a> TryFrom<&'a [AccountView]> for MakeAccounts<'a> {
             vault,
             system_program,
             token_program,
-        })
+            maker_authority,
+            signers,
+        };
+        accounts.check()?;
+
+        // Return the accounts
+        Ok(accounts)
+    }
+}
+
+impl<'a> MakeAccounts<'a> {
+    /// Basic Accounts Checks
+    pub fn check(&self) -> ProgramResult {
+        SignerAccount::check(self.maker)?;
+        TokenAuthority::check(self.maker_authority, self.signers)?;
+        MintInterface::check(self.mint_a)?;
+        MintInterface::check(self.mint_b)?;
+        AssociatedTokenAccount::check(
+            self.maker_ata_a,
+            self.maker_authority.address(),
+            self.mint_a.address(),
+            self.token_program.address(),
+        )?;
+
+        Ok(())
     }
 }
 
@@ -49,22 +82,48 @@ pub struct MakeInstructionData {
     pub seed: u64,
     pub receive: u64,
     pub amount: u64,
+    pub expires_at: i64,
+    pub refund_to: Option<Address>,
+    pub allowed_taker: Option<Address>,
+    pub order_ref: [u8; 32],
 }
 
 impl<'a> TryFrom<&'a [u8]> for MakeInstructionData {
     type Error = ProgramError;
 
     fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
-        if data.len() != size_of::<u64>() * 3 {
-            return Err(ProgramError::InvalidInstructionData);
+        const MAKE_DATA_LEN: usize = size_of::<u64>() * 3;
+        const MAKE_DATA_LEN_WITH_EXPIRY: usize = MAKE_DATA_LEN + size_of::<i64>();
+        const MAKE_DATA_LEN_WITH_REFUND_TO: usize =
+            MAKE_DATA_LEN_WITH_EXPIRY + size_of::<Address>();
+        const MAKE_DATA_LEN_WITH_ALLOWED_TAKER: usize =
+            MAKE_DATA_LEN_WITH_REFUND_TO + size_of::<Address>();
+        const MAKE_DATA_LEN_WITH_ORDER_REF: usize =
+            MAKE_DATA_LEN_WITH_ALLOWED_TAKER + size_of::<[u8; 32]>();
+
+        match data.len() {
+            MAKE_DATA_LEN
+            | MAKE_DATA_LEN_WITH_EXPIRY
+            | MAKE_DATA_LEN_WITH_REFUND_TO
+            | MAKE_DATA_LEN_WITH_ALLOWED_TAKER
+            | MAKE_DATA_LEN_WITH_ORDER_REF => {}
+            _ => return Err(ProgramError::InvalidInstructionData),
         }
 
-        let seed = u64::from_le_bytes(data[0..8].try_into().unwrap());
-        let receive = u64::from_le_bytes(data[8..16].try_into().unwrap());
-        let amount = u64::from_le_bytes(data[16..24].try_into().unwrap());
+        // Optional trailing fields that are not present are left zeroed
+        let mut raw = [0u8; MAKE_DATA_LEN_WITH_ORDER_REF];
+        raw[..data.len()].copy_from_slice(data);
+
+        let seed = u64::from_le_bytes(raw[0..8].try_into().unwrap());
+        let receive = u64::from_le_bytes(raw[8..16].try_into().unwrap());
+        let amount = u64::from_le_bytes(raw[16..24].try_into().unwrap());
+        let expires_at = i64::from_le_bytes(raw[24..32].try_into().unwrap());
+        let refund_to = Self::optional_address(&raw[32..64]);
+        let allowed_taker = Self::optional_address(&raw[64..96]);
+        let order_ref = raw[96..128].try_into().unwrap();
 
         // Instruction Checks
-        if amount == 0 {
+        if amount == 0 || receive == 0 {
             return Err(ProgramError::InvalidInstructionData);
         }
 
@@ -72,10 +131,36 @@ impl<'a> TryFrom<&'a [u8]> for MakeInstructionData {
             seed,
             receive,
             amount,
+            expires_at,
+            refund_to,
+            allowed_taker,
+            order_ref,
         })
     }
 }
 
+impl MakeInstructionData {
+    /// An all zero address stands for an unset field.
+    #[inline(always)]
+    fn optional_address(bytes: &[u8]) -> Option<Address> {
+        let address = Address::new_from_array(bytes.try_into().unwrap());
+        if address == Address::default() {
+            None
+        } else {
+            Some(address)
+        }
+    }
+
+    /// Reject an expiry at or before `now`, an offer that could be swept as soon as it exists.
+    #[inline(always)]
+    pub fn check_expires_at(&self, now: i64) -> ProgramResult {
+        if self.expires_at != 0 && self.expires_at <= now {
+            return Err(PinocchioError::Expired.into());
+        }
+        Ok(())
+    }
+}
+
 pub struct Make<'a> {
     pub accounts: MakeAccounts<'a>,
     pub instruction_data: MakeInstructionData,
@@ -89,34 +174,54 @@ impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for Make<'a> {
         let accounts = MakeAccounts::try_from(accounts)?;
         let instruction_data = MakeInstructionData::try_from(data)?;
 
+        Self::new(accounts, instruction_data)
+    }
+}
+
+impl<'a> Make<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &0;
+
+    /// Create the escrow and its vault for already checked accounts.
+    pub fn new(
+        accounts: MakeAccounts<'a>,
+        instruction_data: MakeInstructionData,
+    ) -> Result<Self, ProgramError> {
+        // Only an offer with an expiry needs the clock
+        if instruction_data.expires_at != 0 {
+            instruction_data.check_expires_at(Clock::get()?.unix_timestamp)?;
+        }
+
         // Initialize the Accounts needed
-        let (_, bump) = Address::find_program_address(
-            &[
-                b"escrow",
-                accounts.maker.address().as_array(),
-                &instruction_data.seed.to_le_bytes(),
-            ],
-            &crate::ID,
-        );
+        let (escrow_key, bump) = derive_escrow(accounts.maker.address(), instruction_data.seed);
 
         let seed_binding = instruction_data.seed.to_le_bytes();
         let bump_binding = [bump];
         let escrow_seeds = [
-            Seed::from(b"escrow"),
+            Seed::from(Escrow::SEED),
             Seed::from(accounts.maker.address().as_ref()),
             Seed::from(&seed_binding),
             Seed::from(&bump_binding),
         ];
 
-        ProgramAccount::init::<Escrow>(
-            accounts.maker,
-            accounts.escrow,
-            &escrow_seeds,
-            Escrow::LEN,
-        )?;
+        if accounts.escrow.owned_by(&crate::ID) {
+            // Resume an escrow an earlier attempt created but never funded
+            if escrow_key.ne(accounts.escrow.address())
+                || accounts.escrow.data_len() != Escrow::LEN
+                || Self::is_funded(accounts.vault)?
+            {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+        } else {
+            ProgramAccount::init::<Escrow>(
+                accounts.maker,
+                accounts.escrow,
+                &escrow_seeds,
+                Escrow::LEN,
+            )?;
+        }
 
         // Initialize the vault
-        AssociatedTokenAccount::init(
+        AssociatedTokenAccount::init_if_needed(
             accounts.vault,
             accounts.mint_a,
             accounts.maker,
@@ -131,10 +236,14 @@ impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for Make<'a> {
             bump,
         })
     }
-}
 
-impl<'a> Make<'a> {
-    pub const DISCRIMINATOR: &'a u8 = &0;
+    /// Whether `vault` already holds tokens. A vault that was never created holds none.
+    pub fn is_funded(vault: &AccountView) -> Result<bool, ProgramError> {
+        if vault.data_len() == 0 {
+            return Ok(false);
+        }
+        Ok(TokenInterface::amount(vault)? > 0)
+    }
 
     pub fn process(&mut self) -> ProgramResult {
         // Populate the escrow account
@@ -149,16 +258,109 @@ impl<'a> Make<'a> {
             self.instruction_data.receive,
             [self.bump],
         );
+        escrow.set_expires_at(self.instruction_data.expires_at);
+        // Refunds go back to the owner of the tokens unless another wallet was requested
+        escrow.set_refund_to(
+            self.instruction_data
+                .refund_to
+                .unwrap_or(*self.accounts.maker_authority.address()),
+        );
+        escrow.set_allowed_taker(self.instruction_data.allowed_taker.unwrap_or_default());
+        escrow.set_order_ref(self.instruction_data.order_ref);
+        escrow.set_maker_authority(*self.accounts.maker_authority.address());
+
+        // Transfer tokens to vault, recording what arrived net of any transfer fee
+        let vault_amount = TokenInterface::amount(self.accounts.vault)?;
+        TokenAuthority::transfer(
+            self.accounts.maker_ata_a,
+            self.accounts.mint_a,
+            self.accounts.vault,
+            self.accounts.maker_authority,
+            self.accounts.signers,
+            self.instruction_data.amount,
+        )?;
+        escrow.set_deposited(
+            TokenInterface::amount(self.accounts.vault)?.saturating_sub(vault_amount),
+        );
 
-        // Transfer tokens to vault
-        Transfer {
-            from: self.accounts.maker_ata_a,
-            to: self.accounts.vault,
-            authority: self.accounts.maker,
+        MakeEvent {
+            escrow: self.accounts.escrow.address(),
+            maker: self.accounts.maker.address(),
             amount: self.instruction_data.amount,
+            receive: self.instruction_data.receive,
+            order_ref: &self.instruction_data.order_ref,
         }
-        .invoke()?;
+        .emit();
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_expiry(expires_at: i64) -> MakeInstructionData {
+        MakeInstructionData {
+            seed: 0,
+            receive: 1,
+            amount: 1,
+            expires_at,
+            refund_to: None,
+            allowed_taker: None,
+            order_ref: [0; 32],
+        }
+    }
+
+    #[test]
+    fn rejects_expiry_at_or_before_now() {
+        let expired = Err(PinocchioError::Expired.into());
+        assert_eq!(with_expiry(999).check_expires_at(1_000), expired);
+        assert_eq!(with_expiry(1_000).check_expires_at(1_000), expired);
+    }
+
+    #[test]
+    fn accepts_future_or_no_expiry() {
+        assert_eq!(with_expiry(1_001).check_expires_at(1_000), Ok(()));
+        assert_eq!(with_expiry(0).check_expires_at(1_000), Ok(()));
+    }
+
+    /// Make data up to `order_ref`, every field set.
+    fn full_data() -> [u8; 128] {
+        let mut data = [0u8; 128];
+        data[0..8].copy_from_slice(&7u64.to_le_bytes());
+        data[8..16].copy_from_slice(&500u64.to_le_bytes());
+        data[16..24].copy_from_slice(&100u64.to_le_bytes());
+        data[24..32].copy_from_slice(&2_000i64.to_le_bytes());
+        data[32..64].copy_from_slice(&[1; 32]);
+        data[64..96].copy_from_slice(&[2; 32]);
+        data[96..128].copy_from_slice(&[3; 32]);
+        data
+    }
+
+    #[test]
+    fn parses_every_accepted_length() {
+        let data = full_data();
+        for len in [24, 32, 64, 96, 128] {
+            let parsed = MakeInstructionData::try_from(&data[..len]).unwrap();
+            assert_eq!((parsed.seed, parsed.receive, parsed.amount), (7, 500, 100));
+            // Fields past the end of the data keep their default
+            assert_eq!(parsed.expires_at, if len >= 32 { 2_000 } else { 0 });
+            assert_eq!(parsed.refund_to.is_some(), len >= 64);
+            assert_eq!(parsed.allowed_taker.is_some(), len >= 96);
+            assert_eq!(parsed.order_ref, if len == 128 { [3; 32] } else { [0; 32] });
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_and_oversized_data() {
+        let mut data = [0u8; 129];
+        data[..128].copy_from_slice(&full_data());
+        for len in [0, 8, 23, 25, 31, 33, 63, 65, 95, 97, 127, 129] {
+            assert_eq!(
+                MakeInstructionData::try_from(&data[..len]).err(),
+                Some(ProgramError::InvalidInstructionData)
+            );
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::helpers::*;
+use super::make::{Make, MakeAccounts, MakeInstructionData};
+
+/// Accounts per offer: `[escrow, mint_a, mint_b, maker_ata_a, vault]`.
+const OFFER_ACCOUNTS_LEN: usize = 5;
+/// Instruction data per offer: `seed`, `receive` and `amount`.
+const OFFER_DATA_LEN: usize = size_of::<u64>() * 3;
+/// Upper bound on offers per instruction, keeps a full batch within the account and compute
+/// limits of a single transaction.
+const MAX_OFFERS_LEN: usize = 5;
+
+pub struct MakeManyAccounts<'a> {
+    pub maker: &'a AccountView,
+    pub system_program: &'a AccountView,
+    pub token_program: &'a AccountView,
+    pub offers: &'a [AccountView],
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for MakeManyAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [maker, system_program, token_program, associated_token_program, offers @ ..] =
+            accounts
+        else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SystemProgram::check(system_program)?;
+        TokenProgram::check(token_program)?;
+        AssociatedTokenProgram::check(associated_token_program)?;
+
+        if offers.is_empty() || offers.len() % OFFER_ACCOUNTS_LEN != 0 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        if offers.len() > OFFER_ACCOUNTS_LEN * MAX_OFFERS_LEN {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(Self {
+            maker,
+            system_program,
+            token_program,
+            offers,
+        })
+    }
+}
+
+pub struct MakeMany<'a> {
+    pub accounts: MakeManyAccounts<'a>,
+    pub instruction_data: &'a [u8],
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for MakeMany<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = MakeManyAccounts::try_from(accounts)?;
+
+        // One set of offer data per set of offer accounts
+        if data.len() != accounts.offers.len() / OFFER_ACCOUNTS_LEN * OFFER_DATA_LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self {
+            accounts,
+            instruction_data: data,
+        })
+    }
+}
+
+impl<'a> MakeMany<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &9;
+
+    pub fn process(&self) -> ProgramResult {
+        let offers = self.accounts.offers.chunks_exact(OFFER_ACCOUNTS_LEN);
+        let data = self.instruction_data.chunks_exact(OFFER_DATA_LEN);
+
+        for (offer, data) in offers.zip(data) {
+            let [escrow, mint_a, mint_b, maker_ata_a, vault] = offer else {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            };
+
+            let accounts = MakeAccounts {
+                maker: self.accounts.maker,
+                escrow,
+                mint_a,
+                mint_b,
+                maker_ata_a,
+                vault,
+                system_program: self.accounts.system_program,
+                token_program: self.accounts.token_program,
+                maker_authority: self.accounts.maker,
+                signers: &[],
+            };
+            accounts.check()?;
+
+            Make::new(accounts, MakeInstructionData::try_from(data)?)?.process()?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use pinocchio::{
+    error::ProgramError,
+    sysvars::{rent::Rent, Sysvar},
+    AccountView, ProgramResult,
+};
+use pinocchio_system::instructions::Transfer;
+
+use super::helpers::*;
+use crate::errors::PinocchioError;
+use crate::state::Escrow;
+
+pub struct MigrateAccounts<'a> {
+    pub maker: &'a AccountView,
+    pub escrow: &'a AccountView,
+    pub system_program: &'a AccountView,
+    pub vault: &'a AccountView,
+    pub token_program: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for MigrateAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [maker, escrow, system_program, vault, token_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(maker)?;
+        SystemProgram::check(system_program)?;
+        TokenProgram::check(token_program)?;
+        ProgramAccount::check(escrow)?;
+
+        Ok(Self {
+            maker,
+            escrow,
+            system_program,
+            vault,
+            token_program,
+        })
+    }
+}
+
+pub struct Migrate<'a> {
+    pub accounts: MigrateAccounts<'a>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for Migrate<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let accounts = MigrateAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> Migrate<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &3;
+
+    pub fn process(&self) -> ProgramResult {
+        let escrow = {
+            let data = self.accounts.escrow.try_borrow()?;
+            if Escrow::version_of(&data)? == Escrow::VERSION {
+                return Ok(());
+            }
+            Escrow::load(&data)?
+        };
+
+        // Only the maker of this escrow may upgrade it
+        let escrow_key = escrow.pda();
+        if escrow.maker().ne(self.accounts.maker.address())
+            || escrow_key.ne(self.accounts.escrow.address())
+        {
+            return Err(PinocchioError::InvalidAddress.into());
+        }
+        AssociatedTokenAccount::check(
+            self.accounts.vault,
+            &escrow_key,
+            escrow.mint_a(),
+            self.accounts.token_program.address(),
+        )?;
+
+        // Top up the rent for the larger layout
+        let lamports = Rent::get()?.try_minimum_balance(Escrow::LEN)?;
+        if let Some(missing) = lamports.checked_sub(self.accounts.escrow.lamports()) {
+            if missing > 0 {
+                Transfer {
+                    from: self.accounts.maker,
+                    to: self.accounts.escrow,
+                    lamports: missing,
+                }
+                .invoke()?;
+            }
+        }
+
+        // Realloc and rewrite in the current layout
+        self.accounts.escrow.resize(Escrow::LEN)?;
+        let mut data = self.accounts.escrow.try_borrow_mut()?;
+        let escrow_data = Escrow::load_mut(&mut data)?;
+        *escrow_data = escrow;
+        // Older layouts never recorded the deposit, the vault is all there is to go by
+        escrow_data.set_deposited(TokenInterface::amount(self.accounts.vault)?);
+
+        Ok(())
+    }
+}
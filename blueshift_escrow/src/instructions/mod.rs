@@ -1,8 +1,28 @@
+pub mod batch_refund;
+pub mod cancel_unfunded;
 pub mod helpers;
+pub mod init_config;
 pub mod make;
+pub mod make_many;
+pub mod migrate;
+pub mod quote_take;
 pub mod refund;
+pub mod refund_partial;
+pub mod set_fee;
+pub mod sweep_expired;
 pub mod take;
+pub mod withdraw_fees;
 
+pub use batch_refund::BatchRefund;
+pub use cancel_unfunded::CancelUnfunded;
+pub use init_config::InitConfig;
 pub use make::Make;
+pub use make_many::MakeMany;
+pub use migrate::Migrate;
+pub use quote_take::QuoteTake;
 pub use refund::Refund;
-pub use take::Take;
+pub use refund_partial::RefundPartial;
+pub use set_fee::SetFee;
+pub use sweep_expired::SweepExpired;
+pub use take::{Take, TakeQuote};
+pub use withdraw_fees::WithdrawFees;
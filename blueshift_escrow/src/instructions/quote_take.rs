@@ -0,0 +1,240 @@
+use pinocchio::{
+    cpi::set_return_data,
+    error::ProgramError,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, ProgramResult,
+};
+
+use super::helpers::*;
+use super::{Take, TakeQuote};
+use crate::state::Escrow;
+
+/// Length of the `QuoteTake` return data: the amount owed and the fee, each a little-endian
+/// `u64`.
+pub const RETURN_DATA_LEN: usize = 2 * size_of::<u64>();
+
+pub struct QuoteTakeAccounts<'a> {
+    pub escrow: &'a AccountView,
+    pub taker: &'a AccountView,
+    pub config: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for QuoteTakeAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [escrow, taker, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        ProgramAccount::check(escrow)?;
+
+        Ok(Self {
+            escrow,
+            taker,
+            config,
+        })
+    }
+}
+
+/// Read-only twin of `Take`: writes the amount of token B a `Take` by `taker` would charge,
+/// and the protocol fee kept out of it, to the return data without moving any funds. Fails
+/// wherever that `Take` would on the escrow: once expired, or when reserved for someone else.
+pub struct QuoteTake<'a> {
+    pub accounts: QuoteTakeAccounts<'a>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for QuoteTake<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let accounts = QuoteTakeAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> QuoteTake<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &4;
+
+    pub fn process(&self) -> ProgramResult {
+        let quote = self.quote(Clock::get()?.unix_timestamp)?;
+
+        set_return_data(&Self::encode(&quote));
+
+        Ok(())
+    }
+
+    /// What a `Take` by the quoted taker charges at `now`.
+    pub fn quote(&self, now: i64) -> Result<TakeQuote, ProgramError> {
+        let data = self.accounts.escrow.try_borrow()?;
+        let escrow = Escrow::load(&data)?;
+
+        // Check if the escrow is valid
+        let escrow_key = escrow.pda();
+        if escrow_key.ne(self.accounts.escrow.address()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        Take::check_taker(&escrow, self.accounts.taker.address(), now)?;
+        Take::quote(&escrow, self.accounts.config)
+    }
+
+    pub fn encode(quote: &TakeQuote) -> [u8; RETURN_DATA_LEN] {
+        let mut return_data = [0u8; RETURN_DATA_LEN];
+        return_data[..8].copy_from_slice(&quote.amount_owed.to_le_bytes());
+        return_data[8..].copy_from_slice(&quote.fee.to_le_bytes());
+        return_data
+    }
+
+    /// Decode the return data of a simulated `QuoteTake`.
+    pub fn decode(return_data: &[u8]) -> Result<TakeQuote, ProgramError> {
+        if return_data.len() != RETURN_DATA_LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(TakeQuote {
+            amount_owed: u64::from_le_bytes(return_data[..8].try_into().unwrap()),
+            fee: u64::from_le_bytes(return_data[8..].try_into().unwrap()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+    use pinocchio::Address;
+
+    use super::*;
+    use crate::errors::PinocchioError;
+    use crate::state::{derive_escrow, Config};
+
+    const SEED: u64 = 9;
+    const NOW: i64 = 1_000;
+    const RECEIVE: u64 = 10_000;
+
+    struct Fixture {
+        escrow: TestAccount,
+        taker: TestAccount,
+        config: TestAccount,
+    }
+
+    /// An escrow asking `RECEIVE` of token B, and a config charging `fee_bps` if any.
+    fn fixture(fee_bps: Option<u16>, allowed_taker: Address, expires_at: i64) -> Fixture {
+        let maker = Address::new_from_array([1; 32]);
+        let (escrow_key, bump) = derive_escrow(&maker, SEED);
+
+        let mut data = [0u8; Escrow::LEN];
+        let escrow = Escrow::load_mut(&mut data).unwrap();
+        escrow.set_inner(
+            SEED,
+            maker,
+            Address::new_from_array([2; 32]),
+            Address::new_from_array([3; 32]),
+            RECEIVE,
+            [bump],
+        );
+        escrow.set_allowed_taker(allowed_taker);
+        escrow.set_expires_at(expires_at);
+
+        let (config_key, config_bump) = Address::find_program_address(&[Config::SEED], &crate::ID);
+        let config = match fee_bps {
+            Some(fee_bps) => {
+                let mut config_data = [0u8; Config::LEN];
+                Config::load_mut(&mut config_data)
+                    .unwrap()
+                    .set_inner(fee_bps, Address::default(), [config_bump])
+                    .unwrap();
+                TestAccount::new(config_key, crate::ID, &config_data)
+            }
+            None => TestAccount::empty(config_key),
+        };
+
+        Fixture {
+            escrow: TestAccount::new(escrow_key, crate::ID, &data),
+            taker: TestAccount::empty(Address::new_from_array([4; 32])).signer(),
+            config,
+        }
+    }
+
+    /// The quote, checked against what `Take` charges for the same accounts.
+    fn quote(fixture: &mut Fixture) -> Result<TakeQuote, ProgramError> {
+        let accounts = [
+            fixture.escrow.view(),
+            fixture.taker.view(),
+            fixture.config.view(),
+        ];
+        let quote = QuoteTake::try_from(&accounts[..])?.quote(NOW);
+
+        let data = accounts[0].try_borrow().unwrap();
+        let escrow = Escrow::load(&data).unwrap();
+        let take = Take::check_taker(&escrow, accounts[1].address(), NOW)
+            .and_then(|_| Take::quote(&escrow, &accounts[2]));
+        assert_eq!(quote, take);
+
+        quote
+    }
+
+    #[test]
+    fn quotes_what_take_charges() {
+        let quote = quote(&mut fixture(Some(25), Address::default(), 0));
+        assert_eq!(
+            quote,
+            Ok(TakeQuote {
+                amount_owed: RECEIVE,
+                fee: 25
+            })
+        );
+
+        let encoded = QuoteTake::encode(quote.as_ref().unwrap());
+        assert_eq!(QuoteTake::decode(&encoded), quote);
+    }
+
+    #[test]
+    fn quotes_no_fee_without_a_config() {
+        assert_eq!(
+            quote(&mut fixture(None, Address::default(), 0)),
+            Ok(TakeQuote {
+                amount_owed: RECEIVE,
+                fee: 0
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_an_expired_escrow() {
+        assert_eq!(
+            quote(&mut fixture(Some(25), Address::default(), NOW - 1)),
+            Err(PinocchioError::Expired.into())
+        );
+        // Still takeable at its expiry
+        assert!(quote(&mut fixture(Some(25), Address::default(), NOW)).is_ok());
+    }
+
+    #[test]
+    fn rejects_a_taker_other_than_the_allowed_one() {
+        assert_eq!(
+            quote(&mut fixture(Some(25), Address::new_from_array([8; 32]), 0)),
+            Err(PinocchioError::TakerNotAllowed.into())
+        );
+        assert!(quote(&mut fixture(Some(25), Address::new_from_array([4; 32]), 0)).is_ok());
+    }
+
+    #[test]
+    fn rejects_a_config_other_than_the_config_pda() {
+        let mut fixture = fixture(None, Address::default(), 0);
+        fixture.config = TestAccount::empty(Address::new_from_array([7; 32]));
+        assert_eq!(
+            quote(&mut fixture),
+            Err(PinocchioError::InvalidAddress.into())
+        );
+    }
+
+    #[test]
+    fn rejects_return_data_of_another_length() {
+        assert_eq!(
+            QuoteTake::decode(&[0; 8]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+}
@@ -1,15 +1,12 @@
+use blueshift_pinocchio_helpers::{require, require_eq};
 use pinocchio::{
     cpi::{Seed, Signer},
     error::ProgramError,
-    AccountView, ProgramResult,
-};
-use pinocchio_pubkey::derive_address;
-use pinocchio_token::{
-    instructions::{CloseAccount, Transfer},
-    state::TokenAccount,
+    AccountView, Address, ProgramResult,
 };
 
 use super::helpers::*;
+use crate::errors::PinocchioError;
 use crate::state::Escrow;
 
 pub struct RefundAccounts<'a> {
@@ -17,48 +14,194 @@ pub struct RefundAccounts<'a> {
     pub escrow: &'a AccountView,
     pub mint_a: &'a AccountView,
     pub vault: &'a AccountView,
-    pub maker_ata_a: &'a AccountView,
+    pub refund_ata_a: &'a AccountView,
     pub system_program: &'a AccountView,
     pub token_program: &'a AccountView,
+    pub refund_to: &'a AccountView,
+    pub maker_authority: &'a AccountView,
+    pub signers: &'a [AccountView],
 }
 
 impl<'a> TryFrom<&'a [AccountView]> for RefundAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
-        let [maker, escrow, mint_a, vault, maker_ata_a, system_program, token_program, _] =
+        let [maker, escrow, mint_a, vault, refund_ata_a, system_program, token_program, associated_token_program, refund_to, authority @ ..] =
             accounts
         else {
             return Err(ProgramError::NotEnoughAccountKeys);
         };
 
-        AssociatedTokenAccount::init_if_needed(
-            maker_ata_a,
-            mint_a,
-            maker,
-            maker,
-            system_program,
-            token_program,
-        )?;
+        // Escrows funded by another owner also need its `[maker_authority, signers @ ..]`
+        let (maker_authority, signers) = authority.split_first().unwrap_or((maker, &[]));
+
+        SystemProgram::check(system_program)?;
+        TokenProgram::check(token_program)?;
+        AssociatedTokenProgram::check(associated_token_program)?;
 
         SignerAccount::check(maker)?;
         ProgramAccount::check(escrow)?;
         MintInterface::check(mint_a)?;
-        AssociatedTokenAccount::check(maker_ata_a, maker, mint_a, token_program)?;
-        AssociatedTokenAccount::check(vault, escrow, mint_a, token_program)?;
+        AssociatedTokenAccount::check(
+            vault,
+            escrow.address(),
+            mint_a.address(),
+            token_program.address(),
+        )?;
 
         Ok(Self {
             maker,
             escrow,
             mint_a,
             vault,
-            maker_ata_a,
+            refund_ata_a,
             system_program,
             token_program,
+            refund_to,
+            maker_authority,
+            signers,
         })
     }
 }
 
+impl<'a> RefundAccounts<'a> {
+    /// Load the escrow and check it matches the other accounts, then create the refund
+    /// wallet's token A account if missing. The maker only pays for it once every check has
+    /// passed.
+    pub fn prepare(&self) -> Result<Escrow, ProgramError> {
+        let escrow = self.verify_escrow()?;
+
+        AssociatedTokenAccount::init_if_needed(
+            self.refund_ata_a,
+            self.mint_a,
+            self.maker,
+            self.refund_to,
+            self.system_program,
+            self.token_program,
+        )?;
+        AssociatedTokenAccount::check(
+            self.refund_ata_a,
+            self.refund_to.address(),
+            self.mint_a.address(),
+            self.token_program.address(),
+        )?;
+
+        Ok(escrow)
+    }
+
+    /// Load the escrow and check it matches the other accounts.
+    pub fn verify_escrow(&self) -> Result<Escrow, ProgramError> {
+        let data = self.escrow.try_borrow()?;
+        let escrow = Escrow::load(&data)?;
+
+        verify_refund(
+            &escrow,
+            self.escrow,
+            self.maker.address(),
+            self.mint_a.address(),
+            self.vault,
+            self.refund_to.address(),
+        )?;
+
+        // The owner of the deposited tokens must approve giving them back too
+        require_eq!(
+            escrow.maker_authority(),
+            self.maker_authority.address(),
+            PinocchioError::InvalidMaker
+        );
+        TokenAuthority::check(self.maker_authority, self.signers)?;
+
+        Ok(escrow)
+    }
+
+    /// Move `amount` of token A from the vault to the refund wallet.
+    pub fn withdraw(&self, escrow: &Escrow, amount: u64) -> ProgramResult {
+        let seed_binding = escrow.seed().to_le_bytes();
+        let bump_binding = escrow.bump();
+        let escrow_seeds = [
+            Seed::from(Escrow::SEED),
+            Seed::from(self.maker.address().as_ref()),
+            Seed::from(&seed_binding),
+            Seed::from(&bump_binding),
+        ];
+
+        // Checked against `mint_a`'s decimals, which token-2022 requires for some mints
+        TokenInterface::transfer_checked(
+            self.vault,
+            self.mint_a,
+            self.refund_ata_a,
+            self.escrow,
+            amount,
+            &[Signer::from(&escrow_seeds)],
+        )
+    }
+
+    /// Close the emptied vault and the escrow, returning their rent to the maker.
+    pub fn close(&self, escrow: &Escrow) -> ProgramResult {
+        let seed_binding = escrow.seed().to_le_bytes();
+        let bump_binding = escrow.bump();
+        let escrow_seeds = [
+            Seed::from(Escrow::SEED),
+            Seed::from(self.maker.address().as_ref()),
+            Seed::from(&seed_binding),
+            Seed::from(&bump_binding),
+        ];
+
+        TokenInterface::close_account(
+            self.vault,
+            self.maker,
+            self.escrow,
+            &[Signer::from(&escrow_seeds)],
+        )?;
+
+        ProgramAccount::close(self.escrow, self.maker)
+    }
+}
+
+/// Check `escrow`, loaded from `escrow_account`, is the offer of `maker` for `mint_a` held in
+/// `vault`, and that its token A goes back to `refund_to`. Shared by every instruction that
+/// refunds an escrow.
+pub fn verify_refund(
+    escrow: &Escrow,
+    escrow_account: &AccountView,
+    maker: &Address,
+    mint_a: &Address,
+    vault: &AccountView,
+    refund_to: &Address,
+) -> ProgramResult {
+    // Only the recorded maker's offer is refunded to it
+    require_eq!(escrow.maker(), maker, PinocchioError::InvalidMaker);
+
+    // Check if the escrow is valid
+    require!(
+        escrow.pda().eq(escrow_account.address()),
+        ProgramError::InvalidAccountOwner
+    );
+
+    require_eq!(escrow.mint_a(), mint_a, PinocchioError::InvalidAccountData);
+
+    // The vault must belong to this escrow
+    require_eq!(
+        &TokenInterface::owner(vault)?,
+        escrow_account.address(),
+        PinocchioError::InvalidAccountData
+    );
+    require_eq!(
+        &TokenInterface::mint(vault)?,
+        mint_a,
+        PinocchioError::InvalidAccountData
+    );
+
+    // Token A may only go to the wallet recorded at Make time
+    require_eq!(
+        escrow.refund_to(),
+        refund_to,
+        PinocchioError::InvalidAddress
+    );
+
+    Ok(())
+}
+
 pub struct Refund<'a> {
     pub accounts: RefundAccounts<'a>,
 }
@@ -77,55 +220,167 @@ impl<'a> Refund<'a> {
     pub const DISCRIMINATOR: &'a u8 = &2;
 
     pub fn process(&self) -> ProgramResult {
-        let (seed_binding, bump_binding) = {
-            let data = self.accounts.escrow.try_borrow()?;
-            let escrow = Escrow::load(&data)?;
-
-            // Check if the escrow is valid
-            let escrow_key = derive_address(
-                &[
-                    b"escrow",
-                    self.accounts.maker.address().as_array(),
-                    &escrow.seed.to_le_bytes(),
-                    &escrow.bump,
-                ],
-                None,
-                &crate::ID.to_bytes(),
-            );
-            if escrow_key != self.accounts.escrow.address().to_bytes() {
-                return Err(ProgramError::InvalidAccountOwner);
-            }
+        let escrow = self.accounts.prepare()?;
 
-            (escrow.seed.to_le_bytes(), escrow.bump)
-        };
+        let amount = TokenInterface::amount(self.accounts.vault)?;
 
-        let escrow_seeds = [
-            Seed::from(b"escrow"),
-            Seed::from(self.accounts.maker.address().as_ref()),
-            Seed::from(&seed_binding),
-            Seed::from(&bump_binding),
+        self.accounts.withdraw(&escrow, amount)?;
+        self.accounts.close(&escrow)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+    use pinocchio::Address;
+    use pinocchio_token::instructions::MAX_MULTISIG_SIGNERS;
+
+    use super::*;
+
+    /// Run the account checks of `Refund` with the given programs, passing `len` accounts.
+    fn check_accounts(programs: [Address; 3], len: usize) -> Result<(), ProgramError> {
+        let maker = TestAccount::empty(Address::new_from_array([1; 32])).signer();
+        check_accounts_of(maker, programs, len)
+    }
+
+    fn check_accounts_of(
+        mut maker: TestAccount,
+        programs: [Address; 3],
+        len: usize,
+    ) -> Result<(), ProgramError> {
+        let [mut system_program, mut token_program, mut associated_token_program] =
+            programs.map(TestAccount::empty);
+        let mut other = TestAccount::empty(Address::new_from_array([9; 32]));
+
+        let accounts = [
+            maker.view(),
+            other.view(),
+            other.view(),
+            other.view(),
+            other.view(),
+            system_program.view(),
+            token_program.view(),
+            associated_token_program.view(),
+            other.view(),
         ];
-        let signers = [Signer::from(&escrow_seeds)];
+        RefundAccounts::try_from(&accounts[..len]).map(|_| ())
+    }
 
-        let amount = TokenAccount::from_account_view(self.accounts.vault)?.amount();
+    const PROGRAMS: [Address; 3] = [
+        pinocchio_system::ID,
+        pinocchio_token::ID,
+        pinocchio_associated_token_account::ID,
+    ];
 
-        Transfer {
-            from: self.accounts.vault,
-            to: self.accounts.maker_ata_a,
-            authority: self.accounts.escrow,
-            amount,
+    #[test]
+    fn rejects_missing_accounts() {
+        for len in [0, 1, 8] {
+            assert_eq!(
+                check_accounts(PROGRAMS, len),
+                Err(ProgramError::NotEnoughAccountKeys)
+            );
         }
-        .invoke_signed(&signers)?;
+    }
 
-        CloseAccount {
-            account: self.accounts.vault,
-            destination: self.accounts.maker_ata_a,
-            authority: self.accounts.escrow,
+    #[test]
+    fn rejects_wrong_programs() {
+        let other = Address::new_from_array([8; 32]);
+        for index in 0..PROGRAMS.len() {
+            let mut programs = PROGRAMS;
+            programs[index] = other;
+            assert_eq!(
+                check_accounts(programs, 9),
+                Err(PinocchioError::InvalidProgram.into())
+            );
         }
-        .invoke_signed(&signers)?;
 
-        ProgramAccount::close(self.accounts.escrow, self.accounts.maker_ata_a)?;
+        // Each program account must be the program for its own slot
+        let [system, token, associated_token] = PROGRAMS;
+        assert_eq!(
+            check_accounts([token, system, associated_token], 9),
+            Err(PinocchioError::InvalidProgram.into())
+        );
+    }
+
+    #[test]
+    fn checks_the_maker_signed_before_creating_the_refund_account() {
+        // The refund account is missing, creating it would be a CPI the maker pays for
+        let maker = TestAccount::empty(Address::new_from_array([1; 32]));
+        assert_eq!(
+            check_accounts_of(maker, PROGRAMS, 9),
+            Err(PinocchioError::NotSigner.into())
+        );
+    }
 
-        Ok(())
+    /// Run `verify_refund` on an escrow by `[1; 32]` for mint `[2; 32]` refunding to `[4; 32]`,
+    /// against the given maker, mint, vault owner and refund wallet.
+    fn verify(
+        maker: [u8; 32],
+        mint_a: [u8; 32],
+        vault_owner: Option<[u8; 32]>,
+        refund_to: [u8; 32],
+    ) -> ProgramResult {
+        let maker_key = Address::new_from_array([1; 32]);
+        let mint_a_key = Address::new_from_array([2; 32]);
+        let (escrow_key, bump) = crate::state::derive_escrow(&maker_key, 0);
+
+        let mut data = [0u8; Escrow::LEN];
+        let state = Escrow::load_mut(&mut data).unwrap();
+        state.set_inner(0, maker_key, mint_a_key, Address::default(), 1, [bump]);
+        state.set_refund_to(Address::new_from_array([4; 32]));
+        let state = *state;
+
+        let mut escrow = TestAccount::new(escrow_key, crate::ID, &data);
+        let mut vault = TestAccount::token(
+            Address::new_from_array([5; 32]),
+            pinocchio_token::ID,
+            &mint_a_key,
+            &vault_owner.map_or(escrow_key, Address::new_from_array),
+            1,
+        );
+
+        verify_refund(
+            &state,
+            &escrow.view(),
+            &Address::new_from_array(maker),
+            &Address::new_from_array(mint_a),
+            &vault.view(),
+            &Address::new_from_array(refund_to),
+        )
+    }
+
+    #[test]
+    fn verifies_every_account_of_a_refund() {
+        assert_eq!(verify([1; 32], [2; 32], None, [4; 32]), Ok(()));
+        assert_eq!(
+            verify([9; 32], [2; 32], None, [4; 32]),
+            Err(PinocchioError::InvalidMaker.into())
+        );
+        assert_eq!(
+            verify([1; 32], [9; 32], None, [4; 32]),
+            Err(PinocchioError::InvalidAccountData.into())
+        );
+        assert_eq!(
+            verify([1; 32], [2; 32], Some([9; 32]), [4; 32]),
+            Err(PinocchioError::InvalidAccountData.into())
+        );
+        assert_eq!(
+            verify([1; 32], [2; 32], None, [9; 32]),
+            Err(PinocchioError::InvalidAddress.into())
+        );
+    }
+
+    #[test]
+    fn rejects_more_multisig_signers_than_a_multisig_holds() {
+        let mut authority = TestAccount::empty(Address::new_from_array([1; 32]));
+        let mut signer = TestAccount::empty(Address::new_from_array([2; 32])).signer();
+        let signers: [AccountView; MAX_MULTISIG_SIGNERS + 1] =
+            core::array::from_fn(|_| signer.view());
+        assert_eq!(
+            TokenAuthority::check(&authority.view(), &signers),
+            Err(ProgramError::InvalidArgument)
+        );
     }
 }
@@ -0,0 +1,243 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::helpers::TokenInterface;
+use super::refund::RefundAccounts;
+use crate::errors::PinocchioError;
+use crate::state::Escrow;
+
+pub struct RefundPartialInstructionData {
+    pub amount: u64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for RefundPartialInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<u64>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let amount = u64::from_le_bytes(data.try_into().unwrap());
+
+        // Instruction Checks
+        if amount == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self { amount })
+    }
+}
+
+/// Give back part of the vault and reprice what is left.
+///
+/// Shrinking scales the escrow's `receive` by the deposit recorded at Make time, which older
+/// layouts lack, so an escrow made before it has to go through `Migrate` first. Refunding the
+/// whole vault needs no rewrite and works on any layout.
+pub struct RefundPartial<'a> {
+    pub accounts: RefundAccounts<'a>,
+    pub instruction_data: RefundPartialInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for RefundPartial<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = RefundAccounts::try_from(accounts)?;
+        let instruction_data = RefundPartialInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> RefundPartial<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &8;
+
+    /// Amount of token B still wanted once `remaining` of `deposited` is left for the offer.
+    ///
+    /// Rounded up so shrinking an offer never lowers its price.
+    #[inline(always)]
+    pub fn remaining_receive(receive: u64, deposited: u64, remaining: u64) -> u64 {
+        (receive as u128 * remaining as u128).div_ceil(deposited as u128) as u64
+    }
+
+    /// Take `amount` off the deposit recorded on `escrow` and reprice what is left, returning
+    /// it. Scaled from the record rather than the vault, so tokens sent to the vault never
+    /// reprice the offer.
+    pub fn shrink(escrow: &mut Escrow, amount: u64) -> Result<u64, ProgramError> {
+        let deposited = escrow.deposited();
+        let remaining = deposited
+            .checked_sub(amount)
+            .ok_or(ProgramError::InsufficientFunds)?;
+
+        escrow.set_receive(Self::remaining_receive(
+            escrow.receive(),
+            deposited,
+            remaining,
+        ));
+        escrow.set_deposited(remaining);
+
+        Ok(remaining)
+    }
+
+    pub fn process(&self) -> ProgramResult {
+        let escrow = self.accounts.prepare()?;
+        let amount = self.instruction_data.amount;
+
+        let vault_amount = TokenInterface::amount(self.accounts.vault)?;
+        if amount > vault_amount {
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        // Refunding everything left is a plain Refund
+        if amount == vault_amount {
+            self.accounts.withdraw(&escrow, amount)?;
+            return self.accounts.close(&escrow);
+        }
+
+        // Fail before moving anything, rather than on the rewrite below
+        if Escrow::version_of(&self.accounts.escrow.try_borrow()?)? != Escrow::VERSION {
+            return Err(PinocchioError::NeedsMigrate.into());
+        }
+
+        let mut shrunk = escrow;
+        // Tokens sent to the vault on top of the deposit leave with the last of it
+        if Self::shrink(&mut shrunk, amount)? == 0 {
+            self.accounts.withdraw(&escrow, vault_amount)?;
+            return self.accounts.close(&escrow);
+        }
+
+        self.accounts.withdraw(&escrow, amount)?;
+
+        let mut data = self.accounts.escrow.try_borrow_mut()?;
+        *Escrow::load_mut(&mut data)? = shrunk;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+    use pinocchio::Address;
+
+    use super::*;
+    use crate::instructions::helpers::AssociatedTokenAccount;
+    use crate::state::derive_escrow;
+
+    const SEED: u64 = 5;
+
+    #[test]
+    fn keeps_the_price_of_the_remaining_offer() {
+        assert_eq!(RefundPartial::remaining_receive(1_000, 100, 50), 500);
+        assert_eq!(RefundPartial::remaining_receive(1_000, 100, 100), 1_000);
+    }
+
+    #[test]
+    fn rounds_the_remaining_receive_up() {
+        // 10 * 1 / 3 is 3.33, a refund must never make token A cheaper
+        assert_eq!(RefundPartial::remaining_receive(10, 3, 1), 4);
+        assert_eq!(RefundPartial::remaining_receive(1, 1_000, 1), 1);
+    }
+
+    #[test]
+    fn scales_amounts_whose_product_overflows_u64() {
+        assert_eq!(
+            RefundPartial::remaining_receive(u64::MAX, u64::MAX, u64::MAX - 1),
+            u64::MAX - 1
+        );
+        assert_eq!(
+            RefundPartial::remaining_receive(u64::MAX, 2, 1),
+            u64::MAX / 2 + 1
+        );
+    }
+
+    fn escrow(receive: u64, deposited: u64) -> Escrow {
+        let mut data = [0u8; Escrow::LEN];
+        let escrow = Escrow::load_mut(&mut data).unwrap();
+        escrow.set_receive(receive);
+        escrow.set_deposited(deposited);
+        *escrow
+    }
+
+    #[test]
+    fn shrinks_the_recorded_deposit() {
+        let mut escrow = escrow(1_000, 100);
+        assert_eq!(RefundPartial::shrink(&mut escrow, 40), Ok(60));
+        assert_eq!((escrow.receive(), escrow.deposited()), (600, 60));
+
+        assert_eq!(RefundPartial::shrink(&mut escrow, 60), Ok(0));
+        assert_eq!((escrow.receive(), escrow.deposited()), (0, 0));
+    }
+
+    #[test]
+    fn rejects_shrinking_past_the_recorded_deposit() {
+        // A vault holding 150 after a donation of 50 still only backs the 100 deposited
+        let mut escrow = escrow(1_000, 100);
+        assert_eq!(
+            RefundPartial::shrink(&mut escrow, 101),
+            Err(ProgramError::InsufficientFunds)
+        );
+        assert_eq!((escrow.receive(), escrow.deposited()), (1_000, 100));
+    }
+
+    #[test]
+    fn rejects_shrinking_a_v1_escrow_before_moving_anything() {
+        let maker_key = Address::new_from_array([1; 32]);
+        let mint_a_key = Address::new_from_array([2; 32]);
+        let (escrow_key, bump) = derive_escrow(&maker_key, SEED);
+        let token_program_key = pinocchio_token::ID;
+
+        // The original layout: seed, maker, mint_a, mint_b, receive and bump, no version byte
+        let mut data = [0u8; Escrow::V1_LEN];
+        data[0..8].copy_from_slice(&SEED.to_le_bytes());
+        data[8..40].copy_from_slice(maker_key.as_ref());
+        data[40..72].copy_from_slice(mint_a_key.as_ref());
+        data[72..104].copy_from_slice(&[3; 32]);
+        data[104..112].copy_from_slice(&1_000u64.to_le_bytes());
+        data[112] = bump;
+
+        let mut maker = TestAccount::empty(maker_key).signer();
+        let mut escrow = TestAccount::new(escrow_key, crate::ID, &data);
+        let mut mint_a = TestAccount::mint(mint_a_key, token_program_key, 6);
+        let mut vault = TestAccount::token(
+            AssociatedTokenAccount::address(&escrow_key, &mint_a_key, &token_program_key),
+            token_program_key,
+            &mint_a_key,
+            &escrow_key,
+            100,
+        );
+        let mut refund_ata_a = TestAccount::token(
+            AssociatedTokenAccount::address(&maker_key, &mint_a_key, &token_program_key),
+            token_program_key,
+            &mint_a_key,
+            &maker_key,
+            0,
+        );
+        let mut system_program = TestAccount::empty(pinocchio_system::ID);
+        let mut token_program = TestAccount::empty(token_program_key);
+        let mut associated_token_program =
+            TestAccount::empty(pinocchio_associated_token_account::ID);
+        let mut refund_to = TestAccount::empty(maker_key);
+
+        let accounts = [
+            maker.view(),
+            escrow.view(),
+            mint_a.view(),
+            vault.view(),
+            refund_ata_a.view(),
+            system_program.view(),
+            token_program.view(),
+            associated_token_program.view(),
+            refund_to.view(),
+        ];
+        let amount = 40u64.to_le_bytes();
+        let refund = RefundPartial::try_from((&amount[..], &accounts[..])).unwrap();
+
+        assert_eq!(refund.process(), Err(PinocchioError::NeedsMigrate.into()));
+        assert_eq!(escrow.data(), &data[..]);
+        assert_eq!(TokenInterface::amount(&vault.view()), Ok(100));
+    }
+}
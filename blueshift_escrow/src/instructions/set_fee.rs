@@ -0,0 +1,83 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::helpers::*;
+use crate::errors::PinocchioError;
+use crate::state::Config;
+
+pub struct SetFeeAccounts<'a> {
+    pub fee_authority: &'a AccountView,
+    pub config: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for SetFeeAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [fee_authority, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(fee_authority)?;
+        ConfigAccount::check(config)?;
+
+        Ok(Self {
+            fee_authority,
+            config,
+        })
+    }
+}
+
+pub struct SetFeeInstructionData {
+    pub fee_bps: u16,
+}
+
+impl<'a> TryFrom<&'a [u8]> for SetFeeInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<u16>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self {
+            fee_bps: u16::from_le_bytes(data.try_into().unwrap()),
+        })
+    }
+}
+
+pub struct SetFee<'a> {
+    pub accounts: SetFeeAccounts<'a>,
+    pub instruction_data: SetFeeInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for SetFee<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = SetFeeAccounts::try_from(accounts)?;
+        let instruction_data = SetFeeInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> SetFee<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &6;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let mut data = self.accounts.config.try_borrow_mut()?;
+        let config = Config::load_mut(data.as_mut())?;
+
+        if config
+            .fee_authority()
+            .ne(self.accounts.fee_authority.address())
+        {
+            return Err(PinocchioError::NotSigner.into());
+        }
+
+        config.set_fee_bps(self.instruction_data.fee_bps)
+    }
+}
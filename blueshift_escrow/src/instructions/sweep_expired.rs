@@ -0,0 +1,201 @@
+use blueshift_pinocchio_helpers::{require, require_eq};
+
+use super::helpers::*;
+use super::refund::verify_refund;
+use crate::errors::PinocchioError;
+use crate::state::Escrow;
+use pinocchio::{
+    cpi::{Seed, Signer},
+    error::ProgramError,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, ProgramResult,
+};
+
+/// Lamports paid to whoever sweeps an expired escrow, out of the escrow rent.
+pub const SWEEP_TIP: u64 = 5_000;
+
+pub struct SweepExpiredAccounts<'a> {
+    pub cranker: &'a AccountView,
+    pub maker: &'a AccountView,
+    pub escrow: &'a AccountView,
+    pub mint_a: &'a AccountView,
+    pub vault: &'a AccountView,
+    pub refund_ata_a: &'a AccountView,
+    pub token_program: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for SweepExpiredAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [cranker, maker, escrow, mint_a, vault, refund_ata_a, token_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(cranker)?;
+        ProgramAccount::check(escrow)?;
+
+        TokenProgram::check(token_program)?;
+        if !vault.owned_by(token_program.address()) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        Ok(Self {
+            cranker,
+            maker,
+            escrow,
+            mint_a,
+            vault,
+            refund_ata_a,
+            token_program,
+        })
+    }
+}
+
+pub struct SweepExpired<'a> {
+    pub accounts: SweepExpiredAccounts<'a>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for SweepExpired<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let accounts = SweepExpiredAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> SweepExpired<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &10;
+
+    pub fn process(&self) -> ProgramResult {
+        let (seed_binding, bump_binding) = {
+            let data = self.accounts.escrow.try_borrow()?;
+            let escrow = Escrow::load(&data)?;
+
+            require!(
+                escrow.is_expired(Clock::get()?.unix_timestamp),
+                PinocchioError::NotExpired
+            );
+
+            verify_refund(
+                &escrow,
+                self.accounts.escrow,
+                self.accounts.maker.address(),
+                self.accounts.mint_a.address(),
+                self.accounts.vault,
+                &TokenInterface::owner(self.accounts.refund_ata_a)?,
+            )?;
+            require_eq!(
+                &TokenInterface::mint(self.accounts.refund_ata_a)?,
+                escrow.mint_a(),
+                PinocchioError::InvalidAccountData
+            );
+
+            (escrow.seed().to_le_bytes(), escrow.bump())
+        };
+
+        let escrow_seeds = [
+            Seed::from(Escrow::SEED),
+            Seed::from(self.accounts.maker.address().as_ref()),
+            Seed::from(&seed_binding),
+            Seed::from(&bump_binding),
+        ];
+        let signers = [Signer::from(&escrow_seeds)];
+
+        let amount = TokenInterface::amount(self.accounts.vault)?;
+
+        TokenInterface::transfer_checked(
+            self.accounts.vault,
+            self.accounts.mint_a,
+            self.accounts.refund_ata_a,
+            self.accounts.escrow,
+            amount,
+            &signers,
+        )?;
+        TokenInterface::close_account(
+            self.accounts.vault,
+            self.accounts.maker,
+            self.accounts.escrow,
+            &signers,
+        )?;
+
+        // Tip the cranker out of the escrow rent, the rest goes back to the maker
+        let tip = SWEEP_TIP.min(self.accounts.escrow.lamports());
+        self.accounts
+            .escrow
+            .set_lamports(self.accounts.escrow.lamports() - tip);
+        self.accounts.cranker.set_lamports(
+            self.accounts
+                .cranker
+                .lamports()
+                .checked_add(tip)
+                .ok_or(ProgramError::ArithmeticOverflow)?,
+        );
+
+        ProgramAccount::close(self.accounts.escrow, self.accounts.maker)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+    use pinocchio::Address;
+    use pinocchio_token_2022::ID as TOKEN_2022_PROGRAM_ID;
+
+    use super::*;
+
+    fn accounts(vault_program: Address, token_program: Address) -> Result<(), ProgramError> {
+        let mut cranker = TestAccount::empty(Address::new_from_array([1; 32])).signer();
+        let mut maker = TestAccount::empty(Address::new_from_array([2; 32]));
+        let mut data = [0u8; Escrow::LEN];
+        Escrow::load_mut(&mut data).unwrap().set_inner(
+            0,
+            *maker.view().address(),
+            Address::new_from_array([4; 32]),
+            Address::default(),
+            1,
+            [0],
+        );
+        let mut escrow = TestAccount::new(Address::new_from_array([3; 32]), crate::ID, &data);
+        let mut mint_a = TestAccount::mint(Address::new_from_array([4; 32]), vault_program, 6);
+        let mut vault = TestAccount::token(
+            Address::new_from_array([5; 32]),
+            vault_program,
+            mint_a.view().address(),
+            escrow.view().address(),
+            1,
+        );
+        let mut refund_ata_a = TestAccount::empty(Address::new_from_array([6; 32]));
+        let mut token_program = TestAccount::empty(token_program);
+
+        let accounts = [
+            cranker.view(),
+            maker.view(),
+            escrow.view(),
+            mint_a.view(),
+            vault.view(),
+            refund_ata_a.view(),
+            token_program.view(),
+        ];
+        SweepExpiredAccounts::try_from(&accounts[..]).map(|_| ())
+    }
+
+    #[test]
+    fn accepts_vault_of_either_token_program() {
+        for program in [pinocchio_token::ID, TOKEN_2022_PROGRAM_ID] {
+            assert_eq!(accounts(program, program), Ok(()));
+        }
+    }
+
+    #[test]
+    fn rejects_token_program_not_owning_the_vault() {
+        assert_eq!(
+            accounts(TOKEN_2022_PROGRAM_ID, pinocchio_token::ID),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+}
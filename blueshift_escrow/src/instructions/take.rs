@@ -1,16 +1,25 @@
+use core::slice;
+
 use pinocchio::{
     cpi::{Seed, Signer},
     error::ProgramError,
-    AccountView, ProgramResult,
-};
-use pinocchio_pubkey::derive_address;
-use pinocchio_token::{
-    instructions::{CloseAccount, Transfer},
-    state::TokenAccount,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, Address, ProgramResult,
 };
 
 use super::helpers::*;
-use crate::state::Escrow;
+use crate::errors::PinocchioError;
+use crate::events::TakeEvent;
+use crate::state::{Config, Escrow};
+
+/// What a `Take` charges the taker for an escrow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TakeQuote {
+    /// Token B the taker pays in total.
+    pub amount_owed: u64,
+    /// Share of `amount_owed` kept as protocol fee, the maker gets the rest.
+    pub fee: u64,
+}
 
 pub struct TakeAccounts<'a> {
     pub taker: &'a AccountView,
@@ -24,13 +33,15 @@ pub struct TakeAccounts<'a> {
     pub maker_ata_b: &'a AccountView,
     pub system_program: &'a AccountView,
     pub token_program: &'a AccountView,
+    pub config: &'a AccountView,
+    pub treasury_ata_b: &'a AccountView,
 }
 
 impl<'a> TryFrom<&'a [AccountView]> for TakeAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
-        let [taker, maker, escrow, mint_a, mint_b, vault, taker_ata_a, taker_ata_b, maker_ata_b, system_program, token_program, _] =
+        let [taker, maker, escrow, mint_a, mint_b, vault, taker_ata_a, taker_ata_b, maker_ata_b, system_program, token_program, associated_token_program, config, treasury_ata_b] =
             accounts
         else {
             return Err(ProgramError::NotEnoughAccountKeys);
@@ -38,11 +49,27 @@ impl<'a> TryFrom<&'a [AccountView]> for TakeAccounts<'a> {
 
         // Basic Accounts Checks
         SignerAccount::check(taker)?;
+        SystemProgram::check(system_program)?;
+        TokenProgram::check(token_program)?;
+        AssociatedTokenProgram::check(associated_token_program)?;
         ProgramAccount::check(escrow)?;
         MintInterface::check(mint_a)?;
         MintInterface::check(mint_b)?;
-        AssociatedTokenAccount::check(taker_ata_b, taker, mint_b, token_program)?;
-        AssociatedTokenAccount::check(vault, escrow, mint_a, token_program)?;
+        // A taker paying in SOL may not hold a wrapped SOL account yet, `Take` creates it
+        if mint_b.address().ne(&NativeMint::ID) {
+            AssociatedTokenAccount::check(
+                taker_ata_b,
+                taker.address(),
+                mint_b.address(),
+                token_program.address(),
+            )?;
+        }
+        AssociatedTokenAccount::check(
+            vault,
+            escrow.address(),
+            mint_a.address(),
+            token_program.address(),
+        )?;
 
         // Return the accounts
         Ok(Self {
@@ -57,12 +84,19 @@ impl<'a> TryFrom<&'a [AccountView]> for TakeAccounts<'a> {
             vault,
             system_program,
             token_program,
+            config,
+            treasury_ata_b,
         })
     }
 }
 
+/// Pay for the offer and take the vault. When token B is the native mint the taker pays in
+/// SOL: it is wrapped into their wrapped SOL account first, and an account created for the
+/// purpose is closed back to them afterwards.
 pub struct Take<'a> {
     pub accounts: TakeAccounts<'a>,
+    /// The taker's wrapped SOL account was created by this instruction, to be closed again.
+    pub temporary_ata_b: bool,
 }
 
 impl<'a> TryFrom<&'a [AccountView]> for Take<'a> {
@@ -71,7 +105,27 @@ impl<'a> TryFrom<&'a [AccountView]> for Take<'a> {
     fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
         let accounts = TakeAccounts::try_from(accounts)?;
 
+        let pays_in_sol = accounts.mint_b.address().eq(&NativeMint::ID);
+        let temporary_ata_b = pays_in_sol && accounts.taker_ata_b.data_len() == 0;
+
         // Initialize necessary accounts
+        if pays_in_sol {
+            AssociatedTokenAccount::init_if_needed(
+                accounts.taker_ata_b,
+                accounts.mint_b,
+                accounts.taker,
+                accounts.taker,
+                accounts.system_program,
+                accounts.token_program,
+            )?;
+            AssociatedTokenAccount::check(
+                accounts.taker_ata_b,
+                accounts.taker.address(),
+                accounts.mint_b.address(),
+                accounts.token_program.address(),
+            )?;
+        }
+
         AssociatedTokenAccount::init_if_needed(
             accounts.taker_ata_a,
             accounts.mint_a,
@@ -90,74 +144,304 @@ impl<'a> TryFrom<&'a [AccountView]> for Take<'a> {
             accounts.token_program,
         )?;
 
-        Ok(Self { accounts })
+        // Both destinations must derive from their rightful owners
+        AssociatedTokenAccount::check(
+            accounts.taker_ata_a,
+            accounts.taker.address(),
+            accounts.mint_a.address(),
+            accounts.token_program.address(),
+        )?;
+        AssociatedTokenAccount::check(
+            accounts.maker_ata_b,
+            accounts.maker.address(),
+            accounts.mint_b.address(),
+            accounts.token_program.address(),
+        )?;
+
+        Ok(Self {
+            accounts,
+            temporary_ata_b,
+        })
     }
 }
 
 impl<'a> Take<'a> {
     pub const DISCRIMINATOR: &'a u8 = &1;
 
+    /// Amount of token B the taker has to pay to the maker.
+    ///
+    /// Shared with `QuoteTake` so quotes always match what `Take` charges.
+    #[inline(always)]
+    pub fn amount_owed(escrow: &Escrow) -> Result<u64, ProgramError> {
+        Ok(escrow.receive())
+    }
+
+    /// Protocol fee taken out of `amount`, zero while the config is not initialized.
+    pub fn protocol_fee(config: &AccountView, amount: u64) -> Result<u64, ProgramError> {
+        if !config.owned_by(&crate::ID) {
+            // Only the real config PDA may stand in for a missing config
+            let (config_key, _) = Address::find_program_address(&[Config::SEED], &crate::ID);
+            if config_key.ne(config.address()) {
+                return Err(PinocchioError::InvalidAddress.into());
+            }
+            return Ok(0);
+        }
+
+        ConfigAccount::check(config)?;
+        let data = config.try_borrow()?;
+        let config_data = Config::load(&data)?;
+
+        if config_data.pda().ne(config.address()) {
+            return Err(PinocchioError::InvalidAddress.into());
+        }
+
+        Ok(config_data.fee_on(amount))
+    }
+
+    /// Check `taker` may take `escrow` at `now`.
+    ///
+    /// Shared with `QuoteTake` so it never quotes a take that would fail.
+    #[inline(always)]
+    pub fn check_taker(escrow: &Escrow, taker: &Address, now: i64) -> ProgramResult {
+        // Reserved offers can only be taken by their named taker
+        if let Some(allowed_taker) = escrow.allowed_taker() {
+            if allowed_taker.ne(taker) {
+                return Err(PinocchioError::TakerNotAllowed.into());
+            }
+        }
+
+        // Expired offers can only be refunded
+        if escrow.is_expired(now) {
+            return Err(PinocchioError::Expired.into());
+        }
+
+        Ok(())
+    }
+
+    /// What taking `escrow` charges, with the fee of the protocol `config`.
+    pub fn quote(escrow: &Escrow, config: &AccountView) -> Result<TakeQuote, ProgramError> {
+        let amount_owed = Self::amount_owed(escrow)?;
+        let fee = Self::protocol_fee(config, amount_owed)?;
+        Ok(TakeQuote { amount_owed, fee })
+    }
+
     pub fn process(&mut self) -> ProgramResult {
         let data = self.accounts.escrow.try_borrow()?;
         let escrow = Escrow::load(&data)?;
 
         // Check if the escrow is valid
-        let escrow_key = derive_address(
-            &[
-                b"escrow",
-                self.accounts.maker.address().as_array(),
-                &escrow.seed.to_le_bytes(),
-                &escrow.bump,
-            ],
-            None,
-            &crate::ID.to_bytes(),
-        );
-        if escrow_key != self.accounts.escrow.address().to_bytes() {
+        let escrow_key = escrow.pda();
+        if escrow_key.ne(self.accounts.escrow.address()) {
             return Err(ProgramError::InvalidAccountOwner);
         }
 
-        let seed_binding = escrow.seed.to_le_bytes();
-        let bump_binding = escrow.bump;
+        // The seeds below sign with the maker account, so it must be the recorded one
+        if escrow.maker().ne(self.accounts.maker.address()) {
+            return Err(PinocchioError::InvalidMaker.into());
+        }
+
+        // The mints must be the ones the offer was made for
+        if escrow.mint_a().ne(self.accounts.mint_a.address())
+            || escrow.mint_b().ne(self.accounts.mint_b.address())
+        {
+            return Err(PinocchioError::InvalidAccountData.into());
+        }
+
+        // The payment must land in a token B account of the token program the offer is paid
+        // through, whatever order the accounts came in
+        if !self
+            .accounts
+            .maker_ata_b
+            .owned_by(self.accounts.token_program.address())
+        {
+            return Err(PinocchioError::InvalidOwner.into());
+        }
+        if TokenInterface::mint(self.accounts.maker_ata_b)?.ne(escrow.mint_b()) {
+            return Err(PinocchioError::InvalidAccountData.into());
+        }
+
+        Self::check_taker(
+            &escrow,
+            self.accounts.taker.address(),
+            Clock::get()?.unix_timestamp,
+        )?;
+        let TakeQuote { amount_owed, fee } = Self::quote(&escrow, self.accounts.config)?;
+
+        let seed_binding = escrow.seed().to_le_bytes();
+        let bump_binding = escrow.bump();
         let escrow_seeds = [
-            Seed::from(b"escrow"),
+            Seed::from(Escrow::SEED),
             Seed::from(self.accounts.maker.address().as_ref()),
             Seed::from(&seed_binding),
             Seed::from(&bump_binding),
         ];
         let signer = Signer::from(&escrow_seeds);
 
-        let amount = TokenAccount::from_account_view(self.accounts.vault)?.amount();
+        let amount = TokenInterface::amount(self.accounts.vault)?;
 
         // Transfer from the Vault to the Taker
-        Transfer {
-            from: self.accounts.vault,
-            to: self.accounts.taker_ata_a,
-            authority: self.accounts.escrow,
+        TokenInterface::transfer_checked(
+            self.accounts.vault,
+            self.accounts.mint_a,
+            self.accounts.taker_ata_a,
+            self.accounts.escrow,
             amount,
-        }
-        .invoke_signed(&[signer.clone()])?;
+            slice::from_ref(&signer),
+        )?;
 
         // Close the Vault
-        CloseAccount {
-            account: self.accounts.vault,
-            destination: self.accounts.maker,
-            authority: self.accounts.escrow,
+        TokenInterface::close_account(
+            self.accounts.vault,
+            self.accounts.maker,
+            self.accounts.escrow,
+            slice::from_ref(&signer),
+        )?;
+
+        // Wrap whatever the taker's wrapped SOL balance does not already cover
+        if self.accounts.mint_b.address().eq(&NativeMint::ID) {
+            NativeMint::wrap(self.accounts.taker, self.accounts.taker_ata_b, amount_owed)?;
+        }
+
+        // Transfer the protocol fee from the Taker to the treasury
+        if fee > 0 {
+            if AssociatedTokenAccount::check(
+                self.accounts.treasury_ata_b,
+                self.accounts.config.address(),
+                self.accounts.mint_b.address(),
+                self.accounts.token_program.address(),
+            )
+            .is_err()
+            {
+                AssociatedTokenAccount::init(
+                    self.accounts.treasury_ata_b,
+                    self.accounts.mint_b,
+                    self.accounts.taker,
+                    self.accounts.config,
+                    self.accounts.system_program,
+                    self.accounts.token_program,
+                )?;
+            }
+
+            TokenInterface::transfer_checked(
+                self.accounts.taker_ata_b,
+                self.accounts.mint_b,
+                self.accounts.treasury_ata_b,
+                self.accounts.taker,
+                fee,
+                &[],
+            )?;
+        }
+
+        // Transfer the remainder from the Taker to the Maker
+        let maker_balance = TokenInterface::amount(self.accounts.maker_ata_b)?;
+        TokenInterface::transfer_checked(
+            self.accounts.taker_ata_b,
+            self.accounts.mint_b,
+            self.accounts.maker_ata_b,
+            self.accounts.taker,
+            amount_owed - fee,
+            &[],
+        )?;
+
+        // Fee-bearing mints must not leave the maker short of the offer
+        let received =
+            TokenInterface::amount(self.accounts.maker_ata_b)?.saturating_sub(maker_balance);
+        if received < amount_owed - fee {
+            return Err(PinocchioError::ReceivedTooLittle.into());
         }
-        .invoke_signed(&[signer.clone()])?;
 
-        // Transfer from the Taker to the Maker
-        Transfer {
-            from: self.accounts.taker_ata_b,
-            to: self.accounts.maker_ata_b,
-            authority: self.accounts.taker,
-            amount: escrow.receive,
+        // Unwrap what is left of a wrapped SOL account made only for this payment
+        if self.temporary_ata_b {
+            TokenInterface::close_account(
+                self.accounts.taker_ata_b,
+                self.accounts.taker,
+                self.accounts.taker,
+                &[],
+            )?;
         }
-        .invoke()?;
 
-        // Close the Escrow
+        TakeEvent {
+            escrow: self.accounts.escrow.address(),
+            taker: self.accounts.taker.address(),
+            amount,
+            receive: amount_owed,
+            order_ref: escrow.order_ref(),
+        }
+        .emit();
+
+        // Close the Escrow, its rent was paid by the maker like the vault's
         drop(data);
-        ProgramAccount::close(self.accounts.escrow, self.accounts.taker)?;
+        ProgramAccount::close(self.accounts.escrow, self.accounts.maker)?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+
+    use super::*;
+
+    /// A config charging `fee_bps`, stored at `address`.
+    fn config(address: Address, fee_bps: u16) -> TestAccount {
+        let (_, bump) = Address::find_program_address(&[Config::SEED], &crate::ID);
+        let mut data = [0u8; Config::LEN];
+        Config::load_mut(&mut data)
+            .unwrap()
+            .set_inner(fee_bps, Address::default(), [bump])
+            .unwrap();
+        TestAccount::new(address, crate::ID, &data)
+    }
+
+    fn config_key() -> Address {
+        Address::find_program_address(&[Config::SEED], &crate::ID).0
+    }
+
+    #[test]
+    fn charges_no_fee_before_the_config_exists() {
+        let mut config = TestAccount::empty(config_key());
+        assert_eq!(Take::protocol_fee(&config.view(), 10_000), Ok(0));
+    }
+
+    #[test]
+    fn charges_the_config_fee() {
+        let mut config = config(config_key(), 25);
+        assert_eq!(Take::protocol_fee(&config.view(), 10_000), Ok(25));
+        assert_eq!(Take::protocol_fee(&config.view(), 399), Ok(0));
+    }
+
+    #[test]
+    fn rejects_an_account_other_than_the_config_pda() {
+        let other = Address::new_from_array([7; 32]);
+        let invalid_address = Err(PinocchioError::InvalidAddress.into());
+
+        // Standing in for a missing config
+        let mut missing = TestAccount::empty(other);
+        assert_eq!(Take::protocol_fee(&missing.view(), 10_000), invalid_address);
+
+        // A program account holding a config elsewhere
+        let mut elsewhere = config(other, 25);
+        assert_eq!(
+            Take::protocol_fee(&elsewhere.view(), 10_000),
+            invalid_address
+        );
+    }
+
+    /// Calls in `source`, its tests aside, that search for a PDA bump, each costing 1500 CU
+    /// per bump tried.
+    fn bump_searches(source: &str) -> usize {
+        let code = source.split("#[cfg(test)]").next().unwrap();
+        ["find_program_address", "AssociatedTokenAccount::"]
+            .iter()
+            .map(|call| code.matches(call).count())
+            .sum()
+    }
+
+    #[test]
+    fn bounds_the_bump_searches_of_take() {
+        // Call sites, not runtime calls: the vault, each token account the taker may have to
+        // create, then check, and the treasury and config of a protocol fee
+        assert_eq!(bump_searches(include_str!("take.rs")), 11);
+    }
+}
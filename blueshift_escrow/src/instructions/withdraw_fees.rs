@@ -0,0 +1,144 @@
+use pinocchio::{cpi::Signer, error::ProgramError, AccountView, ProgramResult};
+
+use super::helpers::*;
+use crate::errors::PinocchioError;
+use crate::state::Config;
+
+pub struct WithdrawFeesAccounts<'a> {
+    pub fee_authority: &'a AccountView,
+    pub config: &'a AccountView,
+    pub mint: &'a AccountView,
+    pub treasury_ata: &'a AccountView,
+    pub destination: &'a AccountView,
+    pub token_program: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for WithdrawFeesAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [fee_authority, config, mint, treasury_ata, destination, token_program] = accounts
+        else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(fee_authority)?;
+        ConfigAccount::check(config)?;
+        TokenProgram::check(token_program)?;
+        MintInterface::check(mint)?;
+
+        Ok(Self {
+            fee_authority,
+            config,
+            mint,
+            treasury_ata,
+            destination,
+            token_program,
+        })
+    }
+}
+
+/// Send every protocol fee the config holds in one mint to an account of the fee authority's
+/// choosing.
+pub struct WithdrawFees<'a> {
+    pub accounts: WithdrawFeesAccounts<'a>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for WithdrawFees<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let accounts = WithdrawFeesAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> WithdrawFees<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &12;
+
+    pub fn process(&self) -> ProgramResult {
+        let data = self.accounts.config.try_borrow()?;
+        let config = Config::load(&data)?;
+
+        if config
+            .fee_authority()
+            .ne(self.accounts.fee_authority.address())
+        {
+            return Err(PinocchioError::InvalidAuthority.into());
+        }
+
+        if config.pda().ne(self.accounts.config.address()) {
+            return Err(PinocchioError::InvalidAddress.into());
+        }
+
+        // `Take` only ever pays fees into the config's own ATA
+        AssociatedTokenAccount::check(
+            self.accounts.treasury_ata,
+            self.accounts.config.address(),
+            self.accounts.mint.address(),
+            self.accounts.token_program.address(),
+        )?;
+
+        let amount = TokenInterface::amount(self.accounts.treasury_ata)?;
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let config_seeds = config.signer_seeds();
+        TokenInterface::transfer_checked(
+            self.accounts.treasury_ata,
+            self.accounts.mint,
+            self.accounts.destination,
+            self.accounts.config,
+            amount,
+            &[Signer::from(&config_seeds)],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+    use pinocchio::Address;
+
+    use super::*;
+
+    #[test]
+    fn rejects_signer_other_than_the_fee_authority() {
+        let fee_authority = Address::new_from_array([1; 32]);
+        let mint_key = Address::new_from_array([2; 32]);
+
+        let mut data = [0u8; Config::LEN];
+        Config::load_mut(&mut data)
+            .unwrap()
+            .set_inner(25, fee_authority, [255])
+            .unwrap();
+
+        let mut signer = TestAccount::empty(Address::new_from_array([9; 32])).signer();
+        let mut config = TestAccount::new(Address::new_from_array([3; 32]), crate::ID, &data);
+        let mut mint = TestAccount::mint(mint_key, pinocchio_token::ID, 6);
+        let mut treasury_ata = TestAccount::token(
+            Address::new_from_array([4; 32]),
+            pinocchio_token::ID,
+            &mint_key,
+            config.view().address(),
+            100,
+        );
+        let mut destination = TestAccount::empty(Address::new_from_array([5; 32]));
+        let mut token_program = TestAccount::empty(pinocchio_token::ID);
+
+        let accounts = [
+            signer.view(),
+            config.view(),
+            mint.view(),
+            treasury_ata.view(),
+            destination.view(),
+            token_program.view(),
+        ];
+        assert_eq!(
+            WithdrawFees::try_from(&accounts[..]).unwrap().process(),
+            Err(PinocchioError::InvalidAuthority.into())
+        );
+    }
+}
@@ -1,4 +1,6 @@
 pub mod errors;
+pub mod events;
+pub mod idl;
 pub mod instructions;
 pub mod state;
 
@@ -21,6 +23,22 @@ fn process_instruction(
         Some((Make::DISCRIMINATOR, data)) => Make::try_from((data, accounts))?.process(),
         Some((Take::DISCRIMINATOR, _)) => Take::try_from(accounts)?.process(),
         Some((Refund::DISCRIMINATOR, _)) => Refund::try_from(accounts)?.process(),
+        Some((Migrate::DISCRIMINATOR, _)) => Migrate::try_from(accounts)?.process(),
+        Some((QuoteTake::DISCRIMINATOR, _)) => QuoteTake::try_from(accounts)?.process(),
+        Some((InitConfig::DISCRIMINATOR, data)) => {
+            InitConfig::try_from((data, accounts))?.process()
+        }
+        Some((SetFee::DISCRIMINATOR, data)) => SetFee::try_from((data, accounts))?.process(),
+        Some((BatchRefund::DISCRIMINATOR, _)) => BatchRefund::try_from(accounts)?.process(),
+        Some((RefundPartial::DISCRIMINATOR, data)) => {
+            RefundPartial::try_from((data, accounts))?.process()
+        }
+        Some((MakeMany::DISCRIMINATOR, data)) => MakeMany::try_from((data, accounts))?.process(),
+        Some((SweepExpired::DISCRIMINATOR, _)) => SweepExpired::try_from(accounts)?.process(),
+        Some((CancelUnfunded::DISCRIMINATOR, data)) => {
+            CancelUnfunded::try_from((data, accounts))?.process()
+        }
+        Some((WithdrawFees::DISCRIMINATOR, _)) => WithdrawFees::try_from(accounts)?.process(),
         _ => Err(ProgramError::InvalidInstructionData),
     }
 }
@@ -1,24 +1,111 @@
-use core::mem::size_of;
-use pinocchio::{error::ProgramError, Address};
+use core::mem::{align_of, offset_of, size_of};
+use pinocchio::{cpi::Seed, error::ProgramError, Address};
+use shank::ShankAccount;
+
+use crate::errors::PinocchioError;
+
+/// Derive the escrow PDA `["escrow", maker, seed]` and its canonical bump.
+#[inline(always)]
+pub fn derive_escrow(maker: &Address, seed: u64) -> (Address, u8) {
+    Address::find_program_address(
+        &[Escrow::SEED, maker.as_array(), &seed.to_le_bytes()],
+        &crate::ID,
+    )
+}
 
 #[repr(C)]
+#[derive(Clone, Copy, ShankAccount)]
 pub struct Escrow {
-    pub seed: u64,       // Random seed for PDA derivation
-    pub maker: Address,  // Creator of the escrow
-    pub mint_a: Address, // Token being deposited
-    pub mint_b: Address, // Token being requested
-    pub receive: u64,    // Amount of token B wanted
-    pub bump: [u8; 1],   // PDA bump seed
+    version: u8, // Account layout version
+    #[idl_type("u64")]
+    seed: [u8; 8], // Random seed for PDA derivation
+    #[idl_type("Pubkey")]
+    maker: Address, // Creator of the escrow
+    #[idl_type("Pubkey")]
+    mint_a: Address, // Token being deposited
+    #[idl_type("Pubkey")]
+    mint_b: Address, // Token being requested
+    #[idl_type("u64")]
+    receive: [u8; 8], // Amount of token B wanted
+    #[idl_type("u8")]
+    bump: [u8; 1], // PDA bump seed
+    #[idl_type("i64")]
+    expires_at: [u8; 8], // Unix timestamp after which the offer expires, 0 = never
+    #[idl_type("Pubkey")]
+    refund_to: Address, // Wallet receiving token A on refund
+    #[idl_type("Pubkey")]
+    allowed_taker: Address, // Only wallet allowed to take the offer, zero = anyone
+    order_ref: [u8; 32], // Opaque reference for off-chain reconciliation
+    #[idl_type("Pubkey")]
+    maker_authority: Address, // Owner of the deposited tokens, a wallet or token multisig
+    #[idl_type("u64")]
+    deposited: [u8; 8], // Token A still held for the offer
 }
 
 impl Escrow {
-    pub const LEN: usize = size_of::<u64>()
+    pub const SEED: &'static [u8] = b"escrow";
+
+    /// Layout written by the current program.
+    pub const VERSION: u8 = 3;
+    pub const LEN: usize = size_of::<Self>();
+
+    /// Original layout, without a version byte. Identified by its length.
+    pub const V1: u8 = 1;
+    pub const V1_LEN: usize = size_of::<u64>()
         + size_of::<Address>()
         + size_of::<Address>()
         + size_of::<Address>()
         + size_of::<u64>()
         + size_of::<[u8; 1]>();
 
+    /// Layout before `deposited`, identified by its length and version byte.
+    pub const V2: u8 = 2;
+    pub const V2_LEN: usize = Self::LEN - size_of::<u64>();
+
+    /// Return the layout version stored in `bytes`, rejecting unknown versions and
+    /// lengths that match no layout.
+    #[inline(always)]
+    pub fn version_of(bytes: &[u8]) -> Result<u8, ProgramError> {
+        match bytes.len() {
+            Self::V1_LEN => Ok(Self::V1),
+            Self::V2_LEN if bytes[0] == Self::V2 => Ok(Self::V2),
+            Self::V2_LEN => Err(PinocchioError::InvalidVersion.into()),
+            Self::LEN if bytes[0] == Self::VERSION => Ok(Self::VERSION),
+            Self::LEN => Err(PinocchioError::InvalidVersion.into()),
+            _ => Err(PinocchioError::InvalidAccountData.into()),
+        }
+    }
+
+    /// Read an escrow of any known version, mapping older layouts onto the current one.
+    #[inline(always)]
+    pub fn load(bytes: &[u8]) -> Result<Self, ProgramError> {
+        let mut raw = [0u8; Self::LEN];
+
+        match Self::version_of(bytes)? {
+            // v1 is the start of the current layout without the leading version byte, the
+            // fields after it keep their zero default
+            Self::V1 => raw[1..=Self::V1_LEN].copy_from_slice(bytes),
+            // v2 is the current layout without deposited, which `Migrate` fills in
+            Self::V2 => raw[..Self::V2_LEN].copy_from_slice(bytes),
+            _ => raw.copy_from_slice(bytes),
+        }
+        raw[0] = Self::VERSION;
+
+        let mut escrow = unsafe { (raw.as_ptr() as *const Self).read_unaligned() };
+        // Escrows made before refund_to existed refund to their maker
+        if escrow.refund_to == Address::default() {
+            escrow.refund_to = escrow.maker;
+        }
+        // Before maker_authority existed the maker always owned the deposited tokens
+        if escrow.maker_authority == Address::default() {
+            escrow.maker_authority = escrow.maker;
+        }
+
+        Ok(escrow)
+    }
+
+    /// Return a mutable reference over the current layout. Only the length is validated:
+    /// the caller is expected to populate the account with `set_inner`.
     #[inline(always)]
     pub fn load_mut(bytes: &mut [u8]) -> Result<&mut Self, ProgramError> {
         if bytes.len() != Escrow::LEN {
@@ -28,16 +115,97 @@ impl Escrow {
     }
 
     #[inline(always)]
-    pub fn load(bytes: &[u8]) -> Result<&Self, ProgramError> {
-        if bytes.len() != Escrow::LEN {
-            return Err(ProgramError::InvalidAccountData);
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    #[inline(always)]
+    pub fn seed(&self) -> u64 {
+        u64::from_le_bytes(self.seed)
+    }
+
+    #[inline(always)]
+    pub fn maker(&self) -> &Address {
+        &self.maker
+    }
+
+    #[inline(always)]
+    pub fn mint_a(&self) -> &Address {
+        &self.mint_a
+    }
+
+    #[inline(always)]
+    pub fn mint_b(&self) -> &Address {
+        &self.mint_b
+    }
+
+    #[inline(always)]
+    pub fn receive(&self) -> u64 {
+        u64::from_le_bytes(self.receive)
+    }
+
+    #[inline(always)]
+    pub fn bump(&self) -> [u8; 1] {
+        self.bump
+    }
+
+    /// Address of this escrow, re-derived from its stored maker, seed and bump.
+    #[inline(always)]
+    pub fn pda(&self) -> Address {
+        Address::derive_address(
+            &[
+                Self::SEED,
+                self.maker().as_array(),
+                &self.seed().to_le_bytes(),
+                &self.bump(),
+            ],
+            None,
+            &crate::ID,
+        )
+    }
+
+    #[inline(always)]
+    pub fn expires_at(&self) -> i64 {
+        i64::from_le_bytes(self.expires_at)
+    }
+
+    #[inline(always)]
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at() != 0 && now > self.expires_at()
+    }
+
+    #[inline(always)]
+    pub fn refund_to(&self) -> &Address {
+        &self.refund_to
+    }
+
+    #[inline(always)]
+    pub fn allowed_taker(&self) -> Option<&Address> {
+        if self.allowed_taker == Address::default() {
+            None
+        } else {
+            Some(&self.allowed_taker)
         }
-        Ok(unsafe { &*core::mem::transmute::<*const u8, *const Self>(bytes.as_ptr()) })
+    }
+
+    #[inline(always)]
+    pub fn order_ref(&self) -> &[u8; 32] {
+        &self.order_ref
+    }
+
+    #[inline(always)]
+    pub fn maker_authority(&self) -> &Address {
+        &self.maker_authority
+    }
+
+    #[inline(always)]
+    pub fn deposited(&self) -> u64 {
+        u64::from_le_bytes(self.deposited)
     }
 
     #[inline(always)]
     pub fn set_seed(&mut self, seed: u64) {
-        self.seed = seed;
+        self.seed = seed.to_le_bytes();
     }
 
     #[inline(always)]
@@ -57,7 +225,7 @@ impl Escrow {
 
     #[inline(always)]
     pub fn set_receive(&mut self, receive: u64) {
-        self.receive = receive;
+        self.receive = receive.to_le_bytes();
     }
 
     #[inline(always)]
@@ -65,6 +233,36 @@ impl Escrow {
         self.bump = bump;
     }
 
+    #[inline(always)]
+    pub fn set_expires_at(&mut self, expires_at: i64) {
+        self.expires_at = expires_at.to_le_bytes();
+    }
+
+    #[inline(always)]
+    pub fn set_refund_to(&mut self, refund_to: Address) {
+        self.refund_to = refund_to;
+    }
+
+    #[inline(always)]
+    pub fn set_allowed_taker(&mut self, allowed_taker: Address) {
+        self.allowed_taker = allowed_taker;
+    }
+
+    #[inline(always)]
+    pub fn set_order_ref(&mut self, order_ref: [u8; 32]) {
+        self.order_ref = order_ref;
+    }
+
+    #[inline(always)]
+    pub fn set_maker_authority(&mut self, maker_authority: Address) {
+        self.maker_authority = maker_authority;
+    }
+
+    #[inline(always)]
+    pub fn set_deposited(&mut self, deposited: u64) {
+        self.deposited = deposited.to_le_bytes();
+    }
+
     #[inline(always)]
     pub fn set_inner(
         &mut self,
@@ -75,11 +273,338 @@ impl Escrow {
         receive: u64,
         bump: [u8; 1],
     ) {
-        self.seed = seed;
+        self.version = Self::VERSION;
+        self.seed = seed.to_le_bytes();
         self.maker = maker;
         self.mint_a = mint_a;
         self.mint_b = mint_b;
-        self.receive = receive;
+        self.receive = receive.to_le_bytes();
+        self.bump = bump;
+    }
+}
+
+// Accounts already on chain depend on this layout, any change needs a new version.
+// v1 ends where the first field it lacks starts, less the version byte, and v2 where
+// deposited starts.
+const _: () = {
+    // Byte fields only, so casting unaligned account data is sound
+    assert!(align_of::<Escrow>() == 1);
+    assert!(Escrow::LEN == 258);
+    assert!(offset_of!(Escrow, version) == 0);
+    assert!(offset_of!(Escrow, seed) == 1);
+    assert!(offset_of!(Escrow, maker) == 9);
+    assert!(offset_of!(Escrow, mint_a) == 41);
+    assert!(offset_of!(Escrow, mint_b) == 73);
+    assert!(offset_of!(Escrow, receive) == 105);
+    assert!(offset_of!(Escrow, bump) == 113);
+    assert!(offset_of!(Escrow, expires_at) == Escrow::V1_LEN + 1);
+    assert!(offset_of!(Escrow, refund_to) == 122);
+    assert!(offset_of!(Escrow, allowed_taker) == 154);
+    assert!(offset_of!(Escrow, order_ref) == 186);
+    assert!(offset_of!(Escrow, maker_authority) == 218);
+    assert!(offset_of!(Escrow, deposited) == Escrow::V2_LEN);
+};
+
+#[repr(C)]
+#[derive(ShankAccount)]
+pub struct Config {
+    #[idl_type("u16")]
+    fee_bps: [u8; 2], // Protocol fee charged on Take, in basis points
+    #[idl_type("Pubkey")]
+    fee_authority: Address, // Allowed to update the fee
+    #[idl_type("u8")]
+    bump: [u8; 1], // PDA bump seed
+}
+
+impl Config {
+    pub const LEN: usize = size_of::<Self>();
+    pub const SEED: &'static [u8] = b"config";
+    /// 10%. Anything close to the whole payment would leave the maker with nothing.
+    pub const MAX_FEE_BPS: u16 = 1_000;
+
+    #[inline(always)]
+    pub fn load_mut(bytes: &mut [u8]) -> Result<&mut Self, ProgramError> {
+        if bytes.len() != Config::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(unsafe { &mut *core::mem::transmute::<*mut u8, *mut Self>(bytes.as_mut_ptr()) })
+    }
+
+    #[inline(always)]
+    pub fn load(bytes: &[u8]) -> Result<&Self, ProgramError> {
+        if bytes.len() != Config::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(unsafe { &*core::mem::transmute::<*const u8, *const Self>(bytes.as_ptr()) })
+    }
+
+    #[inline(always)]
+    pub fn fee_bps(&self) -> u16 {
+        u16::from_le_bytes(self.fee_bps)
+    }
+
+    #[inline(always)]
+    pub fn fee_authority(&self) -> &Address {
+        &self.fee_authority
+    }
+
+    #[inline(always)]
+    pub fn bump(&self) -> [u8; 1] {
+        self.bump
+    }
+
+    /// Address of the config, re-derived from its stored bump.
+    #[inline(always)]
+    pub fn pda(&self) -> Address {
+        Address::derive_address(&[Self::SEED, &self.bump()], None, &crate::ID)
+    }
+
+    /// Seeds the config signs with, for moving the fees it holds.
+    #[inline(always)]
+    pub fn signer_seeds(&self) -> [Seed<'_>; 2] {
+        [Seed::from(Self::SEED), Seed::from(&self.bump)]
+    }
+
+    /// Protocol fee owed on `amount`, rounded down in favor of the maker.
+    #[inline(always)]
+    pub fn fee_on(&self, amount: u64) -> u64 {
+        (amount as u128 * self.fee_bps() as u128 / 10_000) as u64
+    }
+
+    #[inline(always)]
+    pub fn set_fee_bps(&mut self, fee_bps: u16) -> Result<(), ProgramError> {
+        if fee_bps > Self::MAX_FEE_BPS {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        self.fee_bps = fee_bps.to_le_bytes();
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn set_inner(
+        &mut self,
+        fee_bps: u16,
+        fee_authority: Address,
+        bump: [u8; 1],
+    ) -> Result<(), ProgramError> {
+        self.set_fee_bps(fee_bps)?;
+        self.fee_authority = fee_authority;
         self.bump = bump;
+        Ok(())
+    }
+}
+
+const _: () = {
+    assert!(align_of::<Config>() == 1);
+    assert!(Config::LEN == 35);
+    assert!(offset_of!(Config, fee_bps) == 0);
+    assert!(offset_of!(Config, fee_authority) == 2);
+    assert!(offset_of!(Config, bump) == 34);
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A v1 escrow, laid out byte by byte as the original program wrote it.
+    fn v1_escrow() -> [u8; Escrow::V1_LEN] {
+        let mut data = [0u8; Escrow::V1_LEN];
+        data[0..8].copy_from_slice(&42u64.to_le_bytes());
+        data[8..40].copy_from_slice(&[1; 32]);
+        data[40..72].copy_from_slice(&[2; 32]);
+        data[72..104].copy_from_slice(&[3; 32]);
+        data[104..112].copy_from_slice(&500u64.to_le_bytes());
+        data[112] = 253;
+        data
+    }
+
+    #[test]
+    fn loads_a_v1_escrow() {
+        let v1 = v1_escrow();
+        assert_eq!(Escrow::version_of(&v1), Ok(Escrow::V1));
+
+        let escrow = Escrow::load(&v1).unwrap();
+        let maker = Address::new_from_array([1; 32]);
+
+        assert_eq!(escrow.version(), Escrow::VERSION);
+        assert_eq!(escrow.seed(), 42);
+        assert_eq!(escrow.maker(), &maker);
+        assert_eq!(escrow.mint_a(), &Address::new_from_array([2; 32]));
+        assert_eq!(escrow.mint_b(), &Address::new_from_array([3; 32]));
+        assert_eq!(escrow.receive(), 500);
+        assert_eq!(escrow.bump(), [253]);
+        // Fields v1 lacks take the behaviour the original program had
+        assert_eq!(escrow.expires_at(), 0);
+        assert!(!escrow.is_expired(i64::MAX));
+        assert_eq!(escrow.refund_to(), &maker);
+        assert_eq!(escrow.allowed_taker(), None);
+        assert_eq!(escrow.order_ref(), &[0; 32]);
+        assert_eq!(escrow.maker_authority(), &maker);
+        assert_eq!(escrow.deposited(), 0);
+    }
+
+    #[test]
+    fn loads_a_v2_escrow() {
+        let mut v2 = [0u8; Escrow::V2_LEN];
+        v2[0] = Escrow::V2;
+        v2[1..9].copy_from_slice(&42u64.to_le_bytes());
+        v2[9..41].copy_from_slice(&[1; 32]);
+        v2[105..113].copy_from_slice(&500u64.to_le_bytes());
+        v2[218..250].copy_from_slice(&[7; 32]);
+        assert_eq!(Escrow::version_of(&v2), Ok(Escrow::V2));
+
+        let escrow = Escrow::load(&v2).unwrap();
+        assert_eq!(escrow.version(), Escrow::VERSION);
+        assert_eq!(escrow.seed(), 42);
+        assert_eq!(escrow.receive(), 500);
+        assert_eq!(escrow.maker_authority(), &Address::new_from_array([7; 32]));
+        // Unknown until `Migrate` reads the vault
+        assert_eq!(escrow.deposited(), 0);
+    }
+
+    #[test]
+    fn rejects_unknown_escrow_versions_and_lengths() {
+        let mut data = [0u8; Escrow::LEN];
+        data[0] = Escrow::VERSION + 1;
+        assert_eq!(
+            Escrow::version_of(&data).err(),
+            Some(PinocchioError::InvalidVersion.into())
+        );
+        assert_eq!(
+            Escrow::version_of(&data[..Escrow::V2_LEN]).err(),
+            Some(PinocchioError::InvalidVersion.into())
+        );
+        assert_eq!(
+            Escrow::version_of(&data[..Escrow::V1_LEN + 1]).err(),
+            Some(PinocchioError::InvalidAccountData.into())
+        );
+    }
+
+    #[test]
+    fn writes_the_escrow_at_its_fixed_offsets() {
+        let mut data = [0u8; Escrow::LEN];
+        let escrow = Escrow::load_mut(&mut data).unwrap();
+        escrow.set_inner(
+            42,
+            Address::new_from_array([1; 32]),
+            Address::new_from_array([2; 32]),
+            Address::new_from_array([3; 32]),
+            500,
+            [253],
+        );
+        escrow.set_expires_at(-7);
+        escrow.set_refund_to(Address::new_from_array([4; 32]));
+        escrow.set_allowed_taker(Address::new_from_array([5; 32]));
+        escrow.set_order_ref([6; 32]);
+        escrow.set_maker_authority(Address::new_from_array([7; 32]));
+        escrow.set_deposited(900);
+
+        assert_eq!(data[0], Escrow::VERSION);
+        assert_eq!(data[1..9], 42u64.to_le_bytes());
+        assert_eq!(data[9..41], [1; 32]);
+        assert_eq!(data[41..73], [2; 32]);
+        assert_eq!(data[73..105], [3; 32]);
+        assert_eq!(data[105..113], 500u64.to_le_bytes());
+        assert_eq!(data[113], 253);
+        assert_eq!(data[114..122], (-7i64).to_le_bytes());
+        assert_eq!(data[122..154], [4; 32]);
+        assert_eq!(data[154..186], [5; 32]);
+        assert_eq!(data[186..218], [6; 32]);
+        assert_eq!(data[218..250], [7; 32]);
+        assert_eq!(data[250..258], 900u64.to_le_bytes());
+    }
+
+    #[test]
+    fn writes_the_config_at_its_fixed_offsets() {
+        let mut data = config();
+        Config::load_mut(&mut data)
+            .unwrap()
+            .set_inner(25, Address::new_from_array([9; 32]), [254])
+            .unwrap();
+
+        assert_eq!(data[0..2], 25u16.to_le_bytes());
+        assert_eq!(data[2..34], [9; 32]);
+        assert_eq!(data[34], 254);
+    }
+
+    #[test]
+    fn reads_state_from_a_misaligned_buffer() {
+        let mut buffer = [0u8; Escrow::LEN + 1];
+        for offset in [0, 1] {
+            let data = &mut buffer[offset..offset + Escrow::LEN];
+            Escrow::load_mut(data).unwrap().set_inner(
+                42,
+                Address::new_from_array([1; 32]),
+                Address::new_from_array([2; 32]),
+                Address::new_from_array([3; 32]),
+                500,
+                [253],
+            );
+
+            let escrow = Escrow::load(data).unwrap();
+            assert_eq!(escrow.receive(), 500);
+            assert_eq!(escrow.mint_b(), &Address::new_from_array([3; 32]));
+        }
+
+        let mut buffer = [0u8; Config::LEN + 1];
+        for offset in [0, 1] {
+            let data = &mut buffer[offset..offset + Config::LEN];
+            Config::load_mut(data).unwrap().set_fee_bps(25).unwrap();
+            assert_eq!(Config::load(data).unwrap().fee_bps(), 25);
+        }
+    }
+
+    fn config() -> [u8; Config::LEN] {
+        [0; Config::LEN]
+    }
+
+    fn fee_on(fee_bps: u16, amount: u64) -> u64 {
+        let mut data = config();
+        let config = Config::load_mut(&mut data).unwrap();
+        config.set_fee_bps(fee_bps).unwrap();
+        config.fee_on(amount)
+    }
+
+    #[test]
+    fn charges_nothing_at_zero_bps() {
+        assert_eq!(fee_on(0, 1_000_000), 0);
+        assert_eq!(fee_on(0, u64::MAX), 0);
+    }
+
+    #[test]
+    fn charges_25_bps() {
+        assert_eq!(fee_on(25, 1_000_000), 2_500);
+        assert_eq!(fee_on(25, 400), 1);
+    }
+
+    #[test]
+    fn charges_the_max_fee() {
+        assert_eq!(fee_on(Config::MAX_FEE_BPS, 1_000_000), 100_000);
+        // No overflow on the largest payment
+        assert_eq!(fee_on(Config::MAX_FEE_BPS, u64::MAX), u64::MAX / 10);
+    }
+
+    #[test]
+    fn rounds_the_fee_down_for_the_maker() {
+        // 25 bps of 399 is 0.9975
+        assert_eq!(fee_on(25, 399), 0);
+        // 25 bps of 1_999 is 4.9975
+        assert_eq!(fee_on(25, 1_999), 4);
+    }
+
+    #[test]
+    fn caps_the_fee_at_max_fee_bps() {
+        let mut data = config();
+        let config = Config::load_mut(&mut data).unwrap();
+        assert_eq!(config.set_fee_bps(Config::MAX_FEE_BPS), Ok(()));
+        assert_eq!(
+            config.set_fee_bps(Config::MAX_FEE_BPS + 1),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            config.set_inner(10_000, Address::default(), [0]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(config.fee_bps(), Config::MAX_FEE_BPS);
     }
 }
@@ -0,0 +1,216 @@
+use {
+    constant_product_curve::CurveError,
+    num_derive::FromPrimitive,
+    pinocchio::error::{ProgramError, ToStr},
+    thiserror::Error,
+};
+
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum AmmError {
+    // 0
+    /// More accounts than the instruction expects
+    #[error("Too many accounts provided")]
+    TooManyAccountKeys,
+
+    /// 1
+    /// Output vault cannot cover the swap
+    #[error("Insufficient vault liquidity")]
+    InsufficientLiquidity,
+
+    /// 2
+    /// Config layout version is not supported
+    #[error("Unknown config layout version")]
+    InvalidVersion,
+
+    /// 3
+    /// Vault balance was not restored plus fee after a flash swap
+    #[error("Flash swap not repaid")]
+    FlashSwapNotRepaid,
+
+    /// 4
+    /// Instruction executed after its expiration timestamp
+    #[error("Transaction expired")]
+    Expired,
+
+    /// 5
+    /// Emergency pause without any vault shortfall
+    #[error("Vault balances match the reserves")]
+    ReservesIntact,
+
+    /// 6
+    /// Pool resumed before the timelock after its pause
+    #[error("Resume timelock active")]
+    ResumeTimelocked,
+
+    /// 7
+    /// Admin instruction on a pool whose authority was never set or was renounced
+    #[error("Pool has no authority")]
+    ImmutablePool,
+
+    /// 8
+    /// Pool initialized with the same mint on both sides
+    #[error("Pool mints must differ")]
+    IdenticalMints,
+
+    /// 9
+    /// LP burn that would withdraw nothing of either token
+    #[error("Withdraw rounds to zero")]
+    ZeroAmount,
+
+    /// 10
+    /// A pool mint reports more decimals than the AMM supports
+    #[error("Mint decimals out of range")]
+    InvalidMint,
+
+    /// 11
+    /// A swap would pay out the whole output reserve
+    #[error("Swap would empty the output vault")]
+    SwapDrainsVault,
+
+    /// 12
+    /// The result is worse than the caller's bound
+    #[error("Slippage tolerance exceeded")]
+    SlippageExceeded,
+
+    /// 13
+    /// The constant product curve rejected the amounts
+    #[error("Curve math failed")]
+    CurveError,
+
+    /// 14
+    /// The pool state does not allow the operation
+    #[error("Operation disabled for this pool")]
+    PoolDisabled,
+
+    /// 15
+    /// A vault account is not the one recorded in the config
+    #[error("Vault is not the pool's")]
+    InvalidVault,
+
+    /// 16
+    /// mint_x must sort before mint_y
+    #[error("Pool mints out of order")]
+    UnorderedMints,
+
+    /// 17
+    /// A pool amount does not fit its type
+    #[error("Arithmetic overflow")]
+    MathOverflow,
+
+    /// 18
+    /// The LP mint account is not the one recorded in the config
+    #[error("LP mint is not the pool's")]
+    InvalidMintLp,
+
+    /// 19
+    /// The pair's registry already lists MAX_POOLS pools
+    #[error("Pool registry full")]
+    RegistryFull,
+}
+
+impl From<AmmError> for ProgramError {
+    fn from(e: AmmError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl From<CurveError> for AmmError {
+    fn from(e: CurveError) -> Self {
+        match e {
+            CurveError::SlippageLimitExceeded => AmmError::SlippageExceeded,
+            _ => AmmError::CurveError,
+        }
+    }
+}
+
+impl TryFrom<u32> for AmmError {
+    type Error = ProgramError;
+    fn try_from(error: u32) -> Result<Self, Self::Error> {
+        match error {
+            0 => Ok(AmmError::TooManyAccountKeys),
+            1 => Ok(AmmError::InsufficientLiquidity),
+            2 => Ok(AmmError::InvalidVersion),
+            3 => Ok(AmmError::FlashSwapNotRepaid),
+            4 => Ok(AmmError::Expired),
+            5 => Ok(AmmError::ReservesIntact),
+            6 => Ok(AmmError::ResumeTimelocked),
+            7 => Ok(AmmError::ImmutablePool),
+            8 => Ok(AmmError::IdenticalMints),
+            9 => Ok(AmmError::ZeroAmount),
+            10 => Ok(AmmError::InvalidMint),
+            11 => Ok(AmmError::SwapDrainsVault),
+            12 => Ok(AmmError::SlippageExceeded),
+            13 => Ok(AmmError::CurveError),
+            14 => Ok(AmmError::PoolDisabled),
+            15 => Ok(AmmError::InvalidVault),
+            16 => Ok(AmmError::UnorderedMints),
+            17 => Ok(AmmError::MathOverflow),
+            18 => Ok(AmmError::InvalidMintLp),
+            19 => Ok(AmmError::RegistryFull),
+            _ => Err(ProgramError::InvalidArgument),
+        }
+    }
+}
+
+impl TryFrom<ProgramError> for AmmError {
+    type Error = ProgramError;
+
+    /// Recover the `AmmError` behind a `Custom` code, handing any other error back unchanged.
+    fn try_from(error: ProgramError) -> Result<Self, Self::Error> {
+        match error {
+            ProgramError::Custom(code) => AmmError::try_from(code).map_err(|_| error),
+            _ => Err(error),
+        }
+    }
+}
+
+impl ToStr for AmmError {
+    fn to_str(&self) -> &'static str {
+        match self {
+            AmmError::TooManyAccountKeys => "Error: Too many accounts provided",
+            AmmError::InsufficientLiquidity => "Error: Insufficient vault liquidity",
+            AmmError::InvalidVersion => "Error: Unknown config layout version",
+            AmmError::FlashSwapNotRepaid => "Error: Flash swap not repaid",
+            AmmError::Expired => "Error: Transaction expired",
+            AmmError::ReservesIntact => "Error: Vault balances match the reserves",
+            AmmError::ResumeTimelocked => "Error: Resume timelock active",
+            AmmError::ImmutablePool => "Error: Pool has no authority",
+            AmmError::IdenticalMints => "Error: Pool mints must differ",
+            AmmError::ZeroAmount => "Error: Withdraw rounds to zero",
+            AmmError::InvalidMint => "Error: Mint decimals out of range",
+            AmmError::SwapDrainsVault => "Error: Swap would empty the output vault",
+            AmmError::SlippageExceeded => "Error: Slippage tolerance exceeded",
+            AmmError::CurveError => "Error: Curve math failed",
+            AmmError::PoolDisabled => "Error: Operation disabled for this pool",
+            AmmError::InvalidVault => "Error: Vault is not the pool's",
+            AmmError::UnorderedMints => "Error: Pool mints out of order",
+            AmmError::MathOverflow => "Error: Arithmetic overflow",
+            AmmError::InvalidMintLp => "Error: LP mint is not the pool's",
+            AmmError::RegistryFull => "Error: Pool registry full",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_the_amm_error_behind_a_custom_code() {
+        let error: ProgramError = AmmError::SlippageExceeded.into();
+        assert_eq!(AmmError::try_from(error), Ok(AmmError::SlippageExceeded));
+
+        let error: ProgramError = AmmError::MathOverflow.into();
+        assert_eq!(AmmError::try_from(error), Ok(AmmError::MathOverflow));
+    }
+
+    #[test]
+    fn hands_other_errors_back_unchanged() {
+        for error in [
+            ProgramError::InvalidAccountData,
+            ProgramError::Custom(AmmError::RegistryFull as u32 + 1),
+        ] {
+            assert_eq!(AmmError::try_from(error.clone()), Err(error));
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use pinocchio::Address;
+
+/// Log `fields` with `sol_log_data`: an 8-byte discriminator followed by the event fields,
+/// laid out like Anchor events so the same decoders apply.
+#[inline(always)]
+fn emit(fields: &[&[u8]]) {
+    #[cfg(any(target_os = "solana", target_arch = "bpf"))]
+    unsafe {
+        pinocchio::syscalls::sol_log_data(fields.as_ptr() as *const u8, fields.len() as u64)
+    };
+
+    #[cfg(not(any(target_os = "solana", target_arch = "bpf")))]
+    core::hint::black_box(fields);
+}
+
+/// A swap, `amount_in` being what the input vault received and `fee` the LP and protocol
+/// fees taken from it. Reserves exclude accrued protocol fees.
+pub struct SwapEvent<'a> {
+    pub user: &'a Address,
+    pub is_x: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,
+    pub reserve_x_after: u64,
+    pub reserve_y_after: u64,
+}
+
+impl SwapEvent<'_> {
+    /// `sha256("event:SwapEvent")[..8]`
+    pub const DISCRIMINATOR: [u8; 8] = [64, 198, 205, 232, 38, 8, 113, 226];
+
+    pub fn emit(&self) {
+        emit(&[
+            &Self::DISCRIMINATOR,
+            self.user.as_ref(),
+            &[self.is_x as u8],
+            &self.amount_in.to_le_bytes(),
+            &self.amount_out.to_le_bytes(),
+            &self.fee.to_le_bytes(),
+            &self.reserve_x_after.to_le_bytes(),
+            &self.reserve_y_after.to_le_bytes(),
+        ]);
+    }
+}
+
+/// A deposit of `amount_x` and `amount_y`, as received by the vaults, for `lp` LP tokens.
+pub struct DepositEvent<'a> {
+    pub user: &'a Address,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub lp: u64,
+    pub reserve_x_after: u64,
+    pub reserve_y_after: u64,
+}
+
+impl DepositEvent<'_> {
+    /// `sha256("event:DepositEvent")[..8]`
+    pub const DISCRIMINATOR: [u8; 8] = [120, 248, 61, 83, 31, 142, 107, 144];
+
+    pub fn emit(&self) {
+        emit(&[
+            &Self::DISCRIMINATOR,
+            self.user.as_ref(),
+            &self.amount_x.to_le_bytes(),
+            &self.amount_y.to_le_bytes(),
+            &self.lp.to_le_bytes(),
+            &self.reserve_x_after.to_le_bytes(),
+            &self.reserve_y_after.to_le_bytes(),
+        ]);
+    }
+}
+
+/// A withdrawal of `amount_x` and `amount_y` out of the vaults for `lp` burnt LP tokens.
+pub struct WithdrawEvent<'a> {
+    pub user: &'a Address,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub lp: u64,
+    pub reserve_x_after: u64,
+    pub reserve_y_after: u64,
+}
+
+impl WithdrawEvent<'_> {
+    /// `sha256("event:WithdrawEvent")[..8]`
+    pub const DISCRIMINATOR: [u8; 8] = [22, 9, 133, 26, 160, 44, 71, 192];
+
+    pub fn emit(&self) {
+        emit(&[
+            &Self::DISCRIMINATOR,
+            self.user.as_ref(),
+            &self.amount_x.to_le_bytes(),
+            &self.amount_y.to_le_bytes(),
+            &self.lp.to_le_bytes(),
+            &self.reserve_x_after.to_le_bytes(),
+            &self.reserve_y_after.to_le_bytes(),
+        ]);
+    }
+}
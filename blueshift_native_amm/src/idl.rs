@@ -0,0 +1,844 @@
+//! Instruction layout consumed by `shank idl`. Nothing here runs on chain: the variant order
+//! mirrors each instruction's `DISCRIMINATOR` and the accounts mirror their `TryFrom` order.
+//! `expiration` args are unix timestamps after which the instruction fails, zero meaning none.
+
+use pinocchio::Address;
+use shank::ShankInstruction;
+
+type Pubkey = Address;
+
+#[derive(ShankInstruction)]
+pub enum AmmInstruction {
+    /// Create the pool config, LP mint, both vaults and the locked LP account, and append the
+    /// config to the pair's registry. `mint_x` must sort bytewise before `mint_y`. Each mint may
+    /// belong to either token program, the LP mint is created under the program of `mint_x`.
+    /// `authority`, `lp_freeze_authority` and `canonical_pool` are optional trailing fields,
+    /// zero meaning none. A nonzero `canonical_pool` also creates the pair's canonical pool PDA
+    /// naming this pool, failing when the pair already has one.
+    #[account(
+        0,
+        writable,
+        signer,
+        name = "initializer",
+        desc = "Pays for the pool accounts"
+    )]
+    #[account(1, writable, name = "mint_lp", desc = "PDA [\"mint_lp\", config]")]
+    #[account(
+        2,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(3, name = "system_program", desc = "System program")]
+    #[account(
+        4,
+        name = "token_program_x",
+        desc = "Token program owning mint_x, also used for the LP mint"
+    )]
+    #[account(5, name = "token_program_y", desc = "Token program owning mint_y")]
+    #[account(6, name = "mint_x", desc = "Token X mint")]
+    #[account(7, name = "mint_y", desc = "Token Y mint, distinct from mint_x")]
+    #[account(
+        8,
+        writable,
+        name = "vault_x",
+        desc = "Config token X account, created if missing"
+    )]
+    #[account(
+        9,
+        writable,
+        name = "vault_y",
+        desc = "Config token Y account, created if missing"
+    )]
+    #[account(
+        10,
+        name = "associated_token_program",
+        desc = "Associated token program"
+    )]
+    #[account(
+        11,
+        writable,
+        name = "locked_lp",
+        desc = "Config LP token account, created if missing"
+    )]
+    #[account(
+        12,
+        writable,
+        name = "registry",
+        desc = "PDA [\"registry\", mint_x, mint_y], created on the pair's first pool"
+    )]
+    #[account(
+        13,
+        optional,
+        writable,
+        name = "canonical_pool",
+        desc = "PDA [\"canonical_pool\", mint_x, mint_y], required with canonical_pool set"
+    )]
+    Initialize {
+        seed: u64,
+        fee: u16,
+        mint_x: Pubkey,
+        mint_y: Pubkey,
+        config_bump: u8,
+        lp_bump: u8,
+        authority: Pubkey,
+        lp_freeze_authority: Pubkey,
+        canonical_pool: bool,
+    },
+
+    /// Deposit both tokens and mint `amount` LP tokens. Into an empty pool exactly `max_x`
+    /// and `max_y` are deposited for sqrt(max_x * max_y) LP tokens, ignoring `amount`, of
+    /// which `MINIMUM_LIQUIDITY` is locked in `locked_lp`. When a transfer fee makes the
+    /// vaults receive less than was sent, LP is only minted for what arrived. Minting less than
+    /// `min_lp` fails, `min_lp` being an optional trailing field with zero meaning no floor.
+    #[account(0, signer, name = "user", desc = "Liquidity provider")]
+    #[account(1, writable, name = "mint_lp", desc = "PDA [\"mint_lp\", config]")]
+    #[account(2, writable, name = "vault_x", desc = "Config token X account")]
+    #[account(3, writable, name = "vault_y", desc = "Config token Y account")]
+    #[account(4, writable, name = "user_x_ata", desc = "User token X account")]
+    #[account(5, writable, name = "user_y_ata", desc = "User token Y account")]
+    #[account(6, writable, name = "user_lp_ata", desc = "User LP token account")]
+    #[account(
+        7,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(
+        8,
+        name = "token_program_x",
+        desc = "Token program of mint X and the LP mint"
+    )]
+    #[account(9, name = "token_program_y", desc = "Token program of mint Y")]
+    #[account(10, name = "mint_x", desc = "Token X mint")]
+    #[account(11, name = "mint_y", desc = "Token Y mint")]
+    #[account(
+        12,
+        optional,
+        writable,
+        name = "locked_lp",
+        desc = "Config LP token account, required by the first deposit"
+    )]
+    Deposit {
+        amount: u64,
+        max_x: u64,
+        max_y: u64,
+        expiration: i64,
+        min_lp: u64,
+    },
+
+    /// Burn `amount` LP tokens and withdraw both tokens.
+    #[account(0, signer, name = "user", desc = "Liquidity provider")]
+    #[account(1, writable, name = "mint_lp", desc = "PDA [\"mint_lp\", config]")]
+    #[account(2, writable, name = "vault_x", desc = "Config token X account")]
+    #[account(3, writable, name = "vault_y", desc = "Config token Y account")]
+    #[account(4, writable, name = "user_x_ata", desc = "User token X account")]
+    #[account(5, writable, name = "user_y_ata", desc = "User token Y account")]
+    #[account(6, writable, name = "user_lp_ata", desc = "User LP token account")]
+    #[account(
+        7,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(
+        8,
+        name = "token_program_x",
+        desc = "Token program of mint X and the LP mint"
+    )]
+    #[account(9, name = "token_program_y", desc = "Token program of mint Y")]
+    #[account(10, name = "mint_x", desc = "Token X mint")]
+    #[account(11, name = "mint_y", desc = "Token Y mint")]
+    Withdraw {
+        amount: u64,
+        min_x: u64,
+        min_y: u64,
+        expiration: i64,
+    },
+
+    /// Swap `amount` of one token for at least `min` of the other. The curve prices what the
+    /// input vault received, net of any transfer fee.
+    #[account(0, signer, name = "user", desc = "Trader")]
+    #[account(1, writable, name = "user_x_ata", desc = "User token X account")]
+    #[account(2, writable, name = "user_y_ata", desc = "User token Y account")]
+    #[account(3, writable, name = "vault_x", desc = "Config token X account")]
+    #[account(4, writable, name = "vault_y", desc = "Config token Y account")]
+    #[account(
+        5,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(
+        6,
+        name = "token_program_x",
+        desc = "Token program of mint X and the LP mint"
+    )]
+    #[account(7, name = "token_program_y", desc = "Token program of mint Y")]
+    #[account(8, name = "mint_lp", desc = "PDA [\"mint_lp\", config]")]
+    #[account(9, name = "mint_x", desc = "Token X mint")]
+    #[account(10, name = "mint_y", desc = "Token Y mint")]
+    Swap {
+        is_x: bool,
+        amount: u64,
+        min: u64,
+        expiration: i64,
+    },
+
+    /// Rewrite a config in the current layout version, recording its vault and LP mint
+    /// addresses.
+    #[account(
+        0,
+        writable,
+        signer,
+        name = "authority",
+        desc = "Pool authority, any payer if none"
+    )]
+    #[account(
+        1,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(2, name = "system_program", desc = "System program")]
+    #[account(3, name = "vault_x", desc = "Config token X account")]
+    #[account(4, name = "vault_y", desc = "Config token Y account")]
+    #[account(5, name = "token_program", desc = "Token program")]
+    #[account(6, name = "mint_lp", desc = "PDA [\"mint_lp\", config]")]
+    Migrate { lp_bump: u8 },
+
+    /// Lend `amount` of one token, call the borrower back and require it returned plus fee.
+    /// The callback instruction data follows the args, the callback accounts follow the
+    /// listed ones.
+    #[account(0, signer, name = "user", desc = "Borrower")]
+    #[account(
+        1,
+        writable,
+        name = "vault",
+        desc = "Config account of the borrowed token"
+    )]
+    #[account(
+        2,
+        writable,
+        name = "user_ata",
+        desc = "User account of the borrowed token"
+    )]
+    #[account(
+        3,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(4, name = "token_program", desc = "Token program of the lending vault")]
+    #[account(5, name = "mint", desc = "Mint of the lending vault")]
+    #[account(6, name = "callback_program", desc = "Program called with the loan")]
+    FlashSwap { is_x: bool, amount: u64 },
+
+    /// Deposit only one token: half of `amount` is swapped through the pool, fee included,
+    /// and the rest is deposited with the proceeds. Fails if fewer than `min_lp` LP tokens
+    /// would be minted, or if a transfer fee withholds part of the deposit.
+    #[account(0, signer, name = "user", desc = "Liquidity provider")]
+    #[account(1, writable, name = "mint_lp", desc = "PDA [\"mint_lp\", config]")]
+    #[account(2, writable, name = "vault_x", desc = "Config token X account")]
+    #[account(3, writable, name = "vault_y", desc = "Config token Y account")]
+    #[account(4, writable, name = "user_x_ata", desc = "User token X account")]
+    #[account(5, writable, name = "user_y_ata", desc = "User token Y account")]
+    #[account(6, writable, name = "user_lp_ata", desc = "User LP token account")]
+    #[account(
+        7,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(
+        8,
+        name = "token_program_x",
+        desc = "Token program of mint X and the LP mint"
+    )]
+    #[account(9, name = "token_program_y", desc = "Token program of mint Y")]
+    #[account(10, name = "mint_x", desc = "Token X mint")]
+    #[account(11, name = "mint_y", desc = "Token Y mint")]
+    DepositSingleSided {
+        is_x: bool,
+        amount: u64,
+        min_lp: u64,
+        expiration: i64,
+    },
+
+    /// Disable the pool when a vault holds less than the reserves recorded by the last
+    /// instruction. Anyone may call it.
+    #[account(
+        0,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(1, name = "vault_x", desc = "Config token X account")]
+    #[account(2, name = "vault_y", desc = "Config token Y account")]
+    Pause,
+
+    /// Reopen a paused pool once `RESUME_TIMELOCK` has passed, recording the current vault
+    /// balances as its reserves.
+    #[account(0, signer, name = "authority", desc = "Pool authority, anyone if none")]
+    #[account(
+        1,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(2, name = "vault_x", desc = "Config token X account")]
+    #[account(3, name = "vault_y", desc = "Config token Y account")]
+    Resume,
+
+    /// Add tokens to the vaults without minting LP, raising the value of every LP token.
+    /// Either amount may be zero, not both, and the pool must have LP supply.
+    #[account(0, signer, name = "user", desc = "Donor")]
+    #[account(1, name = "mint_lp", desc = "PDA [\"mint_lp\", config]")]
+    #[account(2, writable, name = "vault_x", desc = "Config token X account")]
+    #[account(3, writable, name = "vault_y", desc = "Config token Y account")]
+    #[account(4, writable, name = "user_x_ata", desc = "User token X account")]
+    #[account(5, writable, name = "user_y_ata", desc = "User token Y account")]
+    #[account(
+        6,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(7, name = "token_program_x", desc = "Token program of mint X")]
+    #[account(8, name = "token_program_y", desc = "Token program of mint Y")]
+    #[account(9, name = "mint_x", desc = "Token X mint")]
+    #[account(10, name = "mint_y", desc = "Token Y mint")]
+    Donate { amount_x: u64, amount_y: u64 },
+
+    /// Change the swap fee, in basis points. Fails on pools without an authority.
+    /// `protocol_fee_bps` is an optional trailing field, left unchanged when omitted.
+    #[account(0, signer, name = "authority", desc = "Pool authority")]
+    #[account(
+        1,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    UpdateFee { fee: u16, protocol_fee_bps: u16 },
+
+    /// Set the pool state to `Initialized` (1), `Disabled` (2) or `WithdrawOnly` (3).
+    /// An emergency pause can only be lifted by `Resume`.
+    #[account(0, signer, name = "authority", desc = "Pool authority")]
+    #[account(
+        1,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    UpdateState { state: u8 },
+
+    /// Transfer the pool authority, the zero address renouncing it for good.
+    #[account(0, signer, name = "authority", desc = "Pool authority")]
+    #[account(
+        1,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    UpdateAuthority { authority: Pubkey },
+
+    /// Burn every LP token but the locked minimum, all held by `user`, and withdraw their
+    /// share of both vaults. The way to close out the last LP position.
+    #[account(0, signer, name = "user", desc = "Holder of every unlocked LP token")]
+    #[account(1, writable, name = "mint_lp", desc = "PDA [\"mint_lp\", config]")]
+    #[account(2, writable, name = "vault_x", desc = "Config token X account")]
+    #[account(3, writable, name = "vault_y", desc = "Config token Y account")]
+    #[account(4, writable, name = "user_x_ata", desc = "User token X account")]
+    #[account(5, writable, name = "user_y_ata", desc = "User token Y account")]
+    #[account(6, writable, name = "user_lp_ata", desc = "User LP token account")]
+    #[account(
+        7,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(
+        8,
+        name = "token_program_x",
+        desc = "Token program of mint X and the LP mint"
+    )]
+    #[account(9, name = "token_program_y", desc = "Token program of mint Y")]
+    #[account(10, name = "mint_x", desc = "Token X mint")]
+    #[account(11, name = "mint_y", desc = "Token Y mint")]
+    WithdrawAll {
+        min_x: u64,
+        min_y: u64,
+        expiration: i64,
+    },
+
+    /// Send the protocol fees accrued by swaps to the pool authority and zero the counters.
+    #[account(0, signer, name = "authority", desc = "Pool authority")]
+    #[account(
+        1,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(2, writable, name = "vault_x", desc = "Config token X account")]
+    #[account(3, writable, name = "vault_y", desc = "Config token Y account")]
+    #[account(
+        4,
+        writable,
+        name = "authority_x_ata",
+        desc = "Token X account receiving the fees"
+    )]
+    #[account(
+        5,
+        writable,
+        name = "authority_y_ata",
+        desc = "Token Y account receiving the fees"
+    )]
+    #[account(6, name = "token_program_x", desc = "Token program of mint X")]
+    #[account(7, name = "token_program_y", desc = "Token program of mint Y")]
+    #[account(8, name = "mint_x", desc = "Token X mint")]
+    #[account(9, name = "mint_y", desc = "Token Y mint")]
+    ClaimFees,
+
+    /// Swap for exactly `out` of the other token, paying the input the curve needs for it,
+    /// rounded up, and at most `max_in`. A transfer fee on the input mint makes it fail.
+    #[account(0, signer, name = "user", desc = "Trader")]
+    #[account(1, writable, name = "user_x_ata", desc = "User token X account")]
+    #[account(2, writable, name = "user_y_ata", desc = "User token Y account")]
+    #[account(3, writable, name = "vault_x", desc = "Config token X account")]
+    #[account(4, writable, name = "vault_y", desc = "Config token Y account")]
+    #[account(
+        5,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(
+        6,
+        name = "token_program_x",
+        desc = "Token program of mint X and the LP mint"
+    )]
+    #[account(7, name = "token_program_y", desc = "Token program of mint Y")]
+    #[account(8, name = "mint_lp", desc = "PDA [\"mint_lp\", config]")]
+    #[account(9, name = "mint_x", desc = "Token X mint")]
+    #[account(10, name = "mint_y", desc = "Token Y mint")]
+    SwapExactOut {
+        is_x: bool,
+        out: u64,
+        max_in: u64,
+        expiration: i64,
+    },
+
+    /// Burn `lp_amount` LP tokens and receive only X (`is_x`) or only Y, the other leg being
+    /// swapped back into the pool with the swap fees. Fails below `min_out`, and when the
+    /// withdrawal leaves no liquidity to swap against.
+    #[account(0, signer, name = "user", desc = "Liquidity provider")]
+    #[account(1, writable, name = "mint_lp", desc = "PDA [\"mint_lp\", config]")]
+    #[account(2, writable, name = "vault_x", desc = "Config token X account")]
+    #[account(3, writable, name = "vault_y", desc = "Config token Y account")]
+    #[account(4, writable, name = "user_x_ata", desc = "User token X account")]
+    #[account(5, writable, name = "user_y_ata", desc = "User token Y account")]
+    #[account(6, writable, name = "user_lp_ata", desc = "User LP token account")]
+    #[account(
+        7,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(
+        8,
+        name = "token_program_x",
+        desc = "Token program of mint X and the LP mint"
+    )]
+    #[account(9, name = "token_program_y", desc = "Token program of mint Y")]
+    #[account(10, name = "mint_x", desc = "Token X mint")]
+    #[account(11, name = "mint_y", desc = "Token Y mint")]
+    WithdrawSingle {
+        is_x: bool,
+        lp_amount: u64,
+        min_out: u64,
+        expiration: i64,
+    },
+
+    /// Record the vault balances as the pool reserves, so tokens transferred straight to a
+    /// vault are counted. Fails when a vault holds less than recorded, which is for `Pause`.
+    /// Anyone may call it.
+    #[account(
+        0,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(1, name = "vault_x", desc = "Config token X account")]
+    #[account(2, name = "vault_y", desc = "Config token Y account")]
+    SyncReserves,
+
+    /// Price a swap of `amount` of X (`is_x`) or Y without executing it. Sets return data
+    /// `amount_out: u64`, `fee: u64` and `price_impact_bps: u16`, meant to be read from a
+    /// simulated transaction.
+    #[account(0, name = "config", desc = "PDA [\"config\", seed, mint_x, mint_y]")]
+    #[account(1, name = "vault_x", desc = "Config token X account")]
+    #[account(2, name = "vault_y", desc = "Config token Y account")]
+    #[account(3, name = "mint_lp", desc = "PDA [\"mint_lp\", config]")]
+    #[account(4, name = "token_program", desc = "Token program")]
+    Quote { is_x: bool, amount: u64 },
+
+    /// Send what the vaults hold above the recorded reserves, tokens transferred straight to
+    /// them, to the pool authority.
+    #[account(0, signer, name = "authority", desc = "Pool authority")]
+    #[account(1, name = "config", desc = "PDA [\"config\", seed, mint_x, mint_y]")]
+    #[account(2, writable, name = "vault_x", desc = "Config token X account")]
+    #[account(3, writable, name = "vault_y", desc = "Config token Y account")]
+    #[account(
+        4,
+        writable,
+        name = "authority_x_ata",
+        desc = "Token X account receiving the excess"
+    )]
+    #[account(
+        5,
+        writable,
+        name = "authority_y_ata",
+        desc = "Token Y account receiving the excess"
+    )]
+    #[account(6, name = "token_program_x", desc = "Token program of mint X")]
+    #[account(7, name = "token_program_y", desc = "Token program of mint Y")]
+    #[account(8, name = "mint_x", desc = "Token X mint")]
+    #[account(9, name = "mint_y", desc = "Token Y mint")]
+    Skim,
+
+    /// Set the pool flags: `SWAPS_ENABLED` (1) and `DEPOSITS_ENABLED` (2). Withdrawals
+    /// (`WITHDRAWALS_ENABLED`, 4) are always kept on. An emergency pause can only be lifted by
+    /// `Resume`.
+    #[account(0, signer, name = "authority", desc = "Pool authority")]
+    #[account(
+        1,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    SetFlags { flags: u8 },
+
+    /// `Initialize` followed by the first `Deposit` of exactly `max_x` and `max_y`, both
+    /// nonzero, so the pool never exists empty. The initializer is the depositor and its LP
+    /// token account is created if missing.
+    /// `authority`, `lp_freeze_authority` and `canonical_pool` are optional trailing fields as
+    /// for `Initialize`.
+    #[account(
+        0,
+        writable,
+        signer,
+        name = "initializer",
+        desc = "Pays for the pool accounts"
+    )]
+    #[account(1, writable, name = "mint_lp", desc = "PDA [\"mint_lp\", config]")]
+    #[account(
+        2,
+        writable,
+        name = "config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y]"
+    )]
+    #[account(3, name = "system_program", desc = "System program")]
+    #[account(
+        4,
+        name = "token_program_x",
+        desc = "Token program owning mint_x, also used for the LP mint"
+    )]
+    #[account(5, name = "token_program_y", desc = "Token program owning mint_y")]
+    #[account(6, name = "mint_x", desc = "Token X mint")]
+    #[account(7, name = "mint_y", desc = "Token Y mint, distinct from mint_x")]
+    #[account(
+        8,
+        writable,
+        name = "vault_x",
+        desc = "Config token X account, created if missing"
+    )]
+    #[account(
+        9,
+        writable,
+        name = "vault_y",
+        desc = "Config token Y account, created if missing"
+    )]
+    #[account(
+        10,
+        name = "associated_token_program",
+        desc = "Associated token program"
+    )]
+    #[account(
+        11,
+        writable,
+        name = "locked_lp",
+        desc = "Config LP token account, created if missing"
+    )]
+    #[account(
+        12,
+        writable,
+        name = "registry",
+        desc = "PDA [\"registry\", mint_x, mint_y], created on the pair's first pool"
+    )]
+    #[account(
+        13,
+        writable,
+        name = "user_x_ata",
+        desc = "Initializer token X account"
+    )]
+    #[account(
+        14,
+        writable,
+        name = "user_y_ata",
+        desc = "Initializer token Y account"
+    )]
+    #[account(
+        15,
+        writable,
+        name = "user_lp_ata",
+        desc = "Initializer LP token account, created if missing"
+    )]
+    #[account(
+        16,
+        optional,
+        writable,
+        name = "canonical_pool",
+        desc = "PDA [\"canonical_pool\", mint_x, mint_y], required with canonical_pool set"
+    )]
+    InitializeAndDeposit {
+        max_x: u64,
+        max_y: u64,
+        seed: u64,
+        fee: u16,
+        mint_x: Pubkey,
+        mint_y: Pubkey,
+        config_bump: u8,
+        lp_bump: u8,
+        authority: Pubkey,
+        lp_freeze_authority: Pubkey,
+        canonical_pool: bool,
+    },
+
+    /// Swap A for B in the first pool, then that B for C in the second, failing when the final
+    /// output is below `min_out`. `first_is_x` and `second_is_x` give each leg's input side.
+    /// The user's B account is the first pool's output account and the second's input one.
+    #[account(0, signer, name = "user", desc = "User")]
+    #[account(
+        1,
+        writable,
+        name = "first_user_x_ata",
+        desc = "User token X account, first pool"
+    )]
+    #[account(
+        2,
+        writable,
+        name = "first_user_y_ata",
+        desc = "User token Y account, first pool"
+    )]
+    #[account(
+        3,
+        writable,
+        name = "first_vault_x",
+        desc = "Config token X account, first pool"
+    )]
+    #[account(
+        4,
+        writable,
+        name = "first_vault_y",
+        desc = "Config token Y account, first pool"
+    )]
+    #[account(
+        5,
+        writable,
+        name = "first_config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y], first pool"
+    )]
+    #[account(
+        6,
+        name = "first_token_program_x",
+        desc = "Token program of mint X and the LP mint, first pool"
+    )]
+    #[account(
+        7,
+        name = "first_token_program_y",
+        desc = "Token program of mint Y, first pool"
+    )]
+    #[account(
+        8,
+        name = "first_mint_lp",
+        desc = "PDA [\"mint_lp\", config], first pool"
+    )]
+    #[account(9, name = "first_mint_x", desc = "Token X mint, first pool")]
+    #[account(10, name = "first_mint_y", desc = "Token Y mint, first pool")]
+    #[account(
+        11,
+        writable,
+        name = "second_user_x_ata",
+        desc = "User token X account, second pool"
+    )]
+    #[account(
+        12,
+        writable,
+        name = "second_user_y_ata",
+        desc = "User token Y account, second pool"
+    )]
+    #[account(
+        13,
+        writable,
+        name = "second_vault_x",
+        desc = "Config token X account, second pool"
+    )]
+    #[account(
+        14,
+        writable,
+        name = "second_vault_y",
+        desc = "Config token Y account, second pool"
+    )]
+    #[account(
+        15,
+        writable,
+        name = "second_config",
+        desc = "PDA [\"config\", seed, mint_x, mint_y], second pool"
+    )]
+    #[account(
+        16,
+        name = "second_token_program_x",
+        desc = "Token program of mint X and the LP mint, second pool"
+    )]
+    #[account(
+        17,
+        name = "second_token_program_y",
+        desc = "Token program of mint Y, second pool"
+    )]
+    #[account(
+        18,
+        name = "second_mint_lp",
+        desc = "PDA [\"mint_lp\", config], second pool"
+    )]
+    #[account(19, name = "second_mint_x", desc = "Token X mint, second pool")]
+    #[account(20, name = "second_mint_y", desc = "Token Y mint, second pool")]
+    SwapMultiHop {
+        first_is_x: bool,
+        second_is_x: bool,
+        amount: u64,
+        min_out: u64,
+        expiration: i64,
+    },
+
+    /// Remove the pool from its pair's registry, refunding the freed rent to the authority.
+    /// The pool keeps working.
+    #[account(0, writable, signer, name = "authority", desc = "Pool authority")]
+    #[account(1, name = "config", desc = "PDA [\"config\", seed, mint_x, mint_y]")]
+    #[account(
+        2,
+        writable,
+        name = "registry",
+        desc = "PDA [\"registry\", mint_x, mint_y]"
+    )]
+    DeregisterPool,
+
+    /// Name the pool as the primary one of its pair, creating or replacing the pair's canonical
+    /// pool PDA. Only the program's upgrade authority may.
+    #[account(
+        0,
+        writable,
+        signer,
+        name = "authority",
+        desc = "Program upgrade authority, pays for a new canonical pool"
+    )]
+    #[account(
+        1,
+        name = "program_data",
+        desc = "PDA [program_id] of the upgradeable loader"
+    )]
+    #[account(2, name = "config", desc = "PDA [\"config\", seed, mint_x, mint_y]")]
+    #[account(
+        3,
+        writable,
+        name = "canonical_pool",
+        desc = "PDA [\"canonical_pool\", mint_x, mint_y], created if missing"
+    )]
+    #[account(4, name = "system_program", desc = "System program")]
+    SetCanonicalPool,
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use crate::instructions::*;
+
+    /// Every handler's discriminator, by the name of its `AmmInstruction` variant.
+    const DISCRIMINATORS: [(&str, u8); 25] = [
+        ("Initialize", *Initialize::DISCRIMINATOR),
+        ("Deposit", *Deposit::DISCRIMINATOR),
+        ("Withdraw", *Withdraw::DISCRIMINATOR),
+        ("Swap", *Swap::DISCRIMINATOR),
+        ("Migrate", *Migrate::DISCRIMINATOR),
+        ("FlashSwap", *FlashSwap::DISCRIMINATOR),
+        ("DepositSingleSided", *DepositSingleSided::DISCRIMINATOR),
+        ("Pause", *Pause::DISCRIMINATOR),
+        ("Resume", *Resume::DISCRIMINATOR),
+        ("Donate", *Donate::DISCRIMINATOR),
+        ("UpdateFee", *UpdateFee::DISCRIMINATOR),
+        ("UpdateState", *UpdateState::DISCRIMINATOR),
+        ("UpdateAuthority", *UpdateAuthority::DISCRIMINATOR),
+        ("WithdrawAll", *WithdrawAll::DISCRIMINATOR),
+        ("ClaimFees", *ClaimFees::DISCRIMINATOR),
+        ("SwapExactOut", *SwapExactOut::DISCRIMINATOR),
+        ("WithdrawSingle", *WithdrawSingle::DISCRIMINATOR),
+        ("SyncReserves", *SyncReserves::DISCRIMINATOR),
+        ("Quote", *Quote::DISCRIMINATOR),
+        ("Skim", *Skim::DISCRIMINATOR),
+        ("SetFlags", *SetFlags::DISCRIMINATOR),
+        ("InitializeAndDeposit", *InitializeAndDeposit::DISCRIMINATOR),
+        ("SwapMultiHop", *SwapMultiHop::DISCRIMINATOR),
+        ("DeregisterPool", *DeregisterPool::DISCRIMINATOR),
+        ("SetCanonicalPool", *SetCanonicalPool::DISCRIMINATOR),
+    ];
+
+    /// Variant names of `AmmInstruction`, in declaration order, which shank numbers from 0.
+    fn variants() -> Vec<&'static str> {
+        include_str!("idl.rs")
+            .lines()
+            .filter_map(|line| line.strip_prefix("    "))
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_uppercase()))
+            .map(|line| line.split([' ', ',']).next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn variant_order_matches_the_discriminators() {
+        let variants = variants();
+        assert_eq!(variants.len(), DISCRIMINATORS.len());
+        for (name, discriminator) in DISCRIMINATORS {
+            let position = variants.iter().position(|variant| *variant == name);
+            assert_eq!(position, Some(discriminator as usize), "{name}");
+        }
+    }
+
+    #[test]
+    fn checked_in_idl_is_up_to_date() {
+        let idl: serde_json::Value =
+            serde_json::from_str(include_str!("../idl/blueshift_native_amm.json")).unwrap();
+        let mut instructions: Vec<(&str, u64)> = idl["instructions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|ix| {
+                (
+                    ix["name"].as_str().unwrap(),
+                    ix["discriminant"]["value"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        instructions.sort_by_key(|(_, discriminator)| *discriminator);
+
+        let mut expected: Vec<(&str, u64)> = DISCRIMINATORS
+            .iter()
+            .map(|(name, discriminator)| (*name, *discriminator as u64))
+            .collect();
+        expected.sort_by_key(|(_, discriminator)| *discriminator);
+        assert_eq!(
+            instructions, expected,
+            "regenerate with `shank idl -r blueshift_native_amm -o blueshift_native_amm/idl`"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use pinocchio::cpi::Signer;
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::utils::*;
+use crate::state::*;
+
+pub struct ClaimFeesAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub config: &'a AccountView,
+    pub vault_x: &'a AccountView,
+    pub vault_y: &'a AccountView,
+    pub authority_x_ata: &'a AccountView,
+    pub authority_y_ata: &'a AccountView,
+    pub token_program_x: &'a AccountView,
+    pub token_program_y: &'a AccountView,
+    pub mint_x: &'a AccountView,
+    pub mint_y: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for ClaimFeesAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, config, vault_x, vault_y, authority_x_ata, authority_y_ata, token_program_x, token_program_y, mint_x, mint_y] =
+            accounts
+        else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(authority)?;
+        TokenProgram::check(token_program_x)?;
+        TokenProgram::check(token_program_y)?;
+        ConfigAccount::check(config)?;
+
+        Ok(Self {
+            authority,
+            config,
+            vault_x,
+            vault_y,
+            authority_x_ata,
+            authority_y_ata,
+            token_program_x,
+            token_program_y,
+            mint_x,
+            mint_y,
+        })
+    }
+}
+
+/// Send the protocol fees accrued by swaps to the pool authority.
+pub struct ClaimFees<'a> {
+    pub accounts: ClaimFeesAccounts<'a>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for ClaimFees<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let accounts = ClaimFeesAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> ClaimFees<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &14;
+    /// Pool flags the instruction needs.
+    pub const REQUIRED_FLAGS: u8 = AmmState::WITHDRAWALS_ENABLED;
+
+    pub fn process(&self) -> ProgramResult {
+        let config_data = Config::load(self.accounts.config)?;
+        config_data.require_address(self.accounts.config.address())?;
+        config_data.require_authority(self.accounts.authority.address())?;
+
+        // A paused pool is short of its reserves, any fee paid out would come from the LPs
+        config_data.require_flags(Self::REQUIRED_FLAGS)?;
+
+        config_data.require_mints(
+            self.accounts.mint_x.address(),
+            self.accounts.mint_y.address(),
+        )?;
+
+        config_data.require_vaults(
+            self.accounts.vault_x.address(),
+            self.accounts.vault_y.address(),
+        )?;
+        config_data.require_token_programs(
+            self.accounts.token_program_x.address(),
+            self.accounts.token_program_y.address(),
+        )?;
+
+        // The fees may go to any account of the right mint, not only the authority's own ATAs
+        if TokenInterface::mint(self.accounts.authority_x_ata)?.ne(config_data.mint_x())
+            || TokenInterface::mint(self.accounts.authority_y_ata)?.ne(config_data.mint_y())
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let config_seeds = config_data.signer_seeds();
+        let signer_seeds = [Signer::from(&config_seeds)];
+
+        for (vault, mint, destination, amount) in [
+            (
+                self.accounts.vault_x,
+                self.accounts.mint_x,
+                self.accounts.authority_x_ata,
+                config_data.protocol_fees_x(),
+            ),
+            (
+                self.accounts.vault_y,
+                self.accounts.mint_y,
+                self.accounts.authority_y_ata,
+                config_data.protocol_fees_y(),
+            ),
+        ] {
+            if amount == 0 {
+                continue;
+            }
+            TokenInterface::transfer_checked(
+                vault,
+                mint,
+                destination,
+                self.accounts.config,
+                amount,
+                &signer_seeds,
+            )?;
+        }
+
+        let (fees_x, fees_y) = (config_data.protocol_fees_x(), config_data.protocol_fees_y());
+        drop(config_data);
+        let mut config_data = Config::load_mut(self.accounts.config)?;
+        config_data.clear_protocol_fees();
+        config_data.debit_reserves(fees_x, fees_y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+    use pinocchio::Address;
+
+    use super::*;
+    use crate::errors::AmmError;
+
+    const AUTHORITY: Address = Address::new_from_array([1; 32]);
+    const MINT_X: Address = Address::new_from_array([2; 32]);
+    const MINT_Y: Address = Address::new_from_array([3; 32]);
+
+    /// A pool of `AUTHORITY` in `state`, holding protocol fees.
+    fn config(state: AmmState) -> TestAccount {
+        let (address, bump) = derive_config(1, &MINT_X, &MINT_Y);
+        let mut data = [0u8; Config::LEN];
+        // SAFETY: the buffer is exactly one config long
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut data) };
+        config
+            .set_inner(1, AUTHORITY, MINT_X, MINT_Y, 30, [bump])
+            .unwrap();
+        config.set_state(state as u8).unwrap();
+        config.accrue_protocol_fees(11, 12).unwrap();
+        TestAccount::new(address, crate::ID, &data)
+    }
+
+    fn claim_fees(signer: Address, config: &mut TestAccount) -> ProgramResult {
+        let mut signer = TestAccount::empty(signer).signer();
+        let mut token_program = TestAccount::empty(pinocchio_token::ID);
+        let mut other = TestAccount::empty(Address::new_from_array([9; 32]));
+
+        let accounts = [
+            signer.view(),
+            config.view(),
+            other.view(),
+            other.view(),
+            other.view(),
+            other.view(),
+            token_program.view(),
+            token_program.view(),
+            other.view(),
+            other.view(),
+        ];
+        ClaimFees::try_from(&accounts[..])?.process()
+    }
+
+    #[test]
+    fn rejects_a_signer_other_than_the_authority() {
+        assert_eq!(
+            claim_fees(
+                Address::new_from_array([8; 32]),
+                &mut config(AmmState::Initialized)
+            ),
+            Err(ProgramError::IncorrectAuthority)
+        );
+    }
+
+    #[test]
+    fn rejects_claiming_from_a_disabled_pool() {
+        // A paused pool may be short of its reserves, fees paid out would come from the LPs
+        assert_eq!(
+            claim_fees(AUTHORITY, &mut config(AmmState::Disabled)),
+            Err(AmmError::PoolDisabled.into())
+        );
+    }
+}
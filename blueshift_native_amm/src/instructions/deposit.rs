@@ -1,13 +1,9 @@
 use constant_product_curve::ConstantProduct;
-use pinocchio::{
-    cpi::{Seed, Signer},
-    error::ProgramError,
-    AccountView, ProgramResult,
-};
-use pinocchio_token::instructions::{MintTo, Transfer};
-use pinocchio_token::state::{Mint, TokenAccount};
+use pinocchio::{cpi::Signer, error::ProgramError, AccountView, ProgramResult};
 
 use super::utils::*;
+use crate::errors::AmmError;
+use crate::events::DepositEvent;
 use crate::state::*;
 
 use super::utils::{ConfigAccount, DataAccount, MintInterface, SignerAccount};
@@ -20,20 +16,33 @@ pub struct DepositAccounts<'a> {
     pub user_y_ata: &'a AccountView,
     pub user_lp_ata: &'a AccountView,
     pub config: &'a AccountView,
-    pub token_program: &'a AccountView,
+    pub token_program_x: &'a AccountView,
+    pub token_program_y: &'a AccountView,
+    pub mint_x: &'a AccountView,
+    pub mint_y: &'a AccountView,
+    /// Config LP account receiving `MINIMUM_LIQUIDITY`, only needed by the first deposit.
+    pub locked_lp: Option<&'a AccountView>,
 }
 
+const DEPOSIT_ACCOUNTS_LEN: usize = 13;
+
 impl<'a> TryFrom<&'a [AccountView]> for DepositAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
-        let [user, mint_lp, vault_x, vault_y, user_x_ata, user_y_ata, user_lp_ata, config, token_program] =
+        if accounts.len() > DEPOSIT_ACCOUNTS_LEN {
+            return Err(AmmError::TooManyAccountKeys.into());
+        }
+
+        let [user, mint_lp, vault_x, vault_y, user_x_ata, user_y_ata, user_lp_ata, config, token_program_x, token_program_y, mint_x, mint_y, locked_lp @ ..] =
             accounts
         else {
             return Err(ProgramError::NotEnoughAccountKeys);
         };
 
         SignerAccount::check(user)?;
+        TokenProgram::check(token_program_x)?;
+        TokenProgram::check(token_program_y)?;
         MintInterface::check(mint_lp)?;
         ConfigAccount::check(config)?;
 
@@ -46,27 +55,134 @@ impl<'a> TryFrom<&'a [AccountView]> for DepositAccounts<'a> {
             user_y_ata,
             user_lp_ata,
             config,
-            token_program,
+            token_program_x,
+            token_program_y,
+            mint_x,
+            mint_y,
+            locked_lp: locked_lp.first(),
         })
     }
 }
 
+#[repr(C)]
 pub struct DepositInstructionData {
     pub amount: u64,
     pub max_x: u64,
     pub max_y: u64,
     pub expiration: i64,
+    /// Least LP the deposit may mint, zero meaning no floor.
+    pub min_lp: u64,
 }
 
 impl<'a> TryFrom<&'a [u8]> for DepositInstructionData {
     type Error = ProgramError;
 
     fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
-        if data.len() != size_of::<DepositInstructionData>() {
-            return Err(ProgramError::InvalidInstructionData);
+        const DEPOSIT_DATA_LEN_WITH_MIN_LP: usize = size_of::<DepositInstructionData>();
+        const DEPOSIT_DATA_LEN: usize = DEPOSIT_DATA_LEN_WITH_MIN_LP - size_of::<u64>();
+
+        match data.len() {
+            DEPOSIT_DATA_LEN_WITH_MIN_LP => {
+                Ok(unsafe { (data.as_ptr() as *const Self).read_unaligned() })
+            }
+            // `min_lp` is an optional trailing field, zeroed when omitted
+            DEPOSIT_DATA_LEN => {
+                let mut raw = [0u8; DEPOSIT_DATA_LEN_WITH_MIN_LP];
+                raw[..DEPOSIT_DATA_LEN].copy_from_slice(data);
+                Ok(unsafe { (raw.as_ptr() as *const Self).read_unaligned() })
+            }
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+
+impl DepositInstructionData {
+    /// Amounts of X and Y the deposit pays into a pool with `reserve_x` and `reserve_y` behind
+    /// `lp_supply` LP, within `max_x` and `max_y`.
+    fn amounts_in(
+        &self,
+        reserve_x: u64,
+        reserve_y: u64,
+        lp_supply: u64,
+    ) -> Result<(u64, u64), ProgramError> {
+        // Without LP the first deposit owns the whole pool, including whatever was sent to the
+        // vaults before it. With LP both reserves back every share, a pool short of either
+        // side has no price to deposit at
+        let is_first_deposit = lp_supply == 0;
+        if !is_first_deposit && (reserve_x == 0 || reserve_y == 0) {
+            return Err(AmmError::InsufficientLiquidity.into());
+        }
+
+        // Grab the amounts to deposit
+        let (x, y) = match is_first_deposit {
+            true => (self.max_x, self.max_y),
+            false => {
+                let amounts = ConstantProduct::xy_deposit_amounts_from_l(
+                    reserve_x,
+                    reserve_y,
+                    lp_supply,
+                    self.amount,
+                    Config::LP_DECIMALS.into(),
+                )
+                .map_err(AmmError::from)?;
+                (amounts.x, amounts.y)
+            }
+        };
+
+        // Check for slippage
+        if !(x <= self.max_x && y <= self.max_y) {
+            return Err(AmmError::SlippageExceeded.into());
+        }
+
+        Ok((x, y))
+    }
+
+    /// LP minted to the user once `received` of the `sent` amounts arrived in the vaults.
+    fn lp_out(
+        &self,
+        reserve_x: u64,
+        reserve_y: u64,
+        lp_supply: u64,
+        (x, y): (u64, u64),
+        (received_x, received_y): (u64, u64),
+    ) -> Result<u64, ProgramError> {
+        let lp = match lp_supply == 0 {
+            true => {
+                // The first deposit prices every later share, so its LP is not left to the
+                // user: it is the geometric mean of both reserves once the deposit is in, less
+                // the locked minimum
+                let pool_x = reserve_x as u128 + received_x as u128;
+                let pool_y = reserve_y as u128 + received_y as u128;
+                let pool_k = pool_x.checked_mul(pool_y).ok_or(AmmError::MathOverflow)?;
+                let lp = u64::try_from(pool_k.isqrt()).map_err(|_| AmmError::MathOverflow)?;
+                if lp <= Config::MINIMUM_LIQUIDITY {
+                    return Err(AmmError::InsufficientLiquidity.into());
+                }
+                lp - Config::MINIMUM_LIQUIDITY
+            }
+            false if received_x == x && received_y == y => self.amount,
+            false => {
+                let supply = lp_supply as u128;
+                let lp_x = (received_x as u128 * supply)
+                    .checked_div(reserve_x as u128)
+                    .ok_or(AmmError::InsufficientLiquidity)?;
+                let lp_y = (received_y as u128 * supply)
+                    .checked_div(reserve_y as u128)
+                    .ok_or(AmmError::InsufficientLiquidity)?;
+                u64::try_from(lp_x.min(lp_y)).map_err(|_| AmmError::MathOverflow)?
+            }
+        };
+        if lp == 0 {
+            return Err(AmmError::ZeroAmount.into());
+        }
+
+        // `max_x` and `max_y` bound what is paid, this bounds what it buys. A pool skewed by a
+        // donation or a transfer fee can otherwise mint far less than `amount`
+        if lp < self.min_lp {
+            return Err(AmmError::SlippageExceeded.into());
         }
 
-        Ok(unsafe { (data.as_ptr() as *const Self).read() })
+        Ok(lp)
     }
 }
 
@@ -92,108 +208,272 @@ impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for Deposit<'a> {
 
 impl<'a> Deposit<'a> {
     pub const DISCRIMINATOR: &'a u8 = &1;
+    /// Pool flags the instruction needs.
+    pub const REQUIRED_FLAGS: u8 = AmmState::DEPOSITS_ENABLED;
 
     pub fn process(&mut self) -> ProgramResult {
+        Expiration::check(self.instruction_data.expiration)?;
+
+        ConfigAccount::update_price_cumulative(self.accounts.config)?;
         let config_data = Config::load(self.accounts.config)?;
-        AssociatedTokenAccount::check(
+        config_data.require_address(self.accounts.config.address())?;
+        ConfigAccount::check_vaults(
+            self.accounts.config,
+            &config_data,
             self.accounts.vault_x,
-            self.accounts.config.address(),
-            config_data.mint_x(),
-            self.accounts.token_program.address(),
-        )?;
-        AssociatedTokenAccount::check(
             self.accounts.vault_y,
-            self.accounts.config.address(),
-            config_data.mint_y(),
-            self.accounts.token_program.address(),
         )?;
-        AssociatedTokenAccount::check(
+        config_data.require_token_programs(
+            self.accounts.token_program_x.address(),
+            self.accounts.token_program_y.address(),
+        )?;
+        UserTokenAccount::check(
             self.accounts.user_x_ata,
             self.accounts.user.address(),
             config_data.mint_x(),
-            self.accounts.token_program.address(),
+            self.accounts.token_program_x.address(),
         )?;
-        AssociatedTokenAccount::check(
+        UserTokenAccount::check(
             self.accounts.user_y_ata,
             self.accounts.user.address(),
             config_data.mint_y(),
-            self.accounts.token_program.address(),
+            self.accounts.token_program_y.address(),
         )?;
-        AssociatedTokenAccount::check(
+        UserTokenAccount::check(
             self.accounts.user_lp_ata,
             self.accounts.user.address(),
             self.accounts.mint_lp.address(),
-            self.accounts.token_program.address(),
+            token_program_of(self.accounts.mint_lp)?,
         )?;
 
-        if config_data.state() != AmmState::Initialized as u8 {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        config_data.require_flags(Self::REQUIRED_FLAGS)?;
+
+        config_data.require_mints(
+            self.accounts.mint_x.address(),
+            self.accounts.mint_y.address(),
+        )?;
+
+        config_data.require_mint_lp(self.accounts.mint_lp.address())?;
 
         // Deserialize the token accounts
-        let mint_lp = unsafe { Mint::from_account_view_unchecked(self.accounts.mint_lp)? };
-        let vault_x = unsafe { TokenAccount::from_account_view_unchecked(self.accounts.vault_x)? };
-        let vault_y = unsafe { TokenAccount::from_account_view_unchecked(self.accounts.vault_y)? };
+        let mint_lp = MintInterface::read(self.accounts.mint_lp)?;
+        let vault_x = TokenInterface::read(self.accounts.vault_x)?;
+        let vault_y = TokenInterface::read(self.accounts.vault_y)?;
 
-        // Grab the amounts to deposit
-        let (x, y) = match mint_lp.supply() == 0 && vault_x.amount() == 0 && vault_y.amount() == 0 {
-            true => (self.instruction_data.max_x, self.instruction_data.max_y),
-            false => {
-                let amounts = ConstantProduct::xy_deposit_amounts_from_l(
-                    vault_x.amount(),
-                    vault_y.amount(),
-                    mint_lp.supply(),
-                    self.instruction_data.amount,
-                    6,
-                )
-                .map_err(|_| ProgramError::InvalidArgument)?;
-                (amounts.x, amounts.y)
-            }
-        };
+        // Priced at the recorded reserves, tokens sent straight to the vaults don't move them.
+        // Accrued protocol fees sit in the reserves but belong to no LP
+        let (reserve_x, reserve_y) = config_data.recorded_pool_reserves();
 
-        // Check for slippage
-        if !(x <= self.instruction_data.max_x && y <= self.instruction_data.max_y) {
-            return Err(ProgramError::InvalidArgument);
-        }
+        let is_first_deposit = mint_lp.supply() == 0;
+        let (x, y) = self
+            .instruction_data
+            .amounts_in(reserve_x, reserve_y, mint_lp.supply())?;
 
         // transfer from user ATA to corresponding vault
-        Transfer {
-            from: self.accounts.user_x_ata,
-            to: self.accounts.vault_x,
-            authority: self.accounts.user,
-            amount: x,
-        }
-        .invoke()?;
-        Transfer {
-            from: self.accounts.user_y_ata,
-            to: self.accounts.vault_y,
-            authority: self.accounts.user,
-            amount: y,
+        let (balance_x, balance_y) = (vault_x.amount(), vault_y.amount());
+        TokenInterface::transfer_checked(
+            self.accounts.user_x_ata,
+            self.accounts.mint_x,
+            self.accounts.vault_x,
+            self.accounts.user,
+            x,
+            &[],
+        )?;
+        TokenInterface::transfer_checked(
+            self.accounts.user_y_ata,
+            self.accounts.mint_y,
+            self.accounts.vault_y,
+            self.accounts.user,
+            y,
+            &[],
+        )?;
+
+        // A transfer fee delivers less than was sent, LP is only minted for what arrived
+        let received_x = TokenInterface::amount(self.accounts.vault_x)?
+            .checked_sub(balance_x)
+            .ok_or(AmmError::MathOverflow)?;
+        let received_y = TokenInterface::amount(self.accounts.vault_y)?
+            .checked_sub(balance_y)
+            .ok_or(AmmError::MathOverflow)?;
+
+        let lp = self.instruction_data.lp_out(
+            reserve_x,
+            reserve_y,
+            mint_lp.supply(),
+            (x, y),
+            (received_x, received_y),
+        )?;
+
+        // mint lp token, the only leg the config signs for
+        let config_seeds = config_data.signer_seeds();
+        let mint_lp_signers = [Signer::from(&config_seeds)];
+
+        if is_first_deposit {
+            let locked_lp = self
+                .accounts
+                .locked_lp
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            AssociatedTokenAccount::check(
+                locked_lp,
+                self.accounts.config.address(),
+                self.accounts.mint_lp.address(),
+                token_program_of(self.accounts.mint_lp)?,
+            )?;
+            MintInterface::mint_to(
+                self.accounts.mint_lp,
+                locked_lp,
+                self.accounts.config,
+                Config::MINIMUM_LIQUIDITY,
+                &mint_lp_signers,
+            )?;
         }
-        .invoke()?;
-
-        // mint lp token
-        let config_seed_binding = config_data.seed().to_le_bytes();
-        let config_bump_binding = config_data.config_bump();
-        let config_seeds = [
-            Seed::from(b"config"),
-            Seed::from(&config_seed_binding),
-            Seed::from(config_data.mint_x().as_array()),
-            Seed::from(config_data.mint_y().as_array()),
-            Seed::from(&config_bump_binding),
-        ];
-        let config_signer = Signer::from(&config_seeds);
-        let deposit_signers = [config_signer];
-
-        let mint_lp_signers = deposit_signers;
-        MintTo {
-            mint: self.accounts.mint_lp,
-            account: self.accounts.user_lp_ata,
-            mint_authority: self.accounts.config,
-            amount: self.instruction_data.amount,
+        MintInterface::mint_to(
+            self.accounts.mint_lp,
+            self.accounts.user_lp_ata,
+            self.accounts.config,
+            lp,
+            &mint_lp_signers,
+        )?;
+
+        drop(config_data);
+        Config::load_mut(self.accounts.config)?.credit_reserves(received_x, received_y)?;
+
+        let (reserve_x_after, reserve_y_after) =
+            Config::load(self.accounts.config)?.recorded_pool_reserves();
+        DepositEvent {
+            user: self.accounts.user.address(),
+            amount_x: received_x,
+            amount_y: received_y,
+            lp,
+            reserve_x_after,
+            reserve_y_after,
         }
-        .invoke_signed(&mint_lp_signers)?;
+        .emit();
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::lp_to_underlying;
+
+    fn deposit(amount: u64, max_x: u64, max_y: u64) -> DepositInstructionData {
+        DepositInstructionData {
+            amount,
+            max_x,
+            max_y,
+            expiration: 0,
+            min_lp: 0,
+        }
+    }
+
+    /// Deterministic xorshift, enough to spread the cases over the whole range.
+    fn next(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    /// Deposit for `lp` into the pool, withdraw that LP straight away, and check nothing more
+    /// than was paid comes back.
+    fn round_trip(reserve_x: u64, reserve_y: u64, supply: u64, lp: u64) {
+        let Ok((x, y)) = deposit(lp, u64::MAX, u64::MAX).amounts_in(reserve_x, reserve_y, supply)
+        else {
+            return;
+        };
+        let Ok(minted) =
+            deposit(lp, u64::MAX, u64::MAX).lp_out(reserve_x, reserve_y, supply, (x, y), (x, y))
+        else {
+            return;
+        };
+        let (Some(pool_x), Some(pool_y), Some(pool_supply)) = (
+            reserve_x.checked_add(x),
+            reserve_y.checked_add(y),
+            supply.checked_add(minted),
+        ) else {
+            return;
+        };
+
+        let (out_x, out_y) = lp_to_underlying(pool_x, pool_y, pool_supply, minted).unwrap();
+        assert!(
+            out_x <= x && out_y <= y,
+            "paid ({x}, {y}) for {minted} LP out of ({reserve_x}, {reserve_y}, {supply}), \
+             withdrew ({out_x}, {out_y})"
+        );
+    }
+
+    #[test]
+    fn never_withdraws_more_than_a_deposit_paid() {
+        let mut state = 0x9e37_79b9_7f4a_7c15;
+        for _ in 0..10_000 {
+            // Reserves, supplies and amounts of every magnitude, from dust to `u64::MAX`
+            let reserve_x = next(&mut state) >> (next(&mut state) % 64);
+            let reserve_y = next(&mut state) >> (next(&mut state) % 64);
+            let supply = next(&mut state) >> (next(&mut state) % 64);
+            let lp = supply >> (next(&mut state) % 64);
+            round_trip(reserve_x.max(1), reserve_y.max(1), supply.max(1), lp.max(1));
+        }
+    }
+
+    #[test]
+    fn never_withdraws_more_than_a_first_deposit_paid() {
+        let mut state = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..10_000 {
+            let max_x = next(&mut state) >> (next(&mut state) % 40 + 24);
+            let max_y = next(&mut state) >> (next(&mut state) % 40 + 24);
+            let data = deposit(0, max_x, max_y);
+            let Ok(minted) = data.lp_out(0, 0, 0, (max_x, max_y), (max_x, max_y)) else {
+                continue;
+            };
+
+            // The locked minimum stays behind, the depositor can only withdraw less
+            let supply = minted + Config::MINIMUM_LIQUIDITY;
+            let (out_x, out_y) = lp_to_underlying(max_x, max_y, supply, minted).unwrap();
+            assert!(out_x < max_x && out_y < max_y);
+        }
+    }
+
+    #[test]
+    fn mints_lp_for_what_a_fee_mint_delivered() {
+        let data = deposit(10_000, u64::MAX, u64::MAX);
+        let pool = (1_000_000, 1_000_000, 1_000_000);
+        let sent = data.amounts_in(pool.0, pool.1, pool.2).unwrap();
+        assert_eq!(sent, (10_000, 10_000));
+
+        let lp_out = |received| data.lp_out(pool.0, pool.1, pool.2, sent, received);
+        assert_eq!(lp_out((10_000, 10_000)), Ok(10_000));
+        // 1% of X lost to its transfer fee buys 1% less of the pool
+        assert_eq!(lp_out((9_900, 10_000)), Ok(9_900));
+        assert_eq!(lp_out((9_900, 9_950)), Ok(9_900));
+    }
+
+    #[test]
+    fn floors_the_lp_a_fee_mint_deposit_mints() {
+        let mut data = deposit(10_000, u64::MAX, u64::MAX);
+        data.min_lp = 9_950;
+
+        assert_eq!(
+            data.lp_out(
+                1_000_000,
+                1_000_000,
+                1_000_000,
+                (10_000, 10_000),
+                (9_900, 10_000)
+            ),
+            Err(AmmError::SlippageExceeded.into())
+        );
+    }
+
+    #[test]
+    fn prices_a_first_deposit_at_what_a_fee_mint_delivered() {
+        let data = deposit(0, 1_000_000, 4_000_000);
+        // sqrt(990_000 * 4_000_000) = 1_989_974, less the locked minimum
+        assert_eq!(
+            data.lp_out(0, 0, 0, (1_000_000, 4_000_000), (990_000, 4_000_000)),
+            Ok(1_989_974 - Config::MINIMUM_LIQUIDITY)
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use constant_product_curve::{ConstantProduct, LiquidityPair};
+use pinocchio::{cpi::Signer, error::ProgramError, AccountView, ProgramResult};
+
+use super::deposit::DepositAccounts;
+use super::swap::require_k_preserved;
+use super::utils::*;
+use crate::errors::AmmError;
+use crate::state::*;
+
+pub struct DepositSingleSidedInstructionData {
+    pub is_x: bool,
+    pub amount: u64,
+    pub min_lp: u64,
+    pub expiration: i64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for DepositSingleSidedInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        const DATA_LEN: usize = size_of::<u8>() + size_of::<u64>() * 2 + size_of::<i64>();
+
+        if data.len() != DATA_LEN || data[0] > 1 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let amount = u64::from_le_bytes(data[1..9].try_into().unwrap());
+        // Half of it is swapped, so both halves must be non zero
+        if amount < 2 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self {
+            is_x: data[0] == 1,
+            amount,
+            min_lp: u64::from_le_bytes(data[9..17].try_into().unwrap()),
+            expiration: i64::from_le_bytes(data[17..25].try_into().unwrap()),
+        })
+    }
+}
+
+pub struct DepositSingleSided<'a> {
+    pub accounts: DepositAccounts<'a>,
+    pub instruction_data: DepositSingleSidedInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for DepositSingleSided<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = DepositAccounts::try_from(accounts)?;
+        let instruction_data = DepositSingleSidedInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> DepositSingleSided<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &6;
+    /// Pool flags the instruction needs.
+    pub const REQUIRED_FLAGS: u8 = AmmState::DEPOSITS_ENABLED | AmmState::SWAPS_ENABLED;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let is_x = self.instruction_data.is_x;
+        let amount = self.instruction_data.amount;
+
+        Expiration::check(self.instruction_data.expiration)?;
+
+        ConfigAccount::update_price_cumulative(self.accounts.config)?;
+        let config_data = Config::load(self.accounts.config)?;
+        config_data.require_address(self.accounts.config.address())?;
+        ConfigAccount::check_vaults(
+            self.accounts.config,
+            &config_data,
+            self.accounts.vault_x,
+            self.accounts.vault_y,
+        )?;
+        config_data.require_token_programs(
+            self.accounts.token_program_x.address(),
+            self.accounts.token_program_y.address(),
+        )?;
+        UserTokenAccount::check(
+            self.accounts.user_x_ata,
+            self.accounts.user.address(),
+            config_data.mint_x(),
+            self.accounts.token_program_x.address(),
+        )?;
+        UserTokenAccount::check(
+            self.accounts.user_y_ata,
+            self.accounts.user.address(),
+            config_data.mint_y(),
+            self.accounts.token_program_y.address(),
+        )?;
+        UserTokenAccount::check(
+            self.accounts.user_lp_ata,
+            self.accounts.user.address(),
+            self.accounts.mint_lp.address(),
+            token_program_of(self.accounts.mint_lp)?,
+        )?;
+
+        config_data.require_flags(Self::REQUIRED_FLAGS)?;
+
+        config_data.require_mints(
+            self.accounts.mint_x.address(),
+            self.accounts.mint_y.address(),
+        )?;
+
+        config_data.require_mint_lp(self.accounts.mint_lp.address())?;
+
+        // Deserialize the token accounts
+        let mint_lp = MintInterface::read(self.accounts.mint_lp)?;
+
+        // An empty pool has no price to swap at
+        let supply = mint_lp.supply();
+        if supply == 0 {
+            return Err(AmmError::InsufficientLiquidity.into());
+        }
+
+        // Recorded reserves from the point of view of the deposited token, accrued protocol
+        // fees aside
+        let (reserve_x, reserve_y) = config_data.recorded_pool_reserves();
+        let (reserve_in, reserve_out) = match is_x {
+            true => (reserve_x, reserve_y),
+            false => (reserve_y, reserve_x),
+        };
+
+        // Swap half of the deposit through the pool's own curve, charged the same protocol and
+        // LP fees as a `Swap`
+        let half = amount / 2;
+        let protocol_fee = (half as u128 * config_data.protocol_fee_bps() as u128 / 10_000) as u64;
+        let mut curve =
+            ConstantProduct::init(reserve_x, reserve_y, supply, config_data.fee(), None)
+                .map_err(AmmError::from)?;
+
+        let p = match is_x {
+            true => LiquidityPair::X,
+            false => LiquidityPair::Y,
+        };
+
+        let swap_result = curve
+            .swap(p, half - protocol_fee, 0)
+            .map_err(AmmError::from)?;
+        if swap_result.deposit == 0 || swap_result.withdraw == 0 {
+            return Err(AmmError::CurveError.into());
+        }
+        if reserve_out <= swap_result.withdraw {
+            return Err(AmmError::SwapDrainsVault.into());
+        }
+        require_k_preserved(
+            reserve_in,
+            reserve_out,
+            swap_result.deposit,
+            swap_result.withdraw,
+        )?;
+
+        // Deposit the rest against the reserves left after the swap
+        let reserve_in = reserve_in
+            .checked_add(swap_result.deposit)
+            .ok_or(AmmError::MathOverflow)?;
+        let reserve_out = reserve_out - swap_result.withdraw;
+        let remaining_in = amount
+            .checked_sub(swap_result.deposit)
+            .and_then(|remaining| remaining.checked_sub(protocol_fee))
+            .ok_or(AmmError::MathOverflow)?;
+
+        let lp_in = remaining_in as u128 * supply as u128 / reserve_in as u128;
+        let lp_out = swap_result.withdraw as u128 * supply as u128 / reserve_out as u128;
+        let lp = u64::try_from(lp_in.min(lp_out)).map_err(|_| AmmError::MathOverflow)?;
+
+        // Check for slippage
+        if lp == 0 || lp < self.instruction_data.min_lp {
+            return Err(AmmError::SlippageExceeded.into());
+        }
+
+        let amounts = match is_x {
+            true => ConstantProduct::xy_deposit_amounts_from_l(
+                reserve_in,
+                reserve_out,
+                supply,
+                lp,
+                Config::LP_DECIMALS.into(),
+            ),
+            false => ConstantProduct::xy_deposit_amounts_from_l(
+                reserve_out,
+                reserve_in,
+                supply,
+                lp,
+                Config::LP_DECIMALS.into(),
+            ),
+        }
+        .map_err(AmmError::from)?;
+        let (deposit_in, deposit_out) = match is_x {
+            true => (amounts.x, amounts.y),
+            false => (amounts.y, amounts.x),
+        };
+        if deposit_in > remaining_in || deposit_out > swap_result.withdraw {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Only the net amounts move, any rounding dust of the swapped side goes back to the user
+        let (mint_in, mint_out, user_in_ata, user_out_ata, vault_in, vault_out) = match is_x {
+            true => (
+                self.accounts.mint_x,
+                self.accounts.mint_y,
+                self.accounts.user_x_ata,
+                self.accounts.user_y_ata,
+                self.accounts.vault_x,
+                self.accounts.vault_y,
+            ),
+            false => (
+                self.accounts.mint_y,
+                self.accounts.mint_x,
+                self.accounts.user_y_ata,
+                self.accounts.user_x_ata,
+                self.accounts.vault_y,
+                self.accounts.vault_x,
+            ),
+        };
+
+        let config_seeds = config_data.signer_seeds();
+        let signer_seeds = [Signer::from(&config_seeds)];
+
+        let sent = swap_result
+            .deposit
+            .checked_add(protocol_fee)
+            .and_then(|sent| sent.checked_add(deposit_in))
+            .ok_or(AmmError::MathOverflow)?;
+        let balance_in = TokenInterface::amount(vault_in)?;
+        TokenInterface::transfer_checked(
+            user_in_ata,
+            mint_in,
+            vault_in,
+            self.accounts.user,
+            sent,
+            &[],
+        )?;
+
+        // The LP above was priced on the full amount, a transfer fee would leave it unbacked
+        if TokenInterface::amount(vault_in)?.saturating_sub(balance_in) != sent {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let dust = swap_result.withdraw - deposit_out;
+        if dust > 0 {
+            TokenInterface::transfer_checked(
+                vault_out,
+                mint_out,
+                user_out_ata,
+                self.accounts.config,
+                dust,
+                &signer_seeds,
+            )?;
+        }
+
+        MintInterface::mint_to(
+            self.accounts.mint_lp,
+            self.accounts.user_lp_ata,
+            self.accounts.config,
+            lp,
+            &signer_seeds,
+        )?;
+
+        drop(config_data);
+        let mut config_data = Config::load_mut(self.accounts.config)?;
+        match is_x {
+            true => {
+                config_data.accrue_protocol_fees(protocol_fee, 0)?;
+                config_data.credit_reserves(sent, 0)?;
+                config_data.debit_reserves(0, dust)?;
+            }
+            false => {
+                config_data.accrue_protocol_fees(0, protocol_fee)?;
+                config_data.credit_reserves(0, sent)?;
+                config_data.debit_reserves(dust, 0)?;
+            }
+        }
+
+        Ok(())
+    }
+}
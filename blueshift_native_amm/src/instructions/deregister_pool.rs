@@ -0,0 +1,67 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::utils::{ConfigAccount, DataAccount, RegistryAccount, SignerAccount};
+use crate::state::*;
+
+pub struct DeregisterPoolAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub config: &'a AccountView,
+    pub registry: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for DeregisterPoolAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, config, registry] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(authority)?;
+        ConfigAccount::check(config)?;
+
+        Ok(Self {
+            authority,
+            config,
+            registry,
+        })
+    }
+}
+
+/// Remove the pool from its pair's registry, refunding the freed rent to the authority. The
+/// pool itself keeps working, routers just stop finding it.
+pub struct DeregisterPool<'a> {
+    pub accounts: DeregisterPoolAccounts<'a>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for DeregisterPool<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let accounts = DeregisterPoolAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> DeregisterPool<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &23;
+
+    pub fn process(&self) -> ProgramResult {
+        let config_data = Config::load(self.accounts.config)?;
+        config_data.require_address(self.accounts.config.address())?;
+        config_data.require_authority(self.accounts.authority.address())?;
+
+        Registry::load(self.accounts.registry)?.require_address(
+            self.accounts.registry.address(),
+            config_data.mint_x(),
+            config_data.mint_y(),
+        )?;
+
+        RegistryAccount::deregister(
+            self.accounts.registry,
+            self.accounts.config.address(),
+            self.accounts.authority,
+        )
+    }
+}
@@ -0,0 +1,184 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::utils::*;
+use crate::errors::AmmError;
+use crate::state::*;
+
+pub struct DonateAccounts<'a> {
+    pub user: &'a AccountView,
+    pub mint_lp: &'a AccountView,
+    pub vault_x: &'a AccountView,
+    pub vault_y: &'a AccountView,
+    pub user_x_ata: &'a AccountView,
+    pub user_y_ata: &'a AccountView,
+    pub config: &'a AccountView,
+    pub token_program_x: &'a AccountView,
+    pub token_program_y: &'a AccountView,
+    pub mint_x: &'a AccountView,
+    pub mint_y: &'a AccountView,
+}
+
+const DONATE_ACCOUNTS_LEN: usize = 10;
+
+impl<'a> TryFrom<&'a [AccountView]> for DonateAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        if accounts.len() > DONATE_ACCOUNTS_LEN {
+            return Err(AmmError::TooManyAccountKeys.into());
+        }
+
+        let [user, mint_lp, vault_x, vault_y, user_x_ata, user_y_ata, config, token_program_x, token_program_y, mint_x, mint_y] =
+            accounts
+        else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(user)?;
+        TokenProgram::check(token_program_x)?;
+        TokenProgram::check(token_program_y)?;
+        MintInterface::check(mint_lp)?;
+        ConfigAccount::check(config)?;
+
+        Ok(Self {
+            user,
+            mint_lp,
+            vault_x,
+            vault_y,
+            user_x_ata,
+            user_y_ata,
+            config,
+            token_program_x,
+            token_program_y,
+            mint_x,
+            mint_y,
+        })
+    }
+}
+
+pub struct DonateInstructionData {
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for DonateInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<DonateInstructionData>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let instruction_data = unsafe { (data.as_ptr() as *const Self).read_unaligned() };
+        if instruction_data.amount_x == 0 && instruction_data.amount_y == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(instruction_data)
+    }
+}
+
+/// Moves tokens into the vaults without minting LP, so every existing LP token redeems for
+/// more.
+pub struct Donate<'a> {
+    pub accounts: DonateAccounts<'a>,
+    pub instruction_data: DonateInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for Donate<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = DonateAccounts::try_from(accounts)?;
+        let instruction_data = DonateInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> Donate<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &9;
+    /// Pool flags the instruction needs.
+    pub const REQUIRED_FLAGS: u8 = AmmState::DEPOSITS_ENABLED;
+
+    pub fn process(&mut self) -> ProgramResult {
+        ConfigAccount::update_price_cumulative(self.accounts.config)?;
+        let config_data = Config::load(self.accounts.config)?;
+        ConfigAccount::check_vaults(
+            self.accounts.config,
+            &config_data,
+            self.accounts.vault_x,
+            self.accounts.vault_y,
+        )?;
+        config_data.require_token_programs(
+            self.accounts.token_program_x.address(),
+            self.accounts.token_program_y.address(),
+        )?;
+
+        config_data.require_flags(Self::REQUIRED_FLAGS)?;
+
+        config_data.require_mints(
+            self.accounts.mint_x.address(),
+            self.accounts.mint_y.address(),
+        )?;
+
+        config_data.require_mint_lp(self.accounts.mint_lp.address())?;
+        let mint_lp = MintInterface::read(self.accounts.mint_lp)?;
+
+        // Without LP holders the donation would go to whoever deposits first
+        if mint_lp.supply() == 0 {
+            return Err(AmmError::InsufficientLiquidity.into());
+        }
+
+        // Unlike tokens sent straight to the vaults, a donation is credited to the reserves
+        let mut received = [0u64; 2];
+        if self.instruction_data.amount_x > 0 {
+            UserTokenAccount::check(
+                self.accounts.user_x_ata,
+                self.accounts.user.address(),
+                config_data.mint_x(),
+                self.accounts.token_program_x.address(),
+            )?;
+            let balance = TokenInterface::amount(self.accounts.vault_x)?;
+            TokenInterface::transfer_checked(
+                self.accounts.user_x_ata,
+                self.accounts.mint_x,
+                self.accounts.vault_x,
+                self.accounts.user,
+                self.instruction_data.amount_x,
+                &[],
+            )?;
+            received[0] = TokenInterface::amount(self.accounts.vault_x)?
+                .checked_sub(balance)
+                .ok_or(AmmError::MathOverflow)?;
+        }
+        if self.instruction_data.amount_y > 0 {
+            UserTokenAccount::check(
+                self.accounts.user_y_ata,
+                self.accounts.user.address(),
+                config_data.mint_y(),
+                self.accounts.token_program_y.address(),
+            )?;
+            let balance = TokenInterface::amount(self.accounts.vault_y)?;
+            TokenInterface::transfer_checked(
+                self.accounts.user_y_ata,
+                self.accounts.mint_y,
+                self.accounts.vault_y,
+                self.accounts.user,
+                self.instruction_data.amount_y,
+                &[],
+            )?;
+            received[1] = TokenInterface::amount(self.accounts.vault_y)?
+                .checked_sub(balance)
+                .ok_or(AmmError::MathOverflow)?;
+        }
+
+        drop(config_data);
+        Config::load_mut(self.accounts.config)?.credit_reserves(received[0], received[1])?;
+
+        Ok(())
+    }
+}
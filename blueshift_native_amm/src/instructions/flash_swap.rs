@@ -0,0 +1,253 @@
+use pinocchio::cpi::{invoke_with_bounds, Signer};
+use pinocchio::instruction::{InstructionAccount, InstructionView};
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::utils::{ConfigAccount, DataAccount, SignerAccount, TokenInterface, UserTokenAccount};
+use crate::errors::AmmError;
+use crate::state::Config;
+use crate::AmmState;
+
+/// Upper bound on the accounts forwarded to the callback.
+const MAX_CALLBACK_ACCOUNTS: usize = 16;
+
+pub struct FlashSwapAccounts<'a> {
+    pub user: &'a AccountView,
+    pub vault: &'a AccountView,
+    pub user_ata: &'a AccountView,
+    pub config: &'a AccountView,
+    pub token_program: &'a AccountView,
+    pub mint: &'a AccountView,
+    pub callback_program: &'a AccountView,
+    pub callback_accounts: &'a [AccountView],
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for FlashSwapAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [user, vault, user_ata, config, token_program, mint, callback_program, callback_accounts @ ..] =
+            accounts
+        else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if callback_accounts.len() > MAX_CALLBACK_ACCOUNTS {
+            return Err(AmmError::TooManyAccountKeys.into());
+        }
+
+        SignerAccount::check(user)?;
+        ConfigAccount::check(config)?;
+
+        // The loan is only safe if the CPI cannot come back into this program
+        if callback_program.address().eq(&crate::ID) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        Ok(Self {
+            user,
+            vault,
+            user_ata,
+            config,
+            token_program,
+            mint,
+            callback_program,
+            callback_accounts,
+        })
+    }
+}
+
+pub struct FlashSwapInstructionData<'a> {
+    pub is_x: bool,
+    pub amount: u64,
+    pub callback_data: &'a [u8],
+}
+
+impl<'a> TryFrom<&'a [u8]> for FlashSwapInstructionData<'a> {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() < size_of::<u8>() + size_of::<u64>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let is_x = match data[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+        let amount = u64::from_le_bytes(data[1..9].try_into().unwrap());
+        if amount == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self {
+            is_x,
+            amount,
+            callback_data: &data[9..],
+        })
+    }
+}
+
+pub struct FlashSwap<'a> {
+    pub accounts: FlashSwapAccounts<'a>,
+    pub instruction_data: FlashSwapInstructionData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for FlashSwap<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = FlashSwapAccounts::try_from(accounts)?;
+        let instruction_data = FlashSwapInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> FlashSwap<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &5;
+    /// Pool flags the instruction needs.
+    pub const REQUIRED_FLAGS: u8 = AmmState::SWAPS_ENABLED;
+
+    /// Amount the vault must be up by once `amount` is returned: the swap fee, rounded up.
+    ///
+    /// With the other reserve untouched, repaying `amount + fee` leaves `k = x * y` at least
+    /// where it was and the fee accrues to LPs like on a regular swap.
+    #[inline(always)]
+    pub fn fee(amount: u64, fee: u16) -> Result<u64, ProgramError> {
+        u64::try_from((amount as u128 * fee as u128).div_ceil(10_000))
+            .map_err(|_| AmmError::MathOverflow.into())
+    }
+
+    pub fn process(&mut self) -> ProgramResult {
+        ConfigAccount::update_price_cumulative(self.accounts.config)?;
+        let config_data = Config::load(self.accounts.config)?;
+        config_data.require_address(self.accounts.config.address())?;
+
+        config_data.require_flags(Self::REQUIRED_FLAGS)?;
+
+        let (mint, vault, token_program) = match self.instruction_data.is_x {
+            true => (
+                config_data.mint_x(),
+                config_data.vault_x(),
+                config_data.token_program_x(),
+            ),
+            false => (
+                config_data.mint_y(),
+                config_data.vault_y(),
+                config_data.token_program_y(),
+            ),
+        };
+        if self.accounts.mint.address().ne(mint) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if self.accounts.vault.address().ne(vault) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        // Only the lending side moves, through the token program of its mint
+        if self.accounts.token_program.address().ne(token_program) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        UserTokenAccount::check(
+            self.accounts.user_ata,
+            self.accounts.user.address(),
+            mint,
+            self.accounts.token_program.address(),
+        )?;
+
+        let vault = TokenInterface::read(self.accounts.vault)?;
+        if vault.owner().ne(self.accounts.config.address()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        let amount = self.instruction_data.amount;
+        let balance = vault.amount();
+        if balance < amount {
+            return Err(AmmError::InsufficientLiquidity.into());
+        }
+        let required = balance
+            .checked_add(Self::fee(amount, config_data.fee())?)
+            .ok_or(AmmError::MathOverflow)?;
+
+        let config_seeds = config_data.signer_seeds();
+        let signer_seeds = [Signer::from(&config_seeds)];
+
+        // Lend from the vault to the user
+        TokenInterface::transfer_checked(
+            self.accounts.vault,
+            self.accounts.mint,
+            self.accounts.user_ata,
+            self.accounts.config,
+            amount,
+            &signer_seeds,
+        )?;
+
+        // Hand control to the borrower, without the config signature
+        let callback_accounts = self.accounts.callback_accounts;
+        let instruction_accounts: [InstructionAccount; MAX_CALLBACK_ACCOUNTS] =
+            core::array::from_fn(|i| {
+                callback_accounts
+                    .get(i)
+                    .unwrap_or(self.accounts.user)
+                    .into()
+            });
+        let account_views: [&AccountView; MAX_CALLBACK_ACCOUNTS] =
+            core::array::from_fn(|i| callback_accounts.get(i).unwrap_or(self.accounts.user));
+        invoke_with_bounds::<MAX_CALLBACK_ACCOUNTS>(
+            &InstructionView {
+                program_id: self.accounts.callback_program.address(),
+                data: self.instruction_data.callback_data,
+                accounts: &instruction_accounts[..callback_accounts.len()],
+            },
+            &account_views[..callback_accounts.len()],
+        )?;
+
+        // The vault must be back to its balance plus the fee
+        let repaid = TokenInterface::read(self.accounts.vault)?.amount();
+        if repaid < required {
+            return Err(AmmError::FlashSwapNotRepaid.into());
+        }
+
+        // Only the lending vault moved, up by what the borrower paid on top of the loan
+        let earned = repaid - balance;
+        drop(config_data);
+        let mut config_data = Config::load_mut(self.accounts.config)?;
+        match self.instruction_data.is_x {
+            true => config_data.credit_reserves(earned, 0)?,
+            false => config_data.credit_reserves(0, earned)?,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn charges_the_swap_fee_rounded_up() {
+        assert_eq!(FlashSwap::fee(10_000, 30), Ok(30));
+        // 0.3% of 1 is a fraction of a base unit, still owed in full
+        assert_eq!(FlashSwap::fee(1, 30), Ok(1));
+        assert_eq!(FlashSwap::fee(10_001, 30), Ok(31));
+    }
+
+    #[test]
+    fn charges_nothing_without_a_fee() {
+        assert_eq!(FlashSwap::fee(u64::MAX, 0), Ok(0));
+        assert_eq!(FlashSwap::fee(0, 30), Ok(0));
+    }
+
+    #[test]
+    fn rejects_a_fee_beyond_u64() {
+        assert_eq!(FlashSwap::fee(u64::MAX, 10_000), Ok(u64::MAX));
+        assert_eq!(
+            FlashSwap::fee(u64::MAX, 10_001),
+            Err(AmmError::MathOverflow.into())
+        );
+    }
+}
@@ -1,31 +1,85 @@
 use core::mem::MaybeUninit;
 use pinocchio::cpi::{Seed, Signer};
-use pinocchio::{error::ProgramError, AccountView, ProgramResult};
-use pinocchio_pubkey::derive_address;
+use pinocchio::{error::ProgramError, AccountView, Address, ProgramResult};
 
 use super::utils::*;
+use crate::errors::AmmError;
 use crate::state::*;
 
 struct InitializeAccounts<'a> {
     pub initializer: &'a AccountView,
     pub mint_lp: &'a AccountView,
     pub config: &'a AccountView,
+    pub system_program: &'a AccountView,
+    pub token_program_x: &'a AccountView,
+    pub token_program_y: &'a AccountView,
+    pub mint_x: &'a AccountView,
+    pub mint_y: &'a AccountView,
+    pub vault_x: &'a AccountView,
+    pub vault_y: &'a AccountView,
+    pub locked_lp: &'a AccountView,
+    pub registry: &'a AccountView,
+    pub canonical_pool: Option<&'a AccountView>,
 }
 
 impl<'a> TryFrom<&'a [AccountView]> for InitializeAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
-        let [initializer, mint_lp, config, _, _] = accounts else {
+        let [initializer, mint_lp, config, system_program, token_program_x, token_program_y, mint_x, mint_y, vault_x, vault_y, _, locked_lp, registry, canonical_pool @ ..] =
+            accounts
+        else {
             return Err(ProgramError::NotEnoughAccountKeys);
         };
+        // Only read when the instruction data asks for the canonical pool
+        let canonical_pool = match canonical_pool {
+            [] => None,
+            [canonical_pool] => Some(canonical_pool),
+            _ => return Err(AmmError::TooManyAccountKeys.into()),
+        };
 
         SignerAccount::check(initializer)?;
+        TokenProgram::check(token_program_x)?;
+        TokenProgram::check(token_program_y)?;
+        MintInterface::check(mint_x)?;
+        MintInterface::check(mint_y)?;
+
+        if MintInterface::decimals(mint_x)? > Config::MAX_MINT_DECIMALS
+            || MintInterface::decimals(mint_y)? > Config::MAX_MINT_DECIMALS
+        {
+            return Err(AmmError::InvalidMint.into());
+        }
+
+        if mint_x.address().eq(mint_y.address()) {
+            return Err(AmmError::IdenticalMints.into());
+        }
+
+        // One ordering per pair, so (x, y) and (y, x) cannot be two different pools
+        if mint_x.address().as_array() > mint_y.address().as_array() {
+            return Err(AmmError::UnorderedMints.into());
+        }
+
+        // Each side may use either token program, recorded for the instructions that follow
+        if !mint_x.owned_by(token_program_x.address())
+            || !mint_y.owned_by(token_program_y.address())
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
 
         Ok(Self {
             initializer,
             mint_lp,
             config,
+            system_program,
+            token_program_x,
+            token_program_y,
+            mint_x,
+            mint_y,
+            vault_x,
+            vault_y,
+            locked_lp,
+            registry,
+            canonical_pool,
         })
     }
 }
@@ -39,30 +93,63 @@ struct InitializeInstructionData {
     pub config_bump: [u8; 1],
     pub lp_bump: [u8; 1],
     pub authority: [u8; 32],
+    pub lp_freeze_authority: [u8; 32],
+    pub canonical_pool: u8,
+}
+
+impl InitializeInstructionData {
+    /// Freeze authority of the LP mint, all zeroes meaning none.
+    fn lp_freeze_authority(&self) -> Option<Address> {
+        if self.lp_freeze_authority == [0u8; 32] {
+            None
+        } else {
+            Some(self.lp_freeze_authority.into())
+        }
+    }
+
+    /// Whether the pool is to be named its pair's canonical one.
+    fn canonical_pool(&self) -> Result<bool, ProgramError> {
+        match self.canonical_pool {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
 }
 
 impl TryFrom<&[u8]> for InitializeInstructionData {
     type Error = ProgramError;
 
     fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
-        const INITIALIZE_DATA_LEN_WITH_AUTHORITY: usize = size_of::<InitializeInstructionData>();
+        const INITIALIZE_DATA_LEN_WITH_CANONICAL_POOL: usize =
+            size_of::<InitializeInstructionData>();
+        const INITIALIZE_DATA_LEN_WITH_FREEZE_AUTHORITY: usize =
+            INITIALIZE_DATA_LEN_WITH_CANONICAL_POOL - size_of::<u8>();
+        const INITIALIZE_DATA_LEN_WITH_AUTHORITY: usize =
+            INITIALIZE_DATA_LEN_WITH_FREEZE_AUTHORITY - size_of::<[u8; 32]>();
         const INITIALIZE_DATA_LEN: usize =
             INITIALIZE_DATA_LEN_WITH_AUTHORITY - size_of::<[u8; 32]>();
 
         match data.len() {
-            INITIALIZE_DATA_LEN_WITH_AUTHORITY => {
+            INITIALIZE_DATA_LEN_WITH_CANONICAL_POOL => {
                 Ok(unsafe { (data.as_ptr() as *const Self).read_unaligned() })
             }
-            INITIALIZE_DATA_LEN => {
-                // If the authority is not present, we need to build the buffer and add it at the end before transmuting to the struct
-                let mut raw: MaybeUninit<[u8; INITIALIZE_DATA_LEN_WITH_AUTHORITY]> =
+            INITIALIZE_DATA_LEN
+            | INITIALIZE_DATA_LEN_WITH_AUTHORITY
+            | INITIALIZE_DATA_LEN_WITH_FREEZE_AUTHORITY => {
+                // If the optional fields are not present, we need to build the buffer and zero them at the end before transmuting to the struct
+                let mut raw: MaybeUninit<[u8; INITIALIZE_DATA_LEN_WITH_CANONICAL_POOL]> =
                     MaybeUninit::uninit();
                 let raw_ptr = raw.as_mut_ptr() as *mut u8;
                 unsafe {
                     // Copy the provided data
-                    core::ptr::copy_nonoverlapping(data.as_ptr(), raw_ptr, INITIALIZE_DATA_LEN);
-                    // Add the authority to the end of the buffer
-                    core::ptr::write_bytes(raw_ptr.add(INITIALIZE_DATA_LEN), 0, 32);
+                    core::ptr::copy_nonoverlapping(data.as_ptr(), raw_ptr, data.len());
+                    // Zero the missing fields at the end of the buffer
+                    core::ptr::write_bytes(
+                        raw_ptr.add(data.len()),
+                        0,
+                        INITIALIZE_DATA_LEN_WITH_CANONICAL_POOL - data.len(),
+                    );
                     // Now transmute to the struct
                     Ok((raw.as_ptr() as *const Self).read_unaligned())
                 }
@@ -85,6 +172,7 @@ impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for Initialize<'a> {
     ) -> Result<Self, Self::Error> {
         let accounts = InitializeAccounts::try_from(accounts)?;
         let instruction_data = InitializeInstructionData::try_from(instruction_data)?;
+        instruction_data.canonical_pool()?;
 
         Ok(Self {
             accounts,
@@ -96,27 +184,62 @@ impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for Initialize<'a> {
 impl<'a> Initialize<'a> {
     pub const DISCRIMINATOR: &'a u8 = &0;
 
+    /// Pass the pair's canonical pool PDA on, for callers that take more accounts after the
+    /// ones `Initialize` does.
+    pub fn with_canonical_pool(mut self, canonical_pool: Option<&'a AccountView>) -> Self {
+        self.accounts.canonical_pool = canonical_pool;
+        self
+    }
+
+    /// The canonical pool PDA of the pair and its bump, when the instruction asks to create
+    /// it. Only one pool per pair can hold it, so a second attempt fails here, before any
+    /// account is created.
+    fn canonical_pool(&self) -> Result<Option<(&'a AccountView, u8)>, ProgramError> {
+        if !self.instruction_data.canonical_pool()? {
+            return Ok(None);
+        }
+        let canonical_pool = self
+            .accounts
+            .canonical_pool
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        let (canonical_pool_key, canonical_pool_bump) = derive_canonical_pool(
+            &self.instruction_data.mint_x.into(),
+            &self.instruction_data.mint_y.into(),
+        );
+        if canonical_pool_key.ne(canonical_pool.address()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if !canonical_pool.owned_by(&pinocchio_system::ID) || canonical_pool.data_len() != 0 {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        Ok(Some((canonical_pool, canonical_pool_bump)))
+    }
+
     pub fn process(&self) -> ProgramResult {
+        if self.accounts.mint_x.address().as_array() != &self.instruction_data.mint_x
+            || self.accounts.mint_y.address().as_array() != &self.instruction_data.mint_y
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
         let seed_binding = self.instruction_data.seed.to_le_bytes();
 
-        if derive_address(
-            &[
-                b"config",
-                &seed_binding,
-                &self.instruction_data.mint_x,
-                &self.instruction_data.mint_y,
-                &self.instruction_data.config_bump,
-            ],
-            None,
-            &crate::ID.as_array(),
-        )
-        .ne(self.accounts.config.address().as_array())
+        let (config_key, config_bump) = derive_config(
+            self.instruction_data.seed,
+            &self.instruction_data.mint_x.into(),
+            &self.instruction_data.mint_y.into(),
+        );
+        if config_key.ne(self.accounts.config.address())
+            || [config_bump] != self.instruction_data.config_bump
         {
             return Err(ProgramError::InvalidSeeds);
         }
+        let canonical_pool = self.canonical_pool()?;
 
         let config_seeds = [
-            Seed::from(b"config"),
+            Seed::from(Config::SEED),
             Seed::from(&seed_binding),
             Seed::from(&self.instruction_data.mint_x),
             Seed::from(&self.instruction_data.mint_y),
@@ -138,23 +261,298 @@ impl<'a> Initialize<'a> {
             self.instruction_data.fee,
             self.instruction_data.config_bump,
         )?;
+        // Checked against the config's ATAs below, once they exist
+        config_data.set_vaults(
+            *self.accounts.vault_x.address(),
+            *self.accounts.vault_y.address(),
+        );
+        config_data.set_token_programs(
+            *self.accounts.token_program_x.address(),
+            *self.accounts.token_program_y.address(),
+        );
+
+        let (mint_lp_key, mint_lp_bump) = derive_mint_lp(self.accounts.config.address());
+        if mint_lp_key.ne(self.accounts.mint_lp.address())
+            || [mint_lp_bump] != self.instruction_data.lp_bump
+        {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        config_data.set_mint_lp(mint_lp_key);
+        // The runtime refuses a CPI on an account the program still holds borrowed
+        drop(config_data);
+
+        // The config is fresh, so any mint already at its LP address was not made by it
+        if !self.accounts.mint_lp.owned_by(&pinocchio_system::ID)
+            || self.accounts.mint_lp.data_len() != 0
+        {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
 
-        let mint_lp_decimals = 6;
         let mint_lp_seeds = [
-            Seed::from(b"mint_lp"),
+            Seed::from(Config::MINT_LP_SEED),
             Seed::from(self.accounts.config.address().as_array()),
             Seed::from(&self.instruction_data.lp_bump),
         ];
         let mint_signers = [Signer::from(&mint_lp_seeds)];
-        MintInterface::init_if_need(
+        let lp_freeze_authority = self.instruction_data.lp_freeze_authority();
+        MintInterface::init(
             self.accounts.mint_lp,
             self.accounts.initializer,
-            mint_lp_decimals,
+            Config::LP_DECIMALS,
             self.accounts.config.address(),
-            None,
+            lp_freeze_authority.as_ref(),
             &mint_signers,
+            // The LP mint shares the program of mint X, always passed alongside it
+            self.accounts.token_program_x.address(),
         )?;
 
+        // Create the config owned vaults and locked LP account so the first deposit needs no
+        // client side setup. This is the only place the vault ATAs are derived, later
+        // instructions compare against the addresses stored above
+        for (vault, mint, token_program) in [
+            (
+                self.accounts.vault_x,
+                self.accounts.mint_x,
+                self.accounts.token_program_x,
+            ),
+            (
+                self.accounts.vault_y,
+                self.accounts.mint_y,
+                self.accounts.token_program_y,
+            ),
+            (
+                self.accounts.locked_lp,
+                self.accounts.mint_lp,
+                self.accounts.token_program_x,
+            ),
+        ] {
+            AssociatedTokenAccount::init_if_needed(
+                vault,
+                mint,
+                self.accounts.initializer,
+                self.accounts.config,
+                self.accounts.system_program,
+                token_program,
+            )?;
+            AssociatedTokenAccount::check(
+                vault,
+                self.accounts.config.address(),
+                mint.address(),
+                token_program.address(),
+            )?;
+        }
+
+        RegistryAccount::register(
+            self.accounts.initializer,
+            self.accounts.registry,
+            self.accounts.config.address(),
+            &self.instruction_data.mint_x.into(),
+            &self.instruction_data.mint_y.into(),
+        )?;
+
+        if let Some((canonical_pool, canonical_pool_bump)) = canonical_pool {
+            let bump = [canonical_pool_bump];
+            let canonical_pool_seeds = [
+                Seed::from(CanonicalPool::SEED),
+                Seed::from(&self.instruction_data.mint_x),
+                Seed::from(&self.instruction_data.mint_y),
+                Seed::from(&bump),
+            ];
+            CanonicalPoolAccount::init(
+                self.accounts.initializer,
+                canonical_pool,
+                &canonical_pool_seeds,
+            )?;
+            CanonicalPool::init(canonical_pool, *self.accounts.config.address(), bump)?;
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+    use pinocchio_token_2022::ID as TOKEN_2022_PROGRAM_ID;
+
+    use super::*;
+
+    const MINT_X: Address = Address::new_from_array([2; 32]);
+    const MINT_Y: Address = Address::new_from_array([3; 32]);
+
+    /// Run the account checks of `Initialize` on `mint_x` and `mint_y`, passed with the token
+    /// programs `token_program_x` and `token_program_y`.
+    fn check_accounts(
+        token_program_x: Address,
+        token_program_y: Address,
+        mint_x: &mut TestAccount,
+        mint_y: &mut TestAccount,
+    ) -> ProgramResult {
+        let mut initializer = TestAccount::empty(Address::new_from_array([1; 32])).signer();
+        let mut program_x = TestAccount::empty(token_program_x);
+        let mut program_y = TestAccount::empty(token_program_y);
+        let mut other = TestAccount::empty(Address::new_from_array([9; 32]));
+
+        let accounts = [
+            initializer.view(),
+            other.view(),
+            other.view(),
+            other.view(),
+            program_x.view(),
+            program_y.view(),
+            mint_x.view(),
+            mint_y.view(),
+            other.view(),
+            other.view(),
+            other.view(),
+            other.view(),
+            other.view(),
+        ];
+        InitializeAccounts::try_from(&accounts[..]).map(|_| ())
+    }
+
+    /// Instruction data opening the pool of `seed` on `MINT_X` and `MINT_Y`, with the
+    /// `canonical_pool` flag.
+    fn data(seed: u64, canonical_pool: u8) -> Vec<u8> {
+        let (_, config_bump) = derive_config(seed, &MINT_X, &MINT_Y);
+        let mut data = Vec::new();
+        data.extend_from_slice(&seed.to_le_bytes());
+        data.extend_from_slice(&30u16.to_le_bytes());
+        data.extend_from_slice(MINT_X.as_array());
+        data.extend_from_slice(MINT_Y.as_array());
+        data.extend_from_slice(&[config_bump, 0]);
+        data.extend_from_slice(&[0; 64]);
+        data.push(canonical_pool);
+        data
+    }
+
+    /// Run `Initialize` for the pool of `seed` up to its first CPI, passing `canonical_pool`
+    /// after the accounts every pool takes.
+    fn initialize(data: &[u8], canonical_pool: Option<&mut TestAccount>) -> ProgramResult {
+        let seed = u64::from_le_bytes(data[..8].try_into().unwrap());
+        let mut initializer = TestAccount::empty(Address::new_from_array([1; 32])).signer();
+        let mut config = TestAccount::empty(derive_config(seed, &MINT_X, &MINT_Y).0);
+        let mut token_program = TestAccount::empty(pinocchio_token::ID);
+        let mut mint_x = TestAccount::mint(MINT_X, pinocchio_token::ID, 6);
+        let mut mint_y = TestAccount::mint(MINT_Y, pinocchio_token::ID, 6);
+        let mut other = TestAccount::empty(Address::new_from_array([9; 32]));
+
+        let mut accounts = vec![
+            initializer.view(),
+            other.view(),
+            config.view(),
+            other.view(),
+            token_program.view(),
+            token_program.view(),
+            mint_x.view(),
+            mint_y.view(),
+            other.view(),
+            other.view(),
+            other.view(),
+            other.view(),
+            other.view(),
+        ];
+        accounts.extend(canonical_pool.map(TestAccount::view));
+        Initialize::try_from((data, &accounts[..]))?.process()
+    }
+
+    #[test]
+    fn rejects_a_second_canonical_pool_for_the_pair() {
+        // The pair's first canonical pool, naming the pool of seed 1
+        let (address, bump) = derive_canonical_pool(&MINT_X, &MINT_Y);
+        let mut canonical_pool = TestAccount::new(address, crate::ID, &[0; CanonicalPool::LEN]);
+        let first = derive_config(1, &MINT_X, &MINT_Y).0;
+        CanonicalPool::init(&canonical_pool.view(), first, [bump]).unwrap();
+
+        assert_eq!(
+            initialize(&data(2, 1), Some(&mut canonical_pool)),
+            Err(ProgramError::AccountAlreadyInitialized)
+        );
+        assert_eq!(CanonicalPool::load(&canonical_pool.view()).unwrap().config(), &first);
+    }
+
+    #[test]
+    fn rejects_a_canonical_pool_off_the_pair_pda() {
+        let mut canonical_pool = TestAccount::empty(Address::new_from_array([8; 32]));
+        assert_eq!(
+            initialize(&data(2, 1), Some(&mut canonical_pool)),
+            Err(ProgramError::InvalidSeeds)
+        );
+    }
+
+    #[test]
+    fn requires_the_canonical_pool_only_when_asked() {
+        assert_eq!(
+            initialize(&data(2, 1), None),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            initialize(&data(2, 2), None),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn accepts_a_token_and_token_2022_pair() {
+        let mut mint_x = TestAccount::mint(MINT_X, pinocchio_token::ID, 6);
+        let mut mint_y = TestAccount::mint(MINT_Y, TOKEN_2022_PROGRAM_ID, 9);
+
+        assert_eq!(
+            check_accounts(
+                pinocchio_token::ID,
+                TOKEN_2022_PROGRAM_ID,
+                &mut mint_x,
+                &mut mint_y
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_a_token_program_not_owning_its_mint() {
+        let mut mint_x = TestAccount::mint(MINT_X, pinocchio_token::ID, 6);
+        let mut mint_y = TestAccount::mint(MINT_Y, TOKEN_2022_PROGRAM_ID, 9);
+
+        assert_eq!(
+            check_accounts(
+                pinocchio_token::ID,
+                pinocchio_token::ID,
+                &mut mint_x,
+                &mut mint_y
+            ),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+
+    #[test]
+    fn rejects_a_missing_mint() {
+        let mut mint_x = TestAccount::empty(MINT_X);
+        let mut mint_y = TestAccount::mint(MINT_Y, pinocchio_token::ID, 6);
+
+        assert_eq!(
+            check_accounts(
+                pinocchio_token::ID,
+                pinocchio_token::ID,
+                &mut mint_x,
+                &mut mint_y
+            ),
+            Err(ProgramError::InvalidAccountOwner)
+        );
+    }
+
+    #[test]
+    fn rejects_identical_mints() {
+        let mut mint_x = TestAccount::mint(MINT_X, pinocchio_token::ID, 6);
+        let mut mint_y = TestAccount::mint(MINT_X, pinocchio_token::ID, 6);
+
+        assert_eq!(
+            check_accounts(
+                pinocchio_token::ID,
+                pinocchio_token::ID,
+                &mut mint_x,
+                &mut mint_y
+            ),
+            Err(AmmError::IdenticalMints.into())
+        );
+    }
+}
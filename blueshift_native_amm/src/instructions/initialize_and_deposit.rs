@@ -0,0 +1,131 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::deposit::{Deposit, DepositAccounts, DepositInstructionData};
+use super::initialize::Initialize;
+use super::utils::AssociatedTokenAccount;
+use crate::errors::AmmError;
+
+/// Accounts `Initialize` takes, followed by the initializer's token accounts and the
+/// optional canonical pool PDA.
+const INITIALIZE_ACCOUNTS_LEN: usize = 13;
+
+pub struct InitializeAndDepositInstructionData {
+    pub max_x: u64,
+    pub max_y: u64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for InitializeAndDepositInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<InitializeAndDepositInstructionData>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let instruction_data = unsafe { (data.as_ptr() as *const Self).read_unaligned() };
+
+        // A pool opening with an empty side has no price, and is open to whoever deposits next
+        if instruction_data.max_x == 0 || instruction_data.max_y == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(instruction_data)
+    }
+}
+
+/// `Initialize` followed by the first `Deposit` of exactly `max_x` and `max_y`, so the pool
+/// never exists empty. The `MINIMUM_LIQUIDITY` lock applies as for any first deposit.
+pub struct InitializeAndDeposit<'a> {
+    pub initialize: Initialize<'a>,
+    pub deposit: Deposit<'a>,
+    pub system_program: &'a AccountView,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for InitializeAndDeposit<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        // The deposit amounts come first, `Initialize` data has optional trailing fields
+        const DEPOSIT_DATA_LEN: usize = size_of::<InitializeAndDepositInstructionData>();
+        if data.len() < DEPOSIT_DATA_LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (deposit_data, initialize_data) = data.split_at(DEPOSIT_DATA_LEN);
+        let instruction_data = InitializeAndDepositInstructionData::try_from(deposit_data)?;
+
+        if accounts.len() < INITIALIZE_ACCOUNTS_LEN {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let (initialize_accounts, user_accounts) = accounts.split_at(INITIALIZE_ACCOUNTS_LEN);
+        let (user_x_ata, user_y_ata, user_lp_ata, canonical_pool) = match user_accounts {
+            [user_x_ata, user_y_ata, user_lp_ata] => (user_x_ata, user_y_ata, user_lp_ata, None),
+            [user_x_ata, user_y_ata, user_lp_ata, canonical_pool] => {
+                (user_x_ata, user_y_ata, user_lp_ata, Some(canonical_pool))
+            }
+            [_, _, _, _, ..] => return Err(AmmError::TooManyAccountKeys.into()),
+            _ => return Err(ProgramError::NotEnoughAccountKeys),
+        };
+        let initialize = Initialize::try_from((initialize_data, initialize_accounts))?
+            .with_canonical_pool(canonical_pool);
+
+        let [initializer, mint_lp, config, system_program, token_program_x, token_program_y, mint_x, mint_y, vault_x, vault_y, _, locked_lp, _] =
+            initialize_accounts
+        else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        // `Initialize` checked the signer and the token program `Deposit` would
+        let deposit = Deposit {
+            accounts: DepositAccounts {
+                user: initializer,
+                mint_lp,
+                vault_x,
+                vault_y,
+                user_x_ata,
+                user_y_ata,
+                user_lp_ata,
+                config,
+                token_program_x,
+                token_program_y,
+                mint_x,
+                mint_y,
+                locked_lp: Some(locked_lp),
+            },
+            // A first deposit takes exactly `max_x` and `max_y`, `amount` is unused
+            instruction_data: DepositInstructionData {
+                amount: 0,
+                max_x: instruction_data.max_x,
+                max_y: instruction_data.max_y,
+                expiration: 0,
+                min_lp: 0,
+            },
+        };
+
+        Ok(Self {
+            initialize,
+            deposit,
+            system_program,
+        })
+    }
+}
+
+impl<'a> InitializeAndDeposit<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &21;
+
+    pub fn process(&mut self) -> ProgramResult {
+        self.initialize.process()?;
+
+        // The LP mint was only just created, so the initializer cannot hold an LP account yet
+        let accounts = &self.deposit.accounts;
+        AssociatedTokenAccount::init_if_needed(
+            accounts.user_lp_ata,
+            accounts.mint_lp,
+            accounts.user,
+            accounts.user,
+            self.system_program,
+            accounts.token_program_x,
+        )?;
+
+        self.deposit.process()
+    }
+}
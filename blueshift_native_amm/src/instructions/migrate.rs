@@ -0,0 +1,177 @@
+use pinocchio::{
+    error::ProgramError,
+    sysvars::{rent::Rent, Sysvar},
+    AccountView, Address, ProgramResult,
+};
+use pinocchio_system::instructions::Transfer;
+
+use super::utils::{
+    AssociatedTokenAccount, MintInterface, SignerAccount, TokenInterface, TokenProgram,
+};
+use crate::state::Config;
+
+pub struct MigrateAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub config: &'a AccountView,
+    pub system_program: &'a AccountView,
+    pub vault_x: &'a AccountView,
+    pub vault_y: &'a AccountView,
+    pub token_program: &'a AccountView,
+    pub mint_lp: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for MigrateAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, config, system_program, vault_x, vault_y, token_program, mint_lp] =
+            accounts
+        else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(authority)?;
+        TokenProgram::check(token_program)?;
+        MintInterface::check(mint_lp)?;
+
+        if !config.owned_by(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        Ok(Self {
+            authority,
+            config,
+            system_program,
+            vault_x,
+            vault_y,
+            token_program,
+            mint_lp,
+        })
+    }
+}
+
+pub struct MigrateInstructionData {
+    /// Bump of the pool's LP mint PDA, which v0 configs did not store.
+    pub lp_bump: [u8; 1],
+}
+
+impl<'a> TryFrom<&'a [u8]> for MigrateInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        let [lp_bump] = data else {
+            return Err(ProgramError::InvalidInstructionData);
+        };
+
+        Ok(Self {
+            lp_bump: [*lp_bump],
+        })
+    }
+}
+
+pub struct Migrate<'a> {
+    pub accounts: MigrateAccounts<'a>,
+    pub instruction_data: MigrateInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for Migrate<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = MigrateAccounts::try_from(accounts)?;
+        let instruction_data = MigrateInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> Migrate<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &4;
+
+    pub fn process(&self) -> ProgramResult {
+        let mut raw = {
+            let data = self.accounts.config.try_borrow()?;
+            if Config::version_of(&data)? == Config::VERSION {
+                return Ok(());
+            }
+            Config::upgrade(&data)?
+        };
+        let config_data = Config::from_bytes(&raw)?;
+
+        // Pools with an authority may only be upgraded by it
+        if let Some(authority) = config_data.has_authority() {
+            if authority.ne(self.accounts.authority.address()) {
+                return Err(ProgramError::IncorrectAuthority);
+            }
+        }
+
+        config_data.require_address(self.accounts.config.address())?;
+
+        // v0 configs did not store their vaults, derive them this once
+        AssociatedTokenAccount::check(
+            self.accounts.vault_x,
+            self.accounts.config.address(),
+            config_data.mint_x(),
+            self.accounts.token_program.address(),
+        )?;
+        AssociatedTokenAccount::check(
+            self.accounts.vault_y,
+            self.accounts.config.address(),
+            config_data.mint_y(),
+            self.accounts.token_program.address(),
+        )?;
+        // The v0 `Initialize` took any bump, so the LP mint need not be at the canonical one.
+        // Only it could create a mint at one of the config's PDAs, and only once
+        let mint_lp = Address::derive_address(
+            &[
+                Config::MINT_LP_SEED,
+                self.accounts.config.address().as_array(),
+                &self.instruction_data.lp_bump,
+            ],
+            None,
+            &crate::ID,
+        );
+        if mint_lp.ne(self.accounts.mint_lp.address()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        // SAFETY: `from_bytes` checked `raw` holds a config in the current layout
+        let upgraded = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+        upgraded.set_vaults(
+            *self.accounts.vault_x.address(),
+            *self.accounts.vault_y.address(),
+        );
+        upgraded.set_mint_lp(mint_lp);
+        // v0 priced from the vault balances, they are the reserves the pool starts from
+        upgraded.set_reserves(
+            TokenInterface::amount(self.accounts.vault_x)?,
+            TokenInterface::amount(self.accounts.vault_y)?,
+        );
+        // Both v0 vaults were derived with the one token program checked above
+        upgraded.set_token_programs(
+            *self.accounts.token_program.address(),
+            *self.accounts.token_program.address(),
+        );
+
+        // Top up the rent for the larger layout
+        let lamports = Rent::get()?.try_minimum_balance(Config::LEN)?;
+        if let Some(missing) = lamports.checked_sub(self.accounts.config.lamports()) {
+            if missing > 0 {
+                Transfer {
+                    from: self.accounts.authority,
+                    to: self.accounts.config,
+                    lamports: missing,
+                }
+                .invoke()?;
+            }
+        }
+
+        // Realloc and rewrite in the current layout
+        self.accounts.config.resize(Config::LEN)?;
+        self.accounts.config.try_borrow_mut()?.copy_from_slice(&raw);
+
+        Ok(())
+    }
+}
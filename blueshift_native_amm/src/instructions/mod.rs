@@ -1,10 +1,154 @@
+pub mod claim_fees;
 pub mod deposit;
+pub mod deposit_single_sided;
+pub mod deregister_pool;
+pub mod donate;
+pub mod flash_swap;
 pub mod initialize;
+pub mod initialize_and_deposit;
+pub mod migrate;
+pub mod pause;
+pub mod quote;
+pub mod resume;
+pub mod set_canonical_pool;
+pub mod set_flags;
+pub mod skim;
 pub mod swap;
+pub mod swap_exact_out;
+pub mod swap_multi_hop;
+pub mod sync_reserves;
+pub mod update_authority;
+pub mod update_fee;
+pub mod update_state;
 pub mod utils;
 pub mod withdraw;
+pub mod withdraw_all;
+pub mod withdraw_single;
 
+pub use claim_fees::ClaimFees;
 pub use deposit::Deposit;
+pub use deposit_single_sided::DepositSingleSided;
+pub use deregister_pool::DeregisterPool;
+pub use donate::Donate;
+pub use flash_swap::FlashSwap;
 pub use initialize::Initialize;
-pub use swap::Swap;
-pub use withdraw::Withdraw;
+pub use initialize_and_deposit::InitializeAndDeposit;
+pub use migrate::Migrate;
+pub use pause::Pause;
+pub use quote::Quote;
+pub use resume::Resume;
+pub use set_canonical_pool::SetCanonicalPool;
+pub use set_flags::SetFlags;
+pub use skim::Skim;
+pub use swap::{input_for_output, require_k_preserved, swap_output, Swap};
+pub use swap_exact_out::SwapExactOut;
+pub use swap_multi_hop::SwapMultiHop;
+pub use sync_reserves::SyncReserves;
+pub use update_authority::UpdateAuthority;
+pub use update_fee::UpdateFee;
+pub use update_state::UpdateState;
+pub use withdraw::{lp_to_underlying, Withdraw};
+pub use withdraw_all::WithdrawAll;
+pub use withdraw_single::WithdrawSingle;
+
+#[cfg(test)]
+mod tests {
+    use pinocchio::error::ProgramError;
+
+    use super::*;
+    use crate::errors::AmmError;
+    use crate::state::{AmmState, Config};
+
+    #[test]
+    fn gates_every_instruction_on_the_pool_state() {
+        use AmmState::*;
+
+        // Instruction, then whether it runs while Uninitialized, Initialized, Disabled and
+        // WithdrawOnly
+        let matrix = [
+            (
+                "Deposit",
+                Deposit::REQUIRED_FLAGS,
+                [false, true, false, false],
+            ),
+            (
+                "DepositSingleSided",
+                DepositSingleSided::REQUIRED_FLAGS,
+                [false, true, false, false],
+            ),
+            (
+                "Donate",
+                Donate::REQUIRED_FLAGS,
+                [false, true, false, false],
+            ),
+            ("Swap", Swap::REQUIRED_FLAGS, [false, true, false, false]),
+            (
+                "FlashSwap",
+                FlashSwap::REQUIRED_FLAGS,
+                [false, true, false, false],
+            ),
+            ("Quote", Quote::REQUIRED_FLAGS, [false, true, false, false]),
+            (
+                "WithdrawSingle",
+                WithdrawSingle::REQUIRED_FLAGS,
+                [false, true, false, false],
+            ),
+            (
+                "Withdraw",
+                Withdraw::REQUIRED_FLAGS,
+                [false, true, false, true],
+            ),
+            (
+                "ClaimFees",
+                ClaimFees::REQUIRED_FLAGS,
+                [false, true, false, true],
+            ),
+            ("Skim", Skim::REQUIRED_FLAGS, [false, true, false, true]),
+        ];
+
+        let mut raw = [0u8; Config::LEN];
+        // SAFETY: the buffer holds exactly one config
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+        for (name, required, allowed) in matrix {
+            for (state, allowed) in [Uninitialized, Initialized, Disabled, WithdrawOnly]
+                .into_iter()
+                .zip(allowed)
+            {
+                config.set_state(state as u8).unwrap();
+                let expected: Result<(), ProgramError> = match allowed {
+                    true => Ok(()),
+                    false => Err(AmmError::PoolDisabled.into()),
+                };
+                assert_eq!(
+                    config.require_flags(required),
+                    expected,
+                    "{name} in state {}",
+                    state as u8
+                );
+            }
+        }
+    }
+
+    /// Calls in `source`, its tests aside, that search for a PDA bump, each costing 1500 CU
+    /// per bump tried.
+    fn bump_searches(source: &str) -> usize {
+        let code = source.split("#[cfg(test)]").next().unwrap();
+        [
+            "find_program_address",
+            "derive_config",
+            "derive_mint_lp",
+            "AssociatedTokenAccount::",
+        ]
+        .iter()
+        .map(|call| code.matches(call).count())
+        .sum()
+    }
+
+    #[test]
+    fn keeps_bump_searches_off_the_hot_paths() {
+        // Only the first deposit checks the locked LP account
+        assert_eq!(bump_searches(include_str!("swap.rs")), 0);
+        assert_eq!(bump_searches(include_str!("deposit.rs")), 1);
+        assert_eq!(bump_searches(include_str!("withdraw.rs")), 0);
+    }
+}
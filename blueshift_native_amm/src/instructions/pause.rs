@@ -0,0 +1,77 @@
+use pinocchio::{
+    error::ProgramError,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, ProgramResult,
+};
+
+use super::utils::{ConfigAccount, DataAccount, TokenInterface};
+use crate::errors::AmmError;
+use crate::state::*;
+
+pub struct PauseAccounts<'a> {
+    pub config: &'a AccountView,
+    pub vault_x: &'a AccountView,
+    pub vault_y: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for PauseAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [config, vault_x, vault_y] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        ConfigAccount::check(config)?;
+
+        Ok(Self {
+            config,
+            vault_x,
+            vault_y,
+        })
+    }
+}
+
+/// Permissionless circuit breaker: disables the pool when its vaults hold less than the
+/// program left in them.
+pub struct Pause<'a> {
+    pub accounts: PauseAccounts<'a>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for Pause<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let accounts = PauseAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> Pause<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &7;
+
+    pub fn process(&self) -> ProgramResult {
+        {
+            let config_data = Config::load(self.accounts.config)?;
+            config_data.require_flags(AmmState::WITHDRAWALS_ENABLED)?;
+
+            config_data.require_vaults(
+                self.accounts.vault_x.address(),
+                self.accounts.vault_y.address(),
+            )?;
+
+            let vault_x = TokenInterface::read(self.accounts.vault_x)?;
+            let vault_y = TokenInterface::read(self.accounts.vault_y)?;
+            if !config_data.is_short(vault_x.amount(), vault_y.amount()) {
+                return Err(AmmError::ReservesIntact.into());
+            }
+        }
+
+        let mut config_data = Config::load_mut(self.accounts.config)?;
+        config_data.set_state(AmmState::Disabled as u8)?;
+        config_data.set_paused_at(Clock::get()?.unix_timestamp);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use core::mem::size_of;
+
+use pinocchio::cpi::set_return_data;
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::swap::swap_output;
+use super::utils::{ConfigAccount, DataAccount, MintInterface, TokenProgram};
+use crate::state::*;
+
+pub struct QuoteAccounts<'a> {
+    pub config: &'a AccountView,
+    pub vault_x: &'a AccountView,
+    pub vault_y: &'a AccountView,
+    pub mint_lp: &'a AccountView,
+    pub token_program: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for QuoteAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [config, vault_x, vault_y, mint_lp, token_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        TokenProgram::check(token_program)?;
+        ConfigAccount::check(config)?;
+        MintInterface::check(mint_lp)?;
+
+        Ok(Self {
+            config,
+            vault_x,
+            vault_y,
+            mint_lp,
+            token_program,
+        })
+    }
+}
+
+#[repr(C, packed)]
+pub struct QuoteInstructionData {
+    pub is_x: bool,
+    pub amount: u64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for QuoteInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<QuoteInstructionData>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        // Any byte other than 0 or 1 is not a valid `bool`
+        if data[0] > 1 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let instruction_data = unsafe { (data.as_ptr() as *const Self).read_unaligned() };
+        let amount = instruction_data.amount;
+        if amount == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(instruction_data)
+    }
+}
+
+/// Read only: price a `Swap` of `amount` against the current vaults and set the result as
+/// return data, for clients to read back from a simulated transaction.
+pub struct Quote<'a> {
+    pub accounts: QuoteAccounts<'a>,
+    pub instruction_data: QuoteInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for Quote<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = QuoteAccounts::try_from(accounts)?;
+        let instruction_data = QuoteInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> Quote<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &18;
+    /// Pool flags the instruction needs.
+    pub const REQUIRED_FLAGS: u8 = AmmState::SWAPS_ENABLED;
+
+    /// Return data: `amount_out: u64`, `fee: u64` (LP and protocol fees) and
+    /// `price_impact_bps: u16`, all little endian.
+    pub const RETURN_DATA_LEN: usize = size_of::<u64>() * 2 + size_of::<u16>();
+
+    pub fn process(&self) -> ProgramResult {
+        // Copy out of the packed instruction data, never reference its fields
+        let is_x = self.instruction_data.is_x;
+        let amount = self.instruction_data.amount;
+
+        let config_data = Config::load(self.accounts.config)?;
+        config_data.require_vaults(
+            self.accounts.vault_x.address(),
+            self.accounts.vault_y.address(),
+        )?;
+
+        // Quote only what `Swap` would execute
+        config_data.require_flags(Self::REQUIRED_FLAGS)?;
+
+        config_data.require_mint_lp(self.accounts.mint_lp.address())?;
+        let mint_lp = MintInterface::read(self.accounts.mint_lp)?;
+        let (reserve_x, reserve_y) = config_data.recorded_pool_reserves();
+
+        // The same math `Swap` runs on what its input vault receives. A transfer fee on the
+        // input mint is not known here, callers quote the amount net of it
+        let (protocol_fee, lp_fee, amount_out) = swap_output(
+            &config_data,
+            reserve_x,
+            reserve_y,
+            mint_lp.supply(),
+            is_x,
+            amount,
+            0,
+        )?;
+
+        // Shortfall of the output against the spot price, fees aside
+        let (reserve_in, reserve_out) = match is_x {
+            true => (reserve_x, reserve_y),
+            false => (reserve_y, reserve_x),
+        };
+        let curve_in = (amount - protocol_fee).saturating_sub(lp_fee);
+        let spot_out = curve_in as u128 * reserve_out as u128 / reserve_in as u128;
+        let price_impact_bps = match spot_out {
+            0 => 0,
+            _ => 10_000u128.saturating_sub(amount_out as u128 * 10_000 / spot_out) as u16,
+        };
+
+        let mut return_data = [0u8; Self::RETURN_DATA_LEN];
+        return_data[..8].copy_from_slice(&amount_out.to_le_bytes());
+        return_data[8..16].copy_from_slice(&(protocol_fee + lp_fee).to_le_bytes());
+        return_data[16..].copy_from_slice(&price_impact_bps.to_le_bytes());
+        set_return_data(&return_data);
+
+        Ok(())
+    }
+}
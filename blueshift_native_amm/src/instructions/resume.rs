@@ -0,0 +1,100 @@
+use pinocchio::{
+    error::ProgramError,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, ProgramResult,
+};
+
+use super::utils::{ConfigAccount, DataAccount, SignerAccount};
+use crate::errors::AmmError;
+use crate::state::*;
+
+pub struct ResumeAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub config: &'a AccountView,
+    pub vault_x: &'a AccountView,
+    pub vault_y: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for ResumeAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, config, vault_x, vault_y] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(authority)?;
+        ConfigAccount::check(config)?;
+
+        Ok(Self {
+            authority,
+            config,
+            vault_x,
+            vault_y,
+        })
+    }
+}
+
+/// Reopen a pool disabled by `Pause` once the timelock has passed, accepting the vault
+/// balances as they are.
+pub struct Resume<'a> {
+    pub accounts: ResumeAccounts<'a>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for Resume<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let accounts = ResumeAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> Resume<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &8;
+
+    pub fn process(&self) -> ProgramResult {
+        {
+            let config_data = Config::load(self.accounts.config)?;
+            // Paused pools are the only ones withdrawals are off for
+            if config_data.state() & AmmState::WITHDRAWALS_ENABLED != 0 {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            // Pools without an authority can be resumed by anyone, or LPs could never withdraw
+            if let Some(authority) = config_data.has_authority() {
+                if authority.ne(self.accounts.authority.address()) {
+                    return Err(ProgramError::IncorrectAuthority);
+                }
+            }
+
+            // Only emergency pauses are lifted here
+            let paused_at = config_data.paused_at();
+            if paused_at == 0 {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if Clock::get()?.unix_timestamp < paused_at.saturating_add(Config::RESUME_TIMELOCK) {
+                return Err(AmmError::ResumeTimelocked.into());
+            }
+
+            config_data.require_vaults(
+                self.accounts.vault_x.address(),
+                self.accounts.vault_y.address(),
+            )?;
+        }
+
+        {
+            let mut config_data = Config::load_mut(self.accounts.config)?;
+            config_data.update_price_cumulative(Clock::get()?.unix_timestamp);
+            config_data.set_state(AmmState::Initialized as u8)?;
+            config_data.set_paused_at(0);
+        }
+
+        ConfigAccount::sync_reserves(
+            self.accounts.config,
+            self.accounts.vault_x,
+            self.accounts.vault_y,
+        )
+    }
+}
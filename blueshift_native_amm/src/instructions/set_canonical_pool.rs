@@ -0,0 +1,213 @@
+use pinocchio::cpi::Seed;
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::utils::{CanonicalPoolAccount, ConfigAccount, DataAccount, ProgramData, SignerAccount};
+use crate::state::*;
+
+pub struct SetCanonicalPoolAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub program_data: &'a AccountView,
+    pub config: &'a AccountView,
+    pub canonical_pool: &'a AccountView,
+    pub system_program: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for SetCanonicalPoolAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, program_data, config, canonical_pool, system_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(authority)?;
+        // Anyone can open a pool for any pair, so only the program's own authority vouches for one
+        ProgramData::check_upgrade_authority(program_data, &crate::ID, authority.address())?;
+        ConfigAccount::check(config)?;
+
+        Ok(Self {
+            authority,
+            program_data,
+            config,
+            canonical_pool,
+            system_program,
+        })
+    }
+}
+
+/// Name the pool as the primary one of its pair, creating the pair's canonical pool PDA or
+/// replacing the pool it pointed at. `Initialize` lets whoever opens a pool claim a free slot,
+/// only the program's upgrade authority may take it back from one with a fee and authority
+/// of their choosing.
+pub struct SetCanonicalPool<'a> {
+    pub accounts: SetCanonicalPoolAccounts<'a>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for SetCanonicalPool<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let accounts = SetCanonicalPoolAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> SetCanonicalPool<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &24;
+
+    pub fn process(&self) -> ProgramResult {
+        let (mint_x, mint_y) = {
+            let config_data = Config::load(self.accounts.config)?;
+            config_data.require_address(self.accounts.config.address())?;
+            (*config_data.mint_x(), *config_data.mint_y())
+        };
+
+        let (canonical_pool_key, canonical_pool_bump) = derive_canonical_pool(&mint_x, &mint_y);
+        if canonical_pool_key.ne(self.accounts.canonical_pool.address()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let bump = [canonical_pool_bump];
+
+        // Replace the pool an earlier call named
+        if self.accounts.canonical_pool.owned_by(&crate::ID) {
+            CanonicalPool::load_mut(self.accounts.canonical_pool)?
+                .set_inner(*self.accounts.config.address(), bump);
+            return Ok(());
+        }
+
+        let canonical_pool_seeds = [
+            Seed::from(CanonicalPool::SEED),
+            Seed::from(mint_x.as_array()),
+            Seed::from(mint_y.as_array()),
+            Seed::from(&bump),
+        ];
+        CanonicalPoolAccount::init(
+            self.accounts.authority,
+            self.accounts.canonical_pool,
+            &canonical_pool_seeds,
+        )?;
+
+        CanonicalPool::init(
+            self.accounts.canonical_pool,
+            *self.accounts.config.address(),
+            bump,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+    use pinocchio::Address;
+
+    use super::*;
+
+    const MINT_X: Address = Address::new_from_array([2; 32]);
+    const MINT_Y: Address = Address::new_from_array([3; 32]);
+
+    fn config(seed: u64) -> TestAccount {
+        let (address, bump) = derive_config(seed, &MINT_X, &MINT_Y);
+        let mut data = [0u8; Config::LEN];
+        // SAFETY: the buffer is exactly one config long
+        unsafe { Config::from_bytes_unchecked_mut(&mut data) }
+            .set_inner(seed, Address::default(), MINT_X, MINT_Y, 30, [bump])
+            .unwrap();
+        TestAccount::new(address, crate::ID, &data)
+    }
+
+    /// A canonical pool naming the pool of `seed`.
+    fn canonical_pool(seed: u64) -> TestAccount {
+        let (address, bump) = derive_canonical_pool(&MINT_X, &MINT_Y);
+        let mut account = TestAccount::new(address, crate::ID, &[0; CanonicalPool::LEN]);
+        let config_key = derive_config(seed, &MINT_X, &MINT_Y).0;
+        CanonicalPool::init(&account.view(), config_key, [bump]).unwrap();
+        account
+    }
+
+    fn set_canonical_pool(
+        signer: &Address,
+        upgrade_authority: &Address,
+        config: &mut TestAccount,
+        canonical_pool: &mut TestAccount,
+    ) -> ProgramResult {
+        let mut authority = TestAccount::empty(*signer).signer();
+        let mut program_data = TestAccount::program_data(&crate::ID, Some(upgrade_authority));
+        let mut system_program = TestAccount::empty(pinocchio_system::ID);
+
+        let accounts = [
+            authority.view(),
+            program_data.view(),
+            config.view(),
+            canonical_pool.view(),
+            system_program.view(),
+        ];
+        SetCanonicalPool::try_from(&accounts[..])?.process()
+    }
+
+    #[test]
+    fn replaces_the_canonical_pool() {
+        let upgrade_authority = Address::new_from_array([1; 32]);
+        let mut config = config(2);
+        let mut canonical_pool = canonical_pool(1);
+
+        assert_eq!(
+            set_canonical_pool(
+                &upgrade_authority,
+                &upgrade_authority,
+                &mut config,
+                &mut canonical_pool
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            CanonicalPool::load(&canonical_pool.view())
+                .unwrap()
+                .config(),
+            config.view().address()
+        );
+    }
+
+    #[test]
+    fn rejects_anyone_but_the_upgrade_authority() {
+        let upgrade_authority = Address::new_from_array([1; 32]);
+        let squatter = Address::new_from_array([9; 32]);
+        let mut canonical_pool = canonical_pool(1);
+
+        assert_eq!(
+            set_canonical_pool(
+                &squatter,
+                &upgrade_authority,
+                &mut config(2),
+                &mut canonical_pool
+            ),
+            Err(ProgramError::IncorrectAuthority)
+        );
+        assert_eq!(
+            CanonicalPool::load(&canonical_pool.view())
+                .unwrap()
+                .config(),
+            &derive_config(1, &MINT_X, &MINT_Y).0
+        );
+    }
+
+    #[test]
+    fn rejects_a_canonical_pool_of_another_pair() {
+        let upgrade_authority = Address::new_from_array([1; 32]);
+        let mut other_pair = TestAccount::new(
+            Address::new_from_array([8; 32]),
+            crate::ID,
+            &[0; CanonicalPool::LEN],
+        );
+
+        assert_eq!(
+            set_canonical_pool(
+                &upgrade_authority,
+                &upgrade_authority,
+                &mut config(2),
+                &mut other_pair
+            ),
+            Err(ProgramError::InvalidSeeds)
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::utils::{ConfigAccount, DataAccount, SignerAccount};
+use crate::state::*;
+
+pub struct SetFlagsAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub config: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for SetFlagsAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(authority)?;
+        ConfigAccount::check(config)?;
+
+        Ok(Self { authority, config })
+    }
+}
+
+pub struct SetFlagsInstructionData {
+    pub flags: u8,
+}
+
+impl<'a> TryFrom<&'a [u8]> for SetFlagsInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        let [flags] = data else {
+            return Err(ProgramError::InvalidInstructionData);
+        };
+
+        if flags & !AmmState::ALL_FLAGS != 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self { flags: *flags })
+    }
+}
+
+/// Switch swaps and deposits on or off independently. Withdrawals always stay on, so LPs can
+/// leave whatever the authority does.
+pub struct SetFlags<'a> {
+    pub accounts: SetFlagsAccounts<'a>,
+    pub instruction_data: SetFlagsInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for SetFlags<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = SetFlagsAccounts::try_from(accounts)?;
+        let instruction_data = SetFlagsInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> SetFlags<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &20;
+
+    pub fn process(&self) -> ProgramResult {
+        let mut config_data = Config::load_mut(self.accounts.config)?;
+        config_data.require_authority(self.accounts.authority.address())?;
+
+        // Turning withdrawals back on would reopen an emergency pause without its timelock
+        if config_data.paused_at() != 0 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        config_data.set_flags(self.instruction_data.flags | AmmState::WITHDRAWALS_ENABLED)
+    }
+}
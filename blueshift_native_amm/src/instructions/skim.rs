@@ -0,0 +1,134 @@
+use pinocchio::cpi::Signer;
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::utils::*;
+use crate::state::*;
+
+pub struct SkimAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub config: &'a AccountView,
+    pub vault_x: &'a AccountView,
+    pub vault_y: &'a AccountView,
+    pub authority_x_ata: &'a AccountView,
+    pub authority_y_ata: &'a AccountView,
+    pub token_program_x: &'a AccountView,
+    pub token_program_y: &'a AccountView,
+    pub mint_x: &'a AccountView,
+    pub mint_y: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for SkimAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, config, vault_x, vault_y, authority_x_ata, authority_y_ata, token_program_x, token_program_y, mint_x, mint_y] =
+            accounts
+        else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(authority)?;
+        TokenProgram::check(token_program_x)?;
+        TokenProgram::check(token_program_y)?;
+        ConfigAccount::check(config)?;
+
+        Ok(Self {
+            authority,
+            config,
+            vault_x,
+            vault_y,
+            authority_x_ata,
+            authority_y_ata,
+            token_program_x,
+            token_program_y,
+            mint_x,
+            mint_y,
+        })
+    }
+}
+
+/// Send whatever the vaults hold above the reserves recorded by the last instruction, tokens
+/// transferred straight to them, to the pool authority. `SyncReserves` keeps them for the LPs instead.
+pub struct Skim<'a> {
+    pub accounts: SkimAccounts<'a>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for Skim<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let accounts = SkimAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> Skim<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &19;
+    /// Pool flags the instruction needs.
+    pub const REQUIRED_FLAGS: u8 = AmmState::WITHDRAWALS_ENABLED;
+
+    pub fn process(&self) -> ProgramResult {
+        let config_data = Config::load(self.accounts.config)?;
+        config_data.require_address(self.accounts.config.address())?;
+        config_data.require_authority(self.accounts.authority.address())?;
+
+        // A paused pool is being refilled to its recorded reserves, nothing above them is stray
+        config_data.require_flags(Self::REQUIRED_FLAGS)?;
+
+        config_data.require_mints(
+            self.accounts.mint_x.address(),
+            self.accounts.mint_y.address(),
+        )?;
+
+        config_data.require_vaults(
+            self.accounts.vault_x.address(),
+            self.accounts.vault_y.address(),
+        )?;
+        config_data.require_token_programs(
+            self.accounts.token_program_x.address(),
+            self.accounts.token_program_y.address(),
+        )?;
+
+        // The excess may go to any account of the right mint, not only the authority's own ATAs
+        if TokenInterface::mint(self.accounts.authority_x_ata)?.ne(config_data.mint_x())
+            || TokenInterface::mint(self.accounts.authority_y_ata)?.ne(config_data.mint_y())
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let config_seeds = config_data.signer_seeds();
+        let signer_seeds = [Signer::from(&config_seeds)];
+
+        // The recorded reserves already hold the accrued protocol fees, only the excess moves
+        for (vault, mint, destination, reserve) in [
+            (
+                self.accounts.vault_x,
+                self.accounts.mint_x,
+                self.accounts.authority_x_ata,
+                config_data.reserve_x(),
+            ),
+            (
+                self.accounts.vault_y,
+                self.accounts.mint_y,
+                self.accounts.authority_y_ata,
+                config_data.reserve_y(),
+            ),
+        ] {
+            let excess = TokenInterface::amount(vault)?.saturating_sub(reserve);
+            if excess == 0 {
+                continue;
+            }
+            TokenInterface::transfer_checked(
+                vault,
+                mint,
+                destination,
+                self.accounts.config,
+                excess,
+                &signer_seeds,
+            )?;
+        }
+
+        Ok(())
+    }
+}
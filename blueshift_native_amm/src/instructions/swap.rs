@@ -1,14 +1,125 @@
 use core::mem::size_of;
 
 use constant_product_curve::{ConstantProduct, LiquidityPair};
-use pinocchio::cpi::{Seed, Signer};
+use pinocchio::cpi::Signer;
 use pinocchio::{error::ProgramError, AccountView, ProgramResult};
-use pinocchio_token::{instructions::Transfer, state::TokenAccount};
 
-use super::utils::{AssociatedTokenAccount, ConfigAccount, DataAccount, SignerAccount};
+use super::utils::{
+    ConfigAccount, DataAccount, Expiration, MintInterface, SignerAccount, TokenInterface,
+    UserTokenAccount,
+};
+use crate::errors::AmmError;
+use crate::events::SwapEvent;
 use crate::state::Config;
 use crate::AmmState;
 
+/// Input that buys at least `out` from the curve, once the protocol fee and the LP fee are
+/// taken off it. Every step rounds up, against the swapper.
+pub fn input_for_output(
+    reserve_in: u64,
+    reserve_out: u64,
+    fee: u16,
+    protocol_fee_bps: u16,
+    out: u64,
+) -> Result<u64, ProgramError> {
+    if out >= reserve_out {
+        return Err(AmmError::SwapDrainsVault.into());
+    }
+
+    // Inverse of out = reserve_out * net / (reserve_in + net). `net` can exceed a `u64` on a
+    // lopsided pool, so the fee grossing up is checked too
+    let net = (reserve_in as u128 * out as u128).div_ceil((reserve_out - out) as u128);
+    let curve_amount = net
+        .checked_mul(10_000)
+        .ok_or(AmmError::MathOverflow)?
+        .div_ceil(10_000 - fee as u128);
+    let amount = curve_amount
+        .checked_mul(10_000)
+        .ok_or(AmmError::MathOverflow)?
+        .div_ceil(10_000 - protocol_fee_bps as u128);
+
+    u64::try_from(amount).map_err(|_| AmmError::MathOverflow.into())
+}
+
+/// Reject a curve result that shrinks the constant product, checking
+/// `(reserve_in + deposit) * (reserve_out - withdraw) >= reserve_in * reserve_out`. Rounding
+/// must favour the pool, output floored and input ceiled. This is asserted instead of trusted
+/// to the curve, since rounding the other way leaks value one base unit per swap.
+pub fn require_k_preserved(
+    reserve_in: u64,
+    reserve_out: u64,
+    deposit: u64,
+    withdraw: u64,
+) -> ProgramResult {
+    let reserve_in_after = reserve_in
+        .checked_add(deposit)
+        .ok_or(AmmError::MathOverflow)?;
+    let reserve_out_after = reserve_out
+        .checked_sub(withdraw)
+        .ok_or(AmmError::SwapDrainsVault)?;
+
+    // Both sides are products of two `u64`, which always fit a `u128`
+    if (reserve_in_after as u128 * reserve_out_after as u128)
+        < (reserve_in as u128 * reserve_out as u128)
+    {
+        return Err(AmmError::CurveError.into());
+    }
+    Ok(())
+}
+
+/// Protocol fee, LP fee and curve output of swapping `amount` of X (or Y when `is_x` is false) into
+/// the pool, failing below `min`. The protocol's cut of the input never reaches the curve.
+pub fn swap_output(
+    config: &Config,
+    reserve_x: u64,
+    reserve_y: u64,
+    supply: u64,
+    is_x: bool,
+    amount: u64,
+    min: u64,
+) -> Result<(u64, u64, u64), ProgramError> {
+    let protocol_fee = (amount as u128 * config.protocol_fee_bps() as u128 / 10_000) as u64;
+    let curve_amount = amount - protocol_fee;
+    if curve_amount == 0 {
+        return Err(AmmError::ZeroAmount.into());
+    }
+
+    // Swap Calculations
+    let mut curve = ConstantProduct::init(reserve_x, reserve_y, supply, config.fee(), None)
+        .map_err(AmmError::from)?;
+
+    let (p, reserve_in, reserve_out) = match is_x {
+        true => (LiquidityPair::X, reserve_x, reserve_y),
+        false => (LiquidityPair::Y, reserve_y, reserve_x),
+    };
+
+    let swap_result = curve.swap(p, curve_amount, min).map_err(AmmError::from)?;
+
+    // Check for correct values
+    if swap_result.deposit == 0 || swap_result.withdraw == 0 {
+        return Err(AmmError::CurveError.into());
+    }
+
+    // The curve prices the whole net input, it must not leave any of it unaccounted for
+    if swap_result.deposit != curve_amount {
+        return Err(AmmError::CurveError.into());
+    }
+
+    // The output vault must cover the curve result and keep at least one base unit, an empty
+    // reserve leaves nothing to price the next swap with
+    if swap_result.withdraw >= reserve_out {
+        return Err(AmmError::SwapDrainsVault.into());
+    }
+    require_k_preserved(
+        reserve_in,
+        reserve_out,
+        swap_result.deposit,
+        swap_result.withdraw,
+    )?;
+
+    Ok((protocol_fee, swap_result.fee, swap_result.withdraw))
+}
+
 pub struct SwapAccounts<'a> {
     pub user: &'a AccountView,
     pub user_x_ata: &'a AccountView,
@@ -16,20 +127,26 @@ pub struct SwapAccounts<'a> {
     pub vault_x: &'a AccountView,
     pub vault_y: &'a AccountView,
     pub config: &'a AccountView,
-    pub token_program: &'a AccountView,
+    pub token_program_x: &'a AccountView,
+    pub token_program_y: &'a AccountView,
+    pub mint_lp: &'a AccountView,
+    pub mint_x: &'a AccountView,
+    pub mint_y: &'a AccountView,
 }
 
 impl<'a> TryFrom<&'a [AccountView]> for SwapAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
-        let [user, user_x_ata, user_y_ata, vault_x, vault_y, config, token_program] = accounts
+        let [user, user_x_ata, user_y_ata, vault_x, vault_y, config, token_program_x, token_program_y, mint_lp, mint_x, mint_y] =
+            accounts
         else {
             return Err(ProgramError::NotEnoughAccountKeys);
         };
 
         SignerAccount::check(user)?;
         ConfigAccount::check(config)?;
+        MintInterface::check(mint_lp)?;
 
         Ok(Self {
             user,
@@ -38,7 +155,11 @@ impl<'a> TryFrom<&'a [AccountView]> for SwapAccounts<'a> {
             vault_x,
             vault_y,
             config,
-            token_program,
+            token_program_x,
+            token_program_y,
+            mint_lp,
+            mint_x,
+            mint_y,
         })
     }
 }
@@ -59,7 +180,18 @@ impl<'a> TryFrom<&'a [u8]> for SwapInstructionData {
             return Err(ProgramError::InvalidInstructionData);
         }
 
-        Ok(unsafe { (data.as_ptr() as *const Self).read() })
+        // Any byte other than 0 or 1 is not a valid `bool`
+        if data[0] > 1 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let instruction_data = unsafe { (data.as_ptr() as *const Self).read() };
+        let amount = instruction_data.amount;
+        if amount == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(instruction_data)
     }
 }
 
@@ -84,113 +216,282 @@ impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for Swap<'a> {
 }
 impl<'a> Swap<'a> {
     pub const DISCRIMINATOR: &'a u8 = &3;
+    /// Pool flags the instruction needs.
+    pub const REQUIRED_FLAGS: u8 = AmmState::SWAPS_ENABLED;
 
     pub fn process(&mut self) -> ProgramResult {
+        self.execute(false)
+    }
+
+    /// Run the swap. With `exact_out` the instruction's `amount` is the most the user pays and
+    /// `min` the exact amount they receive.
+    pub(crate) fn execute(&mut self, exact_out: bool) -> ProgramResult {
+        // Copy out of the packed instruction data, never reference its fields
+        let is_x = self.instruction_data.is_x;
+        let amount = self.instruction_data.amount;
+        let min = self.instruction_data.min;
+        let expiration = self.instruction_data.expiration;
+
+        Expiration::check(expiration)?;
+
+        ConfigAccount::update_price_cumulative(self.accounts.config)?;
         let config_data = Config::load(self.accounts.config)?;
-        AssociatedTokenAccount::check(
+        config_data.require_address(self.accounts.config.address())?;
+        ConfigAccount::check_vaults(
+            self.accounts.config,
+            &config_data,
             self.accounts.vault_x,
-            self.accounts.config.address(),
-            config_data.mint_x(),
-            self.accounts.token_program.address(),
-        )?;
-        AssociatedTokenAccount::check(
             self.accounts.vault_y,
-            self.accounts.config.address(),
-            config_data.mint_y(),
-            self.accounts.token_program.address(),
         )?;
-        AssociatedTokenAccount::check(
+        config_data.require_token_programs(
+            self.accounts.token_program_x.address(),
+            self.accounts.token_program_y.address(),
+        )?;
+        UserTokenAccount::check(
             self.accounts.user_x_ata,
             self.accounts.user.address(),
             config_data.mint_x(),
-            self.accounts.token_program.address(),
+            self.accounts.token_program_x.address(),
         )?;
-        AssociatedTokenAccount::check(
+        UserTokenAccount::check(
             self.accounts.user_y_ata,
             self.accounts.user.address(),
             config_data.mint_y(),
-            self.accounts.token_program.address(),
+            self.accounts.token_program_y.address(),
         )?;
 
-        if config_data.state() != AmmState::Initialized as u8 {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        config_data.require_flags(Self::REQUIRED_FLAGS)?;
+
+        config_data.require_mints(
+            self.accounts.mint_x.address(),
+            self.accounts.mint_y.address(),
+        )?;
+
+        config_data.require_mint_lp(self.accounts.mint_lp.address())?;
 
         // Deserialize the token accounts
-        let vault_x = unsafe { TokenAccount::from_account_view_unchecked(self.accounts.vault_x)? };
-        let vault_y = unsafe { TokenAccount::from_account_view_unchecked(self.accounts.vault_y)? };
-
-        // Swap Calculations
-        let mut curve = ConstantProduct::init(
-            vault_x.amount(),
-            vault_y.amount(),
-            vault_x.amount(),
-            config_data.fee(),
-            None,
-        )
-        .map_err(|_| ProgramError::Custom(1))?;
-
-        let p = match self.instruction_data.is_x {
-            true => LiquidityPair::X,
-            false => LiquidityPair::Y,
+        let mint_lp = MintInterface::read(self.accounts.mint_lp)?;
+
+        // Priced at the recorded reserves, not the vault balances, so tokens sent straight to
+        // a vault can't move the price. Fees already in the reserves are not priced
+        let (reserve_x, reserve_y) = config_data.recorded_pool_reserves();
+        let (mint_in, mint_out, user_in, user_out, vault_in, vault_out) = match is_x {
+            // User deposits X, receives Y
+            true => (
+                self.accounts.mint_x,
+                self.accounts.mint_y,
+                self.accounts.user_x_ata,
+                self.accounts.user_y_ata,
+                self.accounts.vault_x,
+                self.accounts.vault_y,
+            ),
+            // User deposits Y, receives X
+            false => (
+                self.accounts.mint_y,
+                self.accounts.mint_x,
+                self.accounts.user_y_ata,
+                self.accounts.user_x_ata,
+                self.accounts.vault_y,
+                self.accounts.vault_x,
+            ),
         };
 
-        let swap_result = curve
-            .swap(p, self.instruction_data.amount, self.instruction_data.min)
-            .map_err(|_| ProgramError::Custom(1))?;
+        let (reserve_in, reserve_out) = match is_x {
+            true => (reserve_x, reserve_y),
+            false => (reserve_y, reserve_x),
+        };
 
-        // Check for correct values
-        if swap_result.deposit == 0 || swap_result.withdraw == 0 {
-            return Err(ProgramError::InvalidArgument);
-        }
+        // Exact-out swaps pay what the output costs, bounded by `amount`
+        let amount = match exact_out {
+            true => {
+                let amount_in = input_for_output(
+                    reserve_in,
+                    reserve_out,
+                    config_data.fee(),
+                    config_data.protocol_fee_bps(),
+                    min,
+                )?;
+                if amount_in > amount {
+                    return Err(AmmError::SlippageExceeded.into());
+                }
+                amount_in
+            }
+            false => amount,
+        };
 
-        let config_seed_binding = config_data.seed().to_le_bytes();
-        let config_bump_binding = config_data.config_bump();
-        let config_seeds = [
-            Seed::from(b"config"),
-            Seed::from(&config_seed_binding),
-            Seed::from(config_data.mint_x().as_array()),
-            Seed::from(config_data.mint_y().as_array()),
-            Seed::from(&config_bump_binding),
-        ];
+        // A transfer fee on the input mint delivers less than was sent, so the curve is fed
+        // what the vault actually received
+        let balance_in = TokenInterface::amount(vault_in)?;
+        TokenInterface::transfer_checked(
+            user_in,
+            mint_in,
+            vault_in,
+            self.accounts.user,
+            amount,
+            &[],
+        )?;
+        let received = TokenInterface::amount(vault_in)?
+            .checked_sub(balance_in)
+            .ok_or(AmmError::MathOverflow)?;
+
+        let (protocol_fee, lp_fee, swap_withdraw) = swap_output(
+            &config_data,
+            reserve_x,
+            reserve_y,
+            mint_lp.supply(),
+            is_x,
+            received,
+            min,
+        )?;
+
+        // The curve pays at least `min`, an exact-out swap keeps any surplus in the pool
+        let withdraw = match exact_out {
+            true => min,
+            false => swap_withdraw,
+        };
+
+        let config_seeds = config_data.signer_seeds();
         let signer_seeds = [Signer::from(&config_seeds)];
 
-        if self.instruction_data.is_x {
-            // User deposits X, receives Y
-            Transfer {
-                from: self.accounts.user_x_ata,
-                to: self.accounts.vault_x,
-                authority: self.accounts.user,
-                amount: swap_result.deposit,
-            }
-            .invoke()?;
+        TokenInterface::transfer_checked(
+            vault_out,
+            mint_out,
+            user_out,
+            self.accounts.config,
+            withdraw,
+            &signer_seeds,
+        )?;
 
-            Transfer {
-                from: self.accounts.vault_y,
-                to: self.accounts.user_y_ata,
-                authority: self.accounts.config,
-                amount: swap_result.withdraw,
-            }
-            .invoke_signed(&signer_seeds)?;
-        } else {
-            // User deposits Y, receives X
-            Transfer {
-                from: self.accounts.user_y_ata,
-                to: self.accounts.vault_y,
-                authority: self.accounts.user,
-                amount: swap_result.deposit,
+        drop(config_data);
+        {
+            let mut config_data = Config::load_mut(self.accounts.config)?;
+            match is_x {
+                true => {
+                    config_data.accrue_protocol_fees(protocol_fee, 0)?;
+                    config_data.credit_reserves(received, 0)?;
+                    config_data.debit_reserves(0, withdraw)?;
+                }
+                false => {
+                    config_data.accrue_protocol_fees(0, protocol_fee)?;
+                    config_data.credit_reserves(0, received)?;
+                    config_data.debit_reserves(withdraw, 0)?;
+                }
             }
-            .invoke()?;
+        }
 
-            Transfer {
-                from: self.accounts.vault_x,
-                to: self.accounts.user_x_ata,
-                authority: self.accounts.config,
-                amount: swap_result.withdraw,
-            }
-            .invoke_signed(&signer_seeds)?;
+        let (reserve_x_after, reserve_y_after) =
+            Config::load(self.accounts.config)?.recorded_pool_reserves();
+        SwapEvent {
+            user: self.accounts.user.address(),
+            is_x,
+            amount_in: received,
+            amount_out: withdraw,
+            fee: protocol_fee
+                .checked_add(lp_fee)
+                .ok_or(AmmError::MathOverflow)?,
+            reserve_x_after,
+            reserve_y_after,
         }
+        .emit();
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pinocchio::Address;
+
+    use super::*;
+
+    const RESERVE: u64 = 1_000_000;
+
+    /// Config bytes of a pool charging `fee` to LPs and `protocol_fee_bps` to the protocol.
+    fn config(fee: u16, protocol_fee_bps: u16) -> [u8; Config::LEN] {
+        let mut data = [0u8; Config::LEN];
+        // SAFETY: the buffer is exactly one config long
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut data) };
+        let mints = (
+            Address::new_from_array([2; 32]),
+            Address::new_from_array([3; 32]),
+        );
+        config
+            .set_inner(1, Address::default(), mints.0, mints.1, fee, [255])
+            .unwrap();
+        config.set_fees(fee, protocol_fee_bps).unwrap();
+        data
+    }
+
+    fn swap(
+        config: &[u8; Config::LEN],
+        amount: u64,
+        min: u64,
+    ) -> Result<(u64, u64, u64), ProgramError> {
+        let config = Config::from_bytes(config).unwrap();
+        swap_output(config, RESERVE, RESERVE, RESERVE, true, amount, min)
+    }
+
+    #[test]
+    fn takes_the_protocol_fee_before_the_curve() {
+        let (protocol_fee, lp_fee, out) = swap(&config(30, 100), 10_000, 0).unwrap();
+        assert_eq!(protocol_fee, 100);
+
+        // The curve only ever sees what the protocol left of the input
+        assert_eq!(swap(&config(30, 0), 9_900, 0), Ok((0, lp_fee, out)));
+    }
+
+    #[test]
+    fn rejects_an_output_below_min() {
+        let (_, _, out) = swap(&config(30, 0), 10_000, 0).unwrap();
+
+        assert!(swap(&config(30, 0), 10_000, out).is_ok());
+        assert_eq!(
+            swap(&config(30, 0), 10_000, out + 1),
+            Err(AmmError::SlippageExceeded.into())
+        );
+    }
+
+    #[test]
+    fn rejects_a_swap_with_nothing_to_price() {
+        assert_eq!(swap(&config(30, 0), 0, 0), Err(AmmError::ZeroAmount.into()));
+
+        // One base unit in cannot buy a fraction of the single unit out
+        let config = config(0, 0);
+        let config = Config::from_bytes(&config).unwrap();
+        assert_eq!(
+            swap_output(config, RESERVE, 1, RESERVE, true, 1, 0),
+            Err(AmmError::CurveError.into())
+        );
+    }
+
+    #[test]
+    fn requires_the_constant_product_to_hold() {
+        // 2000 * 500 is exactly the 1000 * 1000 the pool started at
+        assert_eq!(require_k_preserved(1_000, 1_000, 1_000, 500), Ok(()));
+        assert_eq!(
+            require_k_preserved(1_000, 1_000, 1_000, 501),
+            Err(AmmError::CurveError.into())
+        );
+        assert_eq!(
+            require_k_preserved(1_000, 1_000, 1_000, 1_001),
+            Err(AmmError::SwapDrainsVault.into())
+        );
+    }
+
+    #[test]
+    fn prices_a_fee_mint_input_at_what_arrived() {
+        // A 10% transfer fee delivers 90_000 of the 100_000 sent
+        let (sent, received) = (100_000, 90_000);
+        let config = config(0, 0);
+        let (_, _, gross) = swap(&config, sent, 0).unwrap();
+        let (_, _, net) = swap(&config, received, 0).unwrap();
+
+        // Pricing what was sent pays out of the LPs' constant product
+        assert!(net < gross);
+        assert_eq!(
+            require_k_preserved(RESERVE, RESERVE, received, gross),
+            Err(AmmError::CurveError.into())
+        );
+        assert_eq!(require_k_preserved(RESERVE, RESERVE, received, net), Ok(()));
+    }
+}
@@ -0,0 +1,73 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::swap::{Swap, SwapAccounts, SwapInstructionData};
+
+#[repr(C, packed)]
+pub struct SwapExactOutInstructionData {
+    pub is_x: bool,
+    pub out: u64,
+    pub max_in: u64,
+    pub expiration: i64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for SwapExactOutInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<SwapExactOutInstructionData>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        // Any byte other than 0 or 1 is not a valid `bool`
+        if data[0] > 1 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let instruction_data = unsafe { (data.as_ptr() as *const Self).read_unaligned() };
+        let (out, max_in) = (instruction_data.out, instruction_data.max_in);
+        if out == 0 || max_in == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(instruction_data)
+    }
+}
+
+/// Swap for exactly `out` of the other token, paying the input the curve asks for rounded up,
+/// at most `max_in`.
+pub struct SwapExactOut<'a> {
+    pub accounts: SwapAccounts<'a>,
+    pub instruction_data: SwapExactOutInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for SwapExactOut<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = SwapAccounts::try_from(accounts)?;
+        let instruction_data = SwapExactOutInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> SwapExactOut<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &15;
+
+    pub fn process(self) -> ProgramResult {
+        // `Swap` reads `amount` as the input bound and `min` as the exact output
+        Swap {
+            accounts: self.accounts,
+            instruction_data: SwapInstructionData {
+                is_x: self.instruction_data.is_x,
+                amount: self.instruction_data.max_in,
+                min: self.instruction_data.out,
+                expiration: self.instruction_data.expiration,
+            },
+        }
+        .execute(true)
+    }
+}
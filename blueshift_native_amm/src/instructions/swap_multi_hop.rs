@@ -0,0 +1,160 @@
+use core::mem::size_of;
+
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::swap::{Swap, SwapAccounts, SwapInstructionData};
+use super::utils::{ConfigAccount, DataAccount, MintInterface, SignerAccount, TokenInterface};
+use crate::errors::AmmError;
+
+/// Accounts of one leg, `SwapAccounts` without the user.
+const LEG_ACCOUNTS_LEN: usize = 10;
+
+#[repr(C, packed)]
+pub struct SwapMultiHopInstructionData {
+    pub first_is_x: bool,
+    pub second_is_x: bool,
+    pub amount: u64,
+    pub min_out: u64,
+    pub expiration: i64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for SwapMultiHopInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<SwapMultiHopInstructionData>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        // Any byte other than 0 or 1 is not a valid `bool`
+        if data[0] > 1 || data[1] > 1 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let instruction_data = unsafe { (data.as_ptr() as *const Self).read_unaligned() };
+        let amount = instruction_data.amount;
+        if amount == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(instruction_data)
+    }
+}
+
+/// Swap through two pools sharing a token, A to B in the first and B to C in the second.
+/// The intermediate B passes through the user's B account and only the final output is
+/// checked against `min_out`.
+pub struct SwapMultiHop<'a> {
+    pub first: SwapAccounts<'a>,
+    pub second: SwapAccounts<'a>,
+    pub instruction_data: SwapMultiHopInstructionData,
+}
+
+/// Parse one leg's accounts, with the same checks `SwapAccounts` runs.
+fn leg_accounts<'a>(
+    user: &'a AccountView,
+    accounts: &'a [AccountView],
+) -> Result<SwapAccounts<'a>, ProgramError> {
+    let [user_x_ata, user_y_ata, vault_x, vault_y, config, token_program_x, token_program_y, mint_lp, mint_x, mint_y] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    ConfigAccount::check(config)?;
+    MintInterface::check(mint_lp)?;
+
+    Ok(SwapAccounts {
+        user,
+        user_x_ata,
+        user_y_ata,
+        vault_x,
+        vault_y,
+        config,
+        token_program_x,
+        token_program_y,
+        mint_lp,
+        mint_x,
+        mint_y,
+    })
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for SwapMultiHop<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let [user, legs @ ..] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        if legs.len() != LEG_ACCOUNTS_LEN * 2 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        SignerAccount::check(user)?;
+
+        let (first, second) = legs.split_at(LEG_ACCOUNTS_LEN);
+        let first = leg_accounts(user, first)?;
+        let second = leg_accounts(user, second)?;
+        let instruction_data = SwapMultiHopInstructionData::try_from(data)?;
+
+        Ok(Self {
+            first,
+            second,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> SwapMultiHop<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &22;
+
+    pub fn process(self) -> ProgramResult {
+        // Copy out of the packed instruction data, never reference its fields
+        let first_is_x = self.instruction_data.first_is_x;
+        let second_is_x = self.instruction_data.second_is_x;
+        let amount = self.instruction_data.amount;
+        let min_out = self.instruction_data.min_out;
+        let expiration = self.instruction_data.expiration;
+
+        // The first leg's output is the second leg's input, held in the same user account
+        let (first_out_mint, first_out_ata) = match first_is_x {
+            true => (self.first.mint_y, self.first.user_y_ata),
+            false => (self.first.mint_x, self.first.user_x_ata),
+        };
+        let (second_in_mint, second_in_ata) = match second_is_x {
+            true => (self.second.mint_x, self.second.user_x_ata),
+            false => (self.second.mint_y, self.second.user_y_ata),
+        };
+        if first_out_mint.address().ne(second_in_mint.address())
+            || first_out_ata.address().ne(second_in_ata.address())
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Only the B the user actually received moves on, net of any transfer fee
+        let balance = TokenInterface::amount(first_out_ata)?;
+        Swap {
+            accounts: self.first,
+            instruction_data: SwapInstructionData {
+                is_x: first_is_x,
+                amount,
+                min: 0,
+                expiration,
+            },
+        }
+        .process()?;
+        let intermediate = TokenInterface::amount(first_out_ata)?
+            .checked_sub(balance)
+            .ok_or(AmmError::MathOverflow)?;
+
+        Swap {
+            accounts: self.second,
+            instruction_data: SwapInstructionData {
+                is_x: second_is_x,
+                amount: intermediate,
+                min: min_out,
+                expiration,
+            },
+        }
+        .process()
+    }
+}
@@ -0,0 +1,75 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::utils::{ConfigAccount, DataAccount, TokenInterface};
+use crate::state::*;
+
+pub struct SyncReservesAccounts<'a> {
+    pub config: &'a AccountView,
+    pub vault_x: &'a AccountView,
+    pub vault_y: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for SyncReservesAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [config, vault_x, vault_y] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        ConfigAccount::check(config)?;
+
+        Ok(Self {
+            config,
+            vault_x,
+            vault_y,
+        })
+    }
+}
+
+/// Permissionless: record the vault balances as the pool reserves, so tokens sent straight to
+/// a vault count as a donation to the LPs.
+pub struct SyncReserves<'a> {
+    pub accounts: SyncReservesAccounts<'a>,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for SyncReserves<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let accounts = SyncReservesAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> SyncReserves<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &17;
+
+    pub fn process(&self) -> ProgramResult {
+        {
+            let config_data = Config::load(self.accounts.config)?;
+            config_data.require_flags(AmmState::WITHDRAWALS_ENABLED)?;
+
+            config_data.require_vaults(
+                self.accounts.vault_x.address(),
+                self.accounts.vault_y.address(),
+            )?;
+
+            // Recording a drained vault would hide the shortfall `Pause` is there to catch
+            let vault_x = TokenInterface::read(self.accounts.vault_x)?;
+            let vault_y = TokenInterface::read(self.accounts.vault_y)?;
+            if config_data.is_short(vault_x.amount(), vault_y.amount()) {
+                return Err(ProgramError::InsufficientFunds);
+            }
+        }
+
+        // The time since the last instruction is still priced at the old reserves
+        ConfigAccount::update_price_cumulative(self.accounts.config)?;
+        ConfigAccount::sync_reserves(
+            self.accounts.config,
+            self.accounts.vault_x,
+            self.accounts.vault_y,
+        )
+    }
+}
@@ -0,0 +1,75 @@
+use pinocchio::{error::ProgramError, AccountView, Address, ProgramResult};
+
+use super::utils::{ConfigAccount, DataAccount, SignerAccount};
+use crate::state::*;
+
+pub struct UpdateAuthorityAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub config: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for UpdateAuthorityAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(authority)?;
+        ConfigAccount::check(config)?;
+
+        Ok(Self { authority, config })
+    }
+}
+
+pub struct UpdateAuthorityInstructionData {
+    pub authority: Address,
+}
+
+impl<'a> TryFrom<&'a [u8]> for UpdateAuthorityInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        let authority: [u8; 32] = data
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+        Ok(Self {
+            authority: authority.into(),
+        })
+    }
+}
+
+/// Hand the pool authority to another address, or renounce it with the zero address.
+pub struct UpdateAuthority<'a> {
+    pub accounts: UpdateAuthorityAccounts<'a>,
+    pub instruction_data: UpdateAuthorityInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for UpdateAuthority<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = UpdateAuthorityAccounts::try_from(accounts)?;
+        let instruction_data = UpdateAuthorityInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> UpdateAuthority<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &12;
+
+    pub fn process(&self) -> ProgramResult {
+        let mut config_data = Config::load_mut(self.accounts.config)?;
+        config_data.require_authority(self.accounts.authority.address())?;
+
+        config_data.set_authority(self.instruction_data.authority);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::utils::{ConfigAccount, DataAccount, SignerAccount};
+use crate::state::*;
+
+pub struct UpdateFeeAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub config: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for UpdateFeeAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(authority)?;
+        ConfigAccount::check(config)?;
+
+        Ok(Self { authority, config })
+    }
+}
+
+pub struct UpdateFeeInstructionData {
+    pub fee: u16,
+    pub protocol_fee_bps: Option<u16>,
+}
+
+impl<'a> TryFrom<&'a [u8]> for UpdateFeeInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        // The protocol fee is optional and left unchanged when omitted
+        match data.len() {
+            2 => Ok(Self {
+                fee: u16::from_le_bytes([data[0], data[1]]),
+                protocol_fee_bps: None,
+            }),
+            4 => Ok(Self {
+                fee: u16::from_le_bytes([data[0], data[1]]),
+                protocol_fee_bps: Some(u16::from_le_bytes([data[2], data[3]])),
+            }),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+
+/// Change the swap fee of a pool and optionally its protocol fee, in basis points.
+pub struct UpdateFee<'a> {
+    pub accounts: UpdateFeeAccounts<'a>,
+    pub instruction_data: UpdateFeeInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for UpdateFee<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = UpdateFeeAccounts::try_from(accounts)?;
+        let instruction_data = UpdateFeeInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> UpdateFee<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &10;
+
+    pub fn process(&self) -> ProgramResult {
+        let mut config_data = Config::load_mut(self.accounts.config)?;
+        config_data.require_authority(self.accounts.authority.address())?;
+
+        match self.instruction_data.protocol_fee_bps {
+            Some(protocol_fee_bps) => {
+                config_data.set_fees(self.instruction_data.fee, protocol_fee_bps)
+            }
+            None => config_data.set_fee(self.instruction_data.fee),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+    use pinocchio::Address;
+
+    use super::*;
+
+    const AUTHORITY: Address = Address::new_from_array([1; 32]);
+
+    /// A pool of `AUTHORITY` charging `fee` to LPs and `protocol_fee_bps` to the protocol.
+    fn config(fee: u16, protocol_fee_bps: u16) -> TestAccount {
+        let mut data = [0u8; Config::LEN];
+        // SAFETY: the buffer is exactly one config long
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut data) };
+        let mints = (
+            Address::new_from_array([2; 32]),
+            Address::new_from_array([3; 32]),
+        );
+        config
+            .set_inner(1, AUTHORITY, mints.0, mints.1, fee, [255])
+            .unwrap();
+        config.set_fees(fee, protocol_fee_bps).unwrap();
+        TestAccount::new(Address::new_from_array([4; 32]), crate::ID, &data)
+    }
+
+    /// Run `UpdateFee` with `data` and return the fees it leaves, whether it succeeds or not.
+    fn update_fee(config: &mut TestAccount, data: &[u8]) -> (ProgramResult, (u16, u16)) {
+        let mut authority = TestAccount::empty(AUTHORITY).signer();
+        let accounts = [authority.view(), config.view()];
+        let result = UpdateFee::try_from((data, &accounts[..])).and_then(|ix| ix.process());
+
+        let config = Config::from_bytes(config.data()).unwrap();
+        (result, (config.fee(), config.protocol_fee_bps()))
+    }
+
+    #[test]
+    fn updates_the_fee_and_keeps_the_protocol_share() {
+        let mut config = config(30, 5);
+        assert_eq!(
+            update_fee(&mut config, &25u16.to_le_bytes()),
+            (Ok(()), (25, 5))
+        );
+        assert_eq!(update_fee(&mut config, &[10, 0, 20, 0]), (Ok(()), (10, 20)));
+    }
+
+    #[test]
+    fn rejects_fees_summing_to_the_whole_input() {
+        let mut pool = config(30, 5);
+
+        // Each fee alone is below 10_000 bps, together they would take every swap input
+        let both = [5_000u16.to_le_bytes(), 5_000u16.to_le_bytes()].concat();
+        assert_eq!(
+            update_fee(&mut pool, &both),
+            (Err(ProgramError::InvalidAccountData), (30, 5))
+        );
+
+        let mut pool = config(30, 9_000);
+        assert_eq!(
+            update_fee(&mut pool, &1_000u16.to_le_bytes()),
+            (Err(ProgramError::InvalidAccountData), (30, 9_000))
+        );
+        assert_eq!(
+            update_fee(&mut pool, &999u16.to_le_bytes()),
+            (Ok(()), (999, 9_000))
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::utils::{ConfigAccount, DataAccount, SignerAccount};
+use crate::state::*;
+
+pub struct UpdateStateAccounts<'a> {
+    pub authority: &'a AccountView,
+    pub config: &'a AccountView,
+}
+
+impl<'a> TryFrom<&'a [AccountView]> for UpdateStateAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
+        let [authority, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        SignerAccount::check(authority)?;
+        ConfigAccount::check(config)?;
+
+        Ok(Self { authority, config })
+    }
+}
+
+pub struct UpdateStateInstructionData {
+    pub state: AmmState,
+}
+
+impl<'a> TryFrom<&'a [u8]> for UpdateStateInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        let [state] = data else {
+            return Err(ProgramError::InvalidInstructionData);
+        };
+
+        // A pool cannot be sent back to uninitialized
+        match AmmState::try_from(*state) {
+            Ok(AmmState::Uninitialized) | Err(_) => Err(ProgramError::InvalidInstructionData),
+            Ok(state) => Ok(Self { state }),
+        }
+    }
+}
+
+/// Move a pool between `Initialized`, `Disabled` and `WithdrawOnly`.
+pub struct UpdateState<'a> {
+    pub accounts: UpdateStateAccounts<'a>,
+    pub instruction_data: UpdateStateInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for UpdateState<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = UpdateStateAccounts::try_from(accounts)?;
+        let instruction_data = UpdateStateInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> UpdateState<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &11;
+
+    pub fn process(&self) -> ProgramResult {
+        let mut config_data = Config::load_mut(self.accounts.config)?;
+        config_data.require_authority(self.accounts.authority.address())?;
+
+        // An emergency pause is only reopened through `Resume` and its timelock
+        if config_data.paused_at() != 0
+            && matches!(self.instruction_data.state, AmmState::Initialized)
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        config_data.set_state(self.instruction_data.state as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+    use pinocchio::Address;
+
+    use super::*;
+    use crate::errors::AmmError;
+    use crate::instructions::{
+        ClaimFees, Deposit, DepositSingleSided, Donate, FlashSwap, Quote, Skim, Swap, Withdraw,
+        WithdrawSingle,
+    };
+
+    const AUTHORITY: Address = Address::new_from_array([1; 32]);
+
+    /// An initialized pool of `AUTHORITY`.
+    fn config() -> TestAccount {
+        let mut data = [0u8; Config::LEN];
+        // SAFETY: the buffer is exactly one config long
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut data) };
+        let mints = (
+            Address::new_from_array([2; 32]),
+            Address::new_from_array([3; 32]),
+        );
+        config
+            .set_inner(1, AUTHORITY, mints.0, mints.1, 30, [255])
+            .unwrap();
+        TestAccount::new(Address::new_from_array([4; 32]), crate::ID, &data)
+    }
+
+    fn update_state(signer: Address, config: &mut TestAccount, state: u8) -> ProgramResult {
+        let mut signer = TestAccount::empty(signer).signer();
+        let accounts = [signer.view(), config.view()];
+        UpdateState::try_from((&[state][..], &accounts[..]))?.process()
+    }
+
+    #[test]
+    fn shuts_a_pool_down_to_withdrawals_only() {
+        let mut config = config();
+        assert_eq!(
+            update_state(AUTHORITY, &mut config, AmmState::WithdrawOnly as u8),
+            Ok(())
+        );
+
+        let config = Config::from_bytes(config.data()).unwrap();
+        assert_eq!(config.state(), AmmState::WITHDRAWALS_ENABLED);
+        // LPs can still leave, and the authority still collect its fees and skim
+        for required in [
+            Withdraw::REQUIRED_FLAGS,
+            ClaimFees::REQUIRED_FLAGS,
+            Skim::REQUIRED_FLAGS,
+        ] {
+            assert_eq!(config.require_flags(required), Ok(()));
+        }
+        for required in [
+            Deposit::REQUIRED_FLAGS,
+            DepositSingleSided::REQUIRED_FLAGS,
+            Donate::REQUIRED_FLAGS,
+            Swap::REQUIRED_FLAGS,
+            FlashSwap::REQUIRED_FLAGS,
+            Quote::REQUIRED_FLAGS,
+            WithdrawSingle::REQUIRED_FLAGS,
+        ] {
+            assert_eq!(
+                config.require_flags(required),
+                Err(AmmError::PoolDisabled.into())
+            );
+        }
+    }
+
+    #[test]
+    fn only_lets_the_authority_shut_a_pool_down() {
+        let mut config = config();
+        assert_eq!(
+            update_state(
+                Address::new_from_array([8; 32]),
+                &mut config,
+                AmmState::WithdrawOnly as u8
+            ),
+            Err(ProgramError::IncorrectAuthority)
+        );
+        assert_eq!(
+            Config::from_bytes(config.data()).unwrap().state(),
+            AmmState::ALL_FLAGS
+        );
+    }
+
+    #[test]
+    fn moves_a_shut_down_pool_on_but_never_back_to_uninitialized() {
+        let mut config = config();
+        update_state(AUTHORITY, &mut config, AmmState::WithdrawOnly as u8).unwrap();
+
+        for state in [AmmState::Uninitialized as u8, 4] {
+            assert_eq!(
+                update_state(AUTHORITY, &mut config, state),
+                Err(ProgramError::InvalidInstructionData)
+            );
+        }
+        assert_eq!(
+            update_state(AUTHORITY, &mut config, AmmState::Disabled as u8),
+            Ok(())
+        );
+        assert_eq!(Config::from_bytes(config.data()).unwrap().state(), 0);
+        assert_eq!(
+            update_state(AUTHORITY, &mut config, AmmState::Initialized as u8),
+            Ok(())
+        );
+        assert_eq!(
+            Config::from_bytes(config.data()).unwrap().state(),
+            AmmState::ALL_FLAGS
+        );
+    }
+}
@@ -2,19 +2,18 @@ use core::mem::size_of;
 use pinocchio::{
     cpi::{Seed, Signer},
     error::ProgramError,
-    sysvars::{rent::Rent, Sysvar},
+    sysvars::{clock::Clock, rent::Rent, Sysvar},
     AccountView, Address, ProgramResult,
 };
-use pinocchio_associated_token_account::instructions::Create;
-use pinocchio_system::instructions::CreateAccount;
-use pinocchio_token::{instructions::InitializeMint2, state::Mint};
+use pinocchio_system::instructions::{CreateAccount, Transfer};
 use pinocchio_token_2022::ID as TOKEN_2022_PROGRAM_ID;
 
-use crate::state::Config;
+pub use blueshift_pinocchio_helpers::{
+    token_program_of, AssociatedTokenAccount, MintInterface, ProgramData, TokenInterface,
+};
 
-const TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET: usize = 165;
-const TOKEN_2022_MINT_DISCRIMINATOR: u8 = 0x01;
-const TOKEN_2022_TOKEN_ACCOUNT_DISCRIMINATOR: u8 = 0x02;
+use crate::errors::AmmError;
+use crate::state::{derive_registry, CanonicalPool, Config, Registry};
 
 pub struct SignerAccount;
 
@@ -27,6 +26,54 @@ impl SignerAccount {
     }
 }
 
+pub struct Expiration;
+
+impl Expiration {
+    /// Reject once the clock is past `expiration`. Zero means no deadline.
+    pub fn check(expiration: i64) -> ProgramResult {
+        if expiration != 0 && Clock::get()?.unix_timestamp > expiration {
+            return Err(AmmError::Expired.into());
+        }
+        Ok(())
+    }
+}
+
+pub struct TokenProgram;
+
+impl TokenProgram {
+    pub fn check(account: &AccountView) -> ProgramResult {
+        if account.address().ne(&pinocchio_token::ID)
+            && account.address().ne(&TOKEN_2022_PROGRAM_ID)
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Ok(())
+    }
+}
+
+pub struct UserTokenAccount;
+
+impl UserTokenAccount {
+    /// Check `account` is a `token_program` account holding `mint` for `owner`. Unlike an ATA
+    /// check this derives nothing, so it costs no bump search and accepts any token account the
+    /// owner holds.
+    pub fn check(
+        account: &AccountView,
+        owner: &Address,
+        mint: &Address,
+        token_program: &Address,
+    ) -> ProgramResult {
+        // The transfers go through `token_program`, so the account must belong to it too
+        if !account.owned_by(token_program) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        if TokenInterface::mint(account)?.ne(mint) || TokenInterface::owner(account)?.ne(owner) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+}
+
 pub trait DataAccount {
     type T: Sized;
 
@@ -36,6 +83,47 @@ pub trait DataAccount {
 
 pub struct ConfigAccount;
 
+impl ConfigAccount {
+    /// Bring the TWAP price accumulators up to the current time, at the reserves the previous
+    /// instruction left. Must run before the instruction moves the reserves.
+    pub fn update_price_cumulative(config: &AccountView) -> ProgramResult {
+        let now = Clock::get()?.unix_timestamp;
+        Config::load_mut(config)?.update_price_cumulative(now);
+        Ok(())
+    }
+
+    /// Reject the instruction unless `vault_x` and `vault_y` are the pool's stored vaults.
+    /// They were stored as the config's ATAs, but assert they are still held by it.
+    pub fn check_vaults(
+        config: &AccountView,
+        config_data: &Config,
+        vault_x: &AccountView,
+        vault_y: &AccountView,
+    ) -> ProgramResult {
+        config_data.require_vaults(vault_x.address(), vault_y.address())?;
+        if TokenInterface::owner(vault_x)?.ne(config.address())
+            || TokenInterface::owner(vault_y)?.ne(config.address())
+        {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        Ok(())
+    }
+
+    /// Record the vault balances as the reserves, absorbing any tokens sent straight to the
+    /// vaults. Only `SyncReserves` and `Resume` do, every other instruction moves the reserves
+    /// by what it pays in and out.
+    pub fn sync_reserves(
+        config: &AccountView,
+        vault_x: &AccountView,
+        vault_y: &AccountView,
+    ) -> ProgramResult {
+        let reserve_x = TokenInterface::read(vault_x)?.amount();
+        let reserve_y = TokenInterface::read(vault_y)?.amount();
+        Config::load_mut(config)?.set_reserves(reserve_x, reserve_y);
+        Ok(())
+    }
+}
+
 impl DataAccount for ConfigAccount {
     type T = Config;
 
@@ -58,6 +146,9 @@ impl DataAccount for ConfigAccount {
         }
         .invoke_signed(&signer)?;
 
+        // Stamp the layout version so the fresh config loads like any other
+        account.try_borrow_mut()?[0] = Config::VERSION;
+
         Ok(())
     }
 
@@ -76,164 +167,224 @@ impl DataAccount for ConfigAccount {
     }
 }
 
-pub struct MintInterface;
+pub struct CanonicalPoolAccount;
 
-impl MintInterface {
-    pub fn check(account: &AccountView) -> Result<(), ProgramError> {
-        if !account.owned_by(&TOKEN_2022_PROGRAM_ID) {
-            if !account.owned_by(&pinocchio_token::ID) {
-                return Err(ProgramError::InvalidAccountOwner);
-            } else {
-                if account.data_len().ne(&pinocchio_token::state::Mint::LEN) {
-                    return Err(ProgramError::InvalidAccountOwner);
-                }
-            }
-        } else {
-            let data = account.try_borrow()?;
+impl DataAccount for CanonicalPoolAccount {
+    type T = CanonicalPool;
 
-            if data.len().ne(&pinocchio_token::state::Mint::LEN) {
-                if data.len().le(&TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET) {
-                    return Err(ProgramError::InvalidAccountOwner);
-                }
-                if data[TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET].ne(&TOKEN_2022_MINT_DISCRIMINATOR)
-                {
-                    return Err(ProgramError::InvalidAccountOwner);
-                }
-            }
+    fn init(payer: &AccountView, account: &AccountView, seeds: &[Seed]) -> ProgramResult {
+        let space = size_of::<Self::T>();
+        let lamports = Rent::get()?.try_minimum_balance(space)?;
+
+        CreateAccount {
+            from: payer,
+            to: account,
+            lamports,
+            space: space as u64,
+            owner: &crate::ID,
+        }
+        .invoke_signed(&[Signer::from(seeds)])
+    }
+
+    fn check(account: &AccountView) -> ProgramResult {
+        if !account.owned_by(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        if account.data_len().ne(&size_of::<Self::T>()) {
+            return Err(ProgramError::InvalidAccountData);
         }
 
         Ok(())
     }
+}
 
-    pub fn init_if_need(
-        account: &AccountView,
+pub struct RegistryAccount;
+
+impl RegistryAccount {
+    /// Append `config` to the registry of its pair, creating the registry on the pair's first
+    /// pool. `payer` covers the rent of the larger account.
+    pub fn register(
         payer: &AccountView,
-        decimals: u8,
-        mint_authoriy: &Address,
-        freeze_authority: Option<&Address>,
-        signers: &[Signer],
+        registry: &AccountView,
+        config: &Address,
+        mint_x: &Address,
+        mint_y: &Address,
     ) -> ProgramResult {
-        if let Err(_) = Self::check(account) {
-            let mint_lamport = Rent::get()?.try_minimum_balance(Mint::LEN)?;
+        let (registry_key, registry_bump) = derive_registry(mint_x, mint_y);
+        if registry_key.ne(registry.address()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        if registry.owned_by(&pinocchio_system::ID) {
+            let space = Registry::space(1);
+            let bump = [registry_bump];
+            let seeds = [
+                Seed::from(Registry::SEED),
+                Seed::from(mint_x.as_array()),
+                Seed::from(mint_y.as_array()),
+                Seed::from(&bump),
+            ];
             CreateAccount {
                 from: payer,
-                to: account,
-                lamports: mint_lamport,
-                space: Mint::LEN as u64,
-                owner: &pinocchio_token::ID,
+                to: registry,
+                lamports: Rent::get()?.try_minimum_balance(space)?,
+                space: space as u64,
+                owner: &crate::ID,
             }
-            .invoke_signed(signers)?;
+            .invoke_signed(&[Signer::from(&seeds)])?;
 
-            InitializeMint2 {
-                mint: account,
-                decimals: decimals,
-                mint_authority: mint_authoriy,
-                freeze_authority: freeze_authority,
-            }
-            .invoke()?;
+            Registry::init(registry, bump)?.set_pool(0, *config);
+            return Ok(());
         }
 
-        Ok(())
-    }
-}
-
-pub struct TokenInterface;
-
-impl TokenInterface {
-    fn check(account: &AccountView) -> Result<(), ProgramError> {
-        if !account.owned_by(&TOKEN_2022_PROGRAM_ID) {
-            if !account.owned_by(&pinocchio_token::ID) {
-                return Err(ProgramError::InvalidAccountOwner);
-            } else {
-                if account
-                    .data_len()
-                    .ne(&pinocchio_token::state::TokenAccount::LEN)
-                {
-                    return Err(ProgramError::InvalidAccountOwner);
-                }
-            }
-        } else {
-            let data = account.try_borrow()?;
+        let pools = {
+            let registry_data = Registry::load(registry)?;
+            registry_data.require_room()?;
+            registry_data.pools().len()
+        };
 
-            if data.len().ne(&pinocchio_token::state::TokenAccount::LEN) {
-                if data.len().le(&TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET) {
-                    return Err(ProgramError::InvalidAccountData);
-                }
-                if data[TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET]
-                    .ne(&TOKEN_2022_TOKEN_ACCOUNT_DISCRIMINATOR)
-                {
-                    return Err(ProgramError::InvalidAccountData);
+        // Top up the rent for one more address
+        let space = Registry::space(pools + 1);
+        let lamports = Rent::get()?.try_minimum_balance(space)?;
+        if let Some(missing) = lamports.checked_sub(registry.lamports()) {
+            if missing > 0 {
+                Transfer {
+                    from: payer,
+                    to: registry,
+                    lamports: missing,
                 }
+                .invoke()?;
             }
         }
 
+        registry.resize(space)?;
+        Registry::load_mut(registry)?.set_pool(pools, *config);
+
+        Ok(())
+    }
+
+    /// Remove `config` from `registry`, which the caller must have checked is its pair's, and
+    /// refund the rent of the freed address to `destination`.
+    pub fn deregister(
+        registry: &AccountView,
+        config: &Address,
+        destination: &AccountView,
+    ) -> ProgramResult {
+        let pools = {
+            let mut registry_data = Registry::load_mut(registry)?;
+            let index = registry_data
+                .position(config)
+                .ok_or(ProgramError::InvalidAccountData)?;
+
+            // Swap the last entry into the freed slot, the account then shrinks from the end
+            let last = registry_data.pools().len() - 1;
+            let moved = registry_data.pools()[last];
+            registry_data.set_pool(index, moved);
+            last
+        };
+        registry.resize(Registry::space(pools))?;
+
+        let lamports = Rent::get()?.try_minimum_balance(Registry::space(pools))?;
+        let refund = registry.lamports().saturating_sub(lamports);
+        registry.set_lamports(registry.lamports() - refund);
+        destination.set_lamports(
+            destination
+                .lamports()
+                .checked_add(refund)
+                .ok_or(AmmError::MathOverflow)?,
+        );
+
         Ok(())
     }
 }
 
-pub struct AssociatedTokenAccount;
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
 
-impl AssociatedTokenAccount {
-    pub fn check(
-        account: &AccountView,
-        authority: &Address,
-        mint: &Address,
-        token_program: &Address,
-    ) -> Result<(), ProgramError> {
-        TokenInterface::check(account)?;
-
-        if Address::find_program_address(
-            &[
-                authority.as_array(),
-                token_program.as_array(),
-                mint.as_array(),
-            ],
-            &pinocchio_associated_token_account::ID,
+    use super::*;
+
+    const USER: Address = Address::new_from_array([1; 32]);
+    const MINT: Address = Address::new_from_array([2; 32]);
+
+    fn user_account(token_program: Address, mint: &Address, owner: &Address) -> TestAccount {
+        TestAccount::token(
+            Address::new_from_array([9; 32]),
+            token_program,
+            mint,
+            owner,
+            0,
         )
-        .0
-        .ne(account.address())
-        {
-            return Err(ProgramError::InvalidSeeds);
+    }
+
+    #[test]
+    fn accepts_a_user_account_of_the_token_program() {
+        for token_program in [pinocchio_token::ID, TOKEN_2022_PROGRAM_ID] {
+            let mut account = user_account(token_program, &MINT, &USER);
+            assert_eq!(
+                UserTokenAccount::check(&account.view(), &USER, &MINT, &token_program),
+                Ok(())
+            );
         }
+    }
 
-        Ok(())
+    #[test]
+    fn rejects_a_user_account_of_the_other_token_program() {
+        let mut account = user_account(TOKEN_2022_PROGRAM_ID, &MINT, &USER);
+        assert_eq!(
+            UserTokenAccount::check(&account.view(), &USER, &MINT, &pinocchio_token::ID),
+            Err(ProgramError::InvalidAccountOwner)
+        );
     }
 
-    pub fn init(
-        account: &AccountView,
-        mint: &AccountView,
-        payer: &AccountView,
-        owner: &AccountView,
-        system_program: &AccountView,
-        token_program: &AccountView,
-    ) -> ProgramResult {
-        Create {
-            funding_account: payer,
-            account,
-            wallet: owner,
-            mint,
-            system_program,
-            token_program,
+    #[test]
+    fn rejects_a_user_account_of_another_mint_or_owner() {
+        let other = Address::new_from_array([3; 32]);
+        for (mint, owner) in [(other, USER), (MINT, other)] {
+            let mut account = user_account(pinocchio_token::ID, &mint, &owner);
+            assert_eq!(
+                UserTokenAccount::check(&account.view(), &USER, &MINT, &pinocchio_token::ID),
+                Err(ProgramError::InvalidAccountData)
+            );
         }
-        .invoke()
     }
 
-    pub fn init_if_needed(
-        account: &AccountView,
-        mint: &AccountView,
-        payer: &AccountView,
-        owner: &AccountView,
-        system_program: &AccountView,
-        token_program: &AccountView,
-    ) -> ProgramResult {
-        match Self::check(
-            account,
-            payer.address(),
-            mint.address(),
-            token_program.address(),
-        ) {
-            Ok(_) => Ok(()),
-            Err(_) => Self::init(account, mint, payer, owner, system_program, token_program),
-        }
+    #[test]
+    fn checks_the_stored_vaults_are_held_by_the_config() {
+        let config_address = Address::new_from_array([4; 32]);
+        let (vault_x, vault_y) = (
+            Address::new_from_array([5; 32]),
+            Address::new_from_array([6; 32]),
+        );
+        let mut raw = [0u8; Config::LEN];
+        // SAFETY: the buffer holds exactly one config
+        let config_data = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+        config_data.set_vaults(vault_x, vault_y);
+
+        let vault = |address, owner: &Address| {
+            TestAccount::token(address, pinocchio_token::ID, &MINT, owner, 0)
+        };
+        let mut config = TestAccount::empty(config_address);
+        let (mut x, mut y) = (
+            vault(vault_x, &config_address),
+            vault(vault_y, &config_address),
+        );
+        assert_eq!(
+            ConfigAccount::check_vaults(&config.view(), config_data, &x.view(), &y.view()),
+            Ok(())
+        );
+
+        // A swapped in account is rejected by address, before its data is read
+        assert_eq!(
+            ConfigAccount::check_vaults(&config.view(), config_data, &y.view(), &x.view()),
+            Err(AmmError::InvalidVault.into())
+        );
+
+        let mut stolen = vault(vault_y, &USER);
+        assert_eq!(
+            ConfigAccount::check_vaults(&config.view(), config_data, &x.view(), &stolen.view()),
+            Err(ProgramError::InvalidAccountOwner)
+        );
     }
 }
@@ -1,17 +1,38 @@
 use constant_product_curve::ConstantProduct;
-use pinocchio::{
-    cpi::{Seed, Signer},
-    error::ProgramError,
-    AccountView, ProgramResult,
-};
-use pinocchio_token::instructions::{Burn, Transfer};
-use pinocchio_token::state::{Mint, TokenAccount};
+use pinocchio::{cpi::Signer, error::ProgramError, AccountView, ProgramResult};
 
 use super::utils::{
-    AssociatedTokenAccount, ConfigAccount, DataAccount, MintInterface, SignerAccount,
+    token_program_of, ConfigAccount, DataAccount, Expiration, MintInterface, SignerAccount,
+    TokenInterface, UserTokenAccount,
 };
+use crate::errors::AmmError;
+use crate::events::WithdrawEvent;
 use crate::state::*;
 
+/// Amounts of X and Y that burning `lp_amount` out of `lp_supply` LP tokens withdraws.
+/// Burning the whole supply empties both vaults.
+pub fn lp_to_underlying(
+    vault_x: u64,
+    vault_y: u64,
+    lp_supply: u64,
+    lp_amount: u64,
+) -> Result<(u64, u64), ProgramError> {
+    if lp_supply == lp_amount {
+        return Ok((vault_x, vault_y));
+    }
+
+    let amounts = ConstantProduct::xy_withdraw_amounts_from_l(
+        vault_x,
+        vault_y,
+        lp_supply,
+        lp_amount,
+        Config::LP_DECIMALS.into(),
+    )
+    .map_err(AmmError::from)?;
+
+    Ok((amounts.x, amounts.y))
+}
+
 pub struct WithdrawAccounts<'a> {
     pub user: &'a AccountView,
     pub mint_lp: &'a AccountView,
@@ -21,14 +42,17 @@ pub struct WithdrawAccounts<'a> {
     pub user_y_ata: &'a AccountView,
     pub user_lp_ata: &'a AccountView,
     pub config: &'a AccountView,
-    pub token_program: &'a AccountView,
+    pub token_program_x: &'a AccountView,
+    pub token_program_y: &'a AccountView,
+    pub mint_x: &'a AccountView,
+    pub mint_y: &'a AccountView,
 }
 
 impl<'a> TryFrom<&'a [AccountView]> for WithdrawAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountView]) -> Result<Self, Self::Error> {
-        let [user, mint_lp, vault_x, vault_y, user_x_ata, user_y_ata, user_lp_ata, config, token_program] =
+        let [user, mint_lp, vault_x, vault_y, user_x_ata, user_y_ata, user_lp_ata, config, token_program_x, token_program_y, mint_x, mint_y] =
             accounts
         else {
             return Err(ProgramError::NotEnoughAccountKeys);
@@ -47,7 +71,10 @@ impl<'a> TryFrom<&'a [AccountView]> for WithdrawAccounts<'a> {
             user_y_ata,
             user_lp_ata,
             config,
-            token_program,
+            token_program_x,
+            token_program_y,
+            mint_x,
+            mint_y,
         })
     }
 }
@@ -71,6 +98,34 @@ impl<'a> TryFrom<&'a [u8]> for WithdrawInstructionData {
     }
 }
 
+impl WithdrawInstructionData {
+    /// Amounts of X and Y the withdraw pays out of recorded reserves `reserve_x` and
+    /// `reserve_y`, within the slippage bounds.
+    fn amounts_out(
+        &self,
+        config: &Config,
+        reserve_x: u64,
+        reserve_y: u64,
+        lp_supply: u64,
+    ) -> Result<(u64, u64), ProgramError> {
+        // Accrued protocol fees stay in the vaults for `ClaimFees`
+        let (reserve_x, reserve_y) = config.pool_reserves(reserve_x, reserve_y);
+        let (x, y) = lp_to_underlying(reserve_x, reserve_y, lp_supply, self.amount)?;
+
+        // Dust LP can round to nothing on both sides, only the last LP may burn for nothing
+        if x == 0 && y == 0 && self.amount != lp_supply {
+            return Err(AmmError::ZeroAmount.into());
+        }
+
+        // Check for slippage
+        if !(x >= self.min_x && y >= self.min_y) {
+            return Err(AmmError::SlippageExceeded.into());
+        }
+
+        Ok((x, y))
+    }
+}
+
 pub struct Withdraw<'a> {
     pub accounts: WithdrawAccounts<'a>,
     pub instruction_data: WithdrawInstructionData,
@@ -93,103 +148,228 @@ impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for Withdraw<'a> {
 
 impl<'a> Withdraw<'a> {
     pub const DISCRIMINATOR: &'a u8 = &2;
+    /// Pool flags the instruction needs.
+    pub const REQUIRED_FLAGS: u8 = AmmState::WITHDRAWALS_ENABLED;
 
     pub fn process(&mut self) -> ProgramResult {
+        Expiration::check(self.instruction_data.expiration)?;
+
+        ConfigAccount::update_price_cumulative(self.accounts.config)?;
         let config_data = Config::load(self.accounts.config)?;
-        AssociatedTokenAccount::check(
+        config_data.require_address(self.accounts.config.address())?;
+        ConfigAccount::check_vaults(
+            self.accounts.config,
+            &config_data,
             self.accounts.vault_x,
-            self.accounts.config.address(),
-            config_data.mint_x(),
-            self.accounts.token_program.address(),
-        )?;
-        AssociatedTokenAccount::check(
             self.accounts.vault_y,
-            self.accounts.config.address(),
-            config_data.mint_y(),
-            self.accounts.token_program.address(),
         )?;
-        AssociatedTokenAccount::check(
+        config_data.require_token_programs(
+            self.accounts.token_program_x.address(),
+            self.accounts.token_program_y.address(),
+        )?;
+        UserTokenAccount::check(
             self.accounts.user_x_ata,
             self.accounts.user.address(),
             config_data.mint_x(),
-            self.accounts.token_program.address(),
+            self.accounts.token_program_x.address(),
         )?;
-        AssociatedTokenAccount::check(
+        UserTokenAccount::check(
             self.accounts.user_y_ata,
             self.accounts.user.address(),
             config_data.mint_y(),
-            self.accounts.token_program.address(),
+            self.accounts.token_program_y.address(),
         )?;
-        AssociatedTokenAccount::check(
+        UserTokenAccount::check(
             self.accounts.user_lp_ata,
             self.accounts.user.address(),
             self.accounts.mint_lp.address(),
-            self.accounts.token_program.address(),
+            token_program_of(self.accounts.mint_lp)?,
         )?;
 
-        if config_data.state() == AmmState::Disabled as u8 {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        config_data.require_flags(Self::REQUIRED_FLAGS)?;
 
-        let mint_lp = unsafe { Mint::from_account_view_unchecked(self.accounts.mint_lp)? };
-        let vault_x = unsafe { TokenAccount::from_account_view_unchecked(self.accounts.vault_x)? };
-        let vault_y = unsafe { TokenAccount::from_account_view_unchecked(self.accounts.vault_y)? };
-
-        let (x, y) = match mint_lp.supply() == self.instruction_data.amount {
-            true => (vault_x.amount(), vault_y.amount()),
-            false => {
-                let amounts = ConstantProduct::xy_withdraw_amounts_from_l(
-                    vault_x.amount(),
-                    vault_y.amount(),
-                    mint_lp.supply(),
-                    self.instruction_data.amount,
-                    6,
-                )
-                .map_err(|_| ProgramError::InvalidArgument)?;
-
-                (amounts.x, amounts.y)
-            }
-        };
+        config_data.require_mints(
+            self.accounts.mint_x.address(),
+            self.accounts.mint_y.address(),
+        )?;
 
-        // Check for slippage
-        if !(x >= self.instruction_data.min_x && y >= self.instruction_data.min_y) {
-            return Err(ProgramError::InvalidArgument);
-        }
+        config_data.require_mint_lp(self.accounts.mint_lp.address())?;
+        let mint_lp = MintInterface::read(self.accounts.mint_lp)?;
 
-        let config_seed_binding = config_data.seed().to_le_bytes();
-        let config_bump_binding = config_data.config_bump();
-        let config_seeds = [
-            Seed::from(b"config"),
-            Seed::from(&config_seed_binding),
-            Seed::from(config_data.mint_x().as_array()),
-            Seed::from(config_data.mint_y().as_array()),
-            Seed::from(&config_bump_binding),
-        ];
+        // LP redeems against the recorded reserves, tokens sent straight to the vaults aren't
+        // the LPs' until `SyncReserves`
+        let (x, y) = self.instruction_data.amounts_out(
+            &config_data,
+            config_data.reserve_x(),
+            config_data.reserve_y(),
+            mint_lp.supply(),
+        )?;
+
+        let config_seeds = config_data.signer_seeds();
         let withdraw_signer = [Signer::from(&config_seeds)];
 
-        Transfer {
-            from: self.accounts.vault_x,
-            to: self.accounts.user_x_ata,
-            authority: self.accounts.config,
-            amount: x,
+        // The vaults part with exactly `x` and `y`, any transfer fee comes out of what the user
+        // receives
+        TokenInterface::transfer_checked(
+            self.accounts.vault_x,
+            self.accounts.mint_x,
+            self.accounts.user_x_ata,
+            self.accounts.config,
+            x,
+            &withdraw_signer,
+        )?;
+        TokenInterface::transfer_checked(
+            self.accounts.vault_y,
+            self.accounts.mint_y,
+            self.accounts.user_y_ata,
+            self.accounts.config,
+            y,
+            &withdraw_signer,
+        )?;
+
+        TokenInterface::burn(
+            self.accounts.user_lp_ata,
+            self.accounts.mint_lp,
+            self.accounts.user,
+            self.instruction_data.amount,
+            &[],
+        )?;
+
+        drop(config_data);
+        Config::load_mut(self.accounts.config)?.debit_reserves(x, y)?;
+
+        let (reserve_x_after, reserve_y_after) =
+            Config::load(self.accounts.config)?.recorded_pool_reserves();
+        WithdrawEvent {
+            user: self.accounts.user.address(),
+            amount_x: x,
+            amount_y: y,
+            lp: self.instruction_data.amount,
+            reserve_x_after,
+            reserve_y_after,
+        }
+        .emit();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pinocchio::Address;
+
+    use super::*;
+
+    /// Config bytes of a pool holding `fees_x` and `fees_y` of accrued protocol fees.
+    fn config(fees_x: u64, fees_y: u64) -> [u8; Config::LEN] {
+        let mut data = [0u8; Config::LEN];
+        // SAFETY: the buffer is exactly one config long
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut data) };
+        let mints = (
+            Address::new_from_array([2; 32]),
+            Address::new_from_array([3; 32]),
+        );
+        config
+            .set_inner(1, Address::default(), mints.0, mints.1, 30, [255])
+            .unwrap();
+        config.accrue_protocol_fees(fees_x, fees_y).unwrap();
+        data
+    }
+
+    fn withdraw(amount: u64, min_x: u64, min_y: u64) -> WithdrawInstructionData {
+        WithdrawInstructionData {
+            amount,
+            min_x,
+            min_y,
+            expiration: 0,
         }
-        .invoke_signed(&withdraw_signer)?;
-        Transfer {
-            from: self.accounts.vault_y,
-            to: self.accounts.user_y_ata,
-            authority: self.accounts.config,
-            amount: y,
+    }
+
+    #[test]
+    fn withdraws_a_proportional_share() {
+        // A quarter of the supply is a quarter of each vault
+        assert_eq!(
+            lp_to_underlying(1_000_000, 4_000_000, 2_000_000, 500_000),
+            Ok((250_000, 1_000_000))
+        );
+    }
+
+    #[test]
+    fn empties_both_vaults_for_the_whole_supply() {
+        assert_eq!(
+            lp_to_underlying(1_000_003, 7, 1_000, 1_000),
+            Ok((1_000_003, 7))
+        );
+    }
+
+    #[test]
+    fn rejects_burning_more_than_the_supply() {
+        assert_eq!(
+            lp_to_underlying(1_000_000, 1_000_000, 1_000, 1_001),
+            Err(AmmError::CurveError.into())
+        );
+    }
+
+    #[test]
+    fn pays_out_what_lp_to_underlying_quotes() {
+        let data = config(0, 0);
+        let config = Config::from_bytes(&data).unwrap();
+
+        for (vault_x, vault_y, supply, amount) in [
+            (1_000_000, 4_000_000, 2_000_000, 500_000),
+            (1_000_003, 7, 1_000, 1_000),
+            (999_999, 1_000_001, 1_000_000, 1),
+        ] {
+            assert_eq!(
+                withdraw(amount, 0, 0).amounts_out(config, vault_x, vault_y, supply),
+                lp_to_underlying(vault_x, vault_y, supply, amount)
+            );
         }
-        .invoke_signed(&withdraw_signer)?;
+    }
+
+    #[test]
+    fn leaves_the_protocol_fees_out_of_the_payout() {
+        let data = config(200_000, 400_000);
+        let config = Config::from_bytes(&data).unwrap();
+
+        // The LP reserves are the vaults less the fees, the lone LP gets exactly those
+        assert_eq!(
+            withdraw(1_000, 0, 0).amounts_out(config, 1_200_000, 4_400_000, 1_000),
+            Ok((1_000_000, 4_000_000))
+        );
+        assert_eq!(
+            withdraw(500_000, 0, 0).amounts_out(config, 1_200_000, 4_400_000, 2_000_000),
+            lp_to_underlying(1_000_000, 4_000_000, 2_000_000, 500_000)
+        );
+    }
 
-        Burn {
-            account: self.accounts.user_lp_ata,
-            mint: self.accounts.mint_lp,
-            authority: self.accounts.user,
-            amount: self.instruction_data.amount,
+    #[test]
+    fn rejects_a_payout_below_the_minimums() {
+        let data = config(0, 0);
+        let config = Config::from_bytes(&data).unwrap();
+        let (x, y) = lp_to_underlying(1_000_000, 4_000_000, 2_000_000, 500_000).unwrap();
+
+        assert_eq!(
+            withdraw(500_000, x, y).amounts_out(config, 1_000_000, 4_000_000, 2_000_000),
+            Ok((x, y))
+        );
+        for (min_x, min_y) in [(x + 1, y), (x, y + 1)] {
+            assert_eq!(
+                withdraw(500_000, min_x, min_y)
+                    .amounts_out(config, 1_000_000, 4_000_000, 2_000_000),
+                Err(AmmError::SlippageExceeded.into())
+            );
         }
-        .invoke()?;
+    }
 
-        Ok(())
+    #[test]
+    fn rejects_dust_that_pays_out_nothing() {
+        let data = config(0, 0);
+        let config = Config::from_bytes(&data).unwrap();
+
+        assert_eq!(
+            withdraw(1, 0, 0).amounts_out(config, 10, 10, 1_000_000),
+            Err(AmmError::ZeroAmount.into())
+        );
     }
 }
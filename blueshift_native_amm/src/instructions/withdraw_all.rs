@@ -0,0 +1,74 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+
+use super::utils::{MintInterface, TokenInterface};
+use super::withdraw::{Withdraw, WithdrawAccounts, WithdrawInstructionData};
+use crate::state::Config;
+
+pub struct WithdrawAllInstructionData {
+    pub min_x: u64,
+    pub min_y: u64,
+    pub expiration: i64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for WithdrawAllInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<WithdrawAllInstructionData>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(unsafe { (data.as_ptr() as *const Self).read_unaligned() })
+    }
+}
+
+/// Close out the last LP position: burn every LP token but the locked `MINIMUM_LIQUIDITY` and
+/// receive their share of both vaults. Pools without locked LP are emptied in full, without
+/// going through the curve.
+pub struct WithdrawAll<'a> {
+    pub accounts: WithdrawAccounts<'a>,
+    pub instruction_data: WithdrawAllInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for WithdrawAll<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = WithdrawAccounts::try_from(accounts)?;
+        let instruction_data = WithdrawAllInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> WithdrawAll<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &13;
+
+    pub fn process(self) -> ProgramResult {
+        Config::load(self.accounts.config)?.require_mint_lp(self.accounts.mint_lp.address())?;
+        let supply = MintInterface::read(self.accounts.mint_lp)?.supply();
+        let amount = TokenInterface::amount(self.accounts.user_lp_ata)?;
+
+        // Only a holder of every unlocked LP token is the last LP. Burning the whole supply
+        // takes `Withdraw`'s exact reserves path
+        if amount == 0
+            || (amount != supply && amount.saturating_add(Config::MINIMUM_LIQUIDITY) != supply)
+        {
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        Withdraw {
+            accounts: self.accounts,
+            instruction_data: WithdrawInstructionData {
+                amount,
+                min_x: self.instruction_data.min_x,
+                min_y: self.instruction_data.min_y,
+                expiration: self.instruction_data.expiration,
+            },
+        }
+        .process()
+    }
+}
@@ -0,0 +1,207 @@
+use pinocchio::{cpi::Signer, error::ProgramError, AccountView, ProgramResult};
+
+use super::swap::swap_output;
+use super::utils::*;
+use super::withdraw::{lp_to_underlying, WithdrawAccounts};
+use crate::errors::AmmError;
+use crate::state::*;
+
+#[repr(C, packed)]
+pub struct WithdrawSingleInstructionData {
+    pub is_x: bool,
+    pub lp_amount: u64,
+    pub min_out: u64,
+    pub expiration: i64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for WithdrawSingleInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<WithdrawSingleInstructionData>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        // Any byte other than 0 or 1 is not a valid `bool`
+        if data[0] > 1 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let instruction_data = unsafe { (data.as_ptr() as *const Self).read_unaligned() };
+        let lp_amount = instruction_data.lp_amount;
+        if lp_amount == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(instruction_data)
+    }
+}
+
+/// Burn `lp_amount` LP tokens and receive only X (or only Y): the other leg of the withdrawal
+/// is swapped back into the pool, paying the same fees as a `Swap`.
+pub struct WithdrawSingle<'a> {
+    pub accounts: WithdrawAccounts<'a>,
+    pub instruction_data: WithdrawSingleInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountView])> for WithdrawSingle<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountView])) -> Result<Self, Self::Error> {
+        let accounts = WithdrawAccounts::try_from(accounts)?;
+        let instruction_data = WithdrawSingleInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> WithdrawSingle<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &16;
+    /// Pool flags the instruction needs.
+    pub const REQUIRED_FLAGS: u8 = AmmState::WITHDRAWALS_ENABLED | AmmState::SWAPS_ENABLED;
+
+    pub fn process(&self) -> ProgramResult {
+        // Copy out of the packed instruction data, never reference its fields
+        let is_x = self.instruction_data.is_x;
+        let lp_amount = self.instruction_data.lp_amount;
+        let min_out = self.instruction_data.min_out;
+
+        Expiration::check(self.instruction_data.expiration)?;
+
+        ConfigAccount::update_price_cumulative(self.accounts.config)?;
+        let config_data = Config::load(self.accounts.config)?;
+        config_data.require_address(self.accounts.config.address())?;
+        ConfigAccount::check_vaults(
+            self.accounts.config,
+            &config_data,
+            self.accounts.vault_x,
+            self.accounts.vault_y,
+        )?;
+        config_data.require_token_programs(
+            self.accounts.token_program_x.address(),
+            self.accounts.token_program_y.address(),
+        )?;
+        UserTokenAccount::check(
+            self.accounts.user_lp_ata,
+            self.accounts.user.address(),
+            self.accounts.mint_lp.address(),
+            token_program_of(self.accounts.mint_lp)?,
+        )?;
+
+        // The internal swap needs a pool that still trades
+        config_data.require_flags(Self::REQUIRED_FLAGS)?;
+
+        config_data.require_mints(
+            self.accounts.mint_x.address(),
+            self.accounts.mint_y.address(),
+        )?;
+
+        // Only the receiving side pays out, so only its user account is needed
+        let (user_out_ata, vault_out, mint_out, mint_out_key, token_program_out) = match is_x {
+            true => (
+                self.accounts.user_x_ata,
+                self.accounts.vault_x,
+                self.accounts.mint_x,
+                config_data.mint_x(),
+                config_data.token_program_x(),
+            ),
+            false => (
+                self.accounts.user_y_ata,
+                self.accounts.vault_y,
+                self.accounts.mint_y,
+                config_data.mint_y(),
+                config_data.token_program_y(),
+            ),
+        };
+        UserTokenAccount::check(
+            user_out_ata,
+            self.accounts.user.address(),
+            mint_out_key,
+            token_program_out,
+        )?;
+
+        config_data.require_mint_lp(self.accounts.mint_lp.address())?;
+        let mint_lp = MintInterface::read(self.accounts.mint_lp)?;
+
+        // Withdraw both legs from the recorded reserves as `Withdraw` would, accrued protocol
+        // fees aside
+        let (reserve_x, reserve_y) = config_data.recorded_pool_reserves();
+        let supply = mint_lp.supply();
+        let (x, y) = lp_to_underlying(reserve_x, reserve_y, supply, lp_amount)?;
+
+        // Then sell the unwanted leg to what is left of the pool. Burning every LP token, or
+        // enough to drain either side, leaves nothing to sell it to
+        let reserve_x = reserve_x.checked_sub(x).ok_or(AmmError::MathOverflow)?;
+        let reserve_y = reserve_y.checked_sub(y).ok_or(AmmError::MathOverflow)?;
+        if reserve_x == 0 || reserve_y == 0 {
+            return Err(AmmError::InsufficientLiquidity.into());
+        }
+        let (withdrawn, sold) = match is_x {
+            true => (x, y),
+            false => (y, x),
+        };
+        // `swap_output` refuses a sale that would empty the receiving vault
+        let (protocol_fee, _, bought) = match sold {
+            0 => (0, 0, 0),
+            _ => swap_output(
+                &config_data,
+                reserve_x,
+                reserve_y,
+                supply
+                    .checked_sub(lp_amount)
+                    .ok_or(AmmError::MathOverflow)?,
+                !is_x,
+                sold,
+                0,
+            )?,
+        };
+
+        let out = withdrawn
+            .checked_add(bought)
+            .ok_or(AmmError::MathOverflow)?;
+
+        // Check for slippage
+        if out == 0 || out < min_out {
+            return Err(AmmError::SlippageExceeded.into());
+        }
+
+        let config_seeds = config_data.signer_seeds();
+        let withdraw_signer = [Signer::from(&config_seeds)];
+
+        // The sold leg never leaves its vault, only the receiving side pays out
+        TokenInterface::transfer_checked(
+            vault_out,
+            mint_out,
+            user_out_ata,
+            self.accounts.config,
+            out,
+            &withdraw_signer,
+        )?;
+
+        TokenInterface::burn(
+            self.accounts.user_lp_ata,
+            self.accounts.mint_lp,
+            self.accounts.user,
+            lp_amount,
+            &[],
+        )?;
+
+        drop(config_data);
+        let mut config_data = Config::load_mut(self.accounts.config)?;
+        match is_x {
+            true => {
+                config_data.accrue_protocol_fees(0, protocol_fee)?;
+                config_data.debit_reserves(out, 0)?;
+            }
+            false => {
+                config_data.accrue_protocol_fees(protocol_fee, 0)?;
+                config_data.debit_reserves(0, out)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,3 +1,6 @@
+pub mod errors;
+pub mod events;
+pub mod idl;
 pub mod instructions;
 pub mod state;
 
@@ -5,6 +8,7 @@ use pinocchio::{
     address::declare_id, entrypoint, error::ProgramError, AccountView, Address, ProgramResult,
 };
 
+pub use errors::*;
 pub use instructions::*;
 pub use state::*;
 
@@ -24,6 +28,45 @@ fn process_instruction(
         Some((Deposit::DISCRIMINATOR, data)) => Deposit::try_from((data, accounts))?.process(),
         Some((Withdraw::DISCRIMINATOR, data)) => Withdraw::try_from((data, accounts))?.process(),
         Some((Swap::DISCRIMINATOR, data)) => Swap::try_from((data, accounts))?.process(),
+        Some((Migrate::DISCRIMINATOR, data)) => Migrate::try_from((data, accounts))?.process(),
+        Some((FlashSwap::DISCRIMINATOR, data)) => FlashSwap::try_from((data, accounts))?.process(),
+        Some((DepositSingleSided::DISCRIMINATOR, data)) => {
+            DepositSingleSided::try_from((data, accounts))?.process()
+        }
+        Some((Pause::DISCRIMINATOR, _)) => Pause::try_from(accounts)?.process(),
+        Some((Resume::DISCRIMINATOR, _)) => Resume::try_from(accounts)?.process(),
+        Some((Donate::DISCRIMINATOR, data)) => Donate::try_from((data, accounts))?.process(),
+        Some((UpdateFee::DISCRIMINATOR, data)) => UpdateFee::try_from((data, accounts))?.process(),
+        Some((UpdateState::DISCRIMINATOR, data)) => {
+            UpdateState::try_from((data, accounts))?.process()
+        }
+        Some((UpdateAuthority::DISCRIMINATOR, data)) => {
+            UpdateAuthority::try_from((data, accounts))?.process()
+        }
+        Some((WithdrawAll::DISCRIMINATOR, data)) => {
+            WithdrawAll::try_from((data, accounts))?.process()
+        }
+        Some((ClaimFees::DISCRIMINATOR, _)) => ClaimFees::try_from(accounts)?.process(),
+        Some((SwapExactOut::DISCRIMINATOR, data)) => {
+            SwapExactOut::try_from((data, accounts))?.process()
+        }
+        Some((WithdrawSingle::DISCRIMINATOR, data)) => {
+            WithdrawSingle::try_from((data, accounts))?.process()
+        }
+        Some((SyncReserves::DISCRIMINATOR, _)) => SyncReserves::try_from(accounts)?.process(),
+        Some((Quote::DISCRIMINATOR, data)) => Quote::try_from((data, accounts))?.process(),
+        Some((Skim::DISCRIMINATOR, _)) => Skim::try_from(accounts)?.process(),
+        Some((SetFlags::DISCRIMINATOR, data)) => SetFlags::try_from((data, accounts))?.process(),
+        Some((InitializeAndDeposit::DISCRIMINATOR, data)) => {
+            InitializeAndDeposit::try_from((data, accounts))?.process()
+        }
+        Some((SwapMultiHop::DISCRIMINATOR, data)) => {
+            SwapMultiHop::try_from((data, accounts))?.process()
+        }
+        Some((DeregisterPool::DISCRIMINATOR, _)) => DeregisterPool::try_from(accounts)?.process(),
+        Some((SetCanonicalPool::DISCRIMINATOR, _)) => {
+            SetCanonicalPool::try_from(accounts)?.process()
+        }
         _ => Err(ProgramError::InvalidInstructionData),
     }
 }
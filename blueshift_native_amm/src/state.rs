@@ -1,22 +1,126 @@
-use core::mem::size_of;
+use blueshift_pinocchio_helpers::{require, require_eq};
+use core::mem::{align_of, offset_of, size_of};
 use pinocchio::{
     account::{Ref, RefMut},
+    cpi::Seed,
     error::ProgramError,
-    AccountView, Address,
+    AccountView, Address, ProgramResult,
 };
+use shank::ShankAccount;
+
+use crate::errors::AmmError;
 
 #[repr(C)]
+#[derive(ShankAccount)]
 pub struct Config {
-    state: u8,
+    version: u8,
+    state: u8, // `AmmState` flags, the enum itself in v0
+    #[idl_type("u64")]
     seed: [u8; 8],
+    #[idl_type("Pubkey")]
     authority: Address,
+    #[idl_type("Pubkey")]
     mint_x: Address,
+    #[idl_type("Pubkey")]
     mint_y: Address,
+    #[idl_type("u16")]
     fee: [u8; 2],
+    #[idl_type("u8")]
     config_bump: [u8; 1],
+    #[idl_type("u64")]
+    reserve_x: [u8; 8], // X the pool prices from, moved only by what instructions pay in and out
+    #[idl_type("u64")]
+    reserve_y: [u8; 8], // Y the pool prices from, moved only by what instructions pay in and out
+    #[idl_type("i64")]
+    paused_at: [u8; 8], // Unix timestamp of the last emergency pause, 0 = never
+    #[idl_type("u16")]
+    protocol_fee_bps: [u8; 2], // Share of every swap input taken for the protocol
+    #[idl_type("u64")]
+    protocol_fees_x: [u8; 8], // Protocol fees held in vault X, not part of the LP reserves
+    #[idl_type("u64")]
+    protocol_fees_y: [u8; 8], // Protocol fees held in vault Y, not part of the LP reserves
+    #[idl_type("u128")]
+    price_cumulative_x: [u8; 16], // Q64.64 price of X in Y, summed over every second
+    #[idl_type("u128")]
+    price_cumulative_y: [u8; 16], // Q64.64 price of Y in X, summed over every second
+    #[idl_type("i64")]
+    last_update_ts: [u8; 8], // Unix timestamp the accumulators were last brought up to, 0 = never
+    #[idl_type("Pubkey")]
+    vault_x: Address, // Config's token X ATA, derived once by `Initialize` or `Migrate`
+    #[idl_type("Pubkey")]
+    vault_y: Address, // Config's token Y ATA, derived once by `Initialize` or `Migrate`
+    #[idl_type("Pubkey")]
+    token_program_x: Address, // Token program owning mint X and vault X
+    #[idl_type("Pubkey")]
+    token_program_y: Address, // Token program owning mint Y and vault Y
+    #[idl_type("Pubkey")]
+    mint_lp: Address, // PDA ["mint_lp", config], checked once by `Initialize` or `Migrate`
+}
+
+/// Derive the config PDA `["config", seed, mint_x, mint_y]` and its canonical bump.
+#[inline(always)]
+pub fn derive_config(seed: u64, mint_x: &Address, mint_y: &Address) -> (Address, u8) {
+    Address::find_program_address(
+        &[
+            Config::SEED,
+            &seed.to_le_bytes(),
+            mint_x.as_array(),
+            mint_y.as_array(),
+        ],
+        &crate::ID,
+    )
 }
 
+/// Derive the LP mint PDA `["mint_lp", config]` and its canonical bump.
+#[inline(always)]
+pub fn derive_mint_lp(config: &Address) -> (Address, u8) {
+    Address::find_program_address(&[Config::MINT_LP_SEED, config.as_array()], &crate::ID)
+}
+
+// Accounts already on chain depend on this layout, any change needs a new version
+const _: () = {
+    // Every field is a byte or byte array, so any pointer into account data is aligned
+    assert!(align_of::<Config>() == 1);
+    assert!(Config::LEN == 351);
+    assert!(offset_of!(Config, version) == 0);
+    assert!(offset_of!(Config, state) == 1);
+    assert!(offset_of!(Config, seed) == 2);
+    assert!(offset_of!(Config, authority) == 10);
+    assert!(offset_of!(Config, mint_x) == 42);
+    assert!(offset_of!(Config, mint_y) == 74);
+    assert!(offset_of!(Config, fee) == 106);
+    assert!(offset_of!(Config, config_bump) == 108);
+    // v0 is everything up to here, without the version byte
+    assert!(offset_of!(Config, reserve_x) == Config::V0_LEN + 1);
+    assert!(offset_of!(Config, reserve_y) == 117);
+    assert!(offset_of!(Config, paused_at) == 125);
+    assert!(offset_of!(Config, protocol_fee_bps) == 133);
+    assert!(offset_of!(Config, protocol_fees_x) == 135);
+    assert!(offset_of!(Config, protocol_fees_y) == 143);
+    assert!(offset_of!(Config, price_cumulative_x) == 151);
+    assert!(offset_of!(Config, price_cumulative_y) == 167);
+    assert!(offset_of!(Config, last_update_ts) == 183);
+    assert!(offset_of!(Config, vault_x) == 191);
+    assert!(offset_of!(Config, vault_y) == 223);
+    assert!(offset_of!(Config, token_program_x) == 255);
+    assert!(offset_of!(Config, token_program_y) == 287);
+    assert!(offset_of!(Config, mint_lp) == 319);
+};
+
+/// `numerator / denominator` as a Q64.64 fixed point number. Two `u64` reserves always fit:
+/// the quotient is at most `u64::MAX << 64`.
+#[inline(always)]
+fn price_q64(numerator: u64, denominator: u64) -> u128 {
+    ((numerator as u128) << 64) / denominator as u128
+}
+
+/// Pool lifecycle. Deposits and swaps need `Initialized`, withdrawals are also allowed
+/// in `WithdrawOnly` so LPs can always leave a pool being wound down.
+///
+/// Since v1 the config stores the state as a set of flags, each operation testing its own.
+/// The enum values stay as the presets `UpdateState` and the v0 layout use.
 #[repr(u8)]
+#[derive(Clone, Copy)]
 pub enum AmmState {
     Uninitialized = 0u8,
     Initialized = 1u8,
@@ -24,22 +128,121 @@ pub enum AmmState {
     WithdrawOnly = 3u8,
 }
 
+impl TryFrom<u8> for AmmState {
+    type Error = ProgramError;
+
+    fn try_from(state: u8) -> Result<Self, Self::Error> {
+        match state {
+            0 => Ok(AmmState::Uninitialized),
+            1 => Ok(AmmState::Initialized),
+            2 => Ok(AmmState::Disabled),
+            3 => Ok(AmmState::WithdrawOnly),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+impl AmmState {
+    pub const SWAPS_ENABLED: u8 = 1 << 0;
+    pub const DEPOSITS_ENABLED: u8 = 1 << 1;
+    /// Only an emergency `Pause` or the authority's `Disabled` clears it, `SetFlags` cannot.
+    pub const WITHDRAWALS_ENABLED: u8 = 1 << 2;
+    pub const ALL_FLAGS: u8 =
+        Self::SWAPS_ENABLED | Self::DEPOSITS_ENABLED | Self::WITHDRAWALS_ENABLED;
+
+    /// Flags equivalent to the state.
+    #[inline(always)]
+    pub const fn flags(self) -> u8 {
+        match self {
+            AmmState::Uninitialized | AmmState::Disabled => 0,
+            AmmState::Initialized => Self::ALL_FLAGS,
+            AmmState::WithdrawOnly => Self::WITHDRAWALS_ENABLED,
+        }
+    }
+}
+
 impl Config {
-    const LEN: usize = size_of::<Self>();
+    pub const SEED: &'static [u8] = b"config";
+    pub const MINT_LP_SEED: &'static [u8] = b"mint_lp";
+
+    /// LP minted by the first deposit to the config's own LP account, which nothing ever moves
+    /// out of. With it locked the supply never returns to zero, so the first depositor cannot
+    /// inflate the value of a share by donating to a near empty pool.
+    pub const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+    /// Decimals of the LP mint, also the precision LP amounts are converted with on the curve.
+    pub const LP_DECIMALS: u8 = 6;
+
+    /// Most decimals a pool mint may have. Beyond 18 a single whole token is over 10^18 base
+    /// units, and the `u64` reserves hold fewer than 19 of them.
+    pub const MAX_MINT_DECIMALS: u8 = 18;
+
+    /// Layout written by the current program.
+    pub const VERSION: u8 = 1;
+    pub const LEN: usize = size_of::<Self>();
+
+    /// Original layout, without a version byte. Identified by its length.
+    pub const V0: u8 = 0;
+    pub const V0_LEN: usize = size_of::<u8>()
+        + size_of::<u64>()
+        + size_of::<Address>() * 3
+        + size_of::<u16>()
+        + size_of::<[u8; 1]>();
+
+    /// Time the authority has to wait after an emergency pause before resuming the pool.
+    pub const RESUME_TIMELOCK: i64 = 24 * 60 * 60;
+
+    /// Return the layout version stored in `bytes`, rejecting unknown versions.
+    #[inline(always)]
+    pub fn version_of(bytes: &[u8]) -> Result<u8, ProgramError> {
+        match bytes.len() {
+            Self::V0_LEN => Ok(Self::V0),
+            Self::LEN if bytes[0] == Self::VERSION => Ok(Self::VERSION),
+            _ => Err(AmmError::InvalidVersion.into()),
+        }
+    }
+
+    /// Map a v0 config onto the current layout.
+    #[inline(always)]
+    pub fn upgrade(bytes: &[u8]) -> Result<[u8; Self::LEN], ProgramError> {
+        if Self::version_of(bytes)? != Self::V0 {
+            return Err(AmmError::InvalidVersion.into());
+        }
+
+        // v0 is the start of the current layout without the leading version byte, the fields
+        // after it keep their zero default
+        let mut raw = [0u8; Self::LEN];
+        raw[1..=Self::V0_LEN].copy_from_slice(bytes);
+        raw[0] = Self::VERSION;
+        // v0 stores the state as the enum
+        raw[1] = AmmState::try_from(raw[1])?.flags();
+
+        Ok(raw)
+    }
 
     #[inline(always)]
-    pub fn load(account_info: &AccountView) -> Result<Ref<Self>, ProgramError> {
+    pub fn load(account_info: &AccountView) -> Result<Ref<'_, Self>, ProgramError> {
         if account_info.data_len() != Self::LEN {
             return Err(ProgramError::InvalidAccountData);
         }
         if !account_info.owned_by(&crate::ID) {
             return Err(ProgramError::InvalidAccountOwner);
         }
-        Ok(Ref::map(account_info.try_borrow()?, |data| unsafe {
+        let data = account_info.try_borrow()?;
+        if Self::version_of(&data)? != Self::VERSION {
+            return Err(AmmError::InvalidVersion.into());
+        }
+        Ok(Ref::map(data, |data| unsafe {
             Self::from_bytes_unchecked(data)
         }))
     }
 
+    /// Return the config held by `account_info` without borrowing it.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure no mutable borrow of the account data is alive while the
+    /// returned reference is in use.
     #[inline(always)]
     pub unsafe fn load_unchecked(account_info: &AccountView) -> Result<&Self, ProgramError> {
         if account_info.data_len() != Self::LEN {
@@ -48,7 +251,22 @@ impl Config {
         if account_info.owner() != &crate::ID {
             return Err(ProgramError::InvalidAccountOwner);
         }
-        Ok(Self::from_bytes_unchecked(account_info.borrow_unchecked()))
+        let data = account_info.borrow_unchecked();
+        if Self::version_of(data)? != Self::VERSION {
+            return Err(AmmError::InvalidVersion.into());
+        }
+        Ok(Self::from_bytes_unchecked(data))
+    }
+
+    /// Return a `Config` from the given bytes, which must hold exactly one config in the
+    /// current layout.
+    #[inline(always)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<&Self, ProgramError> {
+        if Self::version_of(bytes)? != Self::VERSION {
+            return Err(AmmError::InvalidVersion.into());
+        }
+        // SAFETY: the length matches and `Config` has an alignment of 1 byte
+        Ok(unsafe { Self::from_bytes_unchecked(bytes) })
     }
 
     /// Return a `Config` from the given bytes.
@@ -57,7 +275,7 @@ impl Config {
     ///
     /// The caller must ensure that `bytes` contains a valid representation of `Config`, and
     /// it is properly aligned to be interpreted as an instance of `Config`.
-    /// At the moment `Config` has an alignment of 1 byte.
+    /// `Config` has an alignment of 1 byte, which is asserted at compile time.
     /// This method does not perform a length validation.
     #[inline(always)]
     pub unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &Self {
@@ -76,6 +294,12 @@ impl Config {
 
     // Getter methods for safe field access
     #[inline(always)]
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// `AmmState` flags of the pool.
+    #[inline(always)]
     pub fn state(&self) -> u8 {
         self.state
     }
@@ -111,36 +335,333 @@ impl Config {
     }
 
     #[inline(always)]
-    pub fn load_mut(account_info: &AccountView) -> Result<RefMut<Self>, ProgramError> {
+    pub fn reserve_x(&self) -> u64 {
+        u64::from_le_bytes(self.reserve_x)
+    }
+
+    #[inline(always)]
+    pub fn reserve_y(&self) -> u64 {
+        u64::from_le_bytes(self.reserve_y)
+    }
+
+    #[inline(always)]
+    pub fn paused_at(&self) -> i64 {
+        i64::from_le_bytes(self.paused_at)
+    }
+
+    #[inline(always)]
+    pub fn protocol_fee_bps(&self) -> u16 {
+        u16::from_le_bytes(self.protocol_fee_bps)
+    }
+
+    #[inline(always)]
+    pub fn protocol_fees_x(&self) -> u64 {
+        u64::from_le_bytes(self.protocol_fees_x)
+    }
+
+    #[inline(always)]
+    pub fn protocol_fees_y(&self) -> u64 {
+        u64::from_le_bytes(self.protocol_fees_y)
+    }
+
+    /// Time weighted sum of the price of X in Y, as a Q64.64 fixed point number times seconds.
+    /// It wraps around on overflow: a TWAP is the wrapping difference of two snapshots divided
+    /// by the seconds between their `last_update_ts`.
+    #[inline(always)]
+    pub fn price_cumulative_x(&self) -> u128 {
+        u128::from_le_bytes(self.price_cumulative_x)
+    }
+
+    /// Time weighted sum of the price of Y in X, see `price_cumulative_x`.
+    #[inline(always)]
+    pub fn price_cumulative_y(&self) -> u128 {
+        u128::from_le_bytes(self.price_cumulative_y)
+    }
+
+    #[inline(always)]
+    pub fn last_update_ts(&self) -> i64 {
+        i64::from_le_bytes(self.last_update_ts)
+    }
+
+    #[inline(always)]
+    pub fn vault_x(&self) -> &Address {
+        &self.vault_x
+    }
+
+    #[inline(always)]
+    pub fn vault_y(&self) -> &Address {
+        &self.vault_y
+    }
+
+    #[inline(always)]
+    pub fn token_program_x(&self) -> &Address {
+        &self.token_program_x
+    }
+
+    #[inline(always)]
+    pub fn token_program_y(&self) -> &Address {
+        &self.token_program_y
+    }
+
+    #[inline(always)]
+    pub fn mint_lp(&self) -> &Address {
+        &self.mint_lp
+    }
+
+    /// Reserves recorded by the last instruction, less the protocol fees they hold.
+    #[inline(always)]
+    pub fn recorded_pool_reserves(&self) -> (u64, u64) {
+        self.pool_reserves(self.reserve_x(), self.reserve_y())
+    }
+
+    /// Vault balances less the protocol fees they hold, the reserves LP tokens are priced
+    /// against.
+    #[inline(always)]
+    pub fn pool_reserves(&self, vault_x: u64, vault_y: u64) -> (u64, u64) {
+        (
+            vault_x.saturating_sub(self.protocol_fees_x()),
+            vault_y.saturating_sub(self.protocol_fees_y()),
+        )
+    }
+
+    /// Whether the vaults hold less than the program left in them. Tokens only leave the
+    /// vaults through this program, which records their balances afterwards, so a shortfall
+    /// means they were drained some other way.
+    #[inline(always)]
+    pub fn is_short(&self, vault_x: u64, vault_y: u64) -> bool {
+        vault_x < self.reserve_x() || vault_y < self.reserve_y()
+    }
+
+    /// Reject the instruction unless the pool has every one of the `required` flags.
+    #[inline(always)]
+    pub fn require_flags(&self, required: u8) -> Result<(), ProgramError> {
+        require_eq!(self.state & required, required, AmmError::PoolDisabled);
+        Ok(())
+    }
+
+    /// Reject the instruction unless `authority` is the pool authority. Pools without one are
+    /// immutable.
+    #[inline(always)]
+    pub fn require_authority(&self, authority: &Address) -> Result<(), ProgramError> {
+        if self.authority == Address::default() {
+            return Err(AmmError::ImmutablePool.into());
+        }
+        if self.authority.ne(authority) {
+            return Err(ProgramError::IncorrectAuthority);
+        }
+        Ok(())
+    }
+
+    /// Reject the instruction unless `vault_x` and `vault_y` are the pool's vaults. Their ATA
+    /// derivation was checked once when they were stored, an address comparison is enough.
+    #[inline(always)]
+    pub fn require_vaults(&self, vault_x: &Address, vault_y: &Address) -> Result<(), ProgramError> {
+        require!(
+            self.vault_x.eq(vault_x) && self.vault_y.eq(vault_y),
+            AmmError::InvalidVault
+        );
+        Ok(())
+    }
+
+    /// Reject the instruction unless `mint_x` and `mint_y` are the pool's mints. `TransferChecked`
+    /// only verifies a vault's mint against the mint passed with it, not that it is the pool's.
+    #[inline(always)]
+    pub fn require_mints(&self, mint_x: &Address, mint_y: &Address) -> Result<(), ProgramError> {
+        require!(
+            self.mint_x.eq(mint_x) && self.mint_y.eq(mint_y),
+            ProgramError::InvalidAccountData
+        );
+        Ok(())
+    }
+
+    /// Reject the instruction unless `mint_lp` is the pool's LP mint. Anyone can create a mint
+    /// with the config as its authority, only the stored address tells the pool's apart.
+    #[inline(always)]
+    pub fn require_mint_lp(&self, mint_lp: &Address) -> Result<(), ProgramError> {
+        require!(self.mint_lp.eq(mint_lp), AmmError::InvalidMintLp);
+        Ok(())
+    }
+
+    /// Reject the instruction unless `token_program_x` and `token_program_y` are the token
+    /// programs of the pool's mints. Pools may pair a token mint with a token-2022 one, each
+    /// side's CPIs go through its own program.
+    #[inline(always)]
+    pub fn require_token_programs(
+        &self,
+        token_program_x: &Address,
+        token_program_y: &Address,
+    ) -> Result<(), ProgramError> {
+        require!(
+            self.token_program_x.eq(token_program_x) && self.token_program_y.eq(token_program_y),
+            ProgramError::IncorrectProgramId
+        );
+        Ok(())
+    }
+
+    /// Seeds signing for the config PDA, the same ones `derive_config` derives it from.
+    #[inline(always)]
+    pub fn signer_seeds(&self) -> [Seed<'_>; 5] {
+        [
+            Seed::from(Self::SEED),
+            Seed::from(&self.seed),
+            Seed::from(self.mint_x.as_array()),
+            Seed::from(self.mint_y.as_array()),
+            Seed::from(&self.config_bump),
+        ]
+    }
+
+    /// Re-derive the config PDA from the stored seed, mints and bump, and check it is `address`.
+    /// `signer_seeds` only signs for the account they derive, so a mismatch fails here instead
+    /// of inside the CPI.
+    #[inline(always)]
+    pub fn require_address(&self, address: &Address) -> Result<(), ProgramError> {
+        let derived = Address::derive_address(
+            &[
+                Self::SEED,
+                &self.seed,
+                self.mint_x.as_array(),
+                self.mint_y.as_array(),
+                &self.config_bump,
+            ],
+            None,
+            &crate::ID,
+        );
+        if derived.ne(address) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn load_mut(account_info: &AccountView) -> Result<RefMut<'_, Self>, ProgramError> {
         if account_info.data_len() != Self::LEN {
             return Err(ProgramError::InvalidAccountData);
         }
         if !account_info.owned_by(&crate::ID) {
             return Err(ProgramError::InvalidAccountOwner);
         }
-        Ok(RefMut::map(account_info.try_borrow_mut()?, |data| unsafe {
+        let data = account_info.try_borrow_mut()?;
+        if Self::version_of(&data)? != Self::VERSION {
+            return Err(AmmError::InvalidVersion.into());
+        }
+        Ok(RefMut::map(data, |data| unsafe {
             Self::from_bytes_unchecked_mut(data)
         }))
     }
 
+    /// Set the flags of one of the `AmmState` presets.
     #[inline(always)]
     pub fn set_state(&mut self, state: u8) -> Result<(), ProgramError> {
-        if state.ge(&(AmmState::WithdrawOnly as u8)) {
-            return Err(ProgramError::InvalidAccountData);
+        self.state = AmmState::try_from(state)?.flags();
+        Ok(())
+    }
+
+    /// Set the pool flags, rejecting unknown ones.
+    #[inline(always)]
+    pub fn set_flags(&mut self, flags: u8) -> Result<(), ProgramError> {
+        if flags & !AmmState::ALL_FLAGS != 0 {
+            return Err(ProgramError::InvalidInstructionData);
         }
-        self.state = state as u8;
+        self.state = flags;
+        Ok(())
+    }
+
+    /// Record the vault balances an instruction leaves behind.
+    #[inline(always)]
+    pub fn set_reserves(&mut self, reserve_x: u64, reserve_y: u64) {
+        self.reserve_x = reserve_x.to_le_bytes();
+        self.reserve_y = reserve_y.to_le_bytes();
+    }
+
+    /// Add what an instruction paid into the vaults to the recorded reserves. Tokens sent to
+    /// the vaults any other way stay outside them until `SyncReserves` or `Skim`.
+    pub fn credit_reserves(&mut self, x: u64, y: u64) -> Result<(), ProgramError> {
+        let reserve_x = self.reserve_x().checked_add(x).ok_or(AmmError::MathOverflow)?;
+        let reserve_y = self.reserve_y().checked_add(y).ok_or(AmmError::MathOverflow)?;
+        self.set_reserves(reserve_x, reserve_y);
+        Ok(())
+    }
+
+    /// Take what an instruction paid out of the vaults off the recorded reserves.
+    pub fn debit_reserves(&mut self, x: u64, y: u64) -> Result<(), ProgramError> {
+        let reserve_x = self.reserve_x().checked_sub(x).ok_or(AmmError::MathOverflow)?;
+        let reserve_y = self.reserve_y().checked_sub(y).ok_or(AmmError::MathOverflow)?;
+        self.set_reserves(reserve_x, reserve_y);
         Ok(())
     }
 
+    #[inline(always)]
+    pub fn set_paused_at(&mut self, paused_at: i64) {
+        self.paused_at = paused_at.to_le_bytes();
+    }
+
     #[inline(always)]
     pub fn set_fee(&mut self, fee: u16) -> Result<(), ProgramError> {
-        if fee.ge(&10_000) {
+        self.set_fees(fee, self.protocol_fee_bps())
+    }
+
+    /// Set the LP fee and the protocol share of swap inputs together, as their sum must stay
+    /// below the whole input.
+    #[inline(always)]
+    pub fn set_fees(&mut self, fee: u16, protocol_fee_bps: u16) -> Result<(), ProgramError> {
+        if (fee as u32 + protocol_fee_bps as u32).ge(&10_000) {
             return Err(ProgramError::InvalidAccountData);
         }
         self.fee = fee.to_le_bytes();
+        self.protocol_fee_bps = protocol_fee_bps.to_le_bytes();
+        Ok(())
+    }
+
+    /// Add to the protocol fees held in the vaults.
+    #[inline(always)]
+    pub fn accrue_protocol_fees(&mut self, fees_x: u64, fees_y: u64) -> Result<(), ProgramError> {
+        let fees_x = self
+            .protocol_fees_x()
+            .checked_add(fees_x)
+            .ok_or(AmmError::MathOverflow)?;
+        let fees_y = self
+            .protocol_fees_y()
+            .checked_add(fees_y)
+            .ok_or(AmmError::MathOverflow)?;
+        self.protocol_fees_x = fees_x.to_le_bytes();
+        self.protocol_fees_y = fees_y.to_le_bytes();
         Ok(())
     }
 
+    /// Add the seconds since the last update, priced at the reserves recorded by the last
+    /// instruction, to both price accumulators. Called before an instruction moves the
+    /// reserves, so every interval is priced at the reserves that held throughout it.
+    #[inline(always)]
+    pub fn update_price_cumulative(&mut self, now: i64) {
+        let last = self.last_update_ts();
+        // The clock is not strictly monotonic, never rewind the accumulators
+        if now <= last {
+            return;
+        }
+
+        // An empty pool has no price, and a first update has no interval to price yet
+        let (reserve_x, reserve_y) = self.recorded_pool_reserves();
+        if last != 0 && reserve_x != 0 && reserve_y != 0 {
+            let elapsed = now.abs_diff(last) as u128;
+            self.price_cumulative_x = self
+                .price_cumulative_x()
+                .wrapping_add(price_q64(reserve_y, reserve_x).wrapping_mul(elapsed))
+                .to_le_bytes();
+            self.price_cumulative_y = self
+                .price_cumulative_y()
+                .wrapping_add(price_q64(reserve_x, reserve_y).wrapping_mul(elapsed))
+                .to_le_bytes();
+        }
+        self.last_update_ts = now.to_le_bytes();
+    }
+
+    /// Zero the protocol fee counters once the fees have left the vaults.
+    #[inline(always)]
+    pub fn clear_protocol_fees(&mut self) {
+        self.protocol_fees_x = [0u8; 8];
+        self.protocol_fees_y = [0u8; 8];
+    }
+
     #[inline(always)]
     pub fn set_inner(
         &mut self,
@@ -151,6 +672,7 @@ impl Config {
         fee: u16,
         config_bump: [u8; 1],
     ) -> Result<(), ProgramError> {
+        self.version = Self::VERSION;
         self.set_state(AmmState::Initialized as u8)?;
         self.set_seed(seed);
         self.set_authority(authority);
@@ -163,12 +685,10 @@ impl Config {
 
     #[inline(always)]
     pub fn has_authority(&self) -> Option<Address> {
-        let bytes = self.authority();
-        let chunks: &[u64; 4] = unsafe { &*(bytes.to_bytes().as_ptr() as *const [u64; 4]) };
-        if chunks.iter().any(|&x| x != 0) {
-            Some(self.authority)
-        } else {
+        if self.authority == Address::default() {
             None
+        } else {
+            Some(self.authority)
         }
     }
 
@@ -177,8 +697,9 @@ impl Config {
         self.seed = seed.to_le_bytes();
     }
 
+    /// Set the pool authority, the zero address renouncing it.
     #[inline(always)]
-    fn set_authority(&mut self, authority: Address) {
+    pub fn set_authority(&mut self, authority: Address) {
         self.authority = authority;
     }
 
@@ -192,8 +713,828 @@ impl Config {
         self.mint_y = mint_y;
     }
 
+    /// Record the vault addresses, which the caller must have checked are the config's ATAs.
+    #[inline(always)]
+    pub fn set_vaults(&mut self, vault_x: Address, vault_y: Address) {
+        self.vault_x = vault_x;
+        self.vault_y = vault_y;
+    }
+
+    /// Record the LP mint address, which the caller must have checked is the config's LP PDA.
+    #[inline(always)]
+    pub fn set_mint_lp(&mut self, mint_lp: Address) {
+        self.mint_lp = mint_lp;
+    }
+
+    /// Record the token programs owning each side's mint and vault.
+    #[inline(always)]
+    pub fn set_token_programs(&mut self, token_program_x: Address, token_program_y: Address) {
+        self.token_program_x = token_program_x;
+        self.token_program_y = token_program_y;
+    }
+
     #[inline(always)]
     fn set_config_bump(&mut self, config_bump: [u8; 1]) {
         self.config_bump = config_bump;
     }
 }
+
+/// Registry PDA `["canonical_pool", mint_x, mint_y]` naming the primary pool of a mint pair, so
+/// routers can find it without knowing its seed. At most one exists per pair, set and replaced
+/// by the program's upgrade authority through `SetCanonicalPool`.
+#[repr(C)]
+#[derive(ShankAccount)]
+pub struct CanonicalPool {
+    discriminator: u8, // `CanonicalPool::DISCRIMINATOR`
+    #[idl_type("Pubkey")]
+    config: Address,
+    bump: [u8; 1],
+}
+
+/// Derive the canonical pool PDA of the ordered pair `["canonical_pool", mint_x, mint_y]` and
+/// its canonical bump.
+#[inline(always)]
+pub fn derive_canonical_pool(mint_x: &Address, mint_y: &Address) -> (Address, u8) {
+    Address::find_program_address(
+        &[CanonicalPool::SEED, mint_x.as_array(), mint_y.as_array()],
+        &crate::ID,
+    )
+}
+
+const _: () = {
+    assert!(align_of::<CanonicalPool>() == 1);
+    assert!(CanonicalPool::LEN == 34);
+    // A one pool registry has the same length, only the discriminators tell them apart
+    assert!(CanonicalPool::LEN == Registry::space(1));
+    assert!(CanonicalPool::DISCRIMINATOR != Registry::DISCRIMINATOR);
+};
+
+impl CanonicalPool {
+    pub const SEED: &'static [u8] = b"canonical_pool";
+    pub const LEN: usize = size_of::<Self>();
+
+    /// First byte of every canonical pool account. Configs start with their layout version
+    /// instead, and are told apart by their length.
+    pub const DISCRIMINATOR: u8 = 1;
+
+    #[inline(always)]
+    pub fn load(account_info: &AccountView) -> Result<Ref<'_, Self>, ProgramError> {
+        if account_info.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !account_info.owned_by(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        let data = account_info.try_borrow()?;
+        if data[0] != Self::DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Ref::map(data, |data| unsafe {
+            &*(data.as_ptr() as *const Self)
+        }))
+    }
+
+    #[inline(always)]
+    pub fn load_mut(account_info: &AccountView) -> Result<RefMut<'_, Self>, ProgramError> {
+        if account_info.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !account_info.owned_by(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        let data = account_info.try_borrow_mut()?;
+        if data[0] != Self::DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(RefMut::map(data, |data| unsafe {
+            &mut *(data.as_mut_ptr() as *mut Self)
+        }))
+    }
+
+    /// Write a new canonical pool into `account_info`, freshly created with `Self::LEN` bytes.
+    #[inline(always)]
+    pub fn init(account_info: &AccountView, config: Address, bump: [u8; 1]) -> ProgramResult {
+        if account_info.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut data = account_info.try_borrow_mut()?;
+        data[0] = Self::DISCRIMINATOR;
+        // SAFETY: the length was checked and all fields are bytes or byte arrays
+        unsafe { &mut *(data.as_mut_ptr() as *mut Self) }.set_inner(config, bump);
+        Ok(())
+    }
+
+    /// Config of the canonical pool.
+    #[inline(always)]
+    pub fn config(&self) -> &Address {
+        &self.config
+    }
+
+    #[inline(always)]
+    pub fn bump(&self) -> [u8; 1] {
+        self.bump
+    }
+
+    #[inline(always)]
+    pub fn set_inner(&mut self, config: Address, bump: [u8; 1]) {
+        self.config = config;
+        self.bump = bump;
+    }
+}
+
+/// Registry PDA `["registry", mint_x, mint_y]` listing the config of every pool of a mint pair.
+/// `Initialize` appends each new pool, reallocating the account one address larger, and
+/// `DeregisterPool` swaps the removed entry with the last one, so the order is not stable.
+/// At most `MAX_POOLS` pools are listed, past that `Initialize` fails for the pair.
+#[repr(C)]
+pub struct Registry {
+    discriminator: u8, // `Registry::DISCRIMINATOR`
+    bump: [u8; 1],
+    pools: [Address],
+}
+
+/// Derive the registry PDA of the ordered pair `["registry", mint_x, mint_y]` and its
+/// canonical bump.
+#[inline(always)]
+pub fn derive_registry(mint_x: &Address, mint_y: &Address) -> (Address, u8) {
+    Address::find_program_address(
+        &[Registry::SEED, mint_x.as_array(), mint_y.as_array()],
+        &crate::ID,
+    )
+}
+
+impl Registry {
+    pub const SEED: &'static [u8] = b"registry";
+
+    /// First byte of every registry account.
+    pub const DISCRIMINATOR: u8 = 2;
+
+    /// The discriminator and the bump stored ahead of the pool list.
+    pub const HEADER_LEN: usize = size_of::<u8>() + size_of::<[u8; 1]>();
+
+    /// Most pools a pair's registry lists, keeping the account and scans of it bounded.
+    pub const MAX_POOLS: usize = 64;
+
+    /// Length of a registry listing `pools` pools.
+    #[inline(always)]
+    pub const fn space(pools: usize) -> usize {
+        Self::HEADER_LEN + pools * size_of::<Address>()
+    }
+
+    /// Number of pools listed in registry data `bytes`, rejecting any other length or account
+    /// type.
+    #[inline(always)]
+    fn pools_in(bytes: &[u8]) -> Result<usize, ProgramError> {
+        let pools = bytes
+            .len()
+            .checked_sub(Self::HEADER_LEN)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        if bytes[0] != Self::DISCRIMINATOR
+            || pools % size_of::<Address>() != 0
+            || pools / size_of::<Address>() > Self::MAX_POOLS
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(pools / size_of::<Address>())
+    }
+
+    #[inline(always)]
+    pub fn load(account_info: &AccountView) -> Result<Ref<'_, Self>, ProgramError> {
+        if !account_info.owned_by(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        let data = account_info.try_borrow()?;
+        let pools = Self::pools_in(&data)?;
+        // SAFETY: the length was checked to hold the header and exactly `pools` addresses, all
+        // fields are byte arrays
+        Ok(Ref::map(data, |data| unsafe {
+            &*(core::ptr::slice_from_raw_parts(data.as_ptr(), pools) as *const Self)
+        }))
+    }
+
+    #[inline(always)]
+    pub fn load_mut(account_info: &AccountView) -> Result<RefMut<'_, Self>, ProgramError> {
+        if !account_info.owned_by(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        let data = account_info.try_borrow_mut()?;
+        let pools = Self::pools_in(&data)?;
+        // SAFETY: as in `load`
+        Ok(RefMut::map(data, |data| unsafe {
+            &mut *(core::ptr::slice_from_raw_parts_mut(data.as_mut_ptr(), pools) as *mut Self)
+        }))
+    }
+
+    #[inline(always)]
+    pub fn bump(&self) -> [u8; 1] {
+        self.bump
+    }
+
+    /// Configs of the registered pools.
+    #[inline(always)]
+    pub fn pools(&self) -> &[Address] {
+        &self.pools
+    }
+
+    /// Reject another pool unless the list is below `MAX_POOLS`.
+    #[inline(always)]
+    pub fn require_room(&self) -> Result<(), ProgramError> {
+        require!(self.pools.len() < Self::MAX_POOLS, AmmError::RegistryFull);
+        Ok(())
+    }
+
+    /// Position of `config` in the list, if registered.
+    #[inline(always)]
+    pub fn position(&self, config: &Address) -> Option<usize> {
+        self.pools.iter().position(|pool| pool.eq(config))
+    }
+
+    /// Reject `address` unless it is the registry of the pair `mint_x`, `mint_y`.
+    #[inline(always)]
+    pub fn require_address(
+        &self,
+        address: &Address,
+        mint_x: &Address,
+        mint_y: &Address,
+    ) -> Result<(), ProgramError> {
+        let derived = Address::derive_address(
+            &[Self::SEED, mint_x.as_array(), mint_y.as_array(), &self.bump],
+            None,
+            &crate::ID,
+        );
+        if derived.ne(address) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(())
+    }
+
+    /// Write the header of a new registry into `account_info`, freshly created with room for
+    /// its first pools, and return it.
+    #[inline(always)]
+    pub fn init(
+        account_info: &AccountView,
+        bump: [u8; 1],
+    ) -> Result<RefMut<'_, Self>, ProgramError> {
+        {
+            let mut data = account_info.try_borrow_mut()?;
+            if data.len() < Self::HEADER_LEN {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            data[0] = Self::DISCRIMINATOR;
+            data[1] = bump[0];
+        }
+        Self::load_mut(account_info)
+    }
+
+    /// Overwrite the entry at `index`, which the caller must have sized the account for.
+    #[inline(always)]
+    pub fn set_pool(&mut self, index: usize, config: Address) {
+        self.pools[index] = config;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blueshift_pinocchio_helpers::testing::TestAccount;
+
+    use super::*;
+
+    /// A v0 config, laid out byte by byte as the original program wrote it.
+    fn v0_config(state: AmmState) -> [u8; Config::V0_LEN] {
+        let mut data = [0u8; Config::V0_LEN];
+        data[0] = state as u8;
+        data[1..9].copy_from_slice(&7u64.to_le_bytes());
+        data[9..41].copy_from_slice(&[1; 32]);
+        data[41..73].copy_from_slice(&[2; 32]);
+        data[73..105].copy_from_slice(&[3; 32]);
+        data[105..107].copy_from_slice(&30u16.to_le_bytes());
+        data[107] = 254;
+        data
+    }
+
+    #[test]
+    fn upgrades_a_v0_config() {
+        let v0 = v0_config(AmmState::WithdrawOnly);
+        assert_eq!(Config::version_of(&v0), Ok(Config::V0));
+
+        let raw = Config::upgrade(&v0).unwrap();
+        let config = Config::from_bytes(&raw).unwrap();
+
+        assert_eq!(config.version(), Config::VERSION);
+        assert_eq!(config.state(), AmmState::WITHDRAWALS_ENABLED);
+        assert_eq!(config.seed(), 7);
+        assert_eq!(config.authority(), &Address::new_from_array([1; 32]));
+        assert_eq!(config.mint_x(), &Address::new_from_array([2; 32]));
+        assert_eq!(config.mint_y(), &Address::new_from_array([3; 32]));
+        assert_eq!(config.fee(), 30);
+        assert_eq!(config.config_bump(), [254]);
+        // Fields v0 lacks start out zero
+        assert_eq!(config.recorded_pool_reserves(), (0, 0));
+        assert_eq!(config.protocol_fee_bps(), 0);
+        assert_eq!(config.last_update_ts(), 0);
+        assert_eq!(config.vault_x(), &Address::default());
+    }
+
+    /// A current config upgraded from a v0 one, with a 30 bps fee.
+    fn current_config() -> [u8; Config::LEN] {
+        Config::upgrade(&v0_config(AmmState::Initialized)).unwrap()
+    }
+
+    #[test]
+    fn writes_the_config_at_its_fixed_offsets() {
+        let mut raw = [0u8; Config::LEN];
+        // SAFETY: the buffer holds exactly one config
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+        config
+            .set_inner(
+                7,
+                Address::new_from_array([1; 32]),
+                Address::new_from_array([2; 32]),
+                Address::new_from_array([3; 32]),
+                30,
+                [254],
+            )
+            .unwrap();
+        config.set_fees(30, 5).unwrap();
+        config.set_reserves(1_000, 2_000);
+        config.set_paused_at(-9);
+        config.update_price_cumulative(100);
+        config.update_price_cumulative(110);
+        config.accrue_protocol_fees(11, 12).unwrap();
+        config.set_vaults(
+            Address::new_from_array([4; 32]),
+            Address::new_from_array([5; 32]),
+        );
+        config.set_token_programs(
+            Address::new_from_array([6; 32]),
+            Address::new_from_array([8; 32]),
+        );
+        config.set_mint_lp(Address::new_from_array([9; 32]));
+
+        assert_eq!(raw[0], Config::VERSION);
+        assert_eq!(raw[1], AmmState::Initialized.flags());
+        assert_eq!(raw[2..10], 7u64.to_le_bytes());
+        assert_eq!(raw[10..42], [1; 32]);
+        assert_eq!(raw[42..74], [2; 32]);
+        assert_eq!(raw[74..106], [3; 32]);
+        assert_eq!(raw[106..108], 30u16.to_le_bytes());
+        assert_eq!(raw[108], 254);
+        assert_eq!(raw[109..117], 1_000u64.to_le_bytes());
+        assert_eq!(raw[117..125], 2_000u64.to_le_bytes());
+        assert_eq!(raw[125..133], (-9i64).to_le_bytes());
+        assert_eq!(raw[133..135], 5u16.to_le_bytes());
+        assert_eq!(raw[135..143], 11u64.to_le_bytes());
+        assert_eq!(raw[143..151], 12u64.to_le_bytes());
+        // 10 seconds at 2 Y per X, and so 0.5 X per Y, in Q64.64
+        assert_eq!(raw[151..167], (20u128 << 64).to_le_bytes());
+        assert_eq!(raw[167..183], (5u128 << 64).to_le_bytes());
+        assert_eq!(raw[183..191], 110i64.to_le_bytes());
+        assert_eq!(raw[191..223], [4; 32]);
+        assert_eq!(raw[223..255], [5; 32]);
+        assert_eq!(raw[255..287], [6; 32]);
+        assert_eq!(raw[287..319], [8; 32]);
+        assert_eq!(raw[319..351], [9; 32]);
+    }
+
+    #[test]
+    fn claims_the_protocol_fees_without_touching_the_lp_reserves() {
+        let mut raw = current_config();
+        // SAFETY: `upgrade` returns exactly one config in the current layout
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+        config.set_reserves(1_011, 2_012);
+        config.accrue_protocol_fees(11, 12).unwrap();
+        assert_eq!(config.pool_reserves(1_011, 2_012), (1_000, 2_000));
+        assert_eq!(config.recorded_pool_reserves(), (1_000, 2_000));
+
+        // `ClaimFees` pays the fees out of the vaults, then clears them
+        config.clear_protocol_fees();
+        config.debit_reserves(11, 12).unwrap();
+        assert_eq!((config.protocol_fees_x(), config.protocol_fees_y()), (0, 0));
+        assert_eq!(config.pool_reserves(1_000, 2_000), (1_000, 2_000));
+        assert_eq!(config.recorded_pool_reserves(), (1_000, 2_000));
+    }
+
+    #[test]
+    fn moves_the_reserves_only_by_what_instructions_pay() {
+        let mut raw = current_config();
+        // SAFETY: `upgrade` returns exactly one config in the current layout
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+        config.set_reserves(1_000, 2_000);
+
+        // A deposit, then a swap of 100 X for 180 Y
+        config.credit_reserves(100, 200).unwrap();
+        config.credit_reserves(100, 0).unwrap();
+        config.debit_reserves(0, 180).unwrap();
+        assert_eq!(config.recorded_pool_reserves(), (1_200, 2_020));
+
+        // Tokens sent straight to a vault show up in neither the price nor a shortfall
+        assert_eq!(config.pool_reserves(5_000, 2_020), (5_000, 2_020));
+        assert_eq!(config.recorded_pool_reserves(), (1_200, 2_020));
+        assert!(!config.is_short(5_000, 2_020));
+
+        assert_eq!(
+            config.debit_reserves(1_201, 0),
+            Err(AmmError::MathOverflow.into())
+        );
+        assert_eq!(
+            config.credit_reserves(0, u64::MAX),
+            Err(AmmError::MathOverflow.into())
+        );
+        assert_eq!(config.recorded_pool_reserves(), (1_200, 2_020));
+    }
+
+    #[test]
+    fn sets_fees_below_the_whole_input() {
+        let mut raw = current_config();
+        // SAFETY: `upgrade` returns exactly one config in the current layout
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+
+        assert_eq!(config.set_fees(30, 9_969), Ok(()));
+        assert_eq!((config.fee(), config.protocol_fee_bps()), (30, 9_969));
+
+        assert_eq!(
+            config.set_fees(31, 9_969),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            config.set_fees(u16::MAX, u16::MAX),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!((config.fee(), config.protocol_fee_bps()), (30, 9_969));
+
+        // `set_fee` keeps the protocol share, and the bound it has to respect
+        assert_eq!(config.set_fee(20), Ok(()));
+        assert_eq!((config.fee(), config.protocol_fee_bps()), (20, 9_969));
+        assert_eq!(config.set_fee(31), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn accumulates_the_price_over_time() {
+        let mut raw = current_config();
+        // SAFETY: `upgrade` returns exactly one config in the current layout
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+        config.set_reserves(1_000, 2_000);
+
+        // The first update has no interval to price yet
+        config.update_price_cumulative(100);
+        assert_eq!(config.last_update_ts(), 100);
+        assert_eq!(config.price_cumulative_x(), 0);
+
+        // 10 seconds at 2 Y per X, and so 0.5 X per Y, in Q64.64
+        config.update_price_cumulative(110);
+        assert_eq!(config.last_update_ts(), 110);
+        assert_eq!(config.price_cumulative_x(), 20 << 64);
+        assert_eq!(config.price_cumulative_y(), 5 << 64);
+    }
+
+    #[test]
+    fn never_rewinds_the_price_accumulators() {
+        let mut raw = current_config();
+        // SAFETY: `upgrade` returns exactly one config in the current layout
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+        config.set_reserves(1_000, 2_000);
+        config.update_price_cumulative(100);
+        config.update_price_cumulative(110);
+
+        for now in [110, 50] {
+            config.update_price_cumulative(now);
+            assert_eq!(config.last_update_ts(), 110);
+            assert_eq!(config.price_cumulative_x(), 20 << 64);
+        }
+    }
+
+    #[test]
+    fn prices_nothing_while_the_pool_is_empty() {
+        let mut raw = current_config();
+        // SAFETY: `upgrade` returns exactly one config in the current layout
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+        config.update_price_cumulative(100);
+        config.update_price_cumulative(110);
+
+        assert_eq!(config.last_update_ts(), 110);
+        assert_eq!(config.price_cumulative_x(), 0);
+        assert_eq!(config.price_cumulative_y(), 0);
+    }
+
+    #[test]
+    fn wraps_the_price_accumulators_around() {
+        let mut raw = current_config();
+        // SAFETY: `upgrade` returns exactly one config in the current layout
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+        // The highest price a pool can quote, for the longest interval there is
+        config.set_reserves(1, u64::MAX);
+        config.update_price_cumulative(1);
+        config.update_price_cumulative(i64::MAX);
+
+        let price = (u64::MAX as u128) << 64;
+        assert_eq!(
+            config.price_cumulative_x(),
+            price.wrapping_mul(i64::MAX as u128 - 1)
+        );
+    }
+
+    #[test]
+    fn requires_the_token_program_of_each_side() {
+        let token_2022 = pinocchio_token_2022::ID;
+        let mut raw = Config::upgrade(&v0_config(AmmState::Initialized)).unwrap();
+        // SAFETY: `upgrade` returns exactly one config in the current layout
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+        config.set_token_programs(pinocchio_token::ID, token_2022);
+
+        assert_eq!(
+            config.require_token_programs(&pinocchio_token::ID, &token_2022),
+            Ok(())
+        );
+        for (token_program_x, token_program_y) in [
+            (pinocchio_token::ID, pinocchio_token::ID),
+            (token_2022, token_2022),
+            (token_2022, pinocchio_token::ID),
+        ] {
+            assert_eq!(
+                config.require_token_programs(&token_program_x, &token_program_y),
+                Err(ProgramError::IncorrectProgramId)
+            );
+        }
+    }
+
+    #[test]
+    fn requires_the_pool_mints_in_order() {
+        let raw = Config::upgrade(&v0_config(AmmState::Initialized)).unwrap();
+        let config = Config::from_bytes(&raw).unwrap();
+        let (mint_x, mint_y) = (
+            Address::new_from_array([2; 32]),
+            Address::new_from_array([3; 32]),
+        );
+
+        assert_eq!(config.require_mints(&mint_x, &mint_y), Ok(()));
+        for (mint_x, mint_y) in [
+            (mint_y, mint_x),
+            (mint_x, mint_x),
+            (Address::new_from_array([4; 32]), mint_y),
+        ] {
+            assert_eq!(
+                config.require_mints(&mint_x, &mint_y),
+                Err(ProgramError::InvalidAccountData)
+            );
+        }
+    }
+
+    #[test]
+    fn requires_the_stored_lp_mint() {
+        let mut raw = Config::upgrade(&v0_config(AmmState::Initialized)).unwrap();
+        // SAFETY: `upgrade` returns exactly one config in the current layout
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+        let address = Address::new_from_array([8; 32]);
+        let (mint_lp, _) = derive_mint_lp(&address);
+        config.set_mint_lp(mint_lp);
+
+        assert_eq!(config.require_mint_lp(&mint_lp), Ok(()));
+        for foreign in [Address::new_from_array([9; 32]), derive_mint_lp(&mint_lp).0] {
+            assert_eq!(
+                config.require_mint_lp(&foreign),
+                Err(AmmError::InvalidMintLp.into())
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_upgrading_a_current_config() {
+        let v0 = v0_config(AmmState::Initialized);
+        let raw = Config::upgrade(&v0).unwrap();
+        assert_eq!(
+            Config::upgrade(&raw).err(),
+            Some(AmmError::InvalidVersion.into())
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_versions_and_lengths() {
+        let mut raw = Config::upgrade(&v0_config(AmmState::Initialized)).unwrap();
+        raw[0] = Config::VERSION + 1;
+        assert_eq!(
+            Config::version_of(&raw).err(),
+            Some(AmmError::InvalidVersion.into())
+        );
+        assert_eq!(
+            Config::version_of(&raw[..Config::LEN - 1]).err(),
+            Some(AmmError::InvalidVersion.into())
+        );
+    }
+
+    #[test]
+    fn loads_only_the_current_version_for_writing() {
+        let address = Address::new_from_array([8; 32]);
+        let mut raw = Config::upgrade(&v0_config(AmmState::Initialized)).unwrap();
+        let mut config = TestAccount::new(address, crate::ID, &raw);
+        assert!(Config::load_mut(&config.view()).is_ok());
+
+        raw[0] = Config::VERSION + 1;
+        let mut config = TestAccount::new(address, crate::ID, &raw);
+        assert_eq!(
+            Config::load_mut(&config.view()).err(),
+            Some(AmmError::InvalidVersion.into())
+        );
+    }
+
+    #[test]
+    fn derives_the_config_the_program_signs_for() {
+        let (mint_x, mint_y) = (
+            Address::new_from_array([2; 32]),
+            Address::new_from_array([3; 32]),
+        );
+
+        for seed in [0, 7, u64::MAX] {
+            let (address, bump) = derive_config(seed, &mint_x, &mint_y);
+            let derived = Address::derive_address(
+                &[
+                    Config::SEED,
+                    &seed.to_le_bytes(),
+                    mint_x.as_array(),
+                    mint_y.as_array(),
+                    &[bump],
+                ],
+                None,
+                &crate::ID,
+            );
+            assert_eq!(derived, address);
+            // The mints are ordered seeds, swapping them is another pool
+            assert_ne!(derive_config(seed, &mint_y, &mint_x).0, address);
+
+            let mut raw = [0u8; Config::LEN];
+            // SAFETY: the buffer holds exactly one config
+            let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+            config
+                .set_inner(seed, Address::default(), mint_x, mint_y, 30, [bump])
+                .unwrap();
+            assert_eq!(config.require_address(&address), Ok(()));
+            assert_eq!(
+                config.require_address(&derive_config(seed.wrapping_add(1), &mint_x, &mint_y).0),
+                Err(ProgramError::InvalidSeeds)
+            );
+        }
+    }
+
+    #[test]
+    fn sets_and_reads_back_every_state() {
+        let mut raw = current_config();
+        // SAFETY: `upgrade` returns exactly one config in the current layout
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+
+        for state in [
+            AmmState::Uninitialized,
+            AmmState::Initialized,
+            AmmState::Disabled,
+            AmmState::WithdrawOnly,
+        ] {
+            assert!(matches!(AmmState::try_from(state as u8), Ok(s) if s as u8 == state as u8));
+            assert_eq!(config.set_state(state as u8), Ok(()));
+            assert_eq!(config.state(), state.flags());
+        }
+        assert_eq!(config.state(), AmmState::WITHDRAWALS_ENABLED);
+    }
+
+    #[test]
+    fn rejects_unknown_states() {
+        let mut raw = current_config();
+        // SAFETY: `upgrade` returns exactly one config in the current layout
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut raw) };
+
+        for state in [4, 5, u8::MAX] {
+            assert!(AmmState::try_from(state).is_err());
+            assert_eq!(
+                config.set_state(state),
+                Err(ProgramError::InvalidAccountData)
+            );
+            assert_eq!(config.state(), AmmState::ALL_FLAGS);
+        }
+    }
+
+    #[test]
+    fn reads_a_config_from_a_misaligned_buffer() {
+        // Every other offset of a wider buffer is odd for at least one of the two reads
+        let mut buffer = [0u8; Config::LEN + 1];
+        for offset in [0, 1] {
+            buffer[offset..offset + Config::LEN].copy_from_slice(&current_config());
+            let config = Config::from_bytes(&buffer[offset..offset + Config::LEN]).unwrap();
+
+            assert_eq!(config.seed(), 7);
+            assert_eq!(config.mint_y(), &Address::new_from_array([3; 32]));
+            assert_eq!(config.fee(), 30);
+        }
+    }
+
+    #[test]
+    fn rejects_a_v0_config_with_an_unknown_state() {
+        let mut v0 = v0_config(AmmState::Initialized);
+        v0[0] = 4;
+        assert_eq!(
+            Config::upgrade(&v0).err(),
+            Some(ProgramError::InvalidAccountData)
+        );
+    }
+
+    /// A registry listing `pools` distinct configs.
+    fn registry_data(pools: usize) -> Vec<u8> {
+        let mut data = vec![Registry::DISCRIMINATOR, 255];
+        for pool in 0..pools {
+            data.extend_from_slice(&[pool as u8; 32]);
+        }
+        data
+    }
+
+    #[test]
+    fn tells_a_one_pool_registry_from_a_canonical_pool() {
+        let address = Address::new_from_array([9; 32]);
+        let mut registry = TestAccount::new(address, crate::ID, &registry_data(1));
+        assert_eq!(registry.data().len(), CanonicalPool::LEN);
+
+        let registry = registry.view();
+        assert_eq!(Registry::load(&registry).unwrap().pools().len(), 1);
+        assert_eq!(
+            CanonicalPool::load(&registry).err(),
+            Some(ProgramError::InvalidAccountData)
+        );
+
+        let mut canonical_pool = TestAccount::new(address, crate::ID, &[0; CanonicalPool::LEN]);
+        let canonical_pool = canonical_pool.view();
+        CanonicalPool::init(&canonical_pool, Address::new_from_array([7; 32]), [254]).unwrap();
+        assert_eq!(
+            CanonicalPool::load(&canonical_pool).unwrap().config(),
+            &Address::new_from_array([7; 32])
+        );
+        assert_eq!(
+            Registry::load(&canonical_pool).err(),
+            Some(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn lists_up_to_max_pools() {
+        let mut registry = TestAccount::new(
+            Address::new_from_array([9; 32]),
+            crate::ID,
+            &registry_data(Registry::MAX_POOLS - 1),
+        );
+        let view = registry.view();
+        let registry_data = Registry::load(&view).unwrap();
+        assert_eq!(registry_data.pools().len(), Registry::MAX_POOLS - 1);
+        assert_eq!(registry_data.bump(), [255]);
+        assert_eq!(
+            registry_data.position(&Address::new_from_array([62; 32])),
+            Some(62)
+        );
+        assert_eq!(registry_data.require_room(), Ok(()));
+    }
+
+    #[test]
+    fn refuses_a_pool_past_max_pools() {
+        let address = Address::new_from_array([9; 32]);
+        let mut full = TestAccount::new(address, crate::ID, &registry_data(Registry::MAX_POOLS));
+        assert_eq!(
+            Registry::load(&full.view()).unwrap().require_room(),
+            Err(AmmError::RegistryFull.into())
+        );
+
+        let mut over = TestAccount::new(
+            address,
+            crate::ID,
+            &registry_data(Registry::MAX_POOLS + 1),
+        );
+        assert_eq!(
+            Registry::load(&over.view()).err(),
+            Some(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn writes_the_registry_header_on_init() {
+        let mut registry = TestAccount::new(
+            Address::new_from_array([9; 32]),
+            crate::ID,
+            &[0; Registry::HEADER_LEN + 32],
+        );
+        let view = registry.view();
+        assert_eq!(
+            Registry::load(&view).err(),
+            Some(ProgramError::InvalidAccountData)
+        );
+
+        Registry::init(&view, [253])
+            .unwrap()
+            .set_pool(0, Address::new_from_array([4; 32]));
+        let mut expected = vec![Registry::DISCRIMINATOR, 253];
+        expected.extend_from_slice(&[4; 32]);
+        assert_eq!(registry.data(), &expected[..]);
+    }
+
+    #[test]
+    fn rejects_a_partial_registry_entry() {
+        let mut data = registry_data(2);
+        data.pop();
+        let mut registry = TestAccount::new(Address::new_from_array([9; 32]), crate::ID, &data);
+        assert_eq!(
+            Registry::load(&registry.view()).err(),
+            Some(ProgramError::InvalidAccountData)
+        );
+    }
+}
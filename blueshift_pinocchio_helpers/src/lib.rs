@@ -0,0 +1,486 @@
+//! Token account helpers, the upgrade authority check and check macros shared by the pinocchio
+//! escrow and AMM. Every token check accepts both the token and the token-2022 program, whose
+//! base mint and account layouts are the same.
+
+#![no_std]
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+use pinocchio::{
+    cpi::Signer,
+    error::ProgramError,
+    sysvars::{rent::Rent, Sysvar},
+    AccountView, Address, ProgramResult,
+};
+use pinocchio_associated_token_account::instructions::Create;
+use pinocchio_system::instructions::CreateAccount;
+use pinocchio_token::state::{Mint, TokenAccount};
+use pinocchio_token_2022::{
+    instructions::{Burn, CloseAccount, InitializeMint2, MintTo, TransferChecked},
+    ID as TOKEN_2022_PROGRAM_ID,
+};
+
+const TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET: usize = 165;
+const TOKEN_2022_MINT_DISCRIMINATOR: u8 = 0x01;
+const TOKEN_2022_TOKEN_ACCOUNT_DISCRIMINATOR: u8 = 0x02;
+
+/// Check `account` is owned by a token program and holds `len` bytes, or for token-2022 an
+/// extended account tagged with `discriminator`.
+fn check_token_layout(account: &AccountView, len: usize, discriminator: u8) -> ProgramResult {
+    if !account.owned_by(&TOKEN_2022_PROGRAM_ID) {
+        if !account.owned_by(&pinocchio_token::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        if account.data_len().ne(&len) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+    } else {
+        let data = account.try_borrow()?;
+
+        if data.len().ne(&len) {
+            if data.len().le(&TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if data[TOKEN_2022_ACCOUNT_DISCRIMINATOR_OFFSET].ne(&discriminator) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Token program owning `account`, the program every CPI on it has to go through.
+pub fn token_program_of(account: &AccountView) -> Result<&'static Address, ProgramError> {
+    if account.owned_by(&TOKEN_2022_PROGRAM_ID) {
+        Ok(&TOKEN_2022_PROGRAM_ID)
+    } else if account.owned_by(&pinocchio_token::ID) {
+        Ok(&pinocchio_token::ID)
+    } else {
+        Err(ProgramError::InvalidAccountOwner)
+    }
+}
+
+pub struct MintInterface;
+
+impl MintInterface {
+    pub fn check(account: &AccountView) -> ProgramResult {
+        check_token_layout(account, Mint::LEN, TOKEN_2022_MINT_DISCRIMINATOR)
+    }
+
+    /// Validate `account` as a mint and read it.
+    pub fn read(account: &AccountView) -> Result<&Mint, ProgramError> {
+        Self::check(account)?;
+
+        // SAFETY: owner and size were validated above, the base mint layout is shared by
+        // both token programs
+        Ok(unsafe { Mint::from_bytes_unchecked(account.borrow_unchecked()) })
+    }
+
+    /// Decimals of `account`, valid for both token programs.
+    pub fn decimals(account: &AccountView) -> Result<u8, ProgramError> {
+        Self::check(account)?;
+
+        // After the 36 byte mint authority option and the 8 byte supply
+        let data = account.try_borrow()?;
+        Ok(data[44])
+    }
+
+    /// Create `account` as a mint of `token_program`, `signers` signing for its address.
+    pub fn init(
+        account: &AccountView,
+        payer: &AccountView,
+        decimals: u8,
+        mint_authority: &Address,
+        freeze_authority: Option<&Address>,
+        signers: &[Signer],
+        token_program: &Address,
+    ) -> ProgramResult {
+        let mint_lamport = Rent::get()?.try_minimum_balance(Mint::LEN)?;
+        CreateAccount {
+            from: payer,
+            to: account,
+            lamports: mint_lamport,
+            space: Mint::LEN as u64,
+            owner: token_program,
+        }
+        .invoke_signed(signers)?;
+
+        InitializeMint2 {
+            mint: account,
+            decimals,
+            mint_authority,
+            freeze_authority,
+            token_program,
+        }
+        .invoke()
+    }
+
+    pub fn init_if_need(
+        account: &AccountView,
+        payer: &AccountView,
+        decimals: u8,
+        mint_authoriy: &Address,
+        freeze_authority: Option<&Address>,
+        signers: &[Signer],
+        token_program: &Address,
+    ) -> ProgramResult {
+        if Self::check(account).is_err() {
+            Self::init(
+                account,
+                payer,
+                decimals,
+                mint_authoriy,
+                freeze_authority,
+                signers,
+                token_program,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// `MintTo` `amount` of `mint` into `account`, through whichever token program owns the
+    /// mint.
+    pub fn mint_to(
+        mint: &AccountView,
+        account: &AccountView,
+        mint_authority: &AccountView,
+        amount: u64,
+        signers: &[Signer],
+    ) -> ProgramResult {
+        Self::check(mint)?;
+
+        MintTo {
+            mint,
+            account,
+            mint_authority,
+            amount,
+            token_program: token_program_of(mint)?,
+        }
+        .invoke_signed(signers)
+    }
+}
+
+pub struct TokenInterface;
+
+impl TokenInterface {
+    pub fn check(account: &AccountView) -> ProgramResult {
+        check_token_layout(
+            account,
+            TokenAccount::LEN,
+            TOKEN_2022_TOKEN_ACCOUNT_DISCRIMINATOR,
+        )
+    }
+
+    /// Validate `account` as a token account and read it.
+    pub fn read(account: &AccountView) -> Result<&TokenAccount, ProgramError> {
+        Self::check(account)?;
+
+        // SAFETY: owner and size were validated above, the base account layout is shared by
+        // both token programs
+        Ok(unsafe { TokenAccount::from_bytes_unchecked(account.borrow_unchecked()) })
+    }
+
+    // Both token programs share the base account layout, so the fields
+    // sit at the same offsets whatever extensions follow them
+
+    /// Mint of `account`, valid for both token programs.
+    pub fn mint(account: &AccountView) -> Result<Address, ProgramError> {
+        Self::check(account)?;
+
+        let data = account.try_borrow()?;
+        Ok(Address::new_from_array(data[0..32].try_into().unwrap()))
+    }
+
+    /// Owner of `account`, valid for both token programs.
+    pub fn owner(account: &AccountView) -> Result<Address, ProgramError> {
+        Self::check(account)?;
+
+        let data = account.try_borrow()?;
+        Ok(Address::new_from_array(data[32..64].try_into().unwrap()))
+    }
+
+    /// Token balance of `account`, valid for both token programs.
+    pub fn amount(account: &AccountView) -> Result<u64, ProgramError> {
+        Self::check(account)?;
+
+        let data = account.try_borrow()?;
+        Ok(u64::from_le_bytes(data[64..72].try_into().unwrap()))
+    }
+
+    /// `TransferChecked` `amount` of `mint` out of `from`, through whichever token program
+    /// owns it. Token-2022 transfer fees come out of what `to` receives.
+    pub fn transfer_checked(
+        from: &AccountView,
+        mint: &AccountView,
+        to: &AccountView,
+        authority: &AccountView,
+        amount: u64,
+        signers: &[Signer],
+    ) -> ProgramResult {
+        Self::check(from)?;
+        let decimals = MintInterface::decimals(mint)?;
+        let token_program = token_program_of(from)?;
+
+        TransferChecked {
+            from,
+            mint,
+            to,
+            authority,
+            amount,
+            decimals,
+            token_program,
+        }
+        .invoke_signed(signers)
+    }
+
+    /// `CloseAccount` on `account`, through whichever token program owns it.
+    pub fn close_account(
+        account: &AccountView,
+        destination: &AccountView,
+        authority: &AccountView,
+        signers: &[Signer],
+    ) -> ProgramResult {
+        Self::check(account)?;
+        let token_program = token_program_of(account)?;
+
+        CloseAccount {
+            account,
+            destination,
+            authority,
+            token_program,
+        }
+        .invoke_signed(signers)
+    }
+
+    /// `Burn` `amount` of `mint` out of `account`, through whichever token program owns it.
+    pub fn burn(
+        account: &AccountView,
+        mint: &AccountView,
+        authority: &AccountView,
+        amount: u64,
+        signers: &[Signer],
+    ) -> ProgramResult {
+        Self::check(account)?;
+
+        Burn {
+            account,
+            mint,
+            authority,
+            amount,
+            token_program: token_program_of(account)?,
+        }
+        .invoke_signed(signers)
+    }
+}
+
+pub struct AssociatedTokenAccount;
+
+impl AssociatedTokenAccount {
+    /// Check `account` is the associated token account of `authority` for `mint`.
+    pub fn check(
+        account: &AccountView,
+        authority: &Address,
+        mint: &Address,
+        token_program: &Address,
+    ) -> ProgramResult {
+        TokenInterface::check(account)?;
+
+        if Self::address(authority, mint, token_program).ne(account.address()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        Ok(())
+    }
+
+    /// Address of the associated token account of `authority` for `mint`, whether or not it
+    /// exists yet.
+    pub fn address(authority: &Address, mint: &Address, token_program: &Address) -> Address {
+        Address::find_program_address(
+            &[
+                authority.as_array(),
+                token_program.as_array(),
+                mint.as_array(),
+            ],
+            &pinocchio_associated_token_account::ID,
+        )
+        .0
+    }
+
+    pub fn init(
+        account: &AccountView,
+        mint: &AccountView,
+        payer: &AccountView,
+        owner: &AccountView,
+        system_program: &AccountView,
+        token_program: &AccountView,
+    ) -> ProgramResult {
+        Create {
+            funding_account: payer,
+            account,
+            wallet: owner,
+            mint,
+            system_program,
+            token_program,
+        }
+        .invoke()
+    }
+
+    pub fn init_if_needed(
+        account: &AccountView,
+        mint: &AccountView,
+        payer: &AccountView,
+        owner: &AccountView,
+        system_program: &AccountView,
+        token_program: &AccountView,
+    ) -> ProgramResult {
+        match Self::check(
+            account,
+            owner.address(),
+            mint.address(),
+            token_program.address(),
+        ) {
+            Ok(_) => Ok(()),
+            Err(_) => Self::init(account, mint, payer, owner, system_program, token_program),
+        }
+    }
+}
+
+/// The program data account the upgradeable loader keeps for a program, recording who may
+/// upgrade it.
+pub struct ProgramData;
+
+impl ProgramData {
+    pub const LOADER_ID: Address =
+        Address::from_str_const("BPFLoaderUpgradeab1e11111111111111111111111");
+
+    /// Address of the program data of `program_id`.
+    pub fn address(program_id: &Address) -> Address {
+        Address::find_program_address(&[program_id.as_ref()], &Self::LOADER_ID).0
+    }
+
+    /// Check `account` is the program data of `program_id` and `authority` its upgrade
+    /// authority.
+    pub fn check_upgrade_authority(
+        account: &AccountView,
+        program_id: &Address,
+        authority: &Address,
+    ) -> ProgramResult {
+        if Self::address(program_id).ne(account.address()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if !account.owned_by(&Self::LOADER_ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        // `ProgramData { slot: u64, upgrade_authority: Option<Address> }` behind a u32 tag
+        let data = account.try_borrow()?;
+        if data.len() < 45 || data[0..4] != [3, 0, 0, 0] {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // An immutable program has no one left to configure it
+        if data[12] != 1 || data[13..45] != *authority.as_array() {
+            return Err(ProgramError::IncorrectAuthority);
+        }
+
+        Ok(())
+    }
+}
+
+/// Return `err` from the enclosing function unless `cond` holds. `err` is converted with
+/// `Into`, so a program error enum works as well as a `ProgramError`.
+#[macro_export]
+macro_rules! require {
+    ($cond:expr, $err:expr $(,)?) => {
+        if !($cond) {
+            return Err($err.into());
+        }
+    };
+}
+
+/// Return `err` from the enclosing function unless `left == right`.
+#[macro_export]
+macro_rules! require_eq {
+    ($left:expr, $right:expr, $err:expr $(,)?) => {
+        if ($left) != ($right) {
+            return Err($err.into());
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::TestAccount;
+    use super::*;
+
+    #[test]
+    fn token_program_of_names_the_owning_token_program() {
+        for token_program in [pinocchio_token::ID, TOKEN_2022_PROGRAM_ID] {
+            let mut mint = TestAccount::mint(Address::new_from_array([1; 32]), token_program, 6);
+            assert_eq!(token_program_of(&mint.view()), Ok(&token_program));
+        }
+
+        let mut account = TestAccount::empty(Address::new_from_array([1; 32]));
+        assert_eq!(
+            token_program_of(&account.view()),
+            Err(ProgramError::InvalidAccountOwner)
+        );
+    }
+
+    #[test]
+    fn accepts_an_associated_token_account_off_the_first_bump() {
+        let mint = Address::new_from_array([9; 32]);
+        let token_program = pinocchio_token::ID;
+        // About half of all wallets need a bump below 255 for their ATA
+        let (wallet, ata) = (1..=u8::MAX)
+            .map(|i| Address::new_from_array([i; 32]))
+            .find_map(|wallet| {
+                let (ata, bump) = Address::find_program_address(
+                    &[wallet.as_array(), token_program.as_array(), mint.as_array()],
+                    &pinocchio_associated_token_account::ID,
+                );
+                (bump < u8::MAX).then_some((wallet, ata))
+            })
+            .unwrap();
+
+        let mut account = TestAccount::token(ata, token_program, &mint, &wallet, 0);
+        assert_eq!(
+            AssociatedTokenAccount::check(&account.view(), &wallet, &mint, &token_program),
+            Ok(())
+        );
+
+        let other_wallet = Address::new_from_array([0; 32]);
+        assert_eq!(
+            AssociatedTokenAccount::check(&account.view(), &other_wallet, &mint, &token_program),
+            Err(ProgramError::InvalidSeeds)
+        );
+    }
+
+    #[test]
+    fn checks_the_upgrade_authority_of_a_program() {
+        let program_id = Address::new_from_array([5; 32]);
+        let authority = Address::new_from_array([6; 32]);
+
+        let mut program_data = TestAccount::program_data(&program_id, Some(&authority));
+        assert_eq!(
+            ProgramData::check_upgrade_authority(&program_data.view(), &program_id, &authority),
+            Ok(())
+        );
+        assert_eq!(
+            ProgramData::check_upgrade_authority(&program_data.view(), &program_id, &program_id),
+            Err(ProgramError::IncorrectAuthority)
+        );
+        assert_eq!(
+            ProgramData::check_upgrade_authority(&program_data.view(), &authority, &authority),
+            Err(ProgramError::InvalidSeeds)
+        );
+
+        let mut immutable = TestAccount::program_data(&program_id, None);
+        assert_eq!(
+            ProgramData::check_upgrade_authority(&immutable.view(), &program_id, &authority),
+            Err(ProgramError::IncorrectAuthority)
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Accounts laid out the way the runtime passes them to a program, so instruction handlers can
+//! be unit tested off-chain up to their first CPI or sysvar read.
+
+extern crate std;
+
+use core::mem::size_of;
+use std::{vec, vec::Vec};
+
+use pinocchio::{
+    account::{RuntimeAccount, NOT_BORROWED},
+    AccountView, Address,
+};
+use pinocchio_token::state::{Mint, TokenAccount};
+
+use crate::ProgramData;
+
+/// One account: the runtime header followed by the account data.
+pub struct TestAccount {
+    // Whole words keep the header and the data 8-byte aligned, as the runtime does
+    buffer: Vec<u64>,
+}
+
+impl TestAccount {
+    pub fn new(address: Address, owner: Address, data: &[u8]) -> Self {
+        const HEADER_LEN: usize = size_of::<RuntimeAccount>();
+
+        let mut buffer = vec![0u64; (HEADER_LEN + data.len()).div_ceil(size_of::<u64>())];
+        let raw = buffer.as_mut_ptr() as *mut RuntimeAccount;
+
+        // SAFETY: the buffer holds the header and `data.len()` bytes after it
+        unsafe {
+            raw.write(RuntimeAccount {
+                borrow_state: NOT_BORROWED,
+                is_signer: 0,
+                is_writable: 1,
+                executable: 0,
+                resize_delta: 0,
+                address,
+                owner,
+                lamports: 1_000_000_000,
+                data_len: data.len() as u64,
+            });
+            core::ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                (raw as *mut u8).add(HEADER_LEN),
+                data.len(),
+            );
+        }
+
+        Self { buffer }
+    }
+
+    /// An account holding no data, owned by the system program.
+    pub fn empty(address: Address) -> Self {
+        Self::new(address, Address::default(), &[])
+    }
+
+    /// A token program account of `mint` owned by `owner`, holding `amount`.
+    pub fn token(
+        address: Address,
+        token_program: Address,
+        mint: &Address,
+        owner: &Address,
+        amount: u64,
+    ) -> Self {
+        Self::new(
+            address,
+            token_program,
+            &token_account_data(mint, owner, amount),
+        )
+    }
+
+    /// A token program mint with `decimals`.
+    pub fn mint(address: Address, token_program: Address, decimals: u8) -> Self {
+        Self::new(address, token_program, &mint_data(decimals))
+    }
+
+    /// The program data of `program_id` under the upgradeable loader, immutable without an
+    /// `upgrade_authority`.
+    pub fn program_data(program_id: &Address, upgrade_authority: Option<&Address>) -> Self {
+        let mut data = [0u8; 45];
+        data[0] = 3;
+        if let Some(authority) = upgrade_authority {
+            data[12] = 1;
+            data[13..45].copy_from_slice(authority.as_ref());
+        }
+        Self::new(
+            ProgramData::address(program_id),
+            ProgramData::LOADER_ID,
+            &data,
+        )
+    }
+
+    pub fn signer(mut self) -> Self {
+        self.header_mut().is_signer = 1;
+        self
+    }
+
+    pub fn view(&mut self) -> AccountView {
+        // SAFETY: the buffer starts with a header whose `data_len` matches the data after it
+        unsafe { AccountView::new_unchecked(self.buffer.as_mut_ptr() as *mut RuntimeAccount) }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        let len = self.header().data_len as usize;
+        // SAFETY: `data_len` bytes follow the header
+        unsafe {
+            core::slice::from_raw_parts(
+                (self.buffer.as_ptr() as *const u8).add(size_of::<RuntimeAccount>()),
+                len,
+            )
+        }
+    }
+
+    fn header(&self) -> &RuntimeAccount {
+        // SAFETY: the buffer starts with a header
+        unsafe { &*(self.buffer.as_ptr() as *const RuntimeAccount) }
+    }
+
+    fn header_mut(&mut self) -> &mut RuntimeAccount {
+        // SAFETY: the buffer starts with a header
+        unsafe { &mut *(self.buffer.as_mut_ptr() as *mut RuntimeAccount) }
+    }
+}
+
+/// Base token account layout: mint, owner, amount, then an initialized state byte.
+pub fn token_account_data(mint: &Address, owner: &Address, amount: u64) -> [u8; TokenAccount::LEN] {
+    let mut data = [0u8; TokenAccount::LEN];
+    data[0..32].copy_from_slice(mint.as_ref());
+    data[32..64].copy_from_slice(owner.as_ref());
+    data[64..72].copy_from_slice(&amount.to_le_bytes());
+    data[108] = 1;
+    data
+}
+
+/// Base mint layout: no mint authority, no supply, `decimals`, initialized.
+pub fn mint_data(decimals: u8) -> [u8; Mint::LEN] {
+    let mut data = [0u8; Mint::LEN];
+    data[44] = decimals;
+    data[45] = 1;
+    data
+}
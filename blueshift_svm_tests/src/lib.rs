@@ -0,0 +1 @@
+//! SVM tests of the escrow and AMM programs, run against their built SBF binaries.
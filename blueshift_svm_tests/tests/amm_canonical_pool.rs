@@ -0,0 +1,47 @@
+//! A pair has at most one canonical pool: `Initialize` names the first pool that asks, and
+//! any later pool of the pair asking as well is refused.
+
+mod common;
+
+use common::amm::{canonical_pool_address, Pool};
+use common::*;
+use mollusk_svm::result::ProgramResult;
+use solana_program_error::ProgramError;
+
+/// Config the canonical pool account of `pool`'s pair names.
+fn canonical_config(pool: &Pool) -> [u8; 32] {
+    let canonical_pool = canonical_pool_address(&pool.mint_x, &pool.mint_y);
+    let account = account(&pool.svm, &canonical_pool).expect("canonical pool exists");
+    assert_eq!(account.owner, PROGRAM_ID);
+    account.data[1..33].try_into().unwrap()
+}
+
+#[test]
+fn second_canonical_pool_of_a_pair_fails() {
+    let first = Pool::uninitialized(amm_svm(), 1);
+    first.run(&first.initialize(true));
+    assert_eq!(canonical_config(&first), first.config.to_bytes());
+
+    // Same pair under another seed, on the same SVM. Restoring the mints and user accounts
+    // leaves them as they were, the first pool holds no liquidity yet
+    let second = Pool::uninitialized(first.svm, 2);
+    let result = second.svm.process_instruction(&second.initialize(true));
+
+    assert_eq!(
+        result.program_result,
+        ProgramResult::Failure(ProgramError::AccountAlreadyInitialized)
+    );
+    assert_eq!(canonical_config(&second), first.config.to_bytes());
+    assert!(account(&second.svm, &second.config).is_none_or(|config| config.data.is_empty()));
+}
+
+#[test]
+fn second_pool_of_a_pair_opens_without_asking_to_be_canonical() {
+    let first = Pool::uninitialized(amm_svm(), 1);
+    first.run(&first.initialize(true));
+
+    let second = Pool::uninitialized(first.svm, 2);
+    second.run(&second.initialize(false));
+
+    assert_eq!(canonical_config(&second), first.config.to_bytes());
+}
@@ -0,0 +1,61 @@
+//! Full deposits into a fresh pool and into one holding liquidity, checked by the tokens that
+//! reach the vaults and the LP minted for them.
+
+mod common;
+
+use common::amm::{Pool, USER_TOKENS};
+use common::*;
+
+/// LP the first deposit mints to the config's own account, never to be withdrawn.
+const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+#[test]
+fn first_deposit_fills_the_vaults_and_mints_the_geometric_mean() {
+    let pool = Pool::new();
+    pool.run(&pool.deposit(1, 4_000_000, 1_000_000, 0));
+
+    assert_eq!(balance(&pool.svm, &pool.vault_x), 4_000_000);
+    assert_eq!(balance(&pool.svm, &pool.vault_y), 1_000_000);
+    assert_eq!(balance(&pool.svm, &pool.user_x), USER_TOKENS - 4_000_000);
+    assert_eq!(balance(&pool.svm, &pool.user_y), USER_TOKENS - 1_000_000);
+
+    // sqrt(4_000_000 * 1_000_000), less the locked minimum
+    assert_eq!(
+        balance(&pool.svm, &pool.user_lp),
+        2_000_000 - MINIMUM_LIQUIDITY
+    );
+    assert_eq!(balance(&pool.svm, &pool.locked_lp), MINIMUM_LIQUIDITY);
+    assert_eq!(supply(&pool.svm, &pool.mint_lp), 2_000_000);
+}
+
+#[test]
+fn later_deposit_pays_in_proportion_to_the_reserves() {
+    let pool = Pool::new();
+    pool.run(&pool.deposit(1, 4_000_000, 1_000_000, 0));
+    let lp_before = balance(&pool.svm, &pool.user_lp);
+
+    // A tenth of the supply costs a tenth of each reserve
+    pool.run(&pool.deposit(200_000, 400_000, 100_000, 0));
+
+    assert_eq!(balance(&pool.svm, &pool.vault_x), 4_400_000);
+    assert_eq!(balance(&pool.svm, &pool.vault_y), 1_100_000);
+    assert_eq!(balance(&pool.svm, &pool.user_x), USER_TOKENS - 4_400_000);
+    assert_eq!(balance(&pool.svm, &pool.user_y), USER_TOKENS - 1_100_000);
+    assert_eq!(balance(&pool.svm, &pool.user_lp), lp_before + 200_000);
+    assert_eq!(supply(&pool.svm, &pool.mint_lp), 2_200_000);
+}
+
+#[test]
+fn rejects_a_deposit_costing_more_than_its_maximum() {
+    /// `AmmError::SlippageExceeded`.
+    const SLIPPAGE_EXCEEDED: u32 = 12;
+
+    let pool = Pool::new();
+    pool.run(&pool.deposit(1, 4_000_000, 1_000_000, 0));
+
+    let result = pool
+        .svm
+        .process_instruction(&pool.deposit(200_000, 399_999, 100_000, 0));
+    assert_eq!(custom_error(&result), Some(SLIPPAGE_EXCEEDED));
+    assert_eq!(balance(&pool.svm, &pool.vault_x), 4_000_000);
+}
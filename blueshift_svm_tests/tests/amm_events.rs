@@ -0,0 +1,208 @@
+//! The events Swap, Deposit and Withdraw log, decoded from the captured program logs and
+//! checked against the token balances the instructions actually moved.
+
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::amm::Pool;
+use common::*;
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_svm_log_collector::LogCollector;
+
+const SWAP_EVENT: [u8; 8] = [64, 198, 205, 232, 38, 8, 113, 226];
+const DEPOSIT_EVENT: [u8; 8] = [120, 248, 61, 83, 31, 142, 107, 144];
+const WITHDRAW_EVENT: [u8; 8] = [22, 9, 133, 26, 160, 44, 71, 192];
+
+/// Reads an event's fields in order.
+struct Fields<'a>(&'a [u8]);
+
+impl Fields<'_> {
+    fn take(&mut self, len: usize) -> &[u8] {
+        let (field, rest) = self.0.split_at(len);
+        self.0 = rest;
+        field
+    }
+
+    fn pubkey(&mut self) -> Pubkey {
+        Pubkey::new_from_array(self.take(32).try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take(8).try_into().unwrap())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct SwapEvent {
+    user: Pubkey,
+    is_x: bool,
+    amount_in: u64,
+    amount_out: u64,
+    fee: u64,
+    reserve_x_after: u64,
+    reserve_y_after: u64,
+}
+
+/// `DepositEvent` and `WithdrawEvent` share their layout.
+#[derive(Debug, PartialEq)]
+struct LiquidityEvent {
+    user: Pubkey,
+    amount_x: u64,
+    amount_y: u64,
+    lp: u64,
+    reserve_x_after: u64,
+    reserve_y_after: u64,
+}
+
+impl SwapEvent {
+    fn decode(data: &[u8]) -> Self {
+        let mut fields = Fields(data);
+        assert_eq!(fields.take(8), SWAP_EVENT);
+        let event = Self {
+            user: fields.pubkey(),
+            is_x: fields.take(1)[0] == 1,
+            amount_in: fields.u64(),
+            amount_out: fields.u64(),
+            fee: fields.u64(),
+            reserve_x_after: fields.u64(),
+            reserve_y_after: fields.u64(),
+        };
+        assert!(fields.0.is_empty());
+        event
+    }
+}
+
+impl LiquidityEvent {
+    fn decode(discriminator: [u8; 8], data: &[u8]) -> Self {
+        let mut fields = Fields(data);
+        assert_eq!(fields.take(8), discriminator);
+        let event = Self {
+            user: fields.pubkey(),
+            amount_x: fields.u64(),
+            amount_y: fields.u64(),
+            lp: fields.u64(),
+            reserve_x_after: fields.u64(),
+            reserve_y_after: fields.u64(),
+        };
+        assert!(fields.0.is_empty());
+        event
+    }
+}
+
+/// Token balances an AMM instruction can move: the user's X, Y and LP, then both vaults.
+fn balances(pool: &Pool) -> [u64; 5] {
+    [
+        pool.user_x,
+        pool.user_y,
+        pool.user_lp,
+        pool.vault_x,
+        pool.vault_y,
+    ]
+    .map(|address| balance(&pool.svm, &address))
+}
+
+/// Run `instruction` and return the one event it logged, each `Program data:` field decoded
+/// and joined back together.
+fn logged_event(pool: &mut Pool, instruction: &Instruction) -> Vec<u8> {
+    let logs = Rc::new(RefCell::new(LogCollector::default()));
+    pool.svm.mollusk.logger = Some(logs.clone());
+    pool.run(instruction);
+    pool.svm.mollusk.logger = None;
+
+    let events: Vec<Vec<u8>> = logs
+        .borrow()
+        .get_recorded_content()
+        .iter()
+        .filter_map(|line| line.strip_prefix("Program data: "))
+        .map(|data| {
+            data.split(' ')
+                .flat_map(|field| STANDARD.decode(field).unwrap())
+                .collect()
+        })
+        .collect();
+    assert_eq!(events.len(), 1, "expected one event");
+    events.into_iter().next().unwrap()
+}
+
+/// A pool with liquidity deposited, the starting point of the swap and withdraw tests.
+fn funded_pool() -> Pool {
+    let pool = Pool::new();
+    pool.run(&pool.deposit(1, 4_000_000, 1_000_000, 0));
+    pool
+}
+
+#[test]
+fn deposit_event_matches_the_balance_changes() {
+    let mut pool = funded_pool();
+    let before = balances(&pool);
+
+    let instruction = pool.deposit(200_000, 400_000, 100_000, 0);
+    let event = LiquidityEvent::decode(DEPOSIT_EVENT, &logged_event(&mut pool, &instruction));
+    let after = balances(&pool);
+
+    assert_eq!(event.user, pool.user);
+    assert_eq!(event.amount_x, before[0] - after[0]);
+    assert_eq!(event.amount_y, before[1] - after[1]);
+    assert_eq!(event.lp, after[2] - before[2]);
+    assert_eq!(event.amount_x, after[3] - before[3]);
+    assert_eq!(event.amount_y, after[4] - before[4]);
+    assert_eq!(
+        (event.reserve_x_after, event.reserve_y_after),
+        (after[3], after[4])
+    );
+}
+
+#[test]
+fn withdraw_event_matches_the_balance_changes() {
+    let mut pool = funded_pool();
+    let before = balances(&pool);
+
+    let instruction = pool.withdraw(500_000, 0, 0, 0);
+    let event = LiquidityEvent::decode(WITHDRAW_EVENT, &logged_event(&mut pool, &instruction));
+    let after = balances(&pool);
+
+    assert_eq!(event.user, pool.user);
+    assert_eq!(event.amount_x, after[0] - before[0]);
+    assert_eq!(event.amount_y, after[1] - before[1]);
+    assert_eq!(event.lp, before[2] - after[2]);
+    assert_eq!(event.amount_x, before[3] - after[3]);
+    assert_eq!(event.amount_y, before[4] - after[4]);
+    assert_eq!(
+        (event.reserve_x_after, event.reserve_y_after),
+        (after[3], after[4])
+    );
+}
+
+#[test]
+fn swap_event_matches_the_balance_changes() {
+    for is_x in [true, false] {
+        let mut pool = funded_pool();
+        let before = balances(&pool);
+
+        let instruction = pool.swap(is_x, 100_000, 1, 0);
+        let event = SwapEvent::decode(&logged_event(&mut pool, &instruction));
+        let after = balances(&pool);
+
+        // Indices of the user's and the pool's input and output sides
+        let (user_in, user_out, vault_in, vault_out) = match is_x {
+            true => (0, 1, 3, 4),
+            false => (1, 0, 4, 3),
+        };
+        assert_eq!(event.user, pool.user);
+        assert_eq!(event.is_x, is_x);
+        assert_eq!(event.amount_in, before[user_in] - after[user_in]);
+        assert_eq!(event.amount_in, after[vault_in] - before[vault_in]);
+        assert_eq!(event.amount_out, after[user_out] - before[user_out]);
+        assert_eq!(event.amount_out, before[vault_out] - after[vault_out]);
+        // 30 bps of the input
+        assert_eq!(event.fee, 300);
+        assert_eq!(
+            (event.reserve_x_after, event.reserve_y_after),
+            (after[3], after[4])
+        );
+    }
+}
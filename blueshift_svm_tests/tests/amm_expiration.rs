@@ -0,0 +1,51 @@
+//! Deposit, Withdraw and Swap run up to and including their `expiration` and are rejected
+//! once the clock is past it.
+
+mod common;
+
+use common::amm::Pool;
+use common::*;
+use solana_instruction::Instruction;
+
+/// `AmmError::Expired`.
+const EXPIRED: u32 = 4;
+const NOW: i64 = 1_700_000_000;
+
+/// A pool holding liquidity, so each instruction has something to act on, at `NOW`.
+fn pool() -> Pool {
+    let mut pool = Pool::new();
+    pool.run(&pool.deposit(1, 1_000_000, 1_000_000, 0));
+    pool.svm.mollusk.sysvars.clock.unix_timestamp = NOW;
+    pool
+}
+
+/// Check the instruction built by `instruction` for a given expiration runs at `NOW` with
+/// the deadline at `NOW`, and fails with the deadline a second earlier.
+fn check_deadline(instruction: impl Fn(&Pool, i64) -> Instruction) {
+    let pool = pool();
+    let result = pool.svm.process_instruction(&instruction(&pool, NOW - 1));
+    assert_eq!(custom_error(&result), Some(EXPIRED));
+
+    pool.run(&instruction(&pool, NOW));
+}
+
+#[test]
+fn deposit_expires_after_its_deadline() {
+    check_deadline(|pool, expiration| pool.deposit(1_000, 10_000, 10_000, expiration));
+}
+
+#[test]
+fn withdraw_expires_after_its_deadline() {
+    check_deadline(|pool, expiration| pool.withdraw(1_000, 0, 0, expiration));
+}
+
+#[test]
+fn swap_expires_after_its_deadline() {
+    check_deadline(|pool, expiration| pool.swap(true, 1_000, 0, expiration));
+}
+
+#[test]
+fn zero_expiration_never_expires() {
+    let pool = pool();
+    pool.run(&pool.swap(true, 1_000, 0, 0));
+}
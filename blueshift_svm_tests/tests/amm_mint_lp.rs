@@ -0,0 +1,66 @@
+//! Deposit and Withdraw only take the pool's own LP mint. A mint anyone can create, even
+//! one whose mint authority is the config, would otherwise price the pool's reserves.
+
+mod common;
+
+use common::amm::{replace_account, Pool};
+use common::*;
+use solana_pubkey::Pubkey;
+
+/// `AmmError::InvalidMintLp`.
+const INVALID_MINT_LP: u32 = 18;
+
+/// A pool holding liquidity, and a mint of `supply` under the config's authority with the
+/// user holding all of it.
+fn pool_with_foreign_mint(supply: u64) -> (Pool, Pubkey, Pubkey) {
+    let pool = Pool::new();
+    pool.run(&pool.deposit(1, 4_000_000, 1_000_000, 0));
+
+    let foreign_mint = key(50);
+    mint(
+        &pool.svm,
+        foreign_mint,
+        &TOKEN_PROGRAM_ID,
+        &pool.config,
+        supply,
+    );
+    let foreign_lp = funded_ata(
+        &pool.svm,
+        &TOKEN_PROGRAM_ID,
+        &foreign_mint,
+        &pool.user,
+        supply,
+    );
+    (pool, foreign_mint, foreign_lp)
+}
+
+#[test]
+fn withdraw_rejects_a_foreign_lp_mint() {
+    // Burning the whole supply of a mint would pay out both vaults in full
+    let (pool, foreign_mint, foreign_lp) = pool_with_foreign_mint(1);
+
+    let mut withdraw = pool.withdraw(1, 0, 0, 0);
+    replace_account(&mut withdraw, &pool.mint_lp, &foreign_mint);
+    replace_account(&mut withdraw, &pool.user_lp, &foreign_lp);
+    let result = pool.svm.process_instruction(&withdraw);
+
+    assert_eq!(custom_error(&result), Some(INVALID_MINT_LP));
+    assert_eq!(balance(&pool.svm, &pool.vault_x), 4_000_000);
+    assert_eq!(balance(&pool.svm, &pool.vault_y), 1_000_000);
+}
+
+#[test]
+fn deposit_rejects_a_foreign_lp_mint() {
+    // A mint without supply would take the first deposit branch, pricing LP off the whole
+    // reserves
+    let (pool, foreign_mint, foreign_lp) = pool_with_foreign_mint(0);
+
+    let mut deposit = pool.deposit(1, 1_000, 1_000, 0);
+    replace_account(&mut deposit, &pool.mint_lp, &foreign_mint);
+    replace_account(&mut deposit, &pool.user_lp, &foreign_lp);
+    let result = pool.svm.process_instruction(&deposit);
+
+    assert_eq!(custom_error(&result), Some(INVALID_MINT_LP));
+    assert_eq!(supply(&pool.svm, &foreign_mint), 0);
+    assert_eq!(balance(&pool.svm, &pool.vault_x), 4_000_000);
+}
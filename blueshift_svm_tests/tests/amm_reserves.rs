@@ -0,0 +1,67 @@
+//! The pool prices off the reserves it records, so tokens sent straight to a vault move
+//! neither a swap nor a deposit.
+
+mod common;
+
+use common::amm::Pool;
+use common::*;
+
+/// Tokens of X sent straight to the X vault between the deposit and the swap.
+const DONATION: u64 = 1_000_000;
+
+/// A pool holding 4M X and 1M Y of liquidity, `donation` more X sitting in its X vault.
+fn pool_with_donation(donation: u64) -> Pool {
+    let pool = Pool::new();
+    pool.run(&pool.deposit(1, 4_000_000, 1_000_000, 0));
+    if donation > 0 {
+        pool.run(&pool.transfer(&pool.user_x, &pool.vault_x, donation));
+    }
+    pool
+}
+
+/// Y paid out for `amount` of X.
+fn swap_x_for_y(pool: &Pool, amount: u64) -> u64 {
+    let before = balance(&pool.svm, &pool.user_y);
+    pool.run(&pool.swap(true, amount, 1, 0));
+    balance(&pool.svm, &pool.user_y) - before
+}
+
+#[test]
+fn donation_does_not_move_the_swap_price() {
+    let plain = pool_with_donation(0);
+    let donated = pool_with_donation(DONATION);
+    assert_eq!(
+        balance(&donated.svm, &donated.vault_x),
+        4_000_000 + DONATION
+    );
+
+    assert_eq!(
+        swap_x_for_y(&donated, 100_000),
+        swap_x_for_y(&plain, 100_000)
+    );
+}
+
+#[test]
+fn donation_does_not_move_the_deposit_price() {
+    let plain = pool_with_donation(0);
+    let donated = pool_with_donation(DONATION);
+
+    for pool in [&plain, &donated] {
+        pool.run(&pool.deposit(200_000, 400_000, 100_000, 0));
+    }
+
+    let paid = |pool: &Pool| {
+        (
+            balance(&pool.svm, &pool.user_x),
+            balance(&pool.svm, &pool.user_y),
+        )
+    };
+    let (plain_x, plain_y) = paid(&plain);
+    let (donated_x, donated_y) = paid(&donated);
+    assert_eq!(donated_x + DONATION, plain_x);
+    assert_eq!(donated_y, plain_y);
+    assert_eq!(
+        balance(&donated.svm, &donated.user_lp),
+        balance(&plain.svm, &plain.user_lp)
+    );
+}
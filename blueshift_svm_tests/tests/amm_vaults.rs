@@ -0,0 +1,86 @@
+//! Instructions compare the vaults they are passed with the ones `Initialize` stored in the
+//! config, instead of re-deriving the config's associated token accounts each time.
+
+mod common;
+
+use common::amm::{replace_account, Pool};
+use common::*;
+use solana_pubkey::Pubkey;
+
+/// `AmmError::InvalidVault`.
+const INVALID_VAULT: u32 = 15;
+
+/// A pool with liquidity deposited and a token account of mint X held by the config, which
+/// is not its vault.
+fn pool_with_impostor_vault() -> (Pool, Pubkey) {
+    let pool = Pool::new();
+    pool.run(&pool.deposit(1, 4_000_000, 1_000_000, 0));
+
+    let impostor = key(40);
+    token_account(
+        &pool.svm,
+        impostor,
+        &TOKEN_PROGRAM_ID,
+        &pool.mint_x,
+        &pool.config,
+        4_000_000,
+    );
+    (pool, impostor)
+}
+
+#[test]
+fn rejects_a_config_token_account_in_place_of_its_vault() {
+    let (pool, impostor) = pool_with_impostor_vault();
+
+    for mut instruction in [
+        pool.swap(false, 100_000, 1, 0),
+        pool.deposit(200_000, 400_000, 100_000, 0),
+        pool.withdraw(200_000, 0, 0, 0),
+    ] {
+        replace_account(&mut instruction, &pool.vault_x, &impostor);
+        let result = pool.svm.process_instruction(&instruction);
+        assert_eq!(custom_error(&result), Some(INVALID_VAULT));
+    }
+    assert_eq!(balance(&pool.svm, &impostor), 4_000_000);
+}
+
+#[test]
+fn rejects_the_vaults_passed_the_wrong_way_round() {
+    let (pool, _) = pool_with_impostor_vault();
+
+    let mut instruction = pool.swap(true, 100_000, 1, 0);
+    instruction.accounts.swap(3, 4);
+    let result = pool.svm.process_instruction(&instruction);
+    assert_eq!(custom_error(&result), Some(INVALID_VAULT));
+}
+
+#[test]
+fn stored_vaults_keep_their_derivation_off_the_swap() {
+    let (pool, _) = pool_with_impostor_vault();
+    let units = pool.svm.mollusk.compute_budget.create_program_address_units;
+
+    // `find_program_address` pays for every bump it tries, starting from 255
+    let derivation: u64 = [pool.mint_x, pool.mint_y]
+        .iter()
+        .map(|mint| {
+            let (_, bump) = Pubkey::find_program_address(
+                &[
+                    pool.config.as_ref(),
+                    TOKEN_PROGRAM_ID.as_ref(),
+                    mint.as_ref(),
+                ],
+                &ASSOCIATED_TOKEN_PROGRAM_ID,
+            );
+            (256 - u64::from(bump)) * units
+        })
+        .sum();
+
+    let cu = pool
+        .run(&pool.swap(true, 100_000, 1, 0))
+        .compute_units_consumed;
+    println!("Swap: {cu} CU, deriving its vaults would add {derivation} CU");
+    // The budget sits under 1500 CU above the measured cost, so re-deriving even one vault
+    // takes a swap past it
+    assert!(cu <= SWAP_CU, "Swap used {cu} CU, over {SWAP_CU}");
+    assert!(cu + derivation > SWAP_CU);
+}
@@ -0,0 +1,257 @@
+//! An initialized AMM pool and the instructions the tests send to it.
+
+use mollusk_svm::result::InstructionResult;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+
+use super::*;
+
+pub const MINT_X: u8 = 10;
+pub const MINT_Y: u8 = 11;
+pub const USER: u8 = 12;
+
+pub const SEED: u64 = 1;
+pub const FEE: u16 = 30;
+/// Tokens of each mint the user starts with.
+pub const USER_TOKENS: u64 = 1_000_000_000;
+
+pub fn config_address(seed: u64, mint_x: &Pubkey, mint_y: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"config",
+            &seed.to_le_bytes(),
+            mint_x.as_ref(),
+            mint_y.as_ref(),
+        ],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn mint_lp_address(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"mint_lp", config.as_ref()], &PROGRAM_ID)
+}
+
+pub fn canonical_pool_address(mint_x: &Pubkey, mint_y: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"canonical_pool", mint_x.as_ref(), mint_y.as_ref()],
+        &PROGRAM_ID,
+    )
+    .0
+}
+
+/// Point every account of `instruction` at `from` to `to` instead.
+pub fn replace_account(instruction: &mut Instruction, from: &Pubkey, to: &Pubkey) {
+    for account in instruction
+        .accounts
+        .iter_mut()
+        .filter(|account| account.pubkey == *from)
+    {
+        account.pubkey = *to;
+    }
+}
+
+/// A pool of `MINT_X` and `MINT_Y` under the token program, with a user holding both.
+pub struct Pool {
+    pub svm: Svm,
+    pub seed: u64,
+    pub user: Pubkey,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub config: Pubkey,
+    pub mint_lp: Pubkey,
+    pub vault_x: Pubkey,
+    pub vault_y: Pubkey,
+    pub locked_lp: Pubkey,
+    pub user_x: Pubkey,
+    pub user_y: Pubkey,
+    pub user_lp: Pubkey,
+}
+
+impl Pool {
+    /// Open the pool of `SEED` on a fresh SVM.
+    pub fn new() -> Self {
+        let pool = Self::uninitialized(amm_svm(), SEED);
+        let result = pool.svm.process_instruction(&pool.initialize(false));
+        assert!(result.program_result.is_ok(), "initialize failed");
+        pool.open_user_lp();
+        pool
+    }
+
+    /// Wallets, mints and user token accounts of the pool of `seed`, before `Initialize`.
+    pub fn uninitialized(svm: Svm, seed: u64) -> Self {
+        let (user, mint_x, mint_y) = (key(USER), key(MINT_X), key(MINT_Y));
+        wallet(&svm, user);
+        for mint_address in [mint_x, mint_y] {
+            mint(
+                &svm,
+                mint_address,
+                &TOKEN_PROGRAM_ID,
+                &user,
+                2 * USER_TOKENS,
+            );
+        }
+        let user_x = funded_ata(&svm, &TOKEN_PROGRAM_ID, &mint_x, &user, USER_TOKENS);
+        let user_y = funded_ata(&svm, &TOKEN_PROGRAM_ID, &mint_y, &user, USER_TOKENS);
+
+        let config = config_address(seed, &mint_x, &mint_y).0;
+        let mint_lp = mint_lp_address(&config).0;
+        Self {
+            svm,
+            seed,
+            user,
+            mint_x,
+            mint_y,
+            config,
+            mint_lp,
+            vault_x: ata(&config, &mint_x, &TOKEN_PROGRAM_ID),
+            vault_y: ata(&config, &mint_y, &TOKEN_PROGRAM_ID),
+            locked_lp: ata(&config, &mint_lp, &TOKEN_PROGRAM_ID),
+            user_x,
+            user_y,
+            user_lp: ata(&user, &mint_lp, &TOKEN_PROGRAM_ID),
+        }
+    }
+
+    /// The user's LP account, which `Deposit` expects to exist.
+    pub fn open_user_lp(&self) {
+        token_account(
+            &self.svm,
+            self.user_lp,
+            &TOKEN_PROGRAM_ID,
+            &self.mint_lp,
+            &self.user,
+            0,
+        );
+    }
+
+    /// `Initialize` of the pool, naming it the pair's canonical pool with `canonical_pool`.
+    pub fn initialize(&self, canonical_pool: bool) -> Instruction {
+        let seed = self.seed;
+        let config_bump = config_address(seed, &self.mint_x, &self.mint_y).1;
+        let lp_bump = mint_lp_address(&self.config).1;
+        let registry = Pubkey::find_program_address(
+            &[b"registry", self.mint_x.as_ref(), self.mint_y.as_ref()],
+            &PROGRAM_ID,
+        )
+        .0;
+
+        let mut data = vec![0];
+        data.extend_from_slice(&seed.to_le_bytes());
+        data.extend_from_slice(&FEE.to_le_bytes());
+        data.extend_from_slice(self.mint_x.as_ref());
+        data.extend_from_slice(self.mint_y.as_ref());
+        data.extend_from_slice(&[config_bump, lp_bump]);
+        // No authority and no LP freeze authority
+        data.extend_from_slice(&[0; 64]);
+        data.push(canonical_pool.into());
+
+        let mut accounts = vec![
+            AccountMeta::new(self.user, true),
+            AccountMeta::new(self.mint_lp, false),
+            AccountMeta::new(self.config, false),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(self.mint_x, false),
+            AccountMeta::new_readonly(self.mint_y, false),
+            AccountMeta::new(self.vault_x, false),
+            AccountMeta::new(self.vault_y, false),
+            AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+            AccountMeta::new(self.locked_lp, false),
+            AccountMeta::new(registry, false),
+        ];
+        if canonical_pool {
+            accounts.push(AccountMeta::new(
+                canonical_pool_address(&self.mint_x, &self.mint_y),
+                false,
+            ));
+        }
+        Instruction::new_with_bytes(PROGRAM_ID, &data, accounts)
+    }
+
+    /// Accounts `Deposit` and `Withdraw` share.
+    fn liquidity_accounts(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.user, true),
+            AccountMeta::new(self.mint_lp, false),
+            AccountMeta::new(self.vault_x, false),
+            AccountMeta::new(self.vault_y, false),
+            AccountMeta::new(self.user_x, false),
+            AccountMeta::new(self.user_y, false),
+            AccountMeta::new(self.user_lp, false),
+            AccountMeta::new(self.config, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(self.mint_x, false),
+            AccountMeta::new_readonly(self.mint_y, false),
+        ]
+    }
+
+    /// `Deposit` for `amount` LP paying at most `max_x` and `max_y`, passing the locked LP
+    /// account the first deposit needs.
+    pub fn deposit(&self, amount: u64, max_x: u64, max_y: u64, expiration: i64) -> Instruction {
+        let mut data = vec![1];
+        for field in [amount, max_x, max_y, expiration as u64] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        let mut accounts = self.liquidity_accounts();
+        accounts.push(AccountMeta::new(self.locked_lp, false));
+        Instruction::new_with_bytes(PROGRAM_ID, &data, accounts)
+    }
+
+    /// `Withdraw` burning `amount` LP for at least `min_x` and `min_y`.
+    pub fn withdraw(&self, amount: u64, min_x: u64, min_y: u64, expiration: i64) -> Instruction {
+        let mut data = vec![2];
+        for field in [amount, min_x, min_y, expiration as u64] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        Instruction::new_with_bytes(PROGRAM_ID, &data, self.liquidity_accounts())
+    }
+
+    /// `Swap` of `amount` X for Y, or Y for X unless `is_x`, receiving at least `min`.
+    pub fn swap(&self, is_x: bool, amount: u64, min: u64, expiration: i64) -> Instruction {
+        let mut data = vec![3, is_x.into()];
+        for field in [amount, min, expiration as u64] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &data,
+            vec![
+                AccountMeta::new(self.user, true),
+                AccountMeta::new(self.user_x, false),
+                AccountMeta::new(self.user_y, false),
+                AccountMeta::new(self.vault_x, false),
+                AccountMeta::new(self.vault_y, false),
+                AccountMeta::new(self.config, false),
+                AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+                AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+                AccountMeta::new_readonly(self.mint_lp, false),
+                AccountMeta::new_readonly(self.mint_x, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+            ],
+        )
+    }
+
+    /// SPL token transfer of `amount` from the user's `from` account to `to`, outside the AMM.
+    pub fn transfer(&self, from: &Pubkey, to: &Pubkey, amount: u64) -> Instruction {
+        let mut data = vec![3];
+        data.extend_from_slice(&amount.to_le_bytes());
+        Instruction::new_with_bytes(
+            TOKEN_PROGRAM_ID,
+            &data,
+            vec![
+                AccountMeta::new(*from, false),
+                AccountMeta::new(*to, false),
+                AccountMeta::new_readonly(self.user, true),
+            ],
+        )
+    }
+
+    /// Process `instruction`, panicking with its logs unless it succeeds.
+    pub fn run(&self, instruction: &Instruction) -> InstructionResult {
+        let result = self.svm.process_instruction(instruction);
+        assert!(result.program_result.is_ok(), "{:?}", result.program_result);
+        result
+    }
+}
@@ -0,0 +1,241 @@
+//! The native and the Anchor escrow behind one interface, over a maker and a taker holding
+//! both tokens.
+
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+
+use super::*;
+
+pub const MAKER: u8 = 1;
+pub const TAKER: u8 = 2;
+pub const MINT_A: u8 = 3;
+pub const MINT_B: u8 = 4;
+pub const OTHER_MINT: u8 = 5;
+
+pub const SEED: u64 = 7;
+pub const AMOUNT: u64 = 100;
+pub const RECEIVE: u64 = 40;
+pub const START: u64 = 1_000;
+
+/// What each escrow program needs to build Make, Take and Refund.
+pub trait EscrowProgram {
+    fn svm(&self) -> Svm;
+    fn make(&self, pool: &Pool, seed: u64) -> Instruction;
+    fn take(&self, pool: &Pool, seed: u64, mint_b: &Pubkey) -> Instruction;
+    /// Refund signed by `signer`, the maker or anyone else.
+    fn refund(&self, pool: &Pool, seed: u64, signer: &Pubkey) -> Instruction;
+}
+
+/// Wallets, mints and token accounts of one scenario.
+pub struct Pool {
+    pub svm: Svm,
+    pub token_program: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+}
+
+impl Pool {
+    pub fn new(program: &dyn EscrowProgram, token_program: Pubkey) -> Self {
+        let svm = program.svm();
+        let (maker, taker) = (key(MAKER), key(TAKER));
+        wallet(&svm, maker);
+        wallet(&svm, taker);
+        for mint_key in [MINT_A, MINT_B, OTHER_MINT] {
+            mint(&svm, key(mint_key), &token_program, &maker, 2 * START);
+        }
+        funded_ata(&svm, &token_program, &key(MINT_A), &maker, START);
+        funded_ata(&svm, &token_program, &key(MINT_B), &taker, START);
+        funded_ata(&svm, &token_program, &key(OTHER_MINT), &taker, START);
+
+        Self {
+            svm,
+            token_program,
+            maker,
+            taker,
+            mint_a: key(MINT_A),
+            mint_b: key(MINT_B),
+        }
+    }
+
+    pub fn ata(&self, owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        ata(owner, mint, &self.token_program)
+    }
+
+    pub fn run(&self, instruction: Instruction) -> bool {
+        self.svm
+            .process_instruction(&instruction)
+            .program_result
+            .is_ok()
+    }
+
+    /// Token A and B of maker and taker, then token A left in `seed`'s vault.
+    pub fn balances(&self, escrow: &Pubkey) -> [u64; 5] {
+        [
+            balance(&self.svm, &self.ata(&self.maker, &self.mint_a)),
+            balance(&self.svm, &self.ata(&self.maker, &self.mint_b)),
+            balance(&self.svm, &self.ata(&self.taker, &self.mint_a)),
+            balance(&self.svm, &self.ata(&self.taker, &self.mint_b)),
+            balance(&self.svm, &self.ata(escrow, &self.mint_a)),
+        ]
+    }
+}
+
+pub fn escrow_address(maker: &Pubkey, seed: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"escrow", maker.as_ref(), &seed.to_le_bytes()],
+        &PROGRAM_ID,
+    )
+    .0
+}
+
+pub struct Native;
+
+impl EscrowProgram for Native {
+    fn svm(&self) -> Svm {
+        escrow_svm()
+    }
+
+    fn make(&self, pool: &Pool, seed: u64) -> Instruction {
+        let escrow = escrow_address(&pool.maker, seed);
+        let mut data = vec![0];
+        for field in [seed, RECEIVE, AMOUNT] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &data,
+            vec![
+                AccountMeta::new(pool.maker, true),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(pool.mint_a, false),
+                AccountMeta::new_readonly(pool.mint_b, false),
+                AccountMeta::new(pool.ata(&pool.maker, &pool.mint_a), false),
+                AccountMeta::new(pool.ata(&escrow, &pool.mint_a), false),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(pool.token_program, false),
+                AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    fn take(&self, pool: &Pool, seed: u64, mint_b: &Pubkey) -> Instruction {
+        let escrow = escrow_address(&pool.maker, seed);
+        let config = Pubkey::find_program_address(&[b"config"], &PROGRAM_ID).0;
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[1],
+            vec![
+                AccountMeta::new(pool.taker, true),
+                AccountMeta::new(pool.maker, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(pool.mint_a, false),
+                AccountMeta::new_readonly(*mint_b, false),
+                AccountMeta::new(pool.ata(&escrow, &pool.mint_a), false),
+                AccountMeta::new(pool.ata(&pool.taker, &pool.mint_a), false),
+                AccountMeta::new(pool.ata(&pool.taker, mint_b), false),
+                AccountMeta::new(pool.ata(&pool.maker, mint_b), false),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(pool.token_program, false),
+                AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+                // No protocol fee without a config, nothing reaches the treasury
+                AccountMeta::new_readonly(config, false),
+                AccountMeta::new(pool.ata(&config, mint_b), false),
+            ],
+        )
+    }
+
+    fn refund(&self, pool: &Pool, seed: u64, signer: &Pubkey) -> Instruction {
+        let escrow = escrow_address(&pool.maker, seed);
+        let accounts = vec![
+            // Only the maker signs for a native refund, anyone else has to sign in its place
+            AccountMeta::new(*signer, true),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(pool.mint_a, false),
+            AccountMeta::new(pool.ata(&escrow, &pool.mint_a), false),
+            AccountMeta::new(pool.ata(&pool.maker, &pool.mint_a), false),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            AccountMeta::new_readonly(pool.token_program, false),
+            AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(pool.maker, false),
+        ];
+        Instruction::new_with_bytes(PROGRAM_ID, &[2], accounts)
+    }
+}
+
+pub struct Anchor;
+
+impl EscrowProgram for Anchor {
+    fn svm(&self) -> Svm {
+        anchor_escrow_svm()
+    }
+
+    fn make(&self, pool: &Pool, seed: u64) -> Instruction {
+        let escrow = escrow_address(&pool.maker, seed);
+        let mut data = vec![0];
+        for field in [seed, RECEIVE, AMOUNT] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        // No band and no deadline
+        data.extend_from_slice(&[0, 0, 0]);
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &data,
+            vec![
+                AccountMeta::new(pool.maker, true),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(pool.mint_a, false),
+                AccountMeta::new_readonly(pool.mint_b, false),
+                AccountMeta::new(pool.ata(&pool.maker, &pool.mint_a), false),
+                AccountMeta::new(pool.ata(&escrow, &pool.mint_a), false),
+                AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+                AccountMeta::new_readonly(pool.token_program, false),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    fn take(&self, pool: &Pool, seed: u64, mint_b: &Pubkey) -> Instruction {
+        let escrow = escrow_address(&pool.maker, seed);
+        let mut data = vec![1];
+        data.extend_from_slice(&RECEIVE.to_le_bytes());
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &data,
+            vec![
+                AccountMeta::new(pool.taker, true),
+                AccountMeta::new(pool.maker, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(pool.mint_a, false),
+                AccountMeta::new_readonly(*mint_b, false),
+                AccountMeta::new(pool.ata(&escrow, &pool.mint_a), false),
+                AccountMeta::new(pool.ata(&pool.taker, &pool.mint_a), false),
+                AccountMeta::new(pool.ata(&pool.taker, mint_b), false),
+                AccountMeta::new(pool.ata(&pool.maker, mint_b), false),
+                AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+                AccountMeta::new_readonly(pool.token_program, false),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            ],
+        )
+    }
+
+    fn refund(&self, pool: &Pool, seed: u64, signer: &Pubkey) -> Instruction {
+        let escrow = escrow_address(&pool.maker, seed);
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[2],
+            vec![
+                AccountMeta::new(*signer, true),
+                AccountMeta::new(pool.maker, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(pool.mint_a, false),
+                AccountMeta::new(pool.ata(&escrow, &pool.mint_a), false),
+                AccountMeta::new(pool.ata(&pool.maker, &pool.mint_a), false),
+                AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+                AccountMeta::new_readonly(pool.token_program, false),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            ],
+        )
+    }
+}
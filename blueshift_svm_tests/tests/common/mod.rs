@@ -0,0 +1,181 @@
+//! Shared setup: programs, token accounts and the account store the tests run against.
+#![allow(dead_code)]
+
+pub mod amm;
+pub mod escrow;
+
+use std::collections::HashMap;
+
+use mollusk_svm::result::{InstructionResult, ProgramResult};
+use mollusk_svm::{Mollusk, MolluskContext};
+use mollusk_svm_programs_token::{associated_token, token, token2022};
+use solana_account::Account;
+use solana_program_error::ProgramError;
+use solana_pubkey::{pubkey, Pubkey};
+
+/// Every program in the repo is deployed at the same address.
+pub const PROGRAM_ID: Pubkey = pubkey!("22222222222222222222222222222222222222222222");
+pub const SYSTEM_PROGRAM_ID: Pubkey = Pubkey::new_from_array([0; 32]);
+pub const TOKEN_PROGRAM_ID: Pubkey = token::ID;
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = token2022::ID;
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = associated_token::ID;
+
+pub const MINT_LEN: usize = 82;
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+
+/// Lamports every wallet starts with.
+pub const WALLET_LAMPORTS: u64 = 10_000_000_000;
+
+/// Compute unit budgets `tests/compute_units.rs` holds each instruction to, published in the
+/// README. AMM instructions run on a pool holding liquidity, Take under the token program.
+pub const DEPOSIT_CU: u64 = 18_500;
+pub const WITHDRAW_CU: u64 = 17_000;
+pub const SWAP_CU: u64 = 15_000;
+pub const TAKE_CU: u64 = 65_000;
+
+pub type Svm = MolluskContext<HashMap<Pubkey, Account>>;
+
+/// Load `name` from `<krate>/target/deploy`, built with `cargo build-sbf`, next to the token
+/// and associated token programs.
+pub fn svm(krate: &str, name: &str) -> Svm {
+    let path = format!(
+        "{}/../{krate}/target/deploy/{name}",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let mut mollusk = Mollusk::new(&PROGRAM_ID, &path);
+    token::add_program(&mut mollusk);
+    token2022::add_program(&mut mollusk);
+    associated_token::add_program(&mut mollusk);
+    mollusk.with_context(HashMap::new())
+}
+
+pub fn escrow_svm() -> Svm {
+    svm("blueshift_escrow", "blueshift_escrow")
+}
+
+pub fn anchor_escrow_svm() -> Svm {
+    svm("blueshift_anchor_escrow", "blueshift_anchor_escrow")
+}
+
+pub fn amm_svm() -> Svm {
+    svm("blueshift_native_amm", "blueshift_native_amm")
+}
+
+/// A fresh address, distinct for every `n`.
+pub fn key(n: u8) -> Pubkey {
+    Pubkey::new_from_array([n; 32])
+}
+
+/// Associated token account of `owner` for `mint` under `token_program`.
+pub fn ata(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+pub fn store(svm: &Svm, address: Pubkey, account: Account) {
+    svm.account_store.borrow_mut().insert(address, account);
+}
+
+pub fn account(svm: &Svm, address: &Pubkey) -> Option<Account> {
+    svm.account_store.borrow().get(address).cloned()
+}
+
+fn rent_exempt(svm: &Svm, len: usize) -> u64 {
+    svm.mollusk.sysvars.rent.minimum_balance(len)
+}
+
+pub fn wallet(svm: &Svm, address: Pubkey) {
+    store(
+        svm,
+        address,
+        Account::new(WALLET_LAMPORTS, 0, &SYSTEM_PROGRAM_ID),
+    );
+}
+
+/// Store an initialized mint of `token_program` minting to `authority`.
+pub fn mint(svm: &Svm, address: Pubkey, token_program: &Pubkey, authority: &Pubkey, supply: u64) {
+    let mut data = vec![0u8; MINT_LEN];
+    data[0..4].copy_from_slice(&1u32.to_le_bytes());
+    data[4..36].copy_from_slice(authority.as_ref());
+    data[36..44].copy_from_slice(&supply.to_le_bytes());
+    data[44] = 6;
+    data[45] = 1;
+    store(
+        svm,
+        address,
+        Account {
+            lamports: rent_exempt(svm, MINT_LEN),
+            data,
+            owner: *token_program,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+}
+
+/// Store an initialized token account of `owner` holding `amount` of `mint`.
+pub fn token_account(
+    svm: &Svm,
+    address: Pubkey,
+    token_program: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) {
+    let mut data = vec![0u8; TOKEN_ACCOUNT_LEN];
+    data[0..32].copy_from_slice(mint.as_ref());
+    data[32..64].copy_from_slice(owner.as_ref());
+    data[64..72].copy_from_slice(&amount.to_le_bytes());
+    data[108] = 1;
+    store(
+        svm,
+        address,
+        Account {
+            lamports: rent_exempt(svm, TOKEN_ACCOUNT_LEN),
+            data,
+            owner: *token_program,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+}
+
+/// Store the associated token account of `owner` holding `amount` of `mint`, returning it.
+pub fn funded_ata(
+    svm: &Svm,
+    token_program: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let address = ata(owner, mint, token_program);
+    token_account(svm, address, token_program, mint, owner, amount);
+    address
+}
+
+/// Token balance of `address`, zero for a missing or closed account.
+pub fn balance(svm: &Svm, address: &Pubkey) -> u64 {
+    match account(svm, address) {
+        Some(account) if account.data.len() >= 72 => {
+            u64::from_le_bytes(account.data[64..72].try_into().unwrap())
+        }
+        _ => 0,
+    }
+}
+
+/// Supply of the mint at `address`.
+pub fn supply(svm: &Svm, address: &Pubkey) -> u64 {
+    let account = account(svm, address).expect("mint exists");
+    u64::from_le_bytes(account.data[36..44].try_into().unwrap())
+}
+
+/// Custom error code `result` failed with, if it failed with one.
+pub fn custom_error(result: &InstructionResult) -> Option<u32> {
+    match result.program_result {
+        ProgramResult::Failure(ProgramError::Custom(code)) => Some(code),
+        _ => None,
+    }
+}
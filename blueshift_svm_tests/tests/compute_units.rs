@@ -0,0 +1,59 @@
+//! Compute unit ceilings, failing when an instruction grows past its published budget. Run
+//! with `--nocapture` to print what each one used.
+
+mod common;
+
+use common::amm::Pool;
+use common::escrow::{self, EscrowProgram, Native};
+use common::*;
+use solana_instruction::Instruction;
+
+/// A pool holding liquidity.
+fn funded_pool() -> Pool {
+    let pool = Pool::new();
+    pool.run(&pool.deposit(1, 4_000_000, 1_000_000, 0));
+    pool
+}
+
+/// Run `instruction` on `pool` and check `name` used at most `budget` CU.
+fn check_budget(pool: &Pool, name: &str, instruction: &Instruction, budget: u64) {
+    let cu = pool.run(instruction).compute_units_consumed;
+    println!("{name}: {cu} CU");
+    assert!(cu <= budget, "{name} used {cu} CU, over {budget}");
+}
+
+#[test]
+fn deposit_stays_within_budget() {
+    let pool = funded_pool();
+    let deposit = pool.deposit(200_000, 400_000, 100_000, 0);
+    check_budget(&pool, "Deposit", &deposit, DEPOSIT_CU);
+}
+
+#[test]
+fn withdraw_stays_within_budget() {
+    let pool = funded_pool();
+    let withdraw = pool.withdraw(200_000, 0, 0, 0);
+    check_budget(&pool, "Withdraw", &withdraw, WITHDRAW_CU);
+}
+
+#[test]
+fn swap_stays_within_budget() {
+    let pool = funded_pool();
+    let swap = pool.swap(true, 100_000, 1, 0);
+    check_budget(&pool, "Swap", &swap, SWAP_CU);
+}
+
+#[test]
+fn escrow_take_stays_within_budget() {
+    let pool = escrow::Pool::new(&Native, TOKEN_PROGRAM_ID);
+    assert!(pool.run(Native.make(&pool, escrow::SEED)));
+
+    // Take opens the taker's token A and the maker's token B accounts on the way
+    let result = pool
+        .svm
+        .process_instruction(&Native.take(&pool, escrow::SEED, &pool.mint_b));
+    assert!(result.program_result.is_ok());
+    let cu = result.compute_units_consumed;
+    println!("Escrow Take: {cu} CU");
+    assert!(cu <= TAKE_CU, "Escrow Take used {cu} CU, over {TAKE_CU}");
+}
@@ -0,0 +1,94 @@
+//! The Anchor and the native escrow run the same scenarios and must end in the same token
+//! balances, succeeding and failing together. Error codes are free to differ.
+
+mod common;
+
+use common::escrow::*;
+use common::*;
+use solana_pubkey::Pubkey;
+
+/// Run `scenario` against both programs, returning each program's step results and final
+/// balances after checking they agree.
+fn parity(
+    token_program: Pubkey,
+    scenario: impl Fn(&dyn EscrowProgram, &Pool) -> Vec<bool>,
+) -> (Vec<bool>, [u64; 5]) {
+    let outcomes: Vec<_> = [&Native as &dyn EscrowProgram, &Anchor]
+        .into_iter()
+        .map(|program| {
+            let pool = Pool::new(program, token_program);
+            let results = scenario(program, &pool);
+            (results, pool.balances(&escrow_address(&pool.maker, SEED)))
+        })
+        .collect();
+
+    assert_eq!(outcomes[0], outcomes[1], "native and anchor escrow diverge");
+    outcomes[0].clone()
+}
+
+#[test]
+fn make_then_take() {
+    for token_program in [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID] {
+        let (results, balances) = parity(token_program, |program, pool| {
+            vec![
+                pool.run(program.make(pool, SEED)),
+                pool.run(program.take(pool, SEED, &pool.mint_b)),
+            ]
+        });
+        assert_eq!(results, [true, true]);
+        assert_eq!(
+            balances,
+            [START - AMOUNT, RECEIVE, AMOUNT, START - RECEIVE, 0]
+        );
+    }
+}
+
+#[test]
+fn make_then_refund() {
+    for token_program in [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID] {
+        let (results, balances) = parity(token_program, |program, pool| {
+            vec![
+                pool.run(program.make(pool, SEED)),
+                pool.run(program.refund(pool, SEED, &pool.maker)),
+            ]
+        });
+        assert_eq!(results, [true, true]);
+        assert_eq!(balances, [START, 0, 0, START, 0]);
+    }
+}
+
+#[test]
+fn rejects_a_refund_signed_by_anyone_but_the_maker() {
+    let (results, balances) = parity(TOKEN_PROGRAM_ID, |program, pool| {
+        vec![
+            pool.run(program.make(pool, SEED)),
+            pool.run(program.refund(pool, SEED, &pool.taker)),
+        ]
+    });
+    assert_eq!(results, [true, false]);
+    assert_eq!(balances, [START - AMOUNT, 0, 0, START, AMOUNT]);
+}
+
+#[test]
+fn rejects_a_take_paying_in_another_mint() {
+    let (results, balances) = parity(TOKEN_PROGRAM_ID, |program, pool| {
+        vec![
+            pool.run(program.make(pool, SEED)),
+            pool.run(program.take(pool, SEED, &key(OTHER_MINT))),
+        ]
+    });
+    assert_eq!(results, [true, false]);
+    assert_eq!(balances, [START - AMOUNT, 0, 0, START, AMOUNT]);
+}
+
+#[test]
+fn rejects_a_second_make_with_the_same_seed() {
+    let (results, balances) = parity(TOKEN_PROGRAM_ID, |program, pool| {
+        vec![
+            pool.run(program.make(pool, SEED)),
+            pool.run(program.make(pool, SEED)),
+        ]
+    });
+    assert_eq!(results, [true, false]);
+    assert_eq!(balances, [START - AMOUNT, 0, 0, START, AMOUNT]);
+}